@@ -30,6 +30,7 @@ impl LuaModule for UserTypes {
 }
 
 gmod13_type!(MyType);
+#[derive(PartialEq)]
 struct MyType {
 	pub x: LuaNumber,
 	pub y: LuaNumber,
@@ -48,6 +49,9 @@ impl LuaUserType for MyType {
 			1
 		}));
 		cx.set_field(-2, c"__tostring");
+
+		// Compare values with `PartialEq` in `==`.
+		cx.set_eq();
 	}
 
 	unsafe fn collect(&mut self, mut cx: LuaSelfCtx<'_, Self>) {
@@ -0,0 +1,10 @@
+//! Runs arbitrary sequences of stack operations against a `MockLua`.
+//! 
+//! Run with `cargo fuzz run stack` from the root of the repository.
+
+#![no_main]
+
+use gmbm::gmod13::testing::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::run(data));
@@ -0,0 +1,151 @@
+//! Helpers for build scripts of binary modules,
+//! which name the binary module file so that Garry's Mod can load it.
+//! 
+//! Garry's Mod only loads a binary module with `require(name)`
+//! if its file is named like `gmsv_<name>_<platform>.dll` or `gmcl_<name>_<platform>.dll`,
+//! even on Linux and macOS, and if it's in `garrysmod/lua/bin`.
+//! Cargo names `cdylib` outputs after the platform instead,
+//! such as `lib<name>.so`, so the output has to be renamed when it's installed.
+//! 
+//! # Examples
+//! In `build.rs`, with this crate as a build dependency with the `build-support` feature:
+//! ```no_run
+//! use gmbm::{
+//!     build,
+//!     gmod13::Realm,
+//! };
+//! 
+//! fn main() {
+//!     // Sets `GMOD13_MODULE_FILE_NAME` to a name like `gmsv_hello_win64.dll`,
+//!     // which can be read with `env!` in the binary module or in an installer.
+//!     build::emit_file_name(Realm::Server, "hello");
+//! }
+//! ```
+
+extern crate std;
+
+use core::fmt;
+use std::{
+	env, println,
+};
+
+use crate::gmod13::Realm;
+
+/// Name of the environment variable set by [`emit_file_name`].
+pub const FILE_NAME_VAR: &str = "GMOD13_MODULE_FILE_NAME";
+
+/// Platform that Garry's Mod runs on, which determines the suffix of binary module file names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+	/// 32-bit Windows.
+	Win32,
+	/// 64-bit Windows, on the `x86-64` branch.
+	Win64,
+	/// 32-bit Linux.
+	Linux,
+	/// 64-bit Linux, on the `x86-64` branch.
+	Linux64,
+	/// 32-bit macOS.
+	Osx,
+	/// 64-bit macOS, on the `x86-64` branch.
+	Osx64,
+}
+
+impl Platform {
+	/// Returns the suffix of binary module file names for this platform, such as `win64`.
+	pub const fn suffix(self) -> &'static str {
+		match self {
+			Self::Win32 => "win32",
+			Self::Win64 => "win64",
+			Self::Linux => "linux",
+			Self::Linux64 => "linux64",
+			Self::Osx => "osx",
+			Self::Osx64 => "osx64",
+		}
+	}
+
+	/// Returns the platform for the target operating system `os` and pointer width `pointer_width`,
+	/// as in `cfg(target_os)` and `cfg(target_pointer_width)`,
+	/// or `None` if Garry's Mod doesn't run on it.
+	pub fn from_target(os: &str, pointer_width: u32) -> Option<Self> {
+		match (os, pointer_width) {
+			("windows", 32) => Some(Self::Win32),
+			("windows", 64) => Some(Self::Win64),
+			("linux", 32) => Some(Self::Linux),
+			("linux", 64) => Some(Self::Linux64),
+			("macos", 32) => Some(Self::Osx),
+			("macos", 64) => Some(Self::Osx64),
+			_ => None,
+		}
+	}
+
+	/// Returns the platform for the target triple `triple`, such as `x86_64-pc-windows-msvc`,
+	/// or `None` if Garry's Mod doesn't run on it.
+	pub fn from_triple(triple: &str) -> Option<Self> {
+		let mut parts = triple.split('-');
+		let pointer_width = match parts.next()? {
+			"i386" | "i586" | "i686" => 32,
+			"x86_64" => 64,
+			_ => return None,
+		};
+		let os = parts.find_map(move |part| match part {
+			"windows" => Some("windows"),
+			"linux" => Some("linux"),
+			"darwin" => Some("macos"),
+			_ => None,
+		})?;
+		Self::from_target(os, pointer_width)
+	}
+
+	/// Returns the platform that the package is being built for,
+	/// from the environment variables that Cargo sets for build scripts,
+	/// or `None` if they are missing or Garry's Mod doesn't run on the target.
+	pub fn from_build_env() -> Option<Self> {
+		let os = env::var("CARGO_CFG_TARGET_OS").ok()?;
+		let pointer_width = env::var("CARGO_CFG_TARGET_POINTER_WIDTH").ok()?.parse().ok()?;
+		Self::from_target(&os, pointer_width)
+	}
+}
+
+/// Returns the prefix of binary module file names for `realm`.
+/// 
+/// The menu state loads the same binary modules as the client.
+pub const fn file_name_prefix(realm: Realm) -> &'static str {
+	match realm {
+		Realm::Server => "gmsv",
+		Realm::Client | Realm::Menu => "gmcl",
+	}
+}
+
+/// File name of a binary module, which is displayed like `gmsv_<name>_<platform>.dll`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileName<'a> {
+	/// Realm that the binary module is loaded in.
+	pub realm: Realm,
+	/// Name that the binary module is loaded with by `require`.
+	pub name: &'a str,
+	/// Platform that the binary module is built for.
+	pub platform: Platform,
+}
+
+impl fmt::Display for FileName<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}_{}_{}.dll", file_name_prefix(self.realm), self.name, self.platform.suffix())
+	}
+}
+
+/// Sets the environment variable [`FILE_NAME_VAR`] to the [`FileName`] of the binary module `name` for `realm`
+/// when the package is compiled,
+/// for the platform that it is being built for.
+/// 
+/// This must be called from a build script.
+/// 
+/// # Panics
+/// Panics if Garry's Mod doesn't run on the target.
+pub fn emit_file_name(realm: Realm, name: &str) {
+	let platform = Platform::from_build_env().expect("Garry's Mod doesn't run on the target platform");
+	let file_name = FileName {
+		realm, name, platform,
+	};
+	println!("cargo::rustc-env={FILE_NAME_VAR}={file_name}");
+}
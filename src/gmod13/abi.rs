@@ -0,0 +1,110 @@
+use core::{
+	error::Error,
+	fmt,
+};
+#[cfg(feature = "verify-abi")]
+use core::fmt::Write;
+
+#[cfg(feature = "verify-abi")]
+use super::chunks::CStrBuf;
+use super::{
+	Lua, StdType,
+};
+
+/// Maximum number of values that a LuaJIT stack can hold.
+const MAX_STACK: u32 = 65500;
+
+/// Error returned by [`Lua::verify_abi`] if the C++ API doesn't behave as expected,
+/// which usually means that the binary module was built for a different version of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AbiError {
+	check: &'static str,
+}
+
+impl AbiError {
+	/// Returns the name of the check that failed.
+	pub const fn check(&self) -> &'static str {
+		self.check
+	}
+}
+
+impl Error for AbiError {}
+impl fmt::Display for AbiError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Lua C++ API doesn't match the one this binary module was built for (`{}` check failed)", self.check)
+	}
+}
+
+/// Returns `Err` with `check` if `ok` is `false`.
+fn ensure(ok: bool, check: &'static str) -> Result<(), AbiError> {
+	if ok {
+		Ok(())
+	} else {
+		Err(AbiError { check })
+	}
+}
+
+/// Functions for checking the C++ API.
+impl Lua {
+	/// Exercises a few harmless functions of the C++ API,
+	/// and returns `Err` if any of them don't behave as expected.
+	/// 
+	/// The stack is left as it was,
+	/// unless the functions which push and pop values are the ones that misbehave.
+	/// 
+	/// This can't detect every mismatch,
+	/// and a badly mismatched API may crash the game before an error is returned,
+	/// but it catches virtual functions which have been moved around.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn verify_abi(&mut self) -> Result<(), AbiError> {
+		let top = self.top();
+		ensure(top <= MAX_STACK, "top")?;
+
+		self.push_nil();
+		ensure(self.top() == top + 1, "push_nil")?;
+		let result = self.verify_abi_values();
+		self.set_top(top);
+		result?;
+
+		ensure(self.top() == top, "pop")?;
+		ensure(self.get_type_name(StdType::Number) == c"number", "get_type_name")
+	}
+
+	fn verify_abi_values(&mut self) -> Result<(), AbiError> {
+		ensure(self.get_type(-1).is_std(StdType::Nil), "get_type")?;
+
+		self.push_number(0.5);
+		ensure(self.is_type(-1, StdType::Number) && self.get_number(-1) == 0.5, "push_number")?;
+
+		self.push_bool(true);
+		ensure(self.is_type(-1, StdType::Bool) && self.get_bool(-1), "push_bool")?;
+
+		self.push_string("gmbm");
+		ensure(self.get_string(-1) == Some(b"gmbm"), "push_string")
+	}
+
+	/// Raises an error if [`verify_abi`](Self::verify_abi) fails
+	/// and the `verify-abi` feature is enabled, and does nothing otherwise.
+	/// 
+	/// This is called before anything else
+	/// by the functions exported with [`gmod13_module_with!`](crate::gmod13_module_with),
+	/// so that the binary module fails to load with a clear error
+	/// instead of corrupting memory later.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_abi(&mut self) {
+		#[cfg(feature = "verify-abi")]
+		if let Err(error) = self.verify_abi() {
+			let mut message = CStrBuf::new();
+			let _ = write!(message, "{error}");
+			self.throw_error(message.as_c_str())
+		}
+	}
+}
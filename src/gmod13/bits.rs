@@ -38,4 +38,52 @@ impl Lua {
 	pub fn push_bits(&self, i: Bits) {
 		self.push_number(Number::from_bits(i))
 	}
+
+	/// Returns the signed integer encoded as a Lua number at `stack_pos` with [`Lua::push_bits_i64`],
+	/// or `0` if the value isn't a Lua number.
+	pub fn get_bits_i64(&self, stack_pos: StackPos) -> i64 {
+		self.get_bits(stack_pos) as i64
+	}
+
+	/// If the value at `stack_pos` is a [`Number`],
+	/// decodes it as a signed integer encoded with [`Lua::push_bits_i64`].
+	/// Otherwise, throws an error.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_bits_i64(&self, stack_pos: StackPos) -> i64 {
+		self.check_bits(stack_pos) as i64
+	}
+
+	/// Pushes the given signed integer onto the stack as a Lua number,
+	/// preserving its two's complement representation.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_bits_i64(&self, i: i64) {
+		self.push_bits(i as Bits)
+	}
+
+	/// Returns the pointer encoded as a Lua number at `stack_pos` with [`Lua::push_bits_ptr`],
+	/// or a null pointer if the value isn't a Lua number.
+	/// 
+	/// The returned pointer has the provenance that was exposed by [`Lua::push_bits_ptr`]
+	/// (see [`core::ptr::with_exposed_provenance_mut`]),
+	/// so it is only valid to dereference if the original pointer is still valid.
+	/// Since Lua code can replace the number with any other,
+	/// values that may have come from Lua must be validated before use.
+	pub fn get_bits_ptr<T>(&self, stack_pos: StackPos) -> *mut T {
+		core::ptr::with_exposed_provenance_mut(self.get_bits(stack_pos) as usize)
+	}
+
+	/// Pushes the address of `ptr` onto the stack as a Lua number,
+	/// so that it can be recovered later with [`Lua::get_bits_ptr`].
+	/// 
+	/// This exposes the provenance of `ptr` (see [`expose_provenance`](https://doc.rust-lang.org/core/primitive.pointer.html#method.expose_provenance)).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_bits_ptr<T>(&self, ptr: *const T) {
+		self.push_bits(ptr.expose_provenance() as Bits)
+	}
 }
@@ -0,0 +1,78 @@
+//! Conversions between Lua strings and [`Bytes`],
+//! for modules which pass binary payloads to and from background threads.
+//! 
+//! Lua strings can't leave the Lua state, since the garbage collector may free them.
+//! [`Lua::get_bytes_shared`] copies a string once into a reference-counted buffer,
+//! which can then be sent through channels and sliced without copying it again.
+//! 
+//! # Examples
+//! ```
+//! use std::sync::mpsc::{Receiver, Sender};
+//! use bytes::Bytes;
+//! use gmbm::prelude::*;
+//! 
+//! /// Sends the payload given by Lua to the background thread.
+//! fn send_payload(lua: &mut Lua, requests: &Sender<Bytes>) {
+//!     let payload = lua.get_bytes_shared(1);
+//!     let _ = requests.send(payload);
+//! }
+//! 
+//! /// Calls `on_reply` with every reply that the background thread has sent.
+//! fn poll_replies(lua: &mut Lua, replies: &Receiver<Bytes>) {
+//!     while let Ok(reply) = replies.try_recv() {
+//!         lua.get_global(c"on_reply");
+//!         lua.push_bytes(&reply);
+//!         lua.call(1, 0);
+//!     }
+//! }
+//! ```
+
+use bytes::Bytes;
+
+use super::{
+	FromLua, ToLua,
+	Lua, StackPos, StdType,
+};
+
+/// Functions for sharing Lua strings with other threads.
+impl Lua {
+	/// Returns a copy of the contents of the Lua string at `stack_pos` in a reference-counted buffer,
+	/// converting any Lua number at that position to a string in the process,
+	/// or an empty buffer if the value can't be converted to a Lua string.
+	/// 
+	/// Unlike the result of [`Lua::get_string`], the buffer stays valid after the string is popped,
+	/// and can be sent to other threads.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn get_bytes_shared(&self, stack_pos: StackPos) -> Bytes {
+		self.get_string(stack_pos).map_or_else(Bytes::new, Bytes::copy_from_slice)
+	}
+
+	/// Pushes the contents of `bytes` onto the stack as a Lua string.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_bytes(&mut self, bytes: &Bytes) {
+		self.push_string(bytes)
+	}
+}
+
+impl ToLua for Bytes {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_bytes(&self)
+	}
+}
+
+impl ToLua for &Bytes {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_bytes(self)
+	}
+}
+
+impl FromLua<'_> for Bytes {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.is_type(stack_pos, StdType::String).then(move || lua.get_bytes_shared(stack_pos))
+	}
+}
@@ -0,0 +1,253 @@
+use core::{
+	ffi::{
+		c_int, c_uint,
+	},
+	mem::forget,
+	ops::{
+		Deref, DerefMut,
+	},
+};
+
+use super::{
+	CallError, FromLua, Lua, Ref, StackPos,
+};
+
+/// View of the values returned by a function call,
+/// which are popped from the stack when it is dropped.
+/// 
+/// Results are numbered from `1`, like the arguments of a Lua function,
+/// regardless of how many values are above them on the stack.
+/// 
+/// Values of this type are created with [`Lua::call_results`] and [`Lua::pcall_results`].
+/// 
+/// # Examples
+/// ```no_run
+/// use gmbm::gmod13::Lua;
+/// 
+/// /// Returns the number of players, or `0` if `player.GetCount` raised an error.
+/// fn player_count(lua: &mut Lua) -> u32 {
+///     lua.get_global_field(c"player", c"GetCount");
+///     let count = lua.pcall_results(0, 1, 0)
+///         .map(move |results| results.get(1).unwrap_or(0));
+///     count.unwrap_or_else(move |_| {
+///         // Pops the error message.
+///         lua.pop(1);
+///         0
+///     })
+/// }
+/// ```
+#[must_use = "the results are popped immediately if they are not used"]
+pub struct CallResults<'l> {
+	lua: &'l mut Lua,
+	base: StackPos,
+	len: c_uint,
+}
+
+impl<'l> CallResults<'l> {
+	/// Returns the number of results.
+	pub const fn len(&self) -> c_uint {
+		self.len
+	}
+
+	/// Returns `true` if the function didn't return any values.
+	pub const fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Returns the stack position of result `i`,
+	/// or `None` if there is no such result.
+	pub const fn pos(&self, i: c_uint) -> Option<StackPos> {
+		if i >= 1 && i <= self.len {
+			Some(self.base + i as StackPos - 1)
+		} else {
+			None
+		}
+	}
+
+	/// Reads result `i` as a `T`,
+	/// returning `None` if there is no such result, or if it isn't of the expected type.
+	pub fn get<'a, T: FromLua<'a>>(&'a self, i: c_uint) -> Option<T> {
+		T::from_lua(self.lua, self.pos(i)?)
+	}
+
+	/// Leaves the results on the stack instead of popping them, and returns the number of results.
+	/// 
+	/// This can be used to return the results from a Lua function.
+	pub fn keep(self) -> c_uint {
+		let len = self.len;
+		forget(self);
+		len
+	}
+}
+
+impl Deref for CallResults<'_> {
+	type Target = Lua;
+	fn deref(&self) -> &Self::Target {
+		self.lua
+	}
+}
+impl DerefMut for CallResults<'_> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.lua
+	}
+}
+
+impl Drop for CallResults<'_> {
+	fn drop(&mut self) {
+		// Values pushed above the results are popped too.
+		self.lua.set_top((self.base - 1) as c_uint);
+	}
+}
+
+/// Functions for protected and sandboxed calls.
+impl Lua {
+	/// Calls an object as a function on the stack like [`call`](Self::call),
+	/// but first checks that the function and its `n_args` arguments are on the stack,
+	/// and that `n_results` results fit on the stack.
+	/// 
+	/// An `n_args` that's too large makes Lua call a value below the arguments, silently corrupting the stack.
+	/// [`call`](Self::call) only checks this with the `debug-stack` feature,
+	/// while this method always does.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// such as when the checks fail.
+	/// With the `testing` feature, this is a panic in tests.
+	pub fn checked_call(&mut self, n_args: c_uint, n_results: c_uint) {
+		self.check_call(n_args, n_results as c_int);
+		self.call(n_args, n_results)
+	}
+
+	/// Calls an object as a function on the stack like [`pcall`](Self::pcall),
+	/// but first checks the stack like [`checked_call`](Self::checked_call).
+	/// 
+	/// Failed checks raise an error instead of returning `Err`,
+	/// since they are mistakes in the calling code rather than errors of the function.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors) if the checks fail.
+	/// With the `testing` feature, this is a panic in tests.
+	pub fn checked_pcall(&mut self, n_args: c_uint, n_results: c_int, error_func: c_int) -> Result<(), CallError> {
+		self.check_call(n_args, n_results);
+		self.pcall(n_args, n_results, error_func)
+	}
+
+	/// Calls an object as a function on the stack like [`call`](Self::call),
+	/// and returns a view of its results.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn call_results(&mut self, n_args: c_uint, n_results: c_uint) -> CallResults<'_> {
+		let base = self.abs_index(-(n_args as c_int) - 1);
+		self.call(n_args, n_results);
+		self.results_from(base)
+	}
+
+	/// Calls an object as a function on the stack like [`pcall`](Self::pcall),
+	/// and returns a view of its results if it didn't raise an error.
+	/// 
+	/// `n_results` may be negative to keep every result.
+	/// If an error is returned, then the error message is left on the stack in place of the function and its arguments.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn pcall_results(&mut self, n_args: c_uint, n_results: c_int, error_func: c_int) -> Result<CallResults<'_>, CallError> {
+		let base = self.abs_index(-(n_args as c_int) - 1);
+		self.pcall(n_args, n_results, error_func)?;
+		Ok(self.results_from(base))
+	}
+
+	fn results_from(&mut self, base: StackPos) -> CallResults<'_> {
+		let len = (self.top() as StackPos + 1 - base) as c_uint;
+		CallResults {
+			lua: self,
+			base,
+			len,
+		}
+	}
+
+	/// Calls an object as a function on the stack like [`pcall`](Self::pcall),
+	/// with `debug.traceback` as the error handler,
+	/// so that the error message on the stack also contains a stack traceback if the function raised an error.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn pcall_traced(&mut self, n_args: c_uint, n_results: c_int) -> Result<(), CallError> {
+		let func = self.abs_index(-(n_args as c_int) - 1);
+		self.get_global_field(c"debug", c"traceback");
+		self.insert(func);
+		let result = self.pcall(n_args, n_results, func);
+		self.remove(func);
+		result
+	}
+
+	/// Calls an object as a function on the stack like [`pcall_traced`](Self::pcall_traced),
+	/// with its environment temporarily set to the table referenced by `env`,
+	/// so that it reads and writes globals in that table instead.
+	/// 
+	/// The original environment of the function is restored after the call,
+	/// even if the function raised an error.
+	/// Since Lua functions share their environment with every call,
+	/// the function must not be called from anywhere else while it runs in `env`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// It is implemented with `getfenv` and `setfenv`,
+	/// which raise an error if the object is not a function.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```no_run
+	/// use gmbm::gmod13::Lua;
+	/// 
+	/// /// Calls the callback at argument `1` with only `print` available to it.
+	/// fn call_sandboxed(lua: &mut Lua) {
+	///     lua.create_table();
+	///     lua.get_global(c"print");
+	///     lua.set_field(-2, c"print");
+	///     let env = lua.create_ref();
+	/// 
+	///     lua.push_value(1);
+	///     if lua.call_in_env(env, 0, 0).is_err() {
+	///         lua.pop(1);
+	///     }
+	///     lua.free_ref(env);
+	/// }
+	/// ```
+	pub fn call_in_env(&mut self, env: Ref, n_args: c_uint, n_results: c_int) -> Result<(), CallError> {
+		let func = self.abs_index(-(n_args as c_int) - 1);
+
+		// Keep the function and its original environment below it, to restore it afterwards.
+		self.push_value(func);
+		self.get_global(c"getfenv");
+		self.push_value(func);
+		self.call(1, 1);
+		self.insert(func);
+		self.insert(func);
+		let saved = func;
+		let func = func + 2;
+
+		self.get_global(c"setfenv");
+		self.push_value(func);
+		self.push_ref(env);
+		self.call(2, 0);
+
+		let result = self.pcall_traced(n_args, n_results);
+
+		self.get_global(c"setfenv");
+		self.push_value(saved);
+		self.push_value(saved + 1);
+		self.call(2, 0);
+		self.remove(saved);
+		self.remove(saved);
+		result
+	}
+}
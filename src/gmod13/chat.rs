@@ -0,0 +1,174 @@
+use core::ffi::{
+	CStr, c_uint,
+};
+
+use crate::source::Color32;
+
+use super::{
+	Lua, Number, Realm, StackPos,
+};
+
+/// Place where a message is printed with [`Lua::print_message`],
+/// from the `HUD_PRINT*` enumerations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum HudPrint {
+	/// The notification area in the top left corner of the screen (`HUD_PRINTNOTIFY`).
+	Notify = 1,
+	/// The console (`HUD_PRINTCONSOLE`).
+	Console = 2,
+	/// The chat (`HUD_PRINTTALK`).
+	Talk = 3,
+	/// The center of the screen (`HUD_PRINTCENTER`).
+	Center = 4,
+}
+
+/// Icon and sound of a notification shown with [`Lua::notify`],
+/// from the `NOTIFY_*` enumerations.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum NotifyKind {
+	/// Generic notification (`NOTIFY_GENERIC`).
+	#[default]
+	Generic = 0,
+	/// Error notification (`NOTIFY_ERROR`).
+	Error = 1,
+	/// Undo notification (`NOTIFY_UNDO`).
+	Undo = 2,
+	/// Hint notification (`NOTIFY_HINT`).
+	Hint = 3,
+	/// Cleanup notification (`NOTIFY_CLEANUP`).
+	Cleanup = 4,
+}
+
+/// Functions for showing messages to players.
+impl Lua {
+	/// Prints `text` in the chat, in the given `color` if it's `Some`.
+	/// 
+	/// On the server, `text` is printed in the chat of the player at `player` with `Player:ChatPrint(text)`,
+	/// or in the chat of every player with `PrintMessage(HUD_PRINTTALK, text)` if it's `None`.
+	/// The server can't color chat messages without code running on the client, so `color` is ignored.
+	/// 
+	/// On the client, `text` is printed in the chat of the local player with `chat.AddText(color, text)`,
+	/// and `player` is ignored.
+	/// 
+	/// In the menu, `text` is printed to the console with `print(text)`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::prelude::*;
+	/// use gmbm::source::Color32;
+	/// 
+	/// /// Lua function `(ply: Player)`, which tells a player that they can't use a command.
+	/// extern "C-unwind" fn deny(cx: LuaCtx<'_>) -> LuaRets {
+	///     let lua = cx.lua();
+	///     let red = Color32::new(255, 90, 90, 255);
+	///     lua.chat_print(Some(1), c"You don't have access to this command.", Some(&red));
+	///     LuaRets::ZERO
+	/// }
+	/// ```
+	pub fn chat_print(&mut self, player: Option<StackPos>, text: &CStr, color: Option<&Color32>) {
+		let player = player.map(|pos| self.abs_index(pos));
+		match self.realm() {
+			Some(Realm::Server) => match player {
+				Some(player) => {
+					self.push_self_method(player, c"ChatPrint");
+					self.push_c_string(text);
+					self.call(2, 0);
+				}
+				None => self.print_message(None, HudPrint::Talk, text),
+			},
+			Some(Realm::Client) => {
+				self.get_global_field(c"chat", c"AddText");
+				let mut n_args: c_uint = 1;
+				if let Some(color) = color {
+					self.push_color_table(color);
+					n_args += 1;
+				}
+				self.push_c_string(text);
+				self.call(n_args, 0);
+			}
+			_ => self.print_to_console(text),
+		}
+	}
+
+	/// Prints `text` at `place` on the screen of the player at `player`
+	/// with `Player:PrintMessage(place, text)`,
+	/// or on the screen of every player with `PrintMessage(place, text)` if it's `None`.
+	/// 
+	/// This is only available on the server.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// including if the Lua state is not of the server realm.
+	pub fn print_message(&mut self, player: Option<StackPos>, place: HudPrint, text: &CStr) {
+		match player {
+			Some(player) => {
+				self.push_self_method(player, c"PrintMessage");
+				self.push_number(place as u8 as Number);
+				self.push_c_string(text);
+				self.call(3, 0);
+			}
+			None => {
+				self.get_global(c"PrintMessage");
+				self.push_number(place as u8 as Number);
+				self.push_c_string(text);
+				self.call(2, 0);
+			}
+		}
+	}
+
+	/// Shows `text` as a notification of the given `kind` for `duration` seconds.
+	/// 
+	/// On the client, the notification is shown to the local player
+	/// with `notification.AddLegacy(text, kind, duration)`, and `player` is ignored.
+	/// 
+	/// The server can't show notifications without code running on the client,
+	/// so `text` is printed in the center of the screen of the player at `player`,
+	/// or of every player if it's `None`, like with [`Lua::print_message`],
+	/// and `kind` and `duration` are ignored.
+	/// 
+	/// In the menu, `text` is printed to the console with `print(text)`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn notify(&mut self, player: Option<StackPos>, text: &CStr, kind: NotifyKind, duration: Number) {
+		match self.realm() {
+			Some(Realm::Server) => self.print_message(player, HudPrint::Center, text),
+			Some(Realm::Client) => {
+				self.get_global_field(c"notification", c"AddLegacy");
+				self.push_c_string(text);
+				self.push_number(kind as u8 as Number);
+				self.push_number(duration);
+				self.call(3, 0);
+			}
+			_ => self.print_to_console(text),
+		}
+	}
+
+	/// Pushes a table with the `r`, `g`, `b` and `a` fields of `color`,
+	/// which is accepted by functions that take a `Color`.
+	fn push_color_table(&mut self, color: &Color32) {
+		self.create_table();
+		for (value, key) in [(color.r, c"r"), (color.g, c"g"), (color.b, c"b"), (color.a, c"a")] {
+			self.push_number(value as Number);
+			self.set_field(-2, key);
+		}
+	}
+
+	/// Prints `text` to the console with `print(text)`.
+	fn print_to_console(&mut self, text: &CStr) {
+		self.get_global(c"print");
+		self.push_c_string(text);
+		self.call(1, 0);
+	}
+}
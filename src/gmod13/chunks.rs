@@ -0,0 +1,95 @@
+use core::{
+	ffi::CStr,
+	fmt,
+};
+
+/// Size of the buffer used by [`ChunkWriter`].
+pub(crate) const CHUNK_SIZE: usize = 256;
+
+/// [`fmt::Write`] implementation which collects output into a fixed-size buffer,
+/// passing it to a function in chunks whenever it is full.
+pub(crate) struct ChunkWriter<F> {
+	buf: [u8; CHUNK_SIZE],
+	len: usize,
+	flush: F,
+}
+
+impl<F: FnMut(&[u8])> ChunkWriter<F> {
+	pub const fn new(flush: F) -> Self {
+		Self {
+			buf: [0; CHUNK_SIZE],
+			len: 0,
+			flush,
+		}
+	}
+
+	/// Passes any remaining buffered output to the flushing function.
+	pub fn finish(mut self) {
+		if self.len > 0 {
+			(self.flush)(&self.buf[..self.len]);
+		}
+	}
+
+	/// Writes arbitrary bytes, which don't have to be valid UTF-8.
+	pub fn write_bytes(&mut self, mut bytes: &[u8]) {
+		while !bytes.is_empty() {
+			let n = bytes.len().min(CHUNK_SIZE - self.len);
+			self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+			self.len += n;
+			bytes = &bytes[n..];
+
+			if self.len == CHUNK_SIZE {
+				(self.flush)(&self.buf);
+				self.len = 0;
+			}
+		}
+	}
+}
+
+impl<F: FnMut(&[u8])> fmt::Write for ChunkWriter<F> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		self.write_bytes(s.as_bytes());
+		Ok(())
+	}
+}
+
+/// Size of the buffer used by [`CStrBuf`], including the nul terminator.
+pub(crate) const C_STR_BUF_SIZE: usize = 256;
+
+/// [`fmt::Write`] implementation which collects output into a fixed-size, nul-terminated buffer,
+/// silently truncating anything that doesn't fit.
+pub(crate) struct CStrBuf {
+	buf: [u8; C_STR_BUF_SIZE],
+	len: usize,
+}
+
+impl CStrBuf {
+	pub const fn new() -> Self {
+		Self {
+			buf: [0; C_STR_BUF_SIZE],
+			len: 0,
+		}
+	}
+
+	/// Returns the output written so far as a C string.
+	pub fn as_c_str(&self) -> &CStr {
+		// The buffer is zero-initialized, and the last byte is never written to.
+		unsafe { CStr::from_bytes_until_nul(&self.buf).unwrap_unchecked() }
+	}
+}
+
+impl fmt::Write for CStrBuf {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		for &byte in s.as_bytes() {
+			if self.len == C_STR_BUF_SIZE - 1 {
+				break
+			}
+			// Interior nul bytes would cut the string short.
+			if byte != 0 {
+				self.buf[self.len] = byte;
+				self.len += 1;
+			}
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,114 @@
+use core::ffi::CStr;
+
+use super::{
+	Lua, Number, StackPos,
+};
+
+/// Trait for types which have a fixed set of named numeric constants,
+/// such as the variants of an enum, which can be exported to Lua.
+/// 
+/// This is usually implemented with [`gmod13_constants!`](crate::gmod13_constants).
+pub trait Constants {
+	/// Names and values of the constants, in order.
+	const CONSTANTS: &'static [(&'static CStr, Number)];
+}
+
+/// Functions for exporting constants.
+impl Lua {
+	/// Pushes a table which maps the name of every constant of `C` to its value.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_constants<C: Constants + ?Sized>(&mut self) {
+		self.create_table();
+		self.set_constants::<C>(-1);
+	}
+
+	/// Sets a field for every constant of `C` in the table at `stack_pos`,
+	/// such as the global table.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_constants<C: Constants + ?Sized>(&mut self, stack_pos: StackPos) {
+		let table = self.abs_index(stack_pos);
+		for &(name, value) in C::CONSTANTS {
+			self.push_number(value);
+			self.set_field(table, name);
+		}
+	}
+}
+
+/// Declares a fieldless enum,
+/// and implements [`Constants`](crate::gmod13::Constants) for it with the names and values of its variants,
+/// as well as [`ToLua`](crate::gmod13::ToLua) to push a variant as its value.
+/// 
+/// Variants are exported with their names as written,
+/// so they may be named in upper case like other Lua constants.
+/// 
+/// # Examples
+/// ```
+/// use gmbm::{
+///     gmod13::{
+///         Constants, Lua,
+///     },
+///     gmod13_constants,
+/// };
+/// 
+/// gmod13_constants! {
+///     /// Flags for a physics object.
+///     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///     pub enum PhysFlags {
+///         NONE = 0,
+///         SOLID = 1,
+///         TRIGGER = 2,
+///         DEBRIS = 4,
+///     }
+/// }
+/// 
+/// assert_eq!(PhysFlags::CONSTANTS[2], (c"TRIGGER", 2.0));
+/// 
+/// fn export(lua: &mut Lua) {
+///     lua.push_globals();
+///     lua.push_constants::<PhysFlags>();
+///     lua.set_field(-2, c"PHYS_FLAGS");
+///     lua.pop(1);
+/// }
+/// ```
+#[macro_export]
+macro_rules! gmod13_constants {
+	{
+		$(#[$attr:meta])*
+		$vis:vis enum $Enum:ident {
+			$($(#[$variant_attr:meta])* $Variant:ident $(= $value:expr)?),* $(,)?
+		}
+	} => {
+		$(#[$attr])*
+		#[allow(non_camel_case_types)]
+		$vis enum $Enum {
+			$($(#[$variant_attr])* $Variant $(= $value)?),*
+		}
+
+		impl $crate::gmod13::Constants for $Enum {
+			const CONSTANTS: &'static [(&'static ::core::ffi::CStr, $crate::gmod13::Number)] = &[$(
+				(
+					unsafe {
+						::core::ffi::CStr::from_bytes_with_nul_unchecked(
+							::core::concat! { ::core::stringify! {$Variant}, '\0' }.as_bytes()
+						)
+					},
+					$Enum::$Variant as i64 as $crate::gmod13::Number,
+				)
+			),*];
+		}
+
+		impl $crate::gmod13::ToLua for $Enum {
+			fn push_to(self, lua: &mut $crate::gmod13::Lua) {
+				lua.push_number(self as i64 as $crate::gmod13::Number)
+			}
+		}
+	};
+}
@@ -0,0 +1,522 @@
+#[cfg(feature = "alloc")]
+use alloc::{
+	string::String, vec::Vec,
+};
+use core::{
+	ffi::{
+		CStr, c_uint,
+	},
+	str,
+};
+
+use crate::source::Vector;
+#[cfg(feature = "rse-math")]
+use crate::source::QAngle;
+
+use super::{
+	number_to_i53,
+	Lua, Number, StackPos, StdType,
+};
+
+/// Trait for Rust values that can be pushed onto the Lua stack as a single value.
+pub trait ToLua {
+	/// Pushes this value onto the stack of `lua`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	fn push_to(self, lua: &mut Lua);
+}
+
+impl ToLua for () {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_nil()
+	}
+}
+
+impl ToLua for bool {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_bool(self)
+	}
+}
+
+macro_rules! number_to_lua {
+	($($Number:ty)*) => {
+		$(
+			impl ToLua for $Number {
+				fn push_to(self, lua: &mut Lua) {
+					lua.push_number(self as Number)
+				}
+			}
+		)*
+	};
+}
+
+// Integers wider than 32 bits may lose precision, since Lua numbers are floating-point.
+number_to_lua!(f32 f64 i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+impl ToLua for &str {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_string(self)
+	}
+}
+
+impl ToLua for &[u8] {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_string(self)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl ToLua for String {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_string(self)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl ToLua for Vec<u8> {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_string(self)
+	}
+}
+
+impl ToLua for &CStr {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_c_string(self)
+	}
+}
+
+impl ToLua for &Vector {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_vector(self)
+	}
+}
+
+impl ToLua for Vector {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_vector(&self)
+	}
+}
+
+// Without `rse-math`, `QAngle` is an alias of `Vector`, so it can't be distinguished.
+#[cfg(feature = "rse-math")]
+impl ToLua for &QAngle {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_angle(self)
+	}
+}
+
+#[cfg(feature = "rse-math")]
+impl ToLua for QAngle {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_angle(&self)
+	}
+}
+
+impl<T: ToLua> ToLua for Option<T> {
+	fn push_to(self, lua: &mut Lua) {
+		match self {
+			Some(value) => value.push_to(lua),
+			None => lua.push_nil(),
+		}
+	}
+}
+
+/// Trait for groups of Rust values that can be pushed onto the Lua stack as multiple values,
+/// such as the arguments of a function call.
+/// 
+/// This is implemented for tuples of up to 8 [`ToLua`] values.
+pub trait ToLuaMulti {
+	/// Number of values that are pushed.
+	const LEN: c_uint;
+
+	/// Pushes these values onto the stack of `lua`, in order.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	fn push_all_to(self, lua: &mut Lua);
+}
+
+macro_rules! tuple_to_lua_multi {
+	($len:literal; $($T:ident)*) => {
+		impl<$($T: ToLua),*> ToLuaMulti for ($($T,)*) {
+			const LEN: c_uint = $len;
+
+			#[allow(non_snake_case, unused_variables)]
+			fn push_all_to(self, lua: &mut Lua) {
+				let ($($T,)*) = self;
+				$($T.push_to(lua);)*
+			}
+		}
+	};
+}
+
+tuple_to_lua_multi!(0;);
+tuple_to_lua_multi!(1; A);
+tuple_to_lua_multi!(2; A B);
+tuple_to_lua_multi!(3; A B C);
+tuple_to_lua_multi!(4; A B C D);
+tuple_to_lua_multi!(5; A B C D E);
+tuple_to_lua_multi!(6; A B C D E F);
+tuple_to_lua_multi!(7; A B C D E F G);
+tuple_to_lua_multi!(8; A B C D E F G H);
+
+/// Functions for pushing Rust values.
+impl Lua {
+	/// Pushes `value` onto the stack.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push<T: ToLua>(&mut self, value: T) {
+		value.push_to(self)
+	}
+
+	/// Creates a new table with the values of `iter` at the keys `1` to `n`, in order,
+	/// and pushes it onto the stack.
+	/// 
+	/// The values are set with [`raw_set`](Self::raw_set),
+	/// so only one value is on the stack above the table at a time, no matter how many there are.
+	/// Values which are pushed as `nil`, such as `None`, leave holes in the sequence.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// /// Returns the squares of the numbers from 1 to `n`.
+	/// fn push_squares(lua: &mut Lua, n: u32) {
+	///     lua.push_sequence((1..=n).map(|i| i * i));
+	/// }
+	/// ```
+	pub fn push_sequence(&mut self, iter: impl IntoIterator<Item: ToLua>) {
+		self.create_table();
+		for (i, value) in iter.into_iter().enumerate() {
+			self.push_number((i + 1) as Number);
+			value.push_to(self);
+			self.raw_set(-3);
+		}
+	}
+
+	/// Creates a new table with the keys and values of `iter`,
+	/// and pushes it onto the stack.
+	/// 
+	/// The pairs are set in order with [`raw_set`](Self::raw_set),
+	/// so only one pair is on the stack above the table at a time, no matter how many there are,
+	/// and later pairs replace earlier ones with the same key.
+	/// Pairs with values which are pushed as `nil` are left out.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// such as when a key is pushed as `nil`.
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// /// Returns a table of player names to their scores.
+	/// fn push_scores(lua: &mut Lua, scores: &[(&str, u32)]) {
+	///     lua.push_map(scores.iter().copied());
+	/// }
+	/// ```
+	pub fn push_map<K: ToLua, V: ToLua>(&mut self, iter: impl IntoIterator<Item = (K, V)>) {
+		self.create_table();
+		for (key, value) in iter {
+			key.push_to(self);
+			value.push_to(self);
+			self.raw_set(-3);
+		}
+	}
+}
+
+/// Creates a new table with the given fields, and pushes it onto the stack of the given [`Lua`].
+/// 
+/// Each field is written as `key = value`, where `key` is an identifier used as a string key,
+/// and `value` is one of:
+/// - an expression which implements [`ToLua`];
+/// - `fn` followed by an expression which evaluates to a [`Func`](crate::gmod13::func::Func),
+///   since function items can't be pushed with [`ToLua`];
+/// - fields in braces, for a nested table.
+/// 
+/// To use a block expression as a value, wrap it in parentheses.
+/// 
+/// # Errors
+/// The inner Lua state may raise an [error](crate::errors).
+/// 
+/// # Examples
+/// ```
+/// use gmbm::prelude::*;
+/// 
+/// extern "C-unwind" fn init(cx: LuaCtx<'_>) -> LuaRets {
+///     let _ = cx;
+///     LuaRets::ZERO
+/// }
+/// 
+/// struct MyMod;
+/// impl LuaModule for MyMod {
+///     fn open(&mut self, lua: &mut Lua) {
+///         lua.push_globals();
+///         lua_table!(lua => {
+///             version = 3,
+///             name = "mymod",
+///             init = fn init,
+///             limits = {
+///                 max_players = 128,
+///             },
+///         });
+///         lua.set_field(-2, c"mymod");
+///         lua.pop(1);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! lua_table {
+	($lua:expr => {$($fields:tt)*}) => {{
+		let lua: &mut $crate::gmod13::Lua = &mut *$lua;
+		lua.create_table();
+		$crate::lua_table!(@fields lua; $($fields)*);
+	}};
+
+	(@fields $lua:ident;) => {};
+
+	(@fields $lua:ident; $key:ident = {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+		$crate::lua_table!($lua => {$($inner)*});
+		$crate::lua_table!(@set $lua; $key);
+		$crate::lua_table!(@fields $lua; $($($rest)*)?);
+	};
+
+	(@fields $lua:ident; $key:ident = fn $func:expr $(, $($rest:tt)*)?) => {
+		$lua.push_function($func);
+		$crate::lua_table!(@set $lua; $key);
+		$crate::lua_table!(@fields $lua; $($($rest)*)?);
+	};
+
+	(@fields $lua:ident; $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+		$crate::gmod13::ToLua::push_to($value, &mut *$lua);
+		$crate::lua_table!(@set $lua; $key);
+		$crate::lua_table!(@fields $lua; $($($rest)*)?);
+	};
+
+	(@set $lua:ident; $key:ident) => {
+		$lua.set_field(-2, const {
+			unsafe {
+				::core::ffi::CStr::from_bytes_with_nul_unchecked(
+					::core::concat! { ::core::stringify! {$key}, '\0' }.as_bytes()
+				)
+			}
+		});
+	};
+
+	($($whatever:tt)*) => {
+		::core::compile_error! {
+			"expected `<Lua> => { <key> = <value>, ... }`"
+		}
+	};
+}
+
+/// Trait for Rust values that can be read from a single value on the Lua stack.
+/// 
+/// Values which borrow from Lua, such as strings, stay valid for as long as the value is on the stack.
+pub trait FromLua<'a>: Sized {
+	/// Reads the value at `stack_pos` of `lua`,
+	/// returning `None` if it isn't of the expected type.
+	/// 
+	/// Unlike [`Lua::get_string`], this doesn't convert numbers to strings,
+	/// so that the value on the stack is not modified.
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self>;
+}
+
+impl FromLua<'_> for bool {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.is_type(stack_pos, StdType::Bool).then(move || lua.get_bool(stack_pos))
+	}
+}
+
+impl FromLua<'_> for Number {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.is_type(stack_pos, StdType::Number).then(move || lua.get_number(stack_pos))
+	}
+}
+
+impl FromLua<'_> for f32 {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		Number::from_lua(lua, stack_pos).map(move |n| n as f32)
+	}
+}
+
+macro_rules! int_from_lua {
+	($($Int:ty)*) => {
+		$(
+			impl FromLua<'_> for $Int {
+				fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+					Number::from_lua(lua, stack_pos)
+						.and_then(number_to_i53)
+						.and_then(move |i| <$Int>::try_from(i).ok())
+				}
+			}
+		)*
+	};
+}
+
+// Integers are only read from numbers which are integers in the range of the integer type.
+int_from_lua!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+impl<'a> FromLua<'a> for &'a [u8] {
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self> {
+		if !lua.is_type(stack_pos, StdType::String) {
+			return None
+		}
+		lua.get_string(stack_pos)
+	}
+}
+
+impl<'a> FromLua<'a> for &'a str {
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self> {
+		<&[u8]>::from_lua(lua, stack_pos).and_then(move |bytes| str::from_utf8(bytes).ok())
+	}
+}
+
+impl<'a> FromLua<'a> for &'a CStr {
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self> {
+		if !lua.is_type(stack_pos, StdType::String) {
+			return None
+		}
+		lua.get_c_string(stack_pos)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl FromLua<'_> for String {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		<&str>::from_lua(lua, stack_pos).map(String::from)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl FromLua<'_> for Vec<u8> {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		<&[u8]>::from_lua(lua, stack_pos).map(Vec::from)
+	}
+}
+
+impl<'a> FromLua<'a> for &'a Vector {
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.try_get_vector(stack_pos)
+	}
+}
+
+impl FromLua<'_> for Vector {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.try_get_vector(stack_pos).cloned()
+	}
+}
+
+#[cfg(feature = "rse-math")]
+impl<'a> FromLua<'a> for &'a QAngle {
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.try_get_angle(stack_pos)
+	}
+}
+
+#[cfg(feature = "rse-math")]
+impl FromLua<'_> for QAngle {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.try_get_angle(stack_pos).cloned()
+	}
+}
+
+/// `nil` is read as `Some(None)`.
+impl<'a, T: FromLua<'a>> FromLua<'a> for Option<T> {
+	fn from_lua(lua: &'a Lua, stack_pos: StackPos) -> Option<Self> {
+		if lua.is_type(stack_pos, StdType::Nil) {
+			return Some(None)
+		}
+		T::from_lua(lua, stack_pos).map(Some)
+	}
+}
+
+/// Functions for reading Rust values.
+impl Lua {
+	/// Reads the value at `stack_pos` as a `T`,
+	/// returning `None` if it isn't of the expected type.
+	pub fn get<'a, T: FromLua<'a>>(&'a self, stack_pos: StackPos) -> Option<T> {
+		T::from_lua(self, stack_pos)
+	}
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use crate::gmod13::testing::MockLua;
+
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push(true);
+		lua.push(2.5);
+		lua.push("text");
+		lua.push(String::from("owned"));
+		lua.push(None::<u32>);
+		lua.push(Some(7u8));
+
+		assert_eq!(lua.get::<bool>(1), Some(true));
+		assert_eq!(lua.get::<Number>(2), Some(2.5));
+		assert_eq!(lua.get::<&str>(3), Some("text"));
+		assert_eq!(lua.get::<String>(4).as_deref(), Some("owned"));
+		assert_eq!(lua.get::<Option<u32>>(5), Some(None));
+		assert_eq!(lua.get::<Option<u8>>(6), Some(Some(7)));
+	}
+
+	#[test]
+	fn mismatched_types() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push(2.5);
+		lua.push(300);
+		lua.push(b"\xFF".as_slice());
+
+		// Numbers are not converted to strings, and integers must be in range.
+		assert_eq!(lua.get::<&[u8]>(1), None);
+		assert_eq!(lua.get::<bool>(1), None);
+		assert_eq!(lua.get::<i32>(1), None);
+		assert_eq!(lua.get::<u8>(2), None);
+		assert_eq!(lua.get::<u16>(2), Some(300));
+		assert_eq!(lua.get::<&str>(3), None);
+		assert_eq!(lua.get::<Vec<u8>>(3), Some(Vec::from([0xFF])));
+		assert!(lua.is_type(1, StdType::Number));
+	}
+
+	#[test]
+	fn tables() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_sequence([Some(1), None, Some(3)]);
+		lua.push_number(3.0);
+		lua.raw_get(-2);
+		assert_eq!(lua.get::<i32>(-1), Some(3));
+		lua.push_number(2.0);
+		lua.raw_get(-3);
+		assert!(lua.is_type(-1, StdType::Nil));
+		lua.pop(3);
+
+		lua.push_map([("a", 1), ("b", 2), ("a", 3)]);
+		lua.get_field(-1, c"a");
+		assert_eq!(lua.get::<i32>(-1), Some(3));
+		lua.pop(2);
+
+		lua_table!(lua => {
+			name = "mod",
+			limits = {
+				max = 4,
+			},
+		});
+		lua.get_field(-1, c"limits");
+		lua.get_field(-1, c"max");
+		assert_eq!(lua.get::<i32>(-1), Some(4));
+	}
+}
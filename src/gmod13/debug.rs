@@ -0,0 +1,81 @@
+use core::fmt;
+
+use super::{
+	Lua, StackPos, StdType,
+};
+
+/// Maximum number of bytes of a Lua string shown in a [`StackDump`].
+const STRING_PREVIEW_LEN: usize = 32;
+
+/// [`Debug`](fmt::Debug)- and [`Display`](fmt::Display)-printable view of every value on the Lua stack,
+/// showing its position, type name and a short preview of its value.
+/// 
+/// See [`Lua::stack_dump`].
+#[derive(Clone, Copy)]
+pub struct StackDump<'a> {
+	lua: &'a Lua,
+}
+
+impl fmt::Display for StackDump<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.lua.dump_stack(f)
+	}
+}
+impl fmt::Debug for StackDump<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+
+/// Functions for inspecting the Lua stack while debugging.
+impl Lua {
+	/// Returns a printable view of every value on the stack.
+	/// 
+	/// See [`StackDump`].
+	pub const fn stack_dump(&self) -> StackDump<'_> {
+		StackDump {
+			lua: self,
+		}
+	}
+
+	/// Writes the position, type name and a short preview of every value on the stack to `w`,
+	/// one value per line, starting from the bottom of the stack.
+	pub fn dump_stack<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+		let top = self.top();
+		if top == 0 {
+			return w.write_str("(empty stack)\n")
+		}
+
+		for stack_pos in 1..=top as StackPos {
+			write!(w, "{stack_pos}: ")?;
+			self.write_preview(w, stack_pos)?;
+			w.write_char('\n')?;
+		}
+		Ok(())
+	}
+
+	fn write_preview<W: fmt::Write + ?Sized>(&self, w: &mut W, stack_pos: StackPos) -> fmt::Result {
+		let ty = self.get_type(stack_pos);
+		let type_name = self.get_type_name(ty);
+		write!(w, "{}", type_name.to_bytes().escape_ascii())?;
+
+		if ty == StdType::Bool {
+			write!(w, " {}", self.get_bool(stack_pos))
+		} else if ty == StdType::Number {
+			write!(w, " {}", self.get_number(stack_pos))
+		} else if ty == StdType::String {
+			// `get_string` is only used for actual strings, since it would convert numbers in-place.
+			let bytes = self.get_string(stack_pos).unwrap_or_default();
+			let preview = &bytes[..bytes.len().min(STRING_PREVIEW_LEN)];
+			write!(w, " \"{}\"", preview.escape_ascii())?;
+			if preview.len() < bytes.len() {
+				write!(w, "... ({} bytes)", bytes.len())?;
+			}
+			Ok(())
+		} else if ty == StdType::UserData || ty == StdType::LightUserData {
+			write!(w, " {:p}", self.get_userdata(stack_pos))
+		} else {
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,63 @@
+use core::{
+	ffi::CStr,
+	fmt::Write,
+};
+
+use super::{
+	chunks::CStrBuf,
+	Lua, StdType,
+};
+
+/// Functions for checking the dependencies of binary modules.
+impl Lua {
+	/// Returns `true` if the dependency `name` is available,
+	/// either as a non-`nil` global variable,
+	/// or as a library that can be loaded with `require(name)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn is_dependency_available(&mut self, name: &CStr) -> bool {
+		self.get_global(name);
+		let is_global = !self.is_type(-1, StdType::Nil);
+		self.pop(1);
+		if is_global {
+			return true
+		}
+
+		self.get_global(c"require");
+		self.push_c_string(name);
+		let is_required = self.pcall(1, 0, 0).is_ok();
+		if !is_required {
+			// Pop the error message.
+			self.pop(1);
+		}
+		is_required
+	}
+
+	/// Checks that every dependency in `requires` is available with [`Lua::is_dependency_available`],
+	/// raising an error that lists every missing one otherwise.
+	/// 
+	/// This is called before [`Module::open`](super::Module::open)
+	/// with [`Module::requires`](super::Module::requires)
+	/// by the functions exported with [`gmod13_module_with!`](crate::gmod13_module_with).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_dependencies(&mut self, requires: &[&CStr]) {
+		let mut message = CStrBuf::new();
+		let mut n_missing: usize = 0;
+		for &name in requires {
+			if self.is_dependency_available(name) {
+				continue
+			}
+
+			let separator = if n_missing == 0 { "missing dependencies: " } else { ", " };
+			let _ = write!(message, "{separator}{}", name.to_bytes().escape_ascii());
+			n_missing += 1;
+		}
+
+		if n_missing > 0 {
+			self.throw_error(message.as_c_str())
+		}
+	}
+}
@@ -0,0 +1,150 @@
+use core::ffi::CStr;
+
+use super::{
+	func::Func,
+	Lua, Ref, StdType,
+};
+
+/// Function which has been replaced with a detour by [`Lua::detour_global`].
+/// 
+/// The detour stays in place until [`restore`](Self::restore) is called,
+/// which should usually happen in [`Module::close`](super::Module::close).
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[must_use = "the detour should be restored with `restore`"]
+pub struct Detour {
+	path: &'static CStr,
+	original: Ref,
+	detour: Ref,
+}
+
+impl Detour {
+	/// Returns the path of the detoured function, as passed to [`Lua::detour_global`].
+	pub const fn path(&self) -> &'static CStr {
+		self.path
+	}
+
+	/// Returns the [`Ref`] to the original function.
+	pub const fn original(&self) -> Ref {
+		self.original
+	}
+
+	/// Puts the original function back in place of the detour,
+	/// and frees the references held by this value.
+	/// 
+	/// Returns `false` if the value at the path is no longer the detour,
+	/// such as when it has been replaced by another detour,
+	/// in which case it is left untouched.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn restore(self, lua: &mut Lua) -> bool {
+		let restored = match lua.push_path_parent(self.path.to_bytes()) {
+			Some(key) => {
+				lua.push_string(key);
+				lua.get_table(-2);
+				lua.push_ref(self.detour);
+				let is_detour = lua.raw_equal(-1, -2);
+				lua.pop(2);
+				if is_detour {
+					lua.push_string(key);
+					lua.push_ref(self.original);
+					lua.set_table(-3);
+				}
+				lua.pop(1);
+				is_detour
+			}
+			None => false,
+		};
+		lua.free_ref(self.detour);
+		lua.free_ref(self.original);
+		restored
+	}
+}
+
+/// Functions for detouring Lua functions.
+impl Lua {
+	/// Replaces the function at `path` with a closure of `wrapper`,
+	/// and returns a [`Detour`] which can put the original function back.
+	/// 
+	/// `path` is a dot-separated path of table fields starting from the global table,
+	/// like `c"print"` or `c"hook.Call"`.
+	/// The original function is the first upvalue of the closure,
+	/// so `wrapper` can push it with [`push_upvalue(0)`](Self::push_upvalue) to call it.
+	/// 
+	/// Returns `None` and changes nothing if the value at `path` isn't a function.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```no_run
+	/// use gmbm::{
+	///     gmod13::{
+	///         Detour,
+	///         Lua,
+	///     },
+	///     gmod13_fn,
+	/// };
+	/// 
+	/// fn detour_print(lua: &mut Lua) -> Option<Detour> {
+	///     lua.detour_global(c"print", gmod13_fn!(lua => {
+	///         let n_args = lua.top();
+	///         lua.push_c_string(c"[detoured]");
+	///         lua.insert(1);
+	///         lua.push_upvalue(0);
+	///         lua.insert(1);
+	///         lua.call(n_args + 1, 0);
+	///         0
+	///     }))
+	/// }
+	/// ```
+	pub fn detour_global(&mut self, path: &'static CStr, wrapper: Func) -> Option<Detour> {
+		let key = self.push_path_parent(path.to_bytes())?;
+		self.push_string(key);
+		self.get_table(-2);
+		if !self.is_type(-1, StdType::Function) {
+			self.pop(2);
+			return None
+		}
+
+		self.push_value(-1);
+		let original = self.create_ref();
+		self.push_closure(wrapper, 1);
+		self.push_value(-1);
+		let detour = self.create_ref();
+
+		self.push_string(key);
+		self.insert(-2);
+		self.set_table(-3);
+		self.pop(1);
+
+		Some(Detour {
+			path,
+			original,
+			detour,
+		})
+	}
+
+	/// Pushes the table which contains the last field of the dot-separated `path`,
+	/// starting from the global table, and returns the name of that field.
+	/// 
+	/// Returns `None` and pushes nothing if any table along the path doesn't exist.
+	fn push_path_parent<'p>(&mut self, path: &'p [u8]) -> Option<&'p [u8]> {
+		let mut fields = path.split(move |&b| b == b'.');
+		let mut key = fields.next()?;
+		self.push_globals();
+		for field in fields {
+			self.push_string(key);
+			self.get_table(-2);
+			self.remove(-2);
+			if !self.is_type(-1, StdType::Table) {
+				self.pop(1);
+				return None
+			}
+			key = field;
+		}
+		Some(key)
+	}
+}
@@ -0,0 +1,146 @@
+//! Basic 2D drawing with the `surface` library, for modules running in the client realm.
+
+use core::ffi::{
+	CStr, c_uint,
+};
+
+use crate::source::Color32;
+
+use super::{
+	Lua, Number, StackPos,
+};
+
+/// Handle to the `surface` library, which is kept on the stack until this is dropped.
+/// 
+/// The functions of the library can only be used while the game is rendering,
+/// such as in a `HUDPaint` hook.
+pub struct Surface<'a> {
+	lua: &'a mut Lua,
+	stack_pos: StackPos,
+}
+
+impl Surface<'_> {
+	/// Sets the color used by the drawing functions that follow, with `surface.SetDrawColor`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_draw_color(&mut self, color: Color32) -> &mut Self {
+		self.call_color(c"SetDrawColor", color)
+	}
+
+	/// Draws a filled rectangle with `surface.DrawRect`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn draw_rect(&mut self, x: Number, y: Number, width: Number, height: Number) -> &mut Self {
+		self.call_numbers(c"DrawRect", &[x, y, width, height])
+	}
+
+	/// Draws the outline of a rectangle with `surface.DrawOutlinedRect`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn draw_outlined_rect(&mut self, x: Number, y: Number, width: Number, height: Number) -> &mut Self {
+		self.call_numbers(c"DrawOutlinedRect", &[x, y, width, height])
+	}
+
+	/// Draws a line from `(x0, y0)` to `(x1, y1)` with `surface.DrawLine`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn draw_line(&mut self, x0: Number, y0: Number, x1: Number, y1: Number) -> &mut Self {
+		self.call_numbers(c"DrawLine", &[x0, y0, x1, y1])
+	}
+
+	/// Sets the font used for drawing text, with `surface.SetFont`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_font(&mut self, font: &CStr) -> &mut Self {
+		self.push_function(c"SetFont");
+		self.lua.push_c_string(font);
+		self.lua.call(1, 0);
+		self
+	}
+
+	/// Sets the color used for drawing text, with `surface.SetTextColor`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_text_color(&mut self, color: Color32) -> &mut Self {
+		self.call_color(c"SetTextColor", color)
+	}
+
+	/// Sets the position where text is drawn next, with `surface.SetTextPos`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_text_pos(&mut self, x: Number, y: Number) -> &mut Self {
+		self.call_numbers(c"SetTextPos", &[x, y])
+	}
+
+	/// Draws `text` with the current font, color and position, with `surface.DrawText`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn draw_text<S: AsRef<[u8]>>(&mut self, text: S) -> &mut Self {
+		self.push_function(c"DrawText");
+		self.lua.push_string(text);
+		self.lua.call(1, 0);
+		self
+	}
+
+	/// Returns the width and height of `text` when drawn with the current font,
+	/// with `surface.GetTextSize`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn text_size<S: AsRef<[u8]>>(&mut self, text: S) -> (Number, Number) {
+		self.push_function(c"GetTextSize");
+		self.lua.push_string(text);
+		self.lua.call(1, 2);
+		let size = (self.lua.get_number(-2), self.lua.get_number(-1));
+		self.lua.pop(2);
+		size
+	}
+
+	fn push_function(&mut self, name: &CStr) {
+		self.lua.get_field(self.stack_pos, name);
+	}
+
+	fn call_numbers(&mut self, name: &CStr, args: &[Number]) -> &mut Self {
+		self.push_function(name);
+		for &n in args {
+			self.lua.push_number(n);
+		}
+		self.lua.call(args.len() as c_uint, 0);
+		self
+	}
+
+	fn call_color(&mut self, name: &CStr, color: Color32) -> &mut Self {
+		let Color32 { r, g, b, a } = color;
+		self.call_numbers(name, &[r as _, g as _, b as _, a as _])
+	}
+}
+
+impl Drop for Surface<'_> {
+	fn drop(&mut self) {
+		self.lua.remove(self.stack_pos)
+	}
+}
+
+/// Functions for drawing on the screen.
+impl Lua {
+	/// Pushes the `surface` library and returns a handle to it.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn surface(&mut self) -> Surface<'_> {
+		self.get_global(c"surface");
+		let stack_pos = self.abs_index(-1);
+		Surface {
+			lua: self,
+			stack_pos,
+		}
+	}
+}
@@ -0,0 +1,75 @@
+use core::ffi::CStr;
+
+use super::{
+	Lua, Number, StackPos,
+};
+
+/// Functions for iterating over sequences and entities.
+impl Lua {
+	/// Calls `f` for every value of the sequence `t[1]` to `t[#t]`, in order,
+	/// where `t` is the table at `stack_pos`.
+	/// 
+	/// While `f` is running, the current value is on the top of the stack,
+	/// and the index of the value is passed to it.
+	/// The stack is restored after every call to `f`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn for_each_in_sequence<F: FnMut(&mut Lua, usize)>(&mut self, stack_pos: StackPos, mut f: F) {
+		let stack_pos = self.abs_index(stack_pos);
+		let len = self.length_of(stack_pos).max(0) as usize;
+		let top = self.top();
+		for i in 1..=len {
+			self.push_number(i as Number);
+			self.raw_get(stack_pos); // t[i]
+			f(self, i);
+			self.drain_to(top);
+		}
+	}
+
+	/// Calls `f` for every player returned by `player.GetAll()`,
+	/// with the player on the top of the stack.
+	/// 
+	/// See [`Lua::for_each_in_sequence`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn for_each_player<F: FnMut(&mut Lua)>(&mut self, f: F) {
+		self.for_each_in_global_list(c"player", c"GetAll", f)
+	}
+
+	/// Calls `f` for every entity returned by `ents.GetAll()`,
+	/// with the entity on the top of the stack.
+	/// 
+	/// See [`Lua::for_each_in_sequence`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn for_each_entity<F: FnMut(&mut Lua)>(&mut self, f: F) {
+		self.for_each_in_global_list(c"ents", c"GetAll", f)
+	}
+
+	/// Calls `f` for every entity of the given class returned by `ents.FindByClass(class)`,
+	/// with the entity on the top of the stack.
+	/// 
+	/// See [`Lua::for_each_in_sequence`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn for_each_entity_of_class<F: FnMut(&mut Lua)>(&mut self, class: &CStr, mut f: F) {
+		self.get_global_field(c"ents", c"FindByClass");
+		self.push_c_string(class);
+		self.call(1, 1);
+		self.for_each_in_sequence(-1, move |lua, _| f(lua));
+		self.pop(1);
+	}
+
+	fn for_each_in_global_list<F: FnMut(&mut Lua)>(&mut self, table: &CStr, func: &CStr, mut f: F) {
+		self.get_global_field(table, func);
+		self.call(0, 1);
+		self.for_each_in_sequence(-1, move |lua, _| f(lua));
+		self.pop(1);
+	}
+}
@@ -1,7 +1,10 @@
 //! Safer APIs for callable native functions.
 
 use core::{
-	ffi::c_int,
+	ffi::{
+		CStr, c_int,
+	},
+	fmt,
 	marker::PhantomData,
 	mem::transmute,
 };
@@ -9,6 +12,7 @@ use core::{
 use super::{
 	CFunc,
 	LuaState, Lua,
+	Number, StackPos,
 };
 
 /// Converts a [`Func`] to a [`CFunc`].
@@ -24,9 +28,18 @@ pub const fn to_c_func(f: Func) -> CFunc {
 /// Use [`to_c_func`] to convert from this type if needed.
 pub type Func = extern "C-unwind" fn(cx: Ctx<'_>) -> Rets;
 
+/// Rust function that returns a [`Result`], which can be called by Lua through [`try_call`].
+/// 
+/// Returning [`Err`] raises the [`LuaThrow`] as a Lua error.
+pub type TryFunc<R = Rets> = fn(lua: &mut Lua) -> Result<R, LuaThrow>;
+
 /// Returns a [`Func`] that can be called by Lua,
 /// given an inline function definition similar to a Rust closure.
 /// 
+/// If the definition is prefixed with `try`,
+/// then the body must return a [`Result`] with [`LuaThrow`] as the error type,
+/// and any [`Err`] is raised as a Lua error with [`try_call`].
+/// 
 /// # Examples
 /// ```
 /// # use gmbm::{gmod13::func::Func, gmod13_fn};
@@ -35,12 +48,37 @@ pub type Func = extern "C-unwind" fn(cx: Ctx<'_>) -> Rets;
 ///     1
 /// });
 /// ```
+/// 
+/// ```
+/// # use gmbm::{gmod13::func::{Func, LuaThrow}, gmod13_fn};
+/// let _: Func = gmod13_fn!(try lua => {
+///     if !lua.is_server() {
+///         return Err(LuaThrow::Message(c"only available on the server"))
+///     }
+///     lua.push_bool(true);
+///     Ok(1)
+/// });
+/// ```
 #[macro_export]
 macro_rules! gmod13_fn {
+	(try $lua:pat => $body:block) => {{
+		extern "C-unwind" fn __gmod13_fn_inline(cx: $crate::gmod13::func::Ctx) -> $crate::gmod13::func::Rets {
+			$crate::gmod13::func::try_call(
+				cx,
+				|$lua: &mut $crate::gmod13::Lua| -> ::core::result::Result<_, $crate::gmod13::func::LuaThrow> { $body },
+			)
+		}
+		__gmod13_fn_inline
+	}};
+
 	($lua:pat => $body:block) => {{
 		extern "C-unwind" fn __gmod13_fn_inline(cx: $crate::gmod13::func::Ctx) -> $crate::gmod13::func::Rets {
-			let $lua = cx.lua();
-			<$crate::gmod13::func::Rets as ::core::convert::From<_>>::from($body)
+			$crate::gmod13::func::call(
+				cx,
+				|$lua: &mut $crate::gmod13::Lua| -> $crate::gmod13::func::Rets {
+					<$crate::gmod13::func::Rets as ::core::convert::From<_>>::from($body)
+				},
+			)
 		}
 		__gmod13_fn_inline
 	}};
@@ -65,7 +103,7 @@ pub struct Ctx<'a> {
 impl<'a> Ctx<'a> {
 	/// Converts this context into [`Lua`].
 	pub const fn lua(self) -> &'a mut Lua {
-		unsafe { Lua::from_mut_ptr(self.ptr) }
+		unsafe { Lua::from_state_ptr(self.ptr) }
 	}
 }
 
@@ -102,6 +140,11 @@ impl Rets {
 	pub const unsafe fn new_unchecked(count: c_int) -> Self {
 		Self { count, }
 	}
+
+	/// Returns the number of returned values.
+	pub const fn count(&self) -> c_int {
+		self.count
+	}
 }
 
 impl From<()> for Rets {
@@ -115,3 +158,226 @@ impl From<usize> for Rets {
 		Self::new(value)
 	}
 }
+
+/// Builder for [`Rets`] which counts the values pushed through it.
+/// 
+/// In debug builds, [`Returns::finish`] checks that the count matches
+/// the number of values actually added to the stack since the builder was created.
+/// 
+/// # Examples
+/// ```
+/// # use gmbm::{gmod13::func::{Func, Returns}, gmod13_fn};
+/// let _: Func = gmod13_fn!(lua => {
+///     let mut ret = Returns::new(lua);
+///     ret.push_bool(true).push_string("ok");
+///     ret.finish()
+/// });
+/// ```
+pub struct Returns<'a> {
+	lua: &'a mut Lua,
+	count: usize,
+	#[cfg(debug_assertions)]
+	base: core::ffi::c_uint,
+}
+
+impl<'a> Returns<'a> {
+	/// Creates a new builder which pushes onto the stack of `lua`.
+	pub fn new(lua: &'a mut Lua) -> Self {
+		Self {
+			#[cfg(debug_assertions)]
+			base: lua.top(),
+			lua,
+			count: 0,
+		}
+	}
+
+	/// Returns the number of values pushed so far.
+	pub const fn count(&self) -> usize {
+		self.count
+	}
+
+	/// Pushes `nil`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_nil(&mut self) -> &mut Self {
+		self.lua.push_nil();
+		self.pushed(1)
+	}
+
+	/// Pushes a boolean.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_bool(&mut self, b: bool) -> &mut Self {
+		self.lua.push_bool(b);
+		self.pushed(1)
+	}
+
+	/// Pushes a number.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_number(&mut self, n: Number) -> &mut Self {
+		self.lua.push_number(n);
+		self.pushed(1)
+	}
+
+	/// Pushes a Lua string.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_string<S: AsRef<[u8]>>(&mut self, bytes: S) -> &mut Self {
+		self.lua.push_string(bytes);
+		self.pushed(1)
+	}
+
+	/// Pushes a copy of the value at `stack_pos`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_value(&mut self, stack_pos: StackPos) -> &mut Self {
+		self.lua.push_value(stack_pos);
+		self.pushed(1)
+	}
+
+	/// Calls `f`, which must push exactly one value.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_with<F: FnOnce(&mut Lua)>(&mut self, f: F) -> &mut Self {
+		f(self.lua);
+		self.pushed(1)
+	}
+
+	/// Calls `f`, which must push exactly as many values as it returns.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_many<F: FnOnce(&mut Lua) -> usize>(&mut self, f: F) -> &mut Self {
+		let n = f(self.lua);
+		self.pushed(n)
+	}
+
+	/// Finishes building, returning the number of values pushed.
+	/// 
+	/// # Panics
+	/// In debug builds, panics if the stack has grown by a different number of values.
+	pub fn finish(self) -> Rets {
+		#[cfg(debug_assertions)]
+		{
+			let actual = self.lua.top().saturating_sub(self.base) as usize;
+			debug_assert_eq!(actual, self.count, "number of returned values doesn't match the stack");
+		}
+		Rets::new(self.count)
+	}
+
+	fn pushed(&mut self, n: usize) -> &mut Self {
+		self.count += n;
+		self
+	}
+}
+
+impl From<Returns<'_>> for Rets {
+	fn from(value: Returns<'_>) -> Self {
+		value.finish()
+	}
+}
+
+/// Calls `f` with the [`Lua`] of `cx`,
+/// converting the returned value into [`Rets`], or raising the [`LuaThrow`] as a Lua error.
+/// 
+/// With the `metrics` feature, calls to the C++ API made by `f` are attributed to the location this is called from,
+/// as described in the `metrics` module.
+/// 
+/// # Errors
+/// The inner Lua state will raise an [error](crate::errors) if `f` returns [`Err`].
+#[cfg_attr(feature = "metrics", track_caller)]
+pub fn try_call<R, F>(cx: Ctx<'_>, f: F) -> Rets
+where
+	R: Into<Rets>,
+	F: FnOnce(&mut Lua) -> Result<R, LuaThrow>,
+{
+	#[cfg(feature = "metrics")]
+	let _entered = super::metrics::Entered::new(core::panic::Location::caller());
+	let lua = cx.lua();
+	match f(lua) {
+		Ok(rets) => lua.debug_check_rets(rets.into()),
+		Err(throw) => throw.throw(lua),
+	}
+}
+
+/// Calls `f` with the [`Lua`] of `cx`, returning its [`Rets`].
+/// 
+/// This is what [`gmod13_fn!`](crate::gmod13_fn) expands to.
+/// With the `debug-stack` feature, it also checks that the function returns at most as many values as it has on the stack.
+/// 
+/// With the `metrics` feature, calls to the C++ API made by `f` are attributed to the location this is called from,
+/// as described in the `metrics` module.
+/// 
+/// # Errors
+/// With the `debug-stack` feature, the inner Lua state will raise an [error](crate::errors)
+/// if `f` returns more values than there are on the stack.
+#[inline(always)]
+#[cfg_attr(feature = "metrics", track_caller)]
+pub fn call<F: FnOnce(&mut Lua) -> Rets>(cx: Ctx<'_>, f: F) -> Rets {
+	#[cfg(feature = "metrics")]
+	let _entered = super::metrics::Entered::new(core::panic::Location::caller());
+	let lua = cx.lua();
+	let rets = f(lua);
+	lua.debug_check_rets(rets)
+}
+
+/// Error which is raised in Lua when returned from a [`TryFunc`] or a `try` [`gmod13_fn!`](crate::gmod13_fn).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LuaThrow {
+	/// Raise an error with a message, like [`Lua::throw_error`].
+	Message(&'static CStr),
+	/// Raise an error related to an argument, like [`Lua::arg_error`].
+	Arg(c_int, &'static CStr),
+	/// Raise an error about an argument having the wrong type, like [`Lua::type_error`],
+	/// given the name of the expected type.
+	Type(c_int, &'static CStr),
+	/// Raise the value at the top of the stack as the error, with the Lua `error` function.
+	Value,
+}
+
+impl LuaThrow {
+	/// Raises this error in `lua`.
+	/// 
+	/// # Errors
+	/// The inner Lua state will raise an [error](crate::errors).
+	pub fn throw(self, lua: &mut Lua) -> ! {
+		match self {
+			Self::Message(message) => lua.throw_error(message),
+			Self::Arg(arg_num, message) => lua.arg_error(arg_num, message),
+			Self::Type(arg_num, expected) => lua.type_error(arg_num, expected),
+			Self::Value => {
+				lua.get_global(c"error");
+				lua.insert(-2);
+				lua.call(1, 0);
+				// `error` never returns.
+				lua.throw_error(c"error did not raise an error")
+			}
+		}
+	}
+}
+
+impl From<&'static CStr> for LuaThrow {
+	fn from(value: &'static CStr) -> Self {
+		Self::Message(value)
+	}
+}
+
+impl fmt::Display for LuaThrow {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Message(message) => write!(f, "{}", message.to_bytes().escape_ascii()),
+			Self::Arg(arg_num, message) => write!(f, "bad argument #{arg_num} ({})", message.to_bytes().escape_ascii()),
+			Self::Type(arg_num, expected) => write!(f, "bad argument #{arg_num} ({} expected)", expected.to_bytes().escape_ascii()),
+			Self::Value => f.write_str("error value on the stack"),
+		}
+	}
+}
+
+impl core::error::Error for LuaThrow {}
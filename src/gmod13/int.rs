@@ -0,0 +1,79 @@
+use core::ffi::c_int;
+
+use super::{
+	Lua, Number, StackPos, StdType,
+};
+
+/// Largest integer that a [`Number`] can represent exactly, along with all integers below it.
+pub const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// Smallest integer that a [`Number`] can represent exactly, along with all integers above it.
+pub const MIN_SAFE_INTEGER: i64 = -MAX_SAFE_INTEGER;
+
+/// Returns `n` as an integer if it is an integer in the range of [`MIN_SAFE_INTEGER`] to [`MAX_SAFE_INTEGER`].
+/// 
+/// # Examples
+/// ```
+/// # use gmbm::gmod13::{number_to_i53, MAX_SAFE_INTEGER};
+/// assert_eq!(number_to_i53(42.0), Some(42));
+/// assert_eq!(number_to_i53(0.5), None);
+/// assert_eq!(number_to_i53(MAX_SAFE_INTEGER as f64), Some(MAX_SAFE_INTEGER));
+/// assert_eq!(number_to_i53(MAX_SAFE_INTEGER as f64 + 1.0), None);
+/// ```
+pub fn number_to_i53(n: Number) -> Option<i64> {
+	const MIN: Number = MIN_SAFE_INTEGER as Number;
+	const MAX: Number = MAX_SAFE_INTEGER as Number;
+	if (MIN..=MAX).contains(&n) && n as i64 as Number == n {
+		Some(n as i64)
+	} else {
+		None
+	}
+}
+
+/// Functions for storing integers in Lua with their *numerical* value.
+/// 
+/// Unlike the [`Bits`](super::Bits) functions, these keep integers usable in Lua arithmetic,
+/// but only for integers that a [`Number`] can represent exactly.
+impl Lua {
+	/// Pushes `i` onto the stack as a Lua number.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// including if `i` is out of the range of [`MIN_SAFE_INTEGER`] to [`MAX_SAFE_INTEGER`].
+	pub fn push_integer(&self, i: i64) {
+		if !(MIN_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&i) {
+			self.throw_error(c"integer cannot be represented exactly as a number")
+		}
+		self.push_number(i as Number)
+	}
+
+	/// Returns the Lua number at `stack_pos` as an integer,
+	/// or `None` if the value isn't a number,
+	/// or if it isn't an integer in the range of [`MIN_SAFE_INTEGER`] to [`MAX_SAFE_INTEGER`].
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn get_i53(&self, stack_pos: StackPos) -> Option<i64> {
+		if self.is_type(stack_pos, StdType::Number) {
+			number_to_i53(self.get_number(stack_pos))
+		} else {
+			None
+		}
+	}
+
+	/// Returns the Lua number at `arg` as an integer.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the value isn't a number,
+	/// or if it isn't an integer in the range of [`MIN_SAFE_INTEGER`] to [`MAX_SAFE_INTEGER`].
+	pub fn check_i53(&self, arg: c_int) -> i64 {
+		match number_to_i53(self.check_number(arg)) {
+			Some(i) => i,
+			None => self.arg_error(arg, c"number has no exact integer representation"),
+		}
+	}
+}
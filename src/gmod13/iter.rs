@@ -0,0 +1,88 @@
+use core::{
+	ffi::c_uint,
+	ptr::drop_in_place,
+};
+
+use super::{
+	func::{
+		Ctx, Rets,
+	},
+	Lua, ToLua,
+};
+
+/// Functions for exposing Rust iterators to Lua.
+impl Lua {
+	/// Pushes a function which returns the next item of `iter` on each call,
+	/// and `nil` once it is exhausted,
+	/// so that it can be used in a generic `for` loop:
+	/// 
+	/// ```lua
+	/// for v in module.items() do print(v) end
+	/// ```
+	/// 
+	/// The iterator is stored in a userdata upvalue of the function,
+	/// and is dropped when the function is garbage collected.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_iterator<I>(&mut self, iter: I)
+	where
+		I: Iterator + 'static,
+		I::Item: ToLua,
+	{
+		let size = iter_userdata_size::<I>();
+		let Ok(size) = c_uint::try_from(size) else {
+			self.throw_error(c"iterator is too large")
+		};
+
+		let ud = unsafe { self.new_userdata_raw(size) };
+		if ud.is_null() {
+			self.throw_error(c"failed to allocate iterator")
+		}
+		unsafe { iter_ptr::<I>(ud.cast()).write(iter) };
+
+		self.create_table();
+		self.push_function(iter_gc::<I>);
+		self.set_field(-2, c"__gc");
+		self.set_metatable(-2);
+
+		self.push_closure(iter_next::<I>, 1);
+	}
+}
+
+/// Returns the size of userdata needed to store `I` at any alignment.
+const fn iter_userdata_size<I>() -> usize {
+	size_of::<I>() + align_of::<I>() - 1
+}
+
+/// Returns a pointer to the iterator stored in the userdata at `ud`.
+/// 
+/// # Safety
+/// `ud` must point to userdata of at least [`iter_userdata_size::<I>()`](iter_userdata_size) bytes.
+unsafe fn iter_ptr<I>(ud: *mut u8) -> *mut I {
+	unsafe { ud.add(ud.align_offset(align_of::<I>())).cast() }
+}
+
+extern "C-unwind" fn iter_next<I>(cx: Ctx<'_>) -> Rets
+where
+	I: Iterator,
+	I::Item: ToLua,
+{
+	let lua = cx.lua();
+	lua.push_upvalue(0);
+	let ud = lua.get_userdata(-1);
+	lua.pop(1);
+	// The upvalue keeps the userdata alive for as long as this function exists.
+	let iter = unsafe { &mut *iter_ptr::<I>(ud.cast()) };
+	iter.next().push_to(lua);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn iter_gc<I>(cx: Ctx<'_>) -> Rets {
+	let lua = cx.lua();
+	let ud = lua.get_userdata(1);
+	if !ud.is_null() {
+		unsafe { drop_in_place(iter_ptr::<I>(ud.cast())) }
+	}
+	Rets::ZERO
+}
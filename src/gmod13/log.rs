@@ -0,0 +1,339 @@
+//! Logging to the Garry's Mod console.
+
+use core::{
+	ffi::c_uint,
+	fmt::{
+		self, Write,
+	},
+};
+
+use super::{
+	chunks::ChunkWriter,
+	Lua, Number, StdType, StackPos,
+};
+
+/// Severity of a message logged with [`Lua::log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+	/// Failure which the binary module can't recover from.
+	Error,
+	/// Unexpected situation which the binary module recovered from.
+	Warn,
+	/// Notable event, such as the binary module being loaded.
+	Info,
+	/// Details which are useful when debugging the binary module.
+	Debug,
+	/// Very verbose details, such as every call into the binary module.
+	Trace,
+}
+
+impl Level {
+	/// Returns the name of this level, as shown in the console.
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Error => "ERROR",
+			Self::Warn => "WARN",
+			Self::Info => "INFO",
+			Self::Debug => "DEBUG",
+			Self::Trace => "TRACE",
+		}
+	}
+
+	/// Returns the RGB color used for messages of this level.
+	pub const fn color(self) -> [u8; 3] {
+		match self {
+			Self::Error => [255, 90, 90],
+			Self::Warn => [255, 210, 80],
+			Self::Info => [230, 230, 230],
+			Self::Debug => [150, 200, 255],
+			Self::Trace => [150, 150, 150],
+		}
+	}
+}
+
+impl fmt::Display for Level {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// Functions for logging to the game console.
+impl Lua {
+	/// Prints a message to the game console, prefixed with the name of the [`Level`]
+	/// and colored depending on it.
+	/// 
+	/// The message is printed with `MsgC`, or `Msg` if the former is not available.
+	/// It is formatted in chunks, so no allocation is required.
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::{gmod13::log::Level, prelude::*};
+	/// fn warn_about(lua: &mut Lua, n: LuaNumber) {
+	///     lua.log(Level::Warn, format_args!("unexpected number: {n}"));
+	/// }
+	/// ```
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn log(&mut self, level: Level, args: fmt::Arguments<'_>) {
+		self.push_globals();
+		self.get_field(-1, c"MsgC");
+		let n_prefix_args: c_uint = if self.is_type(-1, StdType::Function) {
+			let [r, g, b] = level.color();
+			self.create_table();
+			for (value, key) in [(r, c"r"), (g, c"g"), (b, c"b"), (255, c"a")] {
+				self.push_number(value as Number);
+				self.set_field(-2, key);
+			}
+			1
+		} else {
+			self.pop(1);
+			self.get_field(-1, c"Msg");
+			0
+		};
+
+		let func_pos = -1 - n_prefix_args as StackPos;
+		if self.is_type(func_pos, StdType::Function) {
+			let mut writer = ChunkWriter::new(|chunk: &[u8]| {
+				// MsgC(color, chunk) or Msg(chunk)
+				for _ in 0..=n_prefix_args {
+					self.push_value(func_pos);
+				}
+				self.push_string(chunk);
+				self.call(n_prefix_args + 1, 0);
+			});
+			let _ = writeln!(writer, "[{level}] {args}");
+			writer.finish();
+		}
+
+		self.pop(2 + n_prefix_args);
+	}
+}
+
+#[cfg(feature = "log")]
+pub use adapter::*;
+#[cfg(feature = "log")]
+mod adapter {
+	#[cfg(feature = "alloc")]
+	use alloc::{
+		boxed::Box,
+		format,
+		string::String,
+		vec::Vec,
+	};
+	use core::{
+		ptr::null_mut,
+		sync::atomic::{
+			AtomicPtr, AtomicUsize, Ordering,
+		},
+	};
+
+	use super::{
+		super::{
+			thread::current_thread_id,
+			Lua,
+		},
+		Level,
+	};
+
+	static LUA: AtomicPtr<Lua> = AtomicPtr::new(null_mut());
+
+	/// Thread that the attached Lua state is used from.
+	static OWNER: AtomicUsize = AtomicUsize::new(0);
+
+	/// Adapter which routes messages from the `log` crate to the game console with [`Lua::log`].
+	/// 
+	/// Messages are only printed while a Lua state is [attached](Self::attach).
+	/// 
+	/// The Lua state is never used from other threads than the one that it was attached on.
+	/// With the `alloc` feature, messages logged on other threads are queued,
+	/// and printed on that thread before the next message logged on it, or when the logger is flushed;
+	/// otherwise, they're dropped.
+	#[derive(Debug, Clone, Copy)]
+	pub struct ConsoleLogger;
+
+	impl ConsoleLogger {
+		/// Installs this logger as the global logger of the `log` crate,
+		/// with the given maximum level.
+		pub fn install(max_level: ::log::LevelFilter) -> Result<(), ::log::SetLoggerError> {
+			::log::set_logger(&ConsoleLogger)?;
+			::log::set_max_level(max_level);
+			Ok(())
+		}
+
+		/// Makes the logger print messages to the console of `lua`
+		/// on the thread that calls this.
+		/// 
+		/// # Safety
+		/// `lua` must stay valid until [`detach`](Self::detach) is called,
+		/// and must only be used from the thread that calls this.
+		pub unsafe fn attach(lua: &mut Lua) {
+			OWNER.store(current_thread_id(), Ordering::Release);
+			LUA.store(lua, Ordering::Release)
+		}
+
+		/// Stops the logger from printing messages to the console of the attached Lua state,
+		/// and discards the messages which are still queued.
+		pub fn detach() {
+			LUA.store(null_mut(), Ordering::Release);
+			#[cfg(feature = "alloc")]
+			drop(take_queued());
+		}
+	}
+
+	impl From<::log::Level> for Level {
+		fn from(value: ::log::Level) -> Self {
+			match value {
+				::log::Level::Error => Self::Error,
+				::log::Level::Warn => Self::Warn,
+				::log::Level::Info => Self::Info,
+				::log::Level::Debug => Self::Debug,
+				::log::Level::Trace => Self::Trace,
+			}
+		}
+	}
+
+	/// Returns the attached Lua state if this is the thread that it was attached on.
+	/// 
+	/// # Safety
+	/// The returned reference must not be held while another reference to the state is in use.
+	unsafe fn attached_lua<'a>() -> Option<&'a mut Lua> {
+		let lua = LUA.load(Ordering::Acquire);
+		if lua.is_null() || OWNER.load(Ordering::Acquire) != current_thread_id() {
+			return None
+		}
+		// SAFETY: `attach` requires the pointer to be valid and used from this thread.
+		Some(unsafe { &mut *lua })
+	}
+
+	impl ::log::Log for ConsoleLogger {
+		fn enabled(&self, _: &::log::Metadata<'_>) -> bool {
+			!LUA.load(Ordering::Acquire).is_null()
+		}
+
+		fn log(&self, record: &::log::Record<'_>) {
+			let level = Level::from(record.level());
+			if let Some(lua) = unsafe { attached_lua() } {
+				#[cfg(feature = "alloc")]
+				print_queued(lua);
+				lua.log(level, format_args!("{}: {}", record.target(), record.args()))
+			} else if self.enabled(record.metadata()) {
+				#[cfg(feature = "alloc")]
+				queue(level, format!("{}: {}", record.target(), record.args()));
+			}
+		}
+
+		fn flush(&self) {
+			#[cfg(feature = "alloc")]
+			if let Some(lua) = unsafe { attached_lua() } {
+				print_queued(lua)
+			}
+		}
+	}
+
+	/// Message logged on another thread than the one that the Lua state was attached on.
+	#[cfg(feature = "alloc")]
+	struct Queued {
+		level: Level,
+		message: String,
+		next: *mut Queued,
+	}
+
+	/// Most recently queued message, which links to the ones queued before it.
+	#[cfg(feature = "alloc")]
+	static QUEUE: AtomicPtr<Queued> = AtomicPtr::new(null_mut());
+
+	/// Queues `message` to be printed on the thread that the Lua state was attached on.
+	#[cfg(feature = "alloc")]
+	fn queue(level: Level, message: String) {
+		let node = Box::into_raw(Box::new(Queued {
+			level,
+			message,
+			next: null_mut(),
+		}));
+		let mut head = QUEUE.load(Ordering::Relaxed);
+		loop {
+			// SAFETY: `node` isn't shared with other threads until it is the head of the queue.
+			unsafe { (*node).next = head };
+			match QUEUE.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+				Ok(_) => break,
+				Err(current) => head = current,
+			}
+		}
+	}
+
+	/// Removes all queued messages, and returns them in the order that they were queued in.
+	#[cfg(feature = "alloc")]
+	fn take_queued() -> Vec<(Level, String)> {
+		let mut node = QUEUE.swap(null_mut(), Ordering::Acquire);
+		let mut messages = Vec::new();
+		while !node.is_null() {
+			// SAFETY: Nodes are only freed after they have been removed from the queue.
+			let queued = unsafe { Box::from_raw(node) };
+			node = queued.next;
+			messages.push((queued.level, queued.message));
+		}
+		messages.reverse();
+		messages
+	}
+
+	/// Prints all queued messages to the console of `lua`.
+	#[cfg(feature = "alloc")]
+	fn print_queued(lua: &mut Lua) {
+		for (level, message) in take_queued() {
+			lua.log(level, format_args!("{message}"))
+		}
+	}
+}
+
+#[cfg(all(test, feature = "testing", feature = "log"))]
+mod tests {
+	extern crate std;
+
+	use std::{
+		string::String,
+		sync::Mutex,
+		thread,
+	};
+
+	use ::log::{
+		Log, Record,
+	};
+
+	use super::{
+		super::{
+			func::{
+				Ctx, Rets,
+			},
+			testing::MockLua,
+		},
+		ConsoleLogger,
+	};
+
+	static PRINTED: Mutex<String> = Mutex::new(String::new());
+
+	extern "C-unwind" fn msg(cx: Ctx<'_>) -> Rets {
+		let lua = cx.lua();
+		let chunk = lua.get_string(1).unwrap_or_default();
+		PRINTED.lock().unwrap().push_str(&String::from_utf8_lossy(chunk));
+		Rets::ZERO
+	}
+
+	#[test]
+	fn queue_messages_from_other_threads() {
+		let mut mock = MockLua::new();
+		mock.set_global_function(c"Msg", msg);
+		unsafe { ConsoleLogger::attach(mock.lua()) };
+
+		thread::spawn(|| {
+			ConsoleLogger.log(&Record::builder().target("worker").args(format_args!("first")).build());
+			ConsoleLogger.log(&Record::builder().target("worker").args(format_args!("second")).build());
+		}).join().unwrap();
+		assert!(PRINTED.lock().unwrap().is_empty());
+
+		ConsoleLogger.flush();
+		assert_eq!(*PRINTED.lock().unwrap(), "[INFO] worker: first\n[INFO] worker: second\n");
+
+		ConsoleLogger::detach();
+	}
+}
@@ -5,12 +5,16 @@ use core::{
 		CStr,
 		c_int, c_uint, c_void,
 	},
-	fmt,
+	fmt::{
+		self, Write,
+	},
 	mem::MaybeUninit,
 	ops::{
 		Deref, DerefMut,
 	},
-	ptr::null_mut,
+	ptr::{
+		NonNull, null_mut,
+	},
 	slice::{
 		from_raw_parts as slice_from_raw_parts,
 		from_raw_parts_mut as slice_from_raw_parts_mut,
@@ -25,8 +29,40 @@ use crate::source::{
 use super::func::*;
 use super::*;
 
+/// Calls a virtual function of [`LuaBase`] with [`virtual_call!`],
+/// counting the call with the `metrics` feature.
+macro_rules! lua_call {
+	($l:ident => $method:ident $args:tt) => {{
+		#[cfg(feature = "metrics")]
+		super::metrics::record(const { super::metrics::Category::of(stringify!($method)) });
+		virtual_call!($l => $method $args)
+	}};
+}
+
 /// Interface for the Lua environment of
 /// the same Garry's Mod version that uses `gmod13_open` and `gmod13_close` functions for binary modules.
+/// 
+/// # Mutability
+/// Methods which create new Lua values, such as tables, strings and userdata,
+/// or which may run Lua code, such as metamethods, take `&mut self`.
+/// Every other method takes `&self`, even if it changes the stack,
+/// so that pointers returned by Lua, such as strings, can be used while values are pushed and popped.
+/// This includes methods which push values that don't need to be created,
+/// such as [`push_nil`](Self::push_nil), [`push_number`](Self::push_number),
+/// [`push_value`](Self::push_value) and [`push_ref`](Self::push_ref).
+/// 
+/// The exceptions are [`get_string`](Self::get_string), [`get_c_string`](Self::get_c_string),
+/// [`check_string`](Self::check_string), [`check_utf8`](Self::check_utf8)
+/// and the methods built on them.
+/// They convert a number at the given position to a string in place,
+/// which creates the string, and so may run the garbage collector and the `__gc` metamethods of unreachable values.
+/// They still take `&self`, so that several strings can be read at once.
+/// 
+/// The garbage collector never frees values which are on the stack,
+/// so a value returned by a `&self` method stays valid for as long as the value that it points into stays on the stack,
+/// which is what [`Lua::with_no_gc`] relies on.
+/// The borrow checker doesn't know which values are on the stack,
+/// so a string must still not be used after it's popped.
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct Lua {
@@ -36,12 +72,43 @@ pub struct Lua {
 impl Lua {
 	/// Returns a mutable reference to the Lua state provided by Garry's Mod.
 	/// 
+	/// See [`from_state_ptr_checked`](Self::from_state_ptr_checked) for a version which rejects null pointers.
+	/// 
 	/// # Safety
-    /// `ptr` must be a valid Lua state from the Garry's Mod version this structure targets.
-	pub const unsafe fn from_mut_ptr<'a>(ptr: *mut LuaState) -> &'a mut Self {
+	/// `ptr` must be a valid Lua state from the Garry's Mod version this structure targets,
+	/// and must not be used through any other reference for the lifetime `'a`.
+	pub const unsafe fn from_state_ptr<'a>(ptr: *mut LuaState) -> &'a mut Self {
 		unsafe { Self::from_luabase_mut((*ptr).luabase.as_mut()) }
 	}
 
+	/// Returns a mutable reference to the Lua state provided by Garry's Mod,
+	/// or `None` if `ptr` is null, if the state doesn't have the [layout](LuaState#layout) of a LuaJIT thread,
+	/// or if the state doesn't have a pointer to its [`LuaBase`].
+	/// 
+	/// # Safety
+	/// If `ptr` is not null, then it must be valid for reads of a [`LuaState`],
+	/// and if its [`luabase`](LuaState::luabase) is not null,
+	/// then it must be a valid Lua state from the Garry's Mod version this structure targets,
+	/// which must not be used through any other reference for the lifetime `'a`.
+	pub unsafe fn from_state_ptr_checked<'a>(ptr: *mut LuaState) -> Option<&'a mut Self> {
+		if ptr.is_null() || !unsafe { LuaState::has_thread_header(ptr) } {
+			return None
+		}
+		// The field is read as a raw pointer, since it may be null in a state that isn't valid.
+		let luabase = unsafe { (&raw const (*ptr).luabase).cast::<*mut LuaBase>().read() };
+		let luabase = unsafe { luabase.as_mut() }?;
+		Some(unsafe { Self::from_luabase_mut(luabase) })
+	}
+
+	/// Returns a mutable reference to the Lua state provided by Garry's Mod.
+	/// 
+	/// # Safety
+	/// See [`from_state_ptr`](Self::from_state_ptr).
+	#[deprecated = "use `Lua::from_state_ptr` instead"]
+	pub const unsafe fn from_mut_ptr<'a>(ptr: *mut LuaState) -> &'a mut Self {
+		unsafe { Self::from_state_ptr(ptr) }
+	}
+
 	/// See [`LuaState`].
 	/// 
 	/// # Safety
@@ -74,7 +141,7 @@ impl Lua {
 
 	/// Returns the amount of values on the stack.
 	pub fn top(&self) -> c_uint {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => top()) as _) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => top()) as _) }
 	}
 
 	/// Pushes a copy of the value at `stack_pos` to the stack.
@@ -82,13 +149,15 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_value(&self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push(stack_pos))) }
+		self.debug_check_pos(stack_pos);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push(stack_pos))) }
 	}
 
 	/// Pops `n` values from the stack.
 	pub fn pop(&self, amt: c_uint) {
+		self.debug_check_values(amt);
 		// SAFETY: To-be-closed slots aren't a thing in Lua 5.1 and LuaJIT.
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => pop(amt as _))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => pop(amt as _))) }
 	}
 
 	/// Sets the metatable for the value at `stack_pos` to the value popped from the stack.
@@ -96,7 +165,8 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn set_metatable(&self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => set_meta_table(stack_pos))) }
+		self.debug_check_pos_values(stack_pos, 1);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => set_meta_table(stack_pos))) }
 	}
 
 	/// Pushes the metatable of the value at `stack_pos` on the stack, returning `true`,
@@ -105,14 +175,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn get_metatable(&self, stack_pos: StackPos) -> bool {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => get_meta_table(stack_pos))) }
-	}
-
-	/// Returns `true` if the values at `a` and `b` are equal.
-	/// 
-	/// See also [`Lua::raw_equal`].
-	pub fn equal(&self, a: StackPos, b: StackPos) -> bool {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => equal(a, b)) != 0) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => get_meta_table(stack_pos))) }
 	}
 
 	/// Returns `true` if the values at `a` and `b` are equal,
@@ -120,24 +183,33 @@ impl Lua {
 	/// 
 	/// See also [`Lua::equal`].
 	pub fn raw_equal(&self, a: StackPos, b: StackPos) -> bool {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => raw_equal(a, b)) != 0) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => raw_equal(a, b)) != 0) }
 	}
 	
 	/// Moves the value at the top of the stack into `stack_pos`,
 	/// shifting upwards any elements above `stack_pos`.
+	/// 
+	/// `stack_pos` must refer to a value on the stack, and can't be a pseudo-index.
 	pub fn insert(&self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => insert(stack_pos))) }
+		self.debug_check_stack_pos(stack_pos);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => insert(stack_pos))) }
 	}
 
 	/// Removes the value at `stack_pos`,
 	/// shifting values above `stack_pos` downwards.
+	/// 
+	/// `stack_pos` must refer to a value on the stack, and can't be a pseudo-index.
 	pub fn remove(&self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => remove(stack_pos))) }
+		self.debug_check_stack_pos(stack_pos);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => remove(stack_pos))) }
 	}
 
 	/// Throws an error and ceases execution of the function.
-	pub fn throw_error(&self, message: &'static CStr) -> ! {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => throw_error(message.as_ptr()))) }
+	/// 
+	/// The message is copied by Lua before the error is raised,
+	/// so it may be built at runtime, such as in a buffer on the stack.
+	pub fn throw_error(&self, message: &CStr) -> ! {
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => throw_error(message.as_ptr()))) }
 	}
 
 	/// Throws an error if the value at `stack_pos` is not of the given [`Type`].
@@ -145,36 +217,36 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn check_type<Ty: Into<Type>>(&self, stack_pos: StackPos, ty: Ty) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => check_type(stack_pos, ty.into().0))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => check_type(stack_pos, ty.into().0))) }
 	}
 
 	/// Throws an error related to argument `arg_num` and cease execution of the function.
-	pub fn arg_error(&self, arg_num: c_int, message: &'static CStr) -> ! {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => arg_error(arg_num, message.as_ptr()))) }
+	/// 
+	/// The message is copied by Lua before the error is raised,
+	/// so it may be built at runtime, such as in a buffer on the stack.
+	pub fn arg_error(&self, arg_num: c_int, message: &CStr) -> ! {
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => arg_error(arg_num, message.as_ptr()))) }
 	}
 
 	/// Without metamethods, pushes the value of `t[key]`, where
 	/// `t` is the value at the given index,
 	/// and `key` is the value popped from the stack.
 	pub fn raw_get(&self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => raw_get(stack_pos))) }
-	}
-
-	/// Without metamethods, does `t[key] = value`, where
-	/// `t` is the value at the given index,
-	/// `value` is the value popped from the stack,
-	/// and `key` is the value just below the top.
-	pub fn raw_set(&self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => raw_set(stack_pos))) }
+		self.debug_check_pos_values(stack_pos, 1);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => raw_get(stack_pos))) }
 	}
 
 	/// Returns the contents of the Lua string at `stack_pos`,
 	/// converting any Lua number at that position to a string in the process,
 	/// and returns `None` if the value can't be converted to a Lua string.
+	/// 
+	/// Converting a number may run the garbage collector, as described in [Mutability](Self#mutability).
 	pub fn get_string(&self, stack_pos: StackPos) -> Option<&[u8]> {
 		let mut len = MaybeUninit::uninit();
+		// `len` must not be moved into the closure, since `MaybeUninit<c_uint>` is `Copy`.
+		let len_ptr = len.as_mut_ptr();
 		let string_ptr = unsafe {
-			self.with_luabase_mut(move |l| virtual_call!(l => get_string(stack_pos, len.as_mut_ptr())))
+			self.with_luabase_mut(move |l| lua_call!(l => get_string(stack_pos, len_ptr)))
 		};
 		if !string_ptr.is_null() {
 			// SAFETY: If `string_ptr` isn't null, then it should be valid for reads, and `len` should be initialized.
@@ -187,8 +259,10 @@ impl Lua {
 	/// Returns the Lua C string at `stack_pos`,
 	/// converting any Lua number at that position to a string in the process,
 	/// and returns `None` if the value can't be converted to a Lua string.
+	/// 
+	/// Converting a number may run the garbage collector, as described in [Mutability](Self#mutability).
 	pub fn get_c_string(&self, stack_pos: StackPos) -> Option<&CStr> {
-		let string_ptr = unsafe { self.with_luabase_mut(move |l| virtual_call!(l => get_string(stack_pos, null_mut()))) };
+		let string_ptr = unsafe { self.with_luabase_mut(move |l| lua_call!(l => get_string(stack_pos, null_mut()))) };
 		if !string_ptr.is_null() {
 			// SAFETY: If `string_ptr` isn't null, then it should be a valid C string.
 			unsafe { Some(CStr::from_ptr(string_ptr)) }
@@ -200,32 +274,32 @@ impl Lua {
 	/// Returns the [`Number`] at `stack_pos`,
 	/// or `0.0` if the value isn't a Lua number.
 	pub fn get_number(&self, stack_pos: StackPos) -> Number {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => get_number(stack_pos))) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => get_number(stack_pos))) }
 	}
 
 	/// Returns `true` if the value at `stack_pos` is truthy.
 	pub fn get_bool(&self, stack_pos: StackPos) -> bool {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => get_bool(stack_pos))) } 
+		unsafe { self.with_luabase(move |l| lua_call!(l => get_bool(stack_pos))) } 
 	}
 
 	/// Returns the [`CFunc`] at `stack_pos`,
 	/// or a null pointer if the value isn't a C function.
 	pub fn get_c_function(&self, stack_pos: StackPos) -> Option<CFunc> {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => get_c_function(stack_pos))) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => get_c_function(stack_pos))) }
 	}
 
 	/// Returns the non-null pointer to the userdata at `stack_pos`,
 	/// or a null pointer if the value isn't userdata.
 	pub fn get_userdata(&self, stack_pos: StackPos) -> *mut c_void {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => get_userdata(stack_pos))) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => get_userdata(stack_pos))) }
 	}
 
-	/// Pushes a `nil` onto the stack as a Lua string.
+	/// Pushes `nil` onto the stack.
 	/// 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_nil(&self) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_nil())) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_nil())) }
 	}
 
 	/// Pushes the given [`Number`] onto the stack.
@@ -233,7 +307,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_number(&self, n: Number) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_number(n))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_number(n))) }
 	}
 
 	/// Pushes the given boolean onto the stack.
@@ -241,15 +315,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_bool(&self, b: bool) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_bool(b))) }
-	}
-
-	/// Pushes the given C function onto the stack.
-	/// 
-	/// # Errors
-	/// The inner Lua state may raise an [error](crate::errors).
-	pub fn push_c_function(&self, func: CFunc) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_c_function(func))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_bool(b))) }
 	}
 
 	/// Pushes the light userdata `ptr` onto the stack.
@@ -262,7 +328,7 @@ impl Lua {
 	/// and so the exact guarantees for `ptr` vary depending on the use-case.
 	/// Consider using full userdata instead if you can.
 	pub unsafe fn push_light_userdata<T>(&self, ptr: *mut T) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_userdata(ptr as *mut _))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_userdata(ptr as *mut _))) }
 	}
 
 	/// Frees the reference `lua_ref` if it is valid.
@@ -270,7 +336,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn free_ref(&self, lua_ref: Ref) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => reference_free(lua_ref.0))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => reference_free(lua_ref.0))) }
 	}
 
 	/// Pushes the value pointed to by `lua_ref` onto the stack,
@@ -279,7 +345,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_ref(&self, lua_ref: Ref) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => reference_push(lua_ref.0))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => reference_push(lua_ref.0))) }
 	}
 
 	/// Pushes a [`Special`] value onto the stack.
@@ -287,22 +353,30 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_special(&self, what: Special) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_special(what as _))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_special(what as _))) }
 	}
 
 	/// Returns `true` if the value at `stack_pos` is of the given [`Type`].
 	pub fn is_type<Ty: Into<Type>>(&self, stack_pos: StackPos, ty: Ty) -> bool {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => is_type(stack_pos, ty.into().0))) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => is_type(stack_pos, ty.into().0))) }
 	}
 
 	/// Returns the [`Type`] of the value at `stack_pos`.
 	pub fn get_type(&self, stack_pos: StackPos) -> Type {
-		unsafe { Type(self.with_luabase(move |l| virtual_call!(l => get_type(stack_pos)))) }
+		unsafe { Type(self.with_luabase(move |l| lua_call!(l => get_type(stack_pos)))) }
 	}
 	
-	/// Returns the name of the given [`StdType`], as a C string.
-	pub fn get_type_name(&self, ty: StdType) -> &CStr {
-		unsafe { CStr::from_ptr(self.with_luabase(move |l| virtual_call!(l => get_type_name(ty as _)))) }
+	/// Returns the name of the given [`Type`], as a C string.
+	/// 
+	/// Custom types created with [`Lua::create_metatable`] are named after their metatable.
+	pub fn get_type_name<Ty: Into<Type>>(&self, ty: Ty) -> &CStr {
+		let ty = ty.into();
+		let name = unsafe { self.with_luabase(move |l| lua_call!(l => get_type_name(ty.0))) };
+		if !name.is_null() {
+			unsafe { CStr::from_ptr(name) }
+		} else {
+			c"unknown"
+		}
 	}
 
 	/// If the value at `stack_pos` is a string, returns it.
@@ -311,7 +385,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn check_string(&self, stack_pos: StackPos) -> &CStr {
-		unsafe { CStr::from_ptr(self.with_luabase_mut(move |l| virtual_call!(l => check_string(stack_pos)))) }
+		unsafe { CStr::from_ptr(self.with_luabase_mut(move |l| lua_call!(l => check_string(stack_pos)))) }
 	}
 
 	/// If the value at `stack_pos` is a [`Number`], returns it.
@@ -320,19 +394,23 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn check_number(&self, stack_pos: StackPos) -> Number {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => check_number(stack_pos))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => check_number(stack_pos))) }
 	}
 
 	/// If the value at `stack_pos` is a [`QAngle`], returns a reference to it.
 	/// Otherwise, returns a reference to the angle `0, 0, 0`.
+	/// 
+	/// See [`Lua::try_get_angle`] and [`Lua::check_angle`] to tell these cases apart.
 	pub fn get_angle(&self, stack_pos: StackPos) -> &QAngle {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => get_angle(stack_pos)).as_ref()) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => get_angle(stack_pos)).as_ref()) }
 	}
 
 	/// If the value at `stack_pos` is a [`Vector`], returns a reference to it.
 	/// Otherwise, returns a reference to the vector `0, 0, 0`.
+	/// 
+	/// See [`Lua::try_get_vector`] and [`Lua::check_vector`] to tell these cases apart.
 	pub fn get_vector(&self, stack_pos: StackPos) -> &Vector {
-		unsafe { self.with_luabase(move |l| virtual_call!(l => get_vector(stack_pos)).as_ref()) }
+		unsafe { self.with_luabase(move |l| lua_call!(l => get_vector(stack_pos)).as_ref()) }
 	}
 	
 	/// Pushes the metatable associated with the given [`Type`],
@@ -342,15 +420,37 @@ impl Lua {
 	/// The inner Lua state may raise an [error](crate::errors).
 	// TODO: What happens when it doesn't exist?
 	pub fn push_metatable<Ty: Into<Type>>(&self, ty: Ty) -> bool {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_meta_table(ty.into().0))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_meta_table(ty.into().0))) }
 	}
 
-	/// Pushes userdata of type `ty` referencing the data at `ptr`.
+	/// Returns a pointer to the [`UserData`] header of the userdata at `stack_pos`
+	/// if it is of the given [`Type`].
+	/// 
+	/// This method is not part of the public C++ API.
 	/// 
 	/// # Safety
-	/// `ptr` must be valid for values of type `ty`.
-	pub unsafe fn push_user_type_raw<T>(&self, ptr: *mut T, ty: Type) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_user_type(ptr as *mut _, ty.0))) }
+	/// `ty` must be a type identifier for userdata which starts with a [`UserData`] header,
+	/// such as any [`Type`] pushed with [`Lua::push_user_type_raw`].
+	pub unsafe fn test_user_data(&self, stack_pos: StackPos, ty: Type) -> Option<NonNull<UserData>> {
+		if !self.is_type(stack_pos, ty) {
+			return None
+		}
+		NonNull::new(self.get_userdata(stack_pos).cast())
+	}
+
+	/// Returns the data pointer of the userdata at `stack_pos` if it is of the given [`Type`],
+	/// or a null pointer otherwise.
+	/// 
+	/// This is the equivalent of `ILuaBase::GetUserType<T>` in the C++ API,
+	/// which reads the data pointer from the [`UserData`] header of the userdata.
+	/// 
+	/// # Safety
+	/// See [`Lua::test_user_data`].
+	pub unsafe fn get_user_type_raw<T>(&self, stack_pos: StackPos, ty: Type) -> *mut T {
+		match unsafe { self.test_user_data(stack_pos, ty) } {
+			Some(header) => unsafe { header.as_ref() }.data.cast(),
+			None => null_mut(),
+		}
 	}
 
 	/// Sets the data pointer of the userdata value at `stack_pos` to `ptr`.
@@ -358,7 +458,7 @@ impl Lua {
 	/// # Safety
 	/// `ptr` must be valid for values of type `ty`.
 	pub unsafe fn set_user_type<T>(&self, stack_pos: StackPos, ptr: *mut T) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => set_user_type(stack_pos, ptr as *mut _))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => set_user_type(stack_pos, ptr as *mut _))) }
 	}
 
 	/// Returns a context for operations on the Lua state
@@ -386,7 +486,8 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn get_table(&mut self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => get_table(stack_pos))) }
+		self.debug_check_pos_values(stack_pos, 1);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => get_table(stack_pos))) }
 	}
 
 	/// Pushes the value `t[key]`,
@@ -395,7 +496,8 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn get_field(&mut self, stack_pos: StackPos, key: &CStr) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => get_field(stack_pos, key.as_ptr()))) }
+		self.debug_check_pos(stack_pos);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => get_field(stack_pos, key.as_ptr()))) }
 	}
 
 	/// Sets `t[key]` to the value popped from the stack,
@@ -404,7 +506,8 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn set_field(&mut self, stack_pos: StackPos, key: &CStr) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => set_field(stack_pos, key.as_ptr()))) }
+		self.debug_check_pos_values(stack_pos, 1);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => set_field(stack_pos, key.as_ptr()))) }
 	}
 
 	/// Creates a new table and pushes it onto the stack.
@@ -412,7 +515,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn create_table(&mut self) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => create_table())) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => create_table())) }
 	}
 
 	/// Sets `t[key]` to the value popped from the stack,
@@ -422,7 +525,8 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn set_table(&mut self, stack_pos: StackPos) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => set_table(stack_pos))) }
+		self.debug_check_pos_values(stack_pos, 2);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => set_table(stack_pos))) }
 	}
 
 	/// Calls an object as a function on the stack,
@@ -431,13 +535,15 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn call(&mut self, n_args: c_uint, n_results: c_uint) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => call(n_args as _, n_results as _))) }
+		self.debug_check_call(n_args, n_results as c_int);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => call(n_args as _, n_results as _))) }
 	}
 
 	/// Calls an object as a function on the stack,
 	/// returning `Err` if the function raised an error.
 	pub fn pcall(&mut self, n_args: c_uint, n_results: c_int, error_func: c_int) -> Result<(), CallError> {
-		let result = unsafe { self.with_luabase_mut(move |l| virtual_call!(l => pcall(n_args as _, n_results, error_func))) };
+		self.debug_check_call(n_args, n_results);
+		let result = unsafe { self.with_luabase_mut(move |l| lua_call!(l => pcall(n_args as _, n_results, error_func))) };
 		if result == 0 {
 			Ok(())
 		} else {
@@ -456,7 +562,7 @@ impl Lua {
 	/// `non_empty_bytes` must not be empty.
 	pub unsafe fn push_non_empty_bytes(&mut self, non_empty_bytes: &[u8]) {
 		unsafe {
-			self.with_luabase_mut(move |l| virtual_call!(l => push_string(
+			self.with_luabase_mut(move |l| lua_call!(l => push_string(
 				non_empty_bytes.as_ptr() as *const _, non_empty_bytes.len() as _
 			)))
 		}
@@ -472,7 +578,7 @@ impl Lua {
 		if !bytes_ref.is_empty() {
 			unsafe { self.push_non_empty_bytes(bytes_ref) }
 		} else {
-			unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_string(c"".as_ptr(), 0))) }
+			unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_string(c"".as_ptr(), 0))) }
 		}
 	}
 
@@ -483,17 +589,19 @@ impl Lua {
 	pub fn push_c_string<S: AsRef<CStr>>(&mut self, string: S) {
 		let c_string = string.as_ref();
 		// TODO: Is it OK if `count_bytes` replaces the internal `strlen` calculation?
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_string(c_string.as_ptr() as *const _, c_string.count_bytes() as _))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_string(c_string.as_ptr() as *const _, c_string.count_bytes() as _))) }
 	}
 
 	/// Pushes the given C function onto the stack
 	/// with `n_upvalues` to create a closure,
 	/// which must be on the top of the stack.
 	/// 
+	/// See [`Lua::push_closure`] to push a [`Func`] instead.
+	/// 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_c_closure(&mut self, func: CFunc, n_upvalues: u8) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_c_closure(func, n_upvalues as _))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_c_closure(func, n_upvalues as _))) }
 	}
 
 	/// Pops a value from the stack,
@@ -504,16 +612,60 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn create_ref(&mut self) -> Ref {
-		let index = unsafe { self.with_luabase_mut(move |l| virtual_call!(l => reference_create())) };
+		self.debug_check_values(1);
+		let index = unsafe { self.with_luabase_mut(move |l| lua_call!(l => reference_create())) };
 		Ref(index)
 	}
 
 	/// Returns the length of the object at `stack_pos`.
 	/// 
+	/// The length is truncated to a [`c_int`] by the C++ API,
+	/// so [`Lua::str_len`] or [`Lua::table_len`] should be preferred when the type of the value is known.
+	/// 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn length_of(&mut self, stack_pos: StackPos) -> c_int {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => obj_len(stack_pos))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => obj_len(stack_pos))) }
+	}
+
+	/// Returns the length in bytes of the Lua string at `stack_pos`,
+	/// converting any Lua number at that position to a string in the process,
+	/// or `0` if the value can't be converted to a Lua string.
+	/// 
+	/// Unlike [`Lua::length_of`], the length is read like in [`Lua::get_string`],
+	/// so it's not truncated to a [`c_int`] for very large strings,
+	/// and values of other types, such as tables, don't have a length.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn str_len(&mut self, stack_pos: StackPos) -> usize {
+		self.get_string(stack_pos).map_or(0, <[u8]>::len)
+	}
+
+	/// Returns the length of the Lua table at `stack_pos`, like the `#` operator in Lua,
+	/// or `0` if the value isn't a Lua table.
+	/// 
+	/// Like the `#` operator, this returns a *border* of the table,
+	/// which is an index `n` where `t[n]` isn't `nil` and `t[n + 1]` is `nil`,
+	/// or `0` if `t[1]` is `nil`.
+	/// If the table has holes in its sequence, then this may be any of its borders.
+	/// The `__len` metamethod is not called, since Lua 5.1 doesn't call it for tables.
+	/// 
+	/// Unlike [`Lua::length_of`], strings and userdata don't have a length,
+	/// and a negative length is never returned.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn table_len(&mut self, stack_pos: StackPos) -> usize {
+		if self.is_type(stack_pos, StdType::Table) {
+			self.length_of(stack_pos).max(0) as usize
+		} else {
+			0
+		}
 	}
 
 	/// Pushes `angle` onto the stack as a Lua object.
@@ -521,7 +673,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_angle(&mut self, angle: &QAngle) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_angle(angle))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_angle(angle))) }
 	}
 
 	/// Pushes `vector` onto the stack as a Lua object.
@@ -529,7 +681,7 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_vector(&mut self, vector: &Vector) {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => push_vector(vector))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_vector(vector))) }
 	}
 
 	/// Pushes the metatable associated with the given `name`,
@@ -539,14 +691,15 @@ impl Lua {
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn create_metatable(&mut self, name: &CStr) -> Type {
-		unsafe { Type(self.with_luabase_mut(move |l| virtual_call!(l => create_meta_table(name.as_ptr())))) }
+		unsafe { Type(self.with_luabase_mut(move |l| lua_call!(l => create_meta_table(name.as_ptr())))) }
 	}
 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	// TODO: Describe functionality.
 	pub fn next(&mut self, stack_pos: StackPos) -> c_int {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => next(stack_pos))) }
+		self.debug_check_pos_values(stack_pos, 1);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => next(stack_pos))) }
 	}
 
 	/// Allocates a new Lua userdata of the specified `size`,
@@ -559,7 +712,7 @@ impl Lua {
 	/// it is valid at least until the call to its finalizer.
 	/// Do not use the returned pointer outside of these two specific circumstances!
 	pub unsafe fn new_userdata_raw(&mut self, size: c_uint) -> *mut c_void {
-		unsafe { self.with_luabase_mut(move |l| virtual_call!(l => new_userdata(size))) }
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => new_userdata(size))) }
 	}
 
 	/// Allocates a new Lua userdata of the specified `size`,
@@ -574,10 +727,71 @@ impl Lua {
 			None
 		}
 	}
+
+	/// Returns `true` if the values at `a` and `b` are equal.
+	/// 
+	/// See also [`Lua::raw_equal`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn equal(&mut self, a: StackPos, b: StackPos) -> bool {
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => equal(a, b)) != 0) }
+	}
+
+	/// Without metamethods, does `t[key] = value`, where
+	/// `t` is the value at the given index,
+	/// `value` is the value popped from the stack,
+	/// and `key` is the value just below the top.
+	pub fn raw_set(&mut self, stack_pos: StackPos) {
+		self.debug_check_pos_values(stack_pos, 2);
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => raw_set(stack_pos))) }
+	}
+
+	/// Pushes the given C function onto the stack.
+	/// 
+	/// See [`Lua::push_function`] to push a [`Func`] instead,
+	/// which receives a [`Ctx`] rather than a raw Lua state.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_c_function(&mut self, func: CFunc) {
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_c_function(func))) }
+	}
+
+	/// Pushes userdata of type `ty` referencing the data at `ptr`.
+	/// 
+	/// # Safety
+	/// `ptr` must be valid for values of type `ty`.
+	pub unsafe fn push_user_type_raw<T>(&mut self, ptr: *mut T, ty: Type) {
+		unsafe { self.with_luabase_mut(move |l| lua_call!(l => push_user_type(ptr as *mut _, ty.0))) }
+	}
 }
 
 /// Additional functions that are not part of the public C++ API.
 impl Lua {
+	/// Makes the panic handler of the `runtime` feature raise Lua errors in this state,
+	/// and does nothing without the feature.
+	/// 
+	/// This is called when the binary module is opened
+	/// by the functions exported with [`gmod13_module_with!`](crate::gmod13_module_with).
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn report_panics(&mut self) {
+		#[cfg(feature = "runtime")]
+		runtime::set_panic_state(self);
+	}
+
+	/// Undoes [`report_panics`](Self::report_panics) for this state.
+	/// 
+	/// This is called when the binary module is closed
+	/// by the functions exported with [`gmod13_module_with!`](crate::gmod13_module_with).
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn stop_reporting_panics(&mut self) {
+		#[cfg(feature = "runtime")]
+		runtime::clear_panic_state(self);
+	}
+
 	/// Drains the stack so that it has *at most* a specific number of elements.
 	/// 
 	/// This method is not part of the public C++ API.
@@ -586,6 +800,74 @@ impl Lua {
 		self.pop(self.top().saturating_sub(top))
 	}
 
+	/// Returns the [`Type`] of the metatable associated with the given `name`,
+	/// or `None` if it doesn't exist, without creating it.
+	/// 
+	/// This reads the `MetaID` field of `registry[name]`,
+	/// which is set on every metatable created with [`Lua::create_metatable`],
+	/// including the ones of engine classes like `Player` and `Entity`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn find_metatable(&mut self, name: &CStr) -> Option<Type> {
+		self.push_registry();
+		self.get_field(-1, name);
+		let ty = if self.is_type(-1, StdType::Table) {
+			self.get_field(-1, c"MetaID");
+			let ty = self.is_type(-1, StdType::Number).then(|| Type(self.get_number(-1) as _));
+			self.pop(1);
+			ty
+		} else {
+			None
+		};
+		self.pop(2);
+		ty
+	}
+
+	/// Pushes the existing metatable associated with the given `name`,
+	/// calls `f` to modify it, and restores the stack to how it was before,
+	/// returning `false` without calling `f` if the metatable doesn't exist.
+	/// 
+	/// This is useful for adding methods to engine classes like `Player` and `Entity`,
+	/// which look up methods in their metatables.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::{
+	///     gmod13::Lua,
+	///     gmod13_fn,
+	/// };
+	/// 
+	/// fn add_greet(lua: &mut Lua) {
+	///     lua.extend_metatable(c"Player", |mt| {
+	///         mt.push_function(gmod13_fn!(lua => {
+	///             lua.push_c_string(c"Hello!");
+	///             1
+	///         }));
+	///         mt.set_field(-2, c"Greet");
+	///     });
+	/// }
+	/// ```
+	pub fn extend_metatable<F: FnOnce(&mut Self)>(&mut self, name: &CStr, f: F) -> bool {
+		let Some(ty) = self.find_metatable(name) else {
+			return false
+		};
+		let top = self.top();
+		if !self.push_metatable(ty) {
+			return false
+		}
+		f(self);
+		self.set_top(top);
+		true
+	}
+
 	/// Sets the number of elements in the stack,
 	/// filling any excess slots with `nil`.
 	/// 
@@ -605,6 +887,21 @@ impl Lua {
 		}
 	}
 
+	/// Converts `stack_pos` into an equivalent absolute position,
+	/// which refers to the same slot regardless of values being pushed or popped above it.
+	/// 
+	/// Pseudo-indices (like [`upvalue_index`]) are returned unchanged.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// It is implemented with [`Lua::top`] for convenience.
+	pub fn abs_index(&self, stack_pos: StackPos) -> StackPos {
+		if is_relative(stack_pos) {
+			self.top() as StackPos + stack_pos + 1
+		} else {
+			stack_pos
+		}
+	}
+
 	/// Pushes the globals table onto the stack.
 	/// 
 	/// This method is not part of the public C++ API.
@@ -641,6 +938,69 @@ impl Lua {
 		self.push_special(Special::Registry)
 	}
 
+	/// Pushes the value of the global variable `name` onto the stack.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// It is implemented with [`Lua::push_globals`] and [`Lua::get_field`] for convenience.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn get_global(&mut self, name: &CStr) {
+		self.push_globals();
+		self.get_field(-1, name);
+		self.remove(-2);
+	}
+
+	/// Pushes the value of `t[key]`, where `t` is the global variable `table`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// It is implemented with [`Lua::get_global`] and [`Lua::get_field`] for convenience.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn get_global_field(&mut self, table: &CStr, key: &CStr) {
+		self.get_global(table);
+		self.get_field(-1, key);
+		self.remove(-2);
+	}
+
+	/// Pushes `o[name]` and `o`, where `o` is the value at `stack_pos`,
+	/// so that the method can be called with [`Lua::call`] after pushing its arguments,
+	/// like `o:name(...)` in Lua.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_self_method(&mut self, stack_pos: StackPos, name: &CStr) {
+		let stack_pos = self.abs_index(stack_pos);
+		self.get_field(stack_pos, name);
+		self.push_value(stack_pos);
+	}
+
+	/// Throws an error about argument `arg_num` having the wrong type,
+	/// like `bad argument #2 to 'f' (Vector expected, got string)`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// It is implemented with [`Lua::get_type`] and [`Lua::arg_error`],
+	/// matching the behavior of `luaL_typerror`.
+	/// The message is built as a Lua string with [`LuaStringBuilder`], so long type names are not truncated.
+	pub fn type_error(&mut self, arg_num: c_int, expected: &CStr) -> ! {
+		let ty = self.get_type(arg_num);
+		// The name is copied, since building the message allocates.
+		let mut got = chunks::CStrBuf::new();
+		let _ = if ty.is_std(StdType::None) {
+			write!(got, "no value")
+		} else {
+			write!(got, "{}", self.get_type_name(ty).to_bytes().escape_ascii())
+		};
+
+		let mut message = LuaStringBuilder::new(self);
+		let _ = write!(message, "{} expected, got {}", expected.to_bytes().escape_ascii(), got.as_c_str().to_str().unwrap_or_default());
+		message.finish();
+		self.arg_error(arg_num, self.get_c_string(-1).unwrap_or_default())
+	}
+
 	/// Pushes the `n`-th upvalue onto the stack, starting from `0`,
 	/// or `nil` if the upvalue index is invalid.
 	/// 
@@ -657,9 +1017,12 @@ impl Lua {
 
 	/// Pushes the given function onto the stack.
 	/// 
+	/// This is the safer equivalent of [`Lua::push_c_function`],
+	/// and is how functions written with [`Func`] are usually registered.
+	/// 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
-	pub fn push_function(&self, f: Func) {
+	pub fn push_function(&mut self, f: Func) {
 		self.push_c_function(to_c_func(f))
 	}
 
@@ -667,6 +1030,9 @@ impl Lua {
 	/// with `n_upvalues` to create a closure,
 	/// which must be on the top of the stack.
 	/// 
+	/// This is the safer equivalent of [`Lua::push_c_closure`].
+	/// The upvalues are accessed with [`Lua::push_upvalue`].
+	/// 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_closure(&mut self, f: Func, n_upvalues: u8) {
@@ -693,6 +1059,25 @@ pub const fn upvalue_index(n: u8) -> c_int {
 	(LUA_GLOBALSINDEX - 1) - (n as c_int)
 }
 
+/// Returns the position that refers to the same slot as `stack_pos`
+/// after `pushed` more values have been pushed onto the stack.
+/// 
+/// Positive positions and pseudo-indices (like [`upvalue_index`]) are returned unchanged.
+pub(crate) const fn after_push(stack_pos: StackPos, pushed: c_uint) -> StackPos {
+	if is_relative(stack_pos) {
+		stack_pos - pushed as c_int
+	} else {
+		stack_pos
+	}
+}
+
+/// Returns `true` if `stack_pos` is relative to the top of the stack,
+/// i.e. it is negative but not a pseudo-index.
+pub(crate) const fn is_relative(stack_pos: StackPos) -> bool {
+	const LUA_REGISTRYINDEX: c_int = -10000;
+	stack_pos < 0 && stack_pos > LUA_REGISTRYINDEX
+}
+
 /// Integer-based reference to a Lua object.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
@@ -751,3 +1136,80 @@ impl WithNoGc<'_> {
 		unsafe { Lua::from_luabase_mut(self.luabase) }
 	}
 }
+
+/// Handle to a [`Lua`] state which can be stored in a binary module,
+/// such as in a [`Module`](super::Module) implementation,
+/// to use the state outside of the functions that Lua calls.
+/// 
+/// The handle doesn't borrow the state, so nothing stops it from outliving the state.
+/// This is why handles are created with the `unsafe` [`LuaHandle::new`].
+/// Handles are neither [`Send`] nor [`Sync`],
+/// since Lua states may only be used from the thread that owns them.
+/// 
+/// # Examples
+/// ```
+/// use gmbm::gmod13::{
+///     Lua, LuaHandle, Module,
+/// };
+/// 
+/// struct Notifier {
+///     lua: Option<LuaHandle>,
+/// }
+/// 
+/// impl Module for Notifier {
+///     fn open(&mut self, lua: &mut Lua) {
+///         // SAFETY: The handle is dropped in `close`, and `notify` is never called by Lua.
+///         self.lua = Some(unsafe { LuaHandle::new(lua) });
+///     }
+/// 
+///     fn close(&mut self, _: &mut Lua) {
+///         self.lua = None;
+///     }
+/// }
+/// 
+/// impl Notifier {
+///     fn notify(&mut self) {
+///         let Some(handle) = &mut self.lua else { return };
+///         handle.with(|lua| {
+///             lua.get_global(c"print");
+///             lua.push_string("notified");
+///             if lua.pcall(1, 0, 0).is_err() {
+///                 lua.pop(1);
+///             }
+///         });
+///     }
+/// }
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct LuaHandle {
+	luabase: NonNull<LuaBase>,
+}
+
+impl LuaHandle {
+	/// Returns a new handle to `lua`.
+	/// 
+	/// # Safety
+	/// The handle must not be used after the state is closed,
+	/// such as after [`Module::close`](super::Module::close) returns.
+	/// 
+	/// The handle must not be used while another reference to the state is in use,
+	/// such as from a function called by Lua which has its own [`Lua`],
+	/// since [`with`](Self::with) would create a second mutable reference to the same state.
+	pub unsafe fn new(lua: &mut Lua) -> Self {
+		Self {
+			luabase: unsafe { NonNull::new_unchecked(lua.luabase.get()) },
+		}
+	}
+
+	/// Calls `f` with the state that this handle refers to, and returns its result.
+	/// 
+	/// See [`LuaHandle::new`] for when this may be called.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors) in `f`.
+	/// Since this is usually called outside of a function called by Lua,
+	/// such errors are not caught by anything, so `f` should only use protected calls.
+	pub fn with<R, F: FnOnce(&mut Lua) -> R>(&mut self, f: F) -> R {
+		f(unsafe { Lua::from_luabase_mut(self.luabase.as_mut()) })
+	}
+}
@@ -0,0 +1,461 @@
+//! Messaging between the server and clients which prefers the `net` library,
+//! and falls back to the deprecated `umsg` and `usermessage` libraries on servers that lack it.
+//! 
+//! Modules which support both paths can write and read messages through the same functions,
+//! instead of duplicating their logic for each library.
+//! 
+//! # Examples
+//! ```no_run
+//! use gmbm::{
+//!     gmod13::{
+//!         message::Recipients,
+//!         Lua,
+//!     },
+//!     gmod13_fn,
+//! };
+//! 
+//! fn example(lua: &mut Lua) {
+//!     lua.receive_message(c"greeting", gmod13_fn!(lua => {
+//!         let mut reader = lua.message_reader(1);
+//!         let _count = reader.read_long();
+//!         0
+//!     }));
+//! 
+//!     let mut message = lua.start_message(c"greeting", Recipients::All);
+//!     message.write_long(42);
+//!     message.send();
+//! }
+//! ```
+
+use core::ffi::{
+	CStr,
+	c_uint,
+};
+
+use crate::source::{
+	QAngle, Vector,
+};
+
+use super::{
+	func::Func,
+	objects::UserMsg,
+	Lua, LuaString, Number, StackPos, StdType,
+};
+
+/// Library used to send and receive messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessagePath {
+	/// The `net` library, which is available on all current servers.
+	Net,
+	/// The deprecated `umsg` and `usermessage` libraries.
+	UserMsg,
+}
+
+/// Players that an [`OutgoingMessage`] is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Recipients {
+	/// All players on the server.
+	All,
+	/// The player, or `CRecipientFilter`, at the given stack position.
+	At(StackPos),
+}
+
+/// Functions for sending and receiving messages with [`MessagePath`]s.
+impl Lua {
+	/// Returns [`MessagePath::Net`] if the global `net` library is available,
+	/// or [`MessagePath::UserMsg`] otherwise.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn message_path(&mut self) -> MessagePath {
+		self.get_global(c"net");
+		let has_net = self.is_type(-1, StdType::Table);
+		self.pop(1);
+		if has_net {
+			MessagePath::Net
+		} else {
+			MessagePath::UserMsg
+		}
+	}
+
+	/// Registers the message `name` with `util.AddNetworkString(name)` if the [`MessagePath`] is `net`.
+	/// 
+	/// Servers must register every message that they send with `net` before sending it.
+	/// Messages sent with `umsg` don't need to be registered.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn register_message(&mut self, name: &CStr) {
+		if self.message_path() == MessagePath::Net {
+			self.get_global_field(c"util", c"AddNetworkString");
+			self.push_c_string(name);
+			self.call(1, 0);
+		}
+	}
+
+	/// Starts an outgoing message named `name` to `recipients`,
+	/// with `net.Start(name)` or `umsg.Start(name, recipients)`.
+	/// 
+	/// The message is only sent once [`OutgoingMessage::send`] is called.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn start_message(&mut self, name: &CStr, recipients: Recipients) -> OutgoingMessage<'_> {
+		let path = self.message_path();
+		let recipients = match recipients {
+			Recipients::All => Recipients::All,
+			Recipients::At(stack_pos) => Recipients::At(self.abs_index(stack_pos)),
+		};
+
+		match path {
+			MessagePath::Net => {
+				self.get_global_field(c"net", c"Start");
+				self.push_c_string(name);
+				self.call(1, 0);
+			}
+			MessagePath::UserMsg => {
+				self.get_global_field(c"umsg", c"Start");
+				self.push_c_string(name);
+				if let Recipients::At(stack_pos) = recipients {
+					self.push_value(stack_pos);
+					self.call(2, 0);
+				} else {
+					self.call(1, 0);
+				}
+			}
+		}
+
+		OutgoingMessage {
+			lua: self,
+			path,
+			recipients,
+		}
+	}
+
+	/// Calls `f` whenever the message `name` is received,
+	/// registering it with `net.Receive(name, f)` or `usermessage.Hook(name, f)`.
+	/// 
+	/// `f` can read the message with [`Lua::message_reader`] at the first argument.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn receive_message(&mut self, name: &CStr, f: Func) {
+		let (library, register) = match self.message_path() {
+			MessagePath::Net => (c"net", c"Receive"),
+			MessagePath::UserMsg => (c"usermessage", c"Hook"),
+		};
+		self.get_global_field(library, register);
+		self.push_c_string(name);
+		self.push_function(f);
+		self.call(2, 0);
+	}
+
+	/// Returns a [`MessageReader`] for the message being received by the current function,
+	/// where `arg` is the argument which is a `bf_read` for a user message,
+	/// or the length of the message for `net`.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn message_reader(&mut self, arg: StackPos) -> MessageReader<'_> {
+		if self.is_type(arg, StdType::UserMsg) {
+			MessageReader::UserMsg(self.check_user_msg(arg))
+		} else {
+			MessageReader::Net(self)
+		}
+	}
+}
+
+/// Message which is being written, started with [`Lua::start_message`].
+/// 
+/// Values should be read in the same order and with the same types as they are written,
+/// since neither library stores types in the message.
+pub struct OutgoingMessage<'a> {
+	lua: &'a mut Lua,
+	path: MessagePath,
+	recipients: Recipients,
+}
+
+impl OutgoingMessage<'_> {
+	/// Returns the library used to send the message.
+	pub const fn path(&self) -> MessagePath {
+		self.path
+	}
+
+	/// Writes an angle with `net.WriteAngle` or `umsg.Angle`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_angle(&mut self, angle: &QAngle) {
+		self.write(c"WriteAngle", c"Angle", None, move |l| l.push_angle(angle))
+	}
+
+	/// Writes a boolean with `net.WriteBool` or `umsg.Bool`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_bool(&mut self, b: bool) {
+		self.write(c"WriteBool", c"Bool", None, move |l| l.push_bool(b))
+	}
+
+	/// Writes a signed 8-bit integer with `net.WriteInt(c, 8)` or `umsg.Char`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_char(&mut self, c: i8) {
+		self.write(c"WriteInt", c"Char", Some(8), move |l| l.push_number(c as _))
+	}
+
+	/// Writes a signed 16-bit integer with `net.WriteInt(i, 16)` or `umsg.Short`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_short(&mut self, i: i16) {
+		self.write(c"WriteInt", c"Short", Some(16), move |l| l.push_number(i as _))
+	}
+
+	/// Writes a signed 32-bit integer with `net.WriteInt(i, 32)` or `umsg.Long`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_long(&mut self, i: i32) {
+		self.write(c"WriteInt", c"Long", Some(32), move |l| l.push_number(i as _))
+	}
+
+	/// Writes a floating-point number with `net.WriteFloat` or `umsg.Float`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_float(&mut self, n: Number) {
+		self.write(c"WriteFloat", c"Float", None, move |l| l.push_number(n))
+	}
+
+	/// Writes a string with `net.WriteString` or `umsg.String`.
+	/// 
+	/// User messages are limited to 256 bytes in total, which long strings can easily exceed.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_string<S: AsRef<[u8]>>(&mut self, s: S) {
+		self.write(c"WriteString", c"String", None, move |l| l.push_string(s))
+	}
+
+	/// Writes a vector with `net.WriteVector` or `umsg.Vector`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_vector(&mut self, vector: &Vector) {
+		self.write(c"WriteVector", c"Vector", None, move |l| l.push_vector(vector))
+	}
+
+	/// Writes the entity at `stack_pos` with `net.WriteEntity` or `umsg.Entity`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write_entity(&mut self, stack_pos: StackPos) {
+		let stack_pos = self.lua.abs_index(stack_pos);
+		self.write(c"WriteEntity", c"Entity", None, move |l| l.push_value(stack_pos))
+	}
+
+	/// Sends the message to its recipients,
+	/// with `net.Broadcast()`, `net.Send(recipients)`, or `umsg.End()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn send(self) {
+		let lua = self.lua;
+		match (self.path, self.recipients) {
+			(MessagePath::Net, Recipients::All) => {
+				lua.get_global_field(c"net", c"Broadcast");
+				lua.call(0, 0);
+			}
+			(MessagePath::Net, Recipients::At(stack_pos)) => {
+				lua.get_global_field(c"net", c"Send");
+				lua.push_value(stack_pos);
+				lua.call(1, 0);
+			}
+			(MessagePath::UserMsg, _) => {
+				lua.get_global_field(c"umsg", c"End");
+				lua.call(0, 0);
+			}
+		}
+	}
+
+	/// Calls `net[net](value, bits)` or `umsg[umsg](value)`, where `value` is pushed by `push_value`,
+	/// and `bits` is only passed if it is `Some`.
+	fn write(&mut self, net: &CStr, umsg: &CStr, bits: Option<c_uint>, push_value: impl FnOnce(&mut Lua)) {
+		match self.path {
+			MessagePath::Net => self.lua.get_global_field(c"net", net),
+			MessagePath::UserMsg => self.lua.get_global_field(c"umsg", umsg),
+		}
+		push_value(self.lua);
+		match (self.path, bits) {
+			(MessagePath::Net, Some(bits)) => {
+				self.lua.push_number(bits as _);
+				self.lua.call(2, 0);
+			}
+			_ => self.lua.call(1, 0),
+		}
+	}
+}
+
+/// Reader for a message being received, returned by [`Lua::message_reader`].
+pub enum MessageReader<'a> {
+	/// Reader for the message currently being received by the `net` library.
+	Net(&'a mut Lua),
+	/// Reader for a user message.
+	UserMsg(UserMsg<'a>),
+}
+
+impl MessageReader<'_> {
+	/// Returns the library that the message was sent with.
+	pub const fn path(&self) -> MessagePath {
+		match self {
+			Self::Net(..) => MessagePath::Net,
+			Self::UserMsg(..) => MessagePath::UserMsg,
+		}
+	}
+
+	/// Reads an angle with `net.ReadAngle()` or `bf_read:ReadAngle()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_angle(&mut self) -> QAngle {
+		match self {
+			Self::Net(lua) => {
+				read_net(lua, c"ReadAngle", None);
+				let angle = *lua.get_angle(-1);
+				lua.pop(1);
+				angle
+			}
+			Self::UserMsg(msg) => msg.read_angle(),
+		}
+	}
+
+	/// Reads a boolean with `net.ReadBool()` or `bf_read:ReadBool()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_bool(&mut self) -> bool {
+		match self {
+			Self::Net(lua) => {
+				read_net(lua, c"ReadBool", None);
+				let b = lua.get_bool(-1);
+				lua.pop(1);
+				b
+			}
+			Self::UserMsg(msg) => msg.read_bool(),
+		}
+	}
+
+	/// Reads a signed 8-bit integer with `net.ReadInt(8)` or `bf_read:ReadChar()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_char(&mut self) -> i8 {
+		match self {
+			Self::Net(lua) => read_net_number(lua, c"ReadInt", Some(8)) as i8,
+			Self::UserMsg(msg) => msg.read_char(),
+		}
+	}
+
+	/// Reads a signed 16-bit integer with `net.ReadInt(16)` or `bf_read:ReadShort()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_short(&mut self) -> i16 {
+		match self {
+			Self::Net(lua) => read_net_number(lua, c"ReadInt", Some(16)) as i16,
+			Self::UserMsg(msg) => msg.read_short(),
+		}
+	}
+
+	/// Reads a signed 32-bit integer with `net.ReadInt(32)` or `bf_read:ReadLong()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_long(&mut self) -> i32 {
+		match self {
+			Self::Net(lua) => read_net_number(lua, c"ReadInt", Some(32)) as i32,
+			Self::UserMsg(msg) => msg.read_long(),
+		}
+	}
+
+	/// Reads a floating-point number with `net.ReadFloat()` or `bf_read:ReadFloat()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_float(&mut self) -> Number {
+		match self {
+			Self::Net(lua) => read_net_number(lua, c"ReadFloat", None),
+			Self::UserMsg(msg) => msg.read_float(),
+		}
+	}
+
+	/// Reads a string with `net.ReadString()` or `bf_read:ReadString()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_string(&mut self) -> Option<LuaString<'_>> {
+		match self {
+			Self::Net(lua) => {
+				read_net(lua, c"ReadString", None);
+				LuaString::from_top(lua)
+			}
+			Self::UserMsg(msg) => msg.read_string(),
+		}
+	}
+
+	/// Reads a vector with `net.ReadVector()` or `bf_read:ReadVector()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_vector(&mut self) -> Vector {
+		match self {
+			Self::Net(lua) => {
+				read_net(lua, c"ReadVector", None);
+				let vector = *lua.get_vector(-1);
+				lua.pop(1);
+				vector
+			}
+			Self::UserMsg(msg) => msg.read_vector(),
+		}
+	}
+
+	/// Reads an entity with `net.ReadEntity()` or `bf_read:ReadEntity()`,
+	/// pushing it onto the stack.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_entity(&mut self) {
+		match self {
+			Self::Net(lua) => read_net(lua, c"ReadEntity", None),
+			Self::UserMsg(msg) => msg.read_entity(),
+		}
+	}
+}
+
+/// Calls `net[name](bits)`, where `bits` is only passed if it is `Some`, leaving one result on the stack.
+fn read_net(lua: &mut Lua, name: &CStr, bits: Option<c_uint>) {
+	lua.get_global_field(c"net", name);
+	if let Some(bits) = bits {
+		lua.push_number(bits as _);
+		lua.call(1, 1);
+	} else {
+		lua.call(0, 1);
+	}
+}
+
+/// Returns the [`Number`] returned by `net[name](bits)`.
+fn read_net_number(lua: &mut Lua, name: &CStr, bits: Option<c_uint>) -> Number {
+	read_net(lua, name, bits);
+	let n = lua.get_number(-1);
+	lua.pop(1);
+	n
+}
@@ -0,0 +1,300 @@
+//! Counters of calls to the Lua C++ API, grouped by the native function that made them.
+//! 
+//! Every call made through [`Lua`] is counted by its [`Category`].
+//! Calls made while a native function created with [`gmod13_fn!`](crate::gmod13_fn),
+//! [`func::call`](super::func::call) or [`func::try_call`](super::func::try_call) is running
+//! are attributed to that function, which is identified by the location in the source code where it was defined.
+//! Other calls, such as those made in [`Module::open`](super::Module::open), are attributed to no function.
+//! 
+//! Counters are kept for up to [`MAX_ENTRYPOINTS`] native functions.
+//! Calls made by any further functions are attributed to no function.
+//! 
+//! # Examples
+//! ```
+//! use gmbm::prelude::*;
+//! 
+//! /// Logs the counters to the console, and starts counting from zero again.
+//! extern "C-unwind" fn report(cx: LuaCtx<'_>) -> LuaRets {
+//!     let lua = cx.lua();
+//!     lua.log_metrics();
+//!     gmbm::gmod13::metrics::reset();
+//!     LuaRets::ZERO
+//! }
+//! ```
+
+use core::{
+	fmt,
+	panic::Location,
+	ptr::null_mut,
+	sync::atomic::{
+		AtomicPtr, AtomicUsize, Ordering,
+	},
+};
+
+use super::{
+	log::Level,
+	Lua,
+};
+
+/// Maximum number of native functions that counters are kept for.
+pub const MAX_ENTRYPOINTS: usize = 128;
+
+/// Category of a call to the Lua C++ API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+	/// Calls which move values around the stack, such as [`Lua::pop`] and [`Lua::insert`].
+	Stack,
+	/// Calls which push values onto the stack, such as [`Lua::push_number`] and [`Lua::push_value`].
+	Push,
+	/// Calls which read values on the stack, such as [`Lua::get_number`] and [`Lua::check_string`].
+	Get,
+	/// Calls which access tables, such as [`Lua::get_field`] and [`Lua::raw_set`].
+	Table,
+	/// Calls which call Lua functions, which are [`Lua::call`] and [`Lua::pcall`].
+	Call,
+	/// Every other call, such as [`Lua::throw_error`] and [`Lua::create_ref`].
+	Other,
+}
+
+impl Category {
+	/// Number of categories.
+	pub const COUNT: usize = 6;
+
+	/// Every category, in order.
+	pub const ALL: [Self; Self::COUNT] = [
+		Self::Stack, Self::Push, Self::Get, Self::Table, Self::Call, Self::Other,
+	];
+
+	/// Returns the name of this category, as shown in reports.
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Stack => "stack",
+			Self::Push => "push",
+			Self::Get => "get",
+			Self::Table => "table",
+			Self::Call => "call",
+			Self::Other => "other",
+		}
+	}
+
+	/// Returns the category of the virtual function of [`LuaBase`](super::LuaBase) named `method`.
+	pub const fn of(method: &str) -> Self {
+		const STACK: &[&str] = &["top", "pop", "insert", "remove"];
+		const TABLE: &[&str] = &[
+			"create_table", "get_table", "set_table", "get_field", "set_field", "raw_get", "raw_set", "next",
+			"get_meta_table", "set_meta_table",
+		];
+		const CALL: &[&str] = &["call", "pcall"];
+		const GET: &[&str] = &["is_type", "obj_len", "equal", "raw_equal"];
+		const PUSH: &[&str] = &["create_meta_table", "new_userdata", "reference_push"];
+
+		if contains(STACK, method) {
+			Self::Stack
+		} else if contains(TABLE, method) {
+			Self::Table
+		} else if contains(CALL, method) {
+			Self::Call
+		} else if contains(GET, method) || starts_with(method, "get_") || starts_with(method, "check_") {
+			Self::Get
+		} else if contains(PUSH, method) || starts_with(method, "push") {
+			Self::Push
+		} else {
+			Self::Other
+		}
+	}
+}
+
+impl fmt::Display for Category {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+const fn starts_with(s: &str, prefix: &str) -> bool {
+	let (s, prefix) = (s.as_bytes(), prefix.as_bytes());
+	if s.len() < prefix.len() {
+		return false
+	}
+	let mut i = 0;
+	while i < prefix.len() {
+		if s[i] != prefix[i] {
+			return false
+		}
+		i += 1;
+	}
+	true
+}
+
+const fn contains(list: &[&str], s: &str) -> bool {
+	let mut i = 0;
+	while i < list.len() {
+		if list[i].len() == s.len() && starts_with(s, list[i]) {
+			return true
+		}
+		i += 1;
+	}
+	false
+}
+
+/// Counters of a single native function.
+struct Slot {
+	location: AtomicPtr<Location<'static>>,
+	invocations: AtomicUsize,
+	calls: [AtomicUsize; Category::COUNT],
+}
+
+impl Slot {
+	#[allow(clippy::declare_interior_mutable_const)]
+	const EMPTY: Self = Self {
+		location: AtomicPtr::new(null_mut()),
+		invocations: AtomicUsize::new(0),
+		calls: [const { AtomicUsize::new(0) }; Category::COUNT],
+	};
+
+	fn snapshot(&self) -> EntryMetrics {
+		let location = self.location.load(Ordering::Acquire);
+		EntryMetrics {
+			// SAFETY: Only `&'static Location<'static>` are stored.
+			location: unsafe { location.as_ref() },
+			invocations: self.invocations.load(Ordering::Relaxed),
+			calls: core::array::from_fn(|i| self.calls[i].load(Ordering::Relaxed)),
+		}
+	}
+}
+
+/// Counters of native functions, where the first one is for calls attributed to no function.
+static SLOTS: [Slot; MAX_ENTRYPOINTS + 1] = [Slot::EMPTY; MAX_ENTRYPOINTS + 1];
+/// Index of the slot of the native function that is currently running.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the index of the slot for the native function defined at `location`,
+/// claiming a new slot if there isn't one yet.
+fn slot_index(location: &'static Location<'static>) -> usize {
+	let ptr = location as *const _ as *mut _;
+	for (i, slot) in SLOTS.iter().enumerate().skip(1) {
+		match slot.location.compare_exchange(null_mut(), ptr, Ordering::AcqRel, Ordering::Acquire) {
+			Ok(_) => return i,
+			Err(existing) if existing == ptr => return i,
+			Err(_) => {}
+		}
+	}
+	0
+}
+
+/// Counts a call of `category` for the native function that is currently running.
+#[inline]
+pub(crate) fn record(category: Category) {
+	SLOTS[CURRENT.load(Ordering::Relaxed)].calls[category as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Guard which attributes calls to a native function until it's dropped.
+pub(crate) struct Entered {
+	previous: usize,
+}
+
+impl Entered {
+	/// Counts an invocation of the native function defined at `location`,
+	/// and attributes calls to it until the returned guard is dropped.
+	pub(crate) fn new(location: &'static Location<'static>) -> Self {
+		let index = slot_index(location);
+		SLOTS[index].invocations.fetch_add(1, Ordering::Relaxed);
+		Self {
+			previous: CURRENT.swap(index, Ordering::Relaxed),
+		}
+	}
+}
+
+impl Drop for Entered {
+	fn drop(&mut self) {
+		// This also runs if the native function raises a Lua error.
+		CURRENT.store(self.previous, Ordering::Relaxed);
+	}
+}
+
+/// Counters of a single native function, as returned by [`entries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntryMetrics {
+	location: Option<&'static Location<'static>>,
+	invocations: usize,
+	calls: [usize; Category::COUNT],
+}
+
+impl EntryMetrics {
+	/// Returns the location where the native function was defined,
+	/// or `None` for calls which are attributed to no function.
+	pub const fn location(&self) -> Option<&'static Location<'static>> {
+		self.location
+	}
+
+	/// Returns the number of times that the native function was called.
+	pub const fn invocations(&self) -> usize {
+		self.invocations
+	}
+
+	/// Returns the number of calls of `category` made by the native function.
+	pub const fn calls(&self, category: Category) -> usize {
+		self.calls[category as usize]
+	}
+
+	/// Returns the number of calls of every category made by the native function.
+	pub fn total_calls(&self) -> usize {
+		self.calls.iter().sum()
+	}
+}
+
+impl fmt::Display for EntryMetrics {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.location {
+			Some(location) => write!(f, "{location}: {} invocations;", self.invocations)?,
+			None => f.write_str("outside of native functions:")?,
+		}
+		for (i, category) in Category::ALL.into_iter().enumerate() {
+			let separator = if i == 0 { "" } else { "," };
+			write!(f, "{separator} {} {category}", self.calls(category))?;
+		}
+		Ok(())
+	}
+}
+
+/// Returns an iterator over the counters of every native function which has been called,
+/// followed by the counters of calls attributed to no function.
+pub fn entries() -> impl Iterator<Item = EntryMetrics> {
+	SLOTS[1..].iter()
+		.map(Slot::snapshot)
+		.take_while(move |entry| entry.location.is_some())
+		.chain(core::iter::once(SLOTS[0].snapshot()))
+}
+
+/// Sets every counter to zero.
+/// 
+/// Native functions keep their slots, so they are still listed by [`entries`].
+pub fn reset() {
+	for slot in &SLOTS {
+		slot.invocations.store(0, Ordering::Relaxed);
+		for calls in &slot.calls {
+			calls.store(0, Ordering::Relaxed);
+		}
+	}
+}
+
+/// Functions for reporting the counters of calls to the C++ API.
+impl Lua {
+	/// Logs the counters of every native function to the game console with [`Lua::log`],
+	/// as returned by [`entries`].
+	/// 
+	/// The counters are read before logging, so the calls made by this method are not included.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn log_metrics(&mut self) {
+		let mut snapshot = [None; MAX_ENTRYPOINTS + 1];
+		for (entry, slot) in entries().zip(&mut snapshot) {
+			*slot = Some(entry);
+		}
+		for entry in snapshot.into_iter().map_while(core::convert::identity) {
+			self.log(Level::Info, format_args!("{entry}"));
+		}
+	}
+}
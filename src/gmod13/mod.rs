@@ -1,15 +1,88 @@
 //! Items for implementing Garry's Mod Binary Modules which use `gmod13_*` entrypoints.
 
+use core::ffi::CStr;
+
 mod bits;
 pub use bits::*;
+mod int;
+pub use int::*;
 mod raw;
 pub use raw::*;
 mod lua;
 pub use lua::*;
+mod abi;
+pub use abi::*;
 mod types;
 pub use types::*;
+mod realm;
+pub use realm::*;
+mod debug;
+pub use debug::*;
+mod calls;
+pub use calls::*;
+mod stack;
+pub use stack::*;
+mod namespace;
+pub use namespace::*;
+mod stack_check;
+mod chunks;
+mod strings;
+mod tables;
+pub use tables::lua_sort;
+pub use strings::{
+	LuaString, LuaStringBuilder,
+};
+mod vector;
+mod entities;
+mod chat;
+pub use chat::*;
+mod util;
+mod convert;
+pub use convert::*;
+mod constants;
+pub use constants::*;
+mod iter;
+mod time;
+pub use time::Instant;
+#[cfg(any(feature = "log", feature = "runtime"))]
+mod thread;
+mod deps;
+mod preload;
+mod detour;
+pub use detour::*;
+mod reload;
+pub use reload::*;
 
+pub mod draw;
 pub mod func;
+pub mod log;
+pub mod message;
+pub mod objects;
+pub mod rows;
+pub mod serial;
+pub mod sql;
+pub mod trace;
+
+#[cfg(feature = "bytes")]
+pub mod bytes;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+#[cfg(feature = "runtime")]
+pub mod runtime;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "tokio")]
+pub mod task;
+
+#[cfg(feature = "unicode")]
+pub mod unicode;
 
 #[cfg(feature = "user-types")]
 pub mod user_types;
@@ -18,6 +91,15 @@ pub mod user_types;
 // TODO: Is there a better way to express this?
 // Using Rust modules for this would be confusing since it would require a structure defined in prose.
 pub trait Module {
+	/// Returns the names of binary modules or Lua libraries that this module depends on.
+	/// 
+	/// Each one must either be a non-`nil` global variable,
+	/// or be loadable with `require`,
+	/// otherwise an error listing the missing dependencies is raised before [`open`](Self::open) is called.
+	fn requires(&self) -> &[&CStr] {
+		&[]
+	}
+
 	/// Function called when the binary module is first loaded.
 	fn open(&mut self, lua: &mut Lua);
 
@@ -26,6 +108,37 @@ pub trait Module {
 	fn close(&mut self, lua: &mut Lua) {
 		let _ = lua;
 	}
+
+	/// Returns the key under which the state saved by [`save_state`](Self::save_state) is stored,
+	/// or `None` if the module doesn't save any state, which is the default.
+	/// 
+	/// The key must stay the same across builds of the binary module,
+	/// and must be unique among the binary modules loaded in the game,
+	/// so it should include the name of the binary module, such as `"gmsv_example::cache"`.
+	fn state_key(&self) -> Option<&str> {
+		None
+	}
+
+	/// Function called after [`close`](Self::close) to save state that should survive reloading the binary module,
+	/// such as caches, by writing it to `state`.
+	/// 
+	/// The saved state is stored in the registry of the Lua state,
+	/// and is passed to [`restore_state`](Self::restore_state) when the module is loaded again.
+	/// Nothing is saved unless [`state_key`](Self::state_key) returns a key.
+	fn save_state(&mut self, state: &mut StateWriter<'_>) {
+		let _ = state;
+	}
+
+	/// Function called before [`open`](Self::open) with the state previously saved by [`save_state`](Self::save_state),
+	/// if there is any.
+	/// 
+	/// `state` points into a Lua string, which is kept alive by the table of saved states for the duration of the call.
+	/// `&self` methods of `lua` which convert numbers to strings, such as [`Lua::get_string`] and [`Lua::check_string`],
+	/// may still run the garbage collector and `__gc` metamethods, as described in [Mutability](Lua#mutability),
+	/// so `state` should be copied before they are called.
+	fn restore_state(&mut self, lua: &Lua, state: &[u8]) {
+		let _ = (lua, state);
+	}
 }
 
 /// Exports `gmod13_*` C++ entrypoint functions that redirect to
@@ -54,8 +167,13 @@ macro_rules! gmod13_module_with {
 			unsafe extern "C-unwind" fn gmod13_open(
 				state: *mut $crate::gmod13::LuaState,
 			) -> ::core::ffi::c_int {
-				let lua = unsafe { $crate::gmod13::Lua::from_mut_ptr(state) };
-				$crate::gmod13::Module::open($($module)+, lua);
+				let lua = unsafe { $crate::gmod13::Lua::from_state_ptr(state) };
+				lua.check_abi();
+				lua.report_panics();
+				let module = $($module)+;
+				lua.check_dependencies($crate::gmod13::Module::requires(module));
+				lua.restore_module_state(module);
+				$crate::gmod13::Module::open(module, lua);
 				0
 			}
 
@@ -63,8 +181,11 @@ macro_rules! gmod13_module_with {
 			unsafe extern "C-unwind" fn gmod13_close(
 				state: *mut $crate::gmod13::LuaState,
 			) -> ::core::ffi::c_int {
-				let lua = unsafe { $crate::gmod13::Lua::from_mut_ptr(state) };
-				$crate::gmod13::Module::close($($module)+, lua);
+				let lua = unsafe { $crate::gmod13::Lua::from_state_ptr(state) };
+				let module = $($module)+;
+				$crate::gmod13::Module::close(module, lua);
+				lua.save_module_state(module);
+				lua.stop_reporting_panics();
 				0
 			}
 		};
@@ -113,8 +234,128 @@ macro_rules! gmod13_module_static {
 /// 
 /// gmod13_module!(Hello = Hello);
 /// ```
+/// 
+/// Several modules can be exported from one binary by listing them in brackets.
+/// They are opened in order, and closed in reverse order.
+/// If opening one of them raises an error, then only the modules which were opened before it are closed.
+/// Each entry may have `#[cfg(...)]` attributes to select modules with features.
+/// ```
+/// use gmbm::prelude::*;
+/// 
+/// struct Net;
+/// impl LuaModule for Net {
+///     fn open(&mut self, _: &mut Lua) {}
+/// }
+/// 
+/// struct Db {
+///     queries: usize,
+/// }
+/// impl LuaModule for Db {
+///     fn open(&mut self, _: &mut Lua) {}
+/// }
+/// 
+/// gmod13_module!([
+///     Net = Net,
+///     #[cfg(not(feature = "no-db"))]
+///     Db = Db { queries: 0 },
+/// ]);
+/// ```
+/// 
+/// A module can be restricted to one realm by prefixing it with `server only:`,
+/// `client only:` or `menu only:`.
+/// When it's loaded in another realm, such as a server-side module installed with a `gmcl_` file name,
+/// opening it raises an error which names the expected realm instead of running code meant for another realm.
+/// With `server only, silent:` and the like, it's left unopened without an error instead.
+/// See [`RealmOnly`], which can also wrap single entries of a bracketed list.
+/// ```
+/// use gmbm::prelude::*;
+/// 
+/// struct Admin;
+/// impl LuaModule for Admin {
+///     fn open(&mut self, lua: &mut Lua) {
+///         // This is only reached in the server realm.
+///         lua.push_globals();
+///         lua.push_bool(true);
+///         lua.set_field(-2, c"ADMIN_LOADED");
+///     }
+/// }
+/// 
+/// gmod13_module!(server only: Admin = Admin);
+/// ```
 #[macro_export]
 macro_rules! gmod13_module {
+	{[$($(#[$attr:meta])* $Module:ty = $init:expr),+ $(,)?]} => {
+		const _: () = {
+			struct CombinedGmod13Module;
+
+			/// Number of exported modules which have been opened successfully.
+			static OPENED: ::core::sync::atomic::AtomicUsize = ::core::sync::atomic::AtomicUsize::new(0);
+
+			/// Calls `f` with every exported module, in order.
+			fn with_modules(f: impl ::core::ops::FnOnce(&mut [&mut dyn $crate::gmod13::Module])) {
+				f(&mut [$(
+					$(#[$attr])*
+					{
+						static mut EXPORTED_GMOD13_MODULE: $Module = $init;
+						// SAFETY: `gmod13_*` functions are always called from a single thread.
+						(unsafe { &mut *&raw mut EXPORTED_GMOD13_MODULE }) as &mut dyn $crate::gmod13::Module
+					}
+				),+])
+			}
+
+			impl $crate::gmod13::Module for CombinedGmod13Module {
+				fn open(&mut self, lua: &mut $crate::gmod13::Lua) {
+					with_modules(|modules| for module in modules.iter_mut() {
+						lua.check_dependencies(module.requires());
+						lua.restore_module_state(&mut **module);
+						module.open(lua);
+						OPENED.fetch_add(1, ::core::sync::atomic::Ordering::Relaxed);
+					})
+				}
+
+				fn close(&mut self, lua: &mut $crate::gmod13::Lua) {
+					// Modules after one that raised an error in `open` were never opened, so they aren't closed.
+					let opened = OPENED.swap(0, ::core::sync::atomic::Ordering::Relaxed);
+					with_modules(|modules| for module in modules[..opened].iter_mut().rev() {
+						module.close(lua);
+						lua.save_module_state(&mut **module);
+					})
+				}
+			}
+
+			$crate::gmod13_module_with!(&mut CombinedGmod13Module);
+		};
+	};
+
+	{server only: $($rest:tt)+} => {
+		$crate::gmod13_module!(@realm_only Server, Error; $($rest)+);
+	};
+	{client only: $($rest:tt)+} => {
+		$crate::gmod13_module!(@realm_only Client, Error; $($rest)+);
+	};
+	{menu only: $($rest:tt)+} => {
+		$crate::gmod13_module!(@realm_only Menu, Error; $($rest)+);
+	};
+	{server only, silent: $($rest:tt)+} => {
+		$crate::gmod13_module!(@realm_only Server, Skip; $($rest)+);
+	};
+	{client only, silent: $($rest:tt)+} => {
+		$crate::gmod13_module!(@realm_only Client, Skip; $($rest)+);
+	};
+	{menu only, silent: $($rest:tt)+} => {
+		$crate::gmod13_module!(@realm_only Menu, Skip; $($rest)+);
+	};
+
+	{@realm_only $Realm:ident, $Other:ident; $Module:ty = $init:expr} => {
+		$crate::gmod13_module!(
+			$crate::gmod13::RealmOnly<$Module> = $crate::gmod13::RealmOnly::new(
+				$crate::gmod13::Realm::$Realm,
+				$crate::gmod13::OtherRealm::$Other,
+				$init,
+			)
+		);
+	};
+
 	{$Module:ty = $init:expr} => {
 		const _: () = {
 			static mut EXPORTED_GMOD13_MODULE: $Module = $init;
@@ -125,7 +366,7 @@ macro_rules! gmod13_module {
 
 	($($whatever:tt)*) => {
 		::core::compile_error! {
-			"expected `<ModuleType> = <init expression>`"
+			"expected `<ModuleType> = <init expression>`, optionally prefixed with `<realm> only:`, or a bracketed list of them"
 		}
 	};
 }
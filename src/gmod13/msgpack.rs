@@ -0,0 +1,407 @@
+//! Conversion between Lua values and [MessagePack](https://msgpack.org),
+//! for exchanging data with external services.
+//! 
+//! Lua values are converted to MessagePack as follows:
+//! - `nil` and booleans are converted to their MessagePack equivalents.
+//! - Numbers which are integers in the range of [`MIN_SAFE_INTEGER`](super::MIN_SAFE_INTEGER)`..=`[`MAX_SAFE_INTEGER`](super::MAX_SAFE_INTEGER)
+//!   are converted to integers, and other numbers are converted to 64-bit floats.
+//! - Strings are converted to strings if they are valid UTF-8, or to binary data otherwise.
+//! - Tables with only the keys `1..=n` are converted to arrays, and other tables are converted to maps.
+//!   Empty tables are converted to empty arrays.
+//! 
+//! When MessagePack is converted to Lua values, strings and binary data are both converted to strings,
+//! arrays are converted to tables with the keys `1..=n`,
+//! and every integer and float is converted to a [`Number`],
+//! which may lose precision.
+//! Extension types are not supported.
+//! 
+//! Like with [`serial`](super::serial), tables which contain themselves are rejected,
+//! and tables can be nested up to [`MAX_DEPTH`] times.
+//! 
+//! # Examples
+//! ```no_run
+//! use gmbm::gmod13::Lua;
+//! 
+//! fn round_trip(lua: &mut Lua) {
+//!     let bytes = lua.to_msgpack(-1).expect("value should be convertible");
+//!     lua.push_msgpack(&bytes).expect("MessagePack should be valid");
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use rmp::{
+	decode::{
+		self, Bytes,
+	},
+	encode, Marker,
+};
+
+use super::{
+	number_to_i53,
+	serial::{
+		DeserializeError, SerializeError, MAX_DEPTH,
+	},
+	Lua, Number, StackPos, StdType,
+};
+
+/// Functions for converting Lua values to and from MessagePack.
+impl Lua {
+	/// Converts the value at `stack_pos` to MessagePack.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn to_msgpack(&mut self, stack_pos: StackPos) -> Result<Vec<u8>, SerializeError> {
+		let value = self.abs_index(stack_pos);
+		let top = self.top();
+		// Set of the tables that are currently being converted.
+		self.create_table();
+		let visiting = self.abs_index(-1);
+
+		let mut out = Vec::new();
+		let result = self.write_msgpack(value, visiting, &mut out, 0);
+		self.set_top(top);
+		result.map(move |()| out)
+	}
+
+	/// Converts `bytes`, which must contain exactly one MessagePack value, to a Lua value,
+	/// and pushes it onto the stack.
+	/// 
+	/// If an error is returned, then nothing is pushed.
+	/// Map keys which are `nil` or NaN are rejected with [`DeserializeError::InvalidKey`],
+	/// and extension types are rejected with [`DeserializeError::InvalidTag`].
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_msgpack(&mut self, bytes: &[u8]) -> Result<(), DeserializeError> {
+		let mut reader = Bytes::new(bytes);
+		let top = self.top();
+		let mut result = self.read_msgpack(&mut reader, 0);
+		if result.is_ok() && !reader.remaining_slice().is_empty() {
+			result = Err(DeserializeError::TrailingBytes);
+		}
+		if result.is_err() {
+			self.set_top(top);
+		}
+		result
+	}
+
+	fn write_msgpack(&mut self, pos: StackPos, visiting: StackPos, out: &mut Vec<u8>, depth: usize) -> Result<(), SerializeError> {
+		// Writing to a `Vec` can't fail.
+		let ty = self.get_type(pos);
+		match ty.to_std() {
+			Some(StdType::Nil) => {
+				let _ = encode::write_nil(out);
+			}
+			Some(StdType::Bool) => {
+				let _ = encode::write_bool(out, self.get_bool(pos));
+			}
+			Some(StdType::Number) => {
+				let n = self.get_number(pos);
+				match number_to_i53(n) {
+					// Negative zero is kept as a float, since it would be converted back to positive zero.
+					Some(i) if !(i == 0 && n.is_sign_negative()) => {
+						let _ = encode::write_sint(out, i);
+					}
+					_ => {
+						let _ = encode::write_f64(out, n);
+					}
+				}
+			}
+			Some(StdType::String) => {
+				let bytes = self.get_string(pos).unwrap_or_default();
+				let len = u32::try_from(bytes.len()).map_err(|_| SerializeError::Unsupported(ty))?;
+				let _ = if str::from_utf8(bytes).is_ok() {
+					encode::write_str_len(out, len)
+				} else {
+					encode::write_bin_len(out, len)
+				};
+				out.extend_from_slice(bytes);
+			}
+			Some(StdType::Table) => {
+				if depth == MAX_DEPTH {
+					return Err(SerializeError::TooDeep)
+				}
+
+				self.push_value(pos);
+				self.raw_get(visiting);
+				let is_visiting = self.get_bool(-1);
+				self.pop(1);
+				if is_visiting {
+					return Err(SerializeError::Cycle)
+				}
+				self.push_value(pos);
+				self.push_bool(true);
+				self.raw_set(visiting);
+
+				let (n_pairs, is_array) = self.table_shape(pos);
+				let n_pairs = u32::try_from(n_pairs).map_err(|_| SerializeError::Unsupported(ty))?;
+				if is_array {
+					let _ = encode::write_array_len(out, n_pairs);
+					for i in 1..=n_pairs {
+						self.push_number(i as Number);
+						self.raw_get(pos);
+						let value = self.abs_index(-1);
+						self.write_msgpack(value, visiting, out, depth + 1)?;
+						self.pop(1);
+					}
+				} else {
+					let _ = encode::write_map_len(out, n_pairs);
+					self.push_nil();
+					while self.next(pos) != 0 {
+						let key = self.abs_index(-2);
+						let value = self.abs_index(-1);
+						self.write_msgpack(key, visiting, out, depth + 1)?;
+						self.write_msgpack(value, visiting, out, depth + 1)?;
+						self.pop(1);
+					}
+				}
+
+				self.push_value(pos);
+				self.push_nil();
+				self.raw_set(visiting);
+			}
+			_ => return Err(SerializeError::Unsupported(ty)),
+		}
+		Ok(())
+	}
+
+	/// Returns the number of key-value pairs in the table at `pos`,
+	/// and whether its keys are exactly `1..=n`.
+	fn table_shape(&mut self, pos: StackPos) -> (usize, bool) {
+		let len = self.length_of(pos).max(0) as usize;
+		let mut n_pairs = 0;
+		let mut is_array = true;
+		self.push_nil();
+		while self.next(pos) != 0 {
+			n_pairs += 1;
+			if is_array {
+				let is_index = self.is_type(-2, StdType::Number)
+					&& number_to_i53(self.get_number(-2)).is_some_and(move |i| (1..=len as i64).contains(&i));
+				is_array = is_index;
+			}
+			self.pop(1);
+		}
+		(n_pairs, is_array && n_pairs == len)
+	}
+
+	fn read_msgpack(&mut self, reader: &mut Bytes<'_>, depth: usize) -> Result<(), DeserializeError> {
+		use DeserializeError::UnexpectedEnd;
+
+		// Every value is read after its marker is checked, so reading can only fail at the end of the input.
+		let &marker_byte = reader.remaining_slice().first().ok_or(UnexpectedEnd)?;
+		match Marker::from_u8(marker_byte) {
+			Marker::Null => {
+				decode::read_nil(reader).map_err(|_| UnexpectedEnd)?;
+				self.push_nil()
+			}
+			Marker::True | Marker::False => {
+				let b = decode::read_bool(reader).map_err(|_| UnexpectedEnd)?;
+				self.push_bool(b)
+			}
+			Marker::FixPos(_) | Marker::FixNeg(_)
+			| Marker::U8 | Marker::U16 | Marker::U32 | Marker::U64
+			| Marker::I8 | Marker::I16 | Marker::I32 | Marker::I64 => {
+				let n = decode::read_int::<Number, _>(reader).map_err(|_| UnexpectedEnd)?;
+				self.push_number(n)
+			}
+			Marker::F32 => {
+				let n = decode::read_f32(reader).map_err(|_| UnexpectedEnd)?;
+				self.push_number(n as Number)
+			}
+			Marker::F64 => {
+				let n = decode::read_f64(reader).map_err(|_| UnexpectedEnd)?;
+				self.push_number(n)
+			}
+			Marker::FixStr(_) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+				let len = decode::read_str_len(reader).map_err(|_| UnexpectedEnd)?;
+				let bytes = take(reader, len)?;
+				self.push_string(bytes)
+			}
+			Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => {
+				let len = decode::read_bin_len(reader).map_err(|_| UnexpectedEnd)?;
+				let bytes = take(reader, len)?;
+				self.push_string(bytes)
+			}
+			Marker::FixArray(_) | Marker::Array16 | Marker::Array32 => {
+				if depth == MAX_DEPTH {
+					return Err(DeserializeError::TooDeep)
+				}
+				let len = decode::read_array_len(reader).map_err(|_| UnexpectedEnd)?;
+				self.create_table();
+				for i in 1..=len {
+					self.push_number(i as Number);
+					self.read_msgpack(reader, depth + 1)?;
+					self.raw_set(-3);
+				}
+			}
+			Marker::FixMap(_) | Marker::Map16 | Marker::Map32 => {
+				if depth == MAX_DEPTH {
+					return Err(DeserializeError::TooDeep)
+				}
+				let len = decode::read_map_len(reader).map_err(|_| UnexpectedEnd)?;
+				self.create_table();
+				for _ in 0..len {
+					self.read_msgpack(reader, depth + 1)?;
+					let is_invalid_key = match self.get_type(-1).to_std() {
+						Some(StdType::Nil) => true,
+						Some(StdType::Number) => self.get_number(-1).is_nan(),
+						_ => false,
+					};
+					if is_invalid_key {
+						return Err(DeserializeError::InvalidKey)
+					}
+					self.read_msgpack(reader, depth + 1)?;
+					self.raw_set(-3);
+				}
+			}
+			_ => return Err(DeserializeError::InvalidTag(marker_byte)),
+		}
+		Ok(())
+	}
+}
+
+/// Takes `len` bytes of data from `reader`.
+fn take<'a>(reader: &mut Bytes<'a>, len: u32) -> Result<&'a [u8], DeserializeError> {
+	let len = usize::try_from(len).map_err(|_| DeserializeError::UnexpectedEnd)?;
+	let (data, rest) = reader.remaining_slice().split_at_checked(len).ok_or(DeserializeError::UnexpectedEnd)?;
+	*reader = Bytes::new(rest);
+	Ok(data)
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use crate::gmod13::{
+		func::{
+			Ctx, Rets,
+		},
+		testing::MockLua,
+	};
+
+	use super::*;
+
+	/// Converts the value on top of the stack to MessagePack, checks the encoding
+	/// and pushes the value converted back from it.
+	fn round_trip(lua: &mut Lua, expected: &[u8]) {
+		let bytes = lua.to_msgpack(-1).unwrap();
+		assert_eq!(bytes, expected);
+		lua.push_msgpack(&bytes).unwrap();
+	}
+
+	#[test]
+	fn round_trip_scalars() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+
+		lua.push_nil();
+		round_trip(lua, &[0xc0]);
+		assert!(lua.is_type(-1, StdType::Nil));
+
+		lua.push_bool(true);
+		round_trip(lua, &[0xc3]);
+		assert!(lua.get_bool(-1));
+		lua.push_bool(false);
+		round_trip(lua, &[0xc2]);
+		assert!(lua.is_type(-1, StdType::Bool) && !lua.get_bool(-1));
+
+		lua.push_number(42.0);
+		round_trip(lua, &[0x2a]);
+		assert_eq!(lua.get_number(-1), 42.0);
+		lua.push_number(-300.0);
+		round_trip(lua, &[0xd1, 0xfe, 0xd4]);
+		assert_eq!(lua.get_number(-1), -300.0);
+
+		let mut float = Vec::from([0xcb]);
+		float.extend_from_slice(&1.5f64.to_be_bytes());
+		lua.push_number(1.5);
+		round_trip(lua, &float);
+		assert_eq!(lua.get_number(-1), 1.5);
+		lua.push_number(-0.0);
+		let bytes = lua.to_msgpack(-1).unwrap();
+		lua.push_msgpack(&bytes).unwrap();
+		assert!(lua.get_number(-1).is_sign_negative());
+
+		lua.push_str("hi");
+		round_trip(lua, &[0xa2, b'h', b'i']);
+		assert_eq!(lua.get_string(-1), Some(&b"hi"[..]));
+		lua.push_string(b"\xFF");
+		round_trip(lua, &[0xc4, 0x01, 0xff]);
+		assert_eq!(lua.get_string(-1), Some(&b"\xFF"[..]));
+	}
+
+	#[test]
+	fn round_trip_tables() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+
+		lua.create_table();
+		round_trip(lua, &[0x90]);
+		assert!(lua.is_type(-1, StdType::Table));
+		lua.set_top(0);
+
+		// { "a", { x = true } }
+		lua.create_table();
+		lua.push_number(1.0);
+		lua.push_str("a");
+		lua.raw_set(-3);
+		lua.push_number(2.0);
+		lua.create_table();
+		lua.push_bool(true);
+		lua.set_field(-2, c"x");
+		lua.raw_set(-3);
+		round_trip(lua, &[0x92, 0xa1, b'a', 0x81, 0xa1, b'x', 0xc3]);
+
+		lua.push_number(1.0);
+		lua.raw_get(-2);
+		assert_eq!(lua.get_string(-1), Some(&b"a"[..]));
+		lua.pop(1);
+		lua.push_number(2.0);
+		lua.raw_get(-2);
+		lua.get_field(-1, c"x");
+		assert!(lua.get_bool(-1));
+	}
+
+	#[test]
+	fn unsupported_values() {
+		extern "C-unwind" fn f(_: Ctx<'_>) -> Rets {
+			Rets::ZERO
+		}
+
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_function(f);
+		assert!(matches!(lua.to_msgpack(-1), Err(SerializeError::Unsupported(ty)) if ty.is_std(StdType::Function)));
+
+		// A table with a function in it is rejected too.
+		lua.create_table();
+		lua.push_value(-2);
+		lua.set_field(-2, c"f");
+		assert!(matches!(lua.to_msgpack(-1), Err(SerializeError::Unsupported(_))));
+
+		lua.create_table();
+		lua.push_value(-1);
+		lua.set_field(-2, c"self");
+		assert_eq!(lua.to_msgpack(-1), Err(SerializeError::Cycle));
+		assert_eq!(lua.top(), 3);
+	}
+
+	#[test]
+	fn malformed_input() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		assert_eq!(lua.push_msgpack(&[]), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_msgpack(&[0xcb, 0x00, 0x00]), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_msgpack(&[0xa3, b'a']), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_msgpack(&[0x92, 0xc0]), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_msgpack(&[0x81, 0xc3]), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_msgpack(&[0xc1]), Err(DeserializeError::InvalidTag(0xc1)));
+		assert_eq!(lua.push_msgpack(&[0xd4, 0x01, 0x00]), Err(DeserializeError::InvalidTag(0xd4)));
+		assert_eq!(lua.push_msgpack(&[0x81, 0xc0, 0xc3]), Err(DeserializeError::InvalidKey));
+		assert_eq!(lua.push_msgpack(&[0xc0, 0xc0]), Err(DeserializeError::TrailingBytes));
+		assert_eq!(lua.top(), 0);
+	}
+}
@@ -0,0 +1,180 @@
+use core::{
+	ffi::{
+		CStr, c_uint,
+	},
+	fmt::Write,
+	ops::{
+		Deref, DerefMut,
+	},
+	str,
+};
+
+use super::{
+	chunks::CStrBuf,
+	func::Func,
+	Lua, StackPos, StdType, ToLua,
+};
+
+/// Table of a namespace which is being populated, as returned by [`Lua::namespace`].
+/// 
+/// The table stays on the stack until this is dropped,
+/// and values pushed above it are popped along with it.
+/// This dereferences to [`Lua`], so the stack can still be used as usual.
+#[derive(Debug)]
+pub struct Namespace<'l> {
+	lua: &'l mut Lua,
+	stack_pos: StackPos,
+}
+
+impl Namespace<'_> {
+	/// Returns the absolute stack position of the table.
+	pub const fn stack_pos(&self) -> StackPos {
+		self.stack_pos
+	}
+
+	/// Sets `t[key]` to `value`, where `t` is the table,
+	/// replacing any existing value.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set<T: ToLua>(&mut self, key: &CStr, value: T) -> &mut Self {
+		value.push_to(self.lua);
+		self.lua.set_field(self.stack_pos, key);
+		self
+	}
+
+	/// Sets `t[key]` to `value`, where `t` is the table,
+	/// only if `t[key]` is `nil`.
+	/// 
+	/// This is useful for values which other binary modules in the same namespace may have set already,
+	/// such as shared configuration.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_default<T: ToLua>(&mut self, key: &CStr, value: T) -> &mut Self {
+		self.lua.get_field(self.stack_pos, key);
+		let is_nil = self.lua.is_type(-1, StdType::Nil);
+		self.lua.pop(1);
+		if is_nil {
+			self.set(key, value);
+		}
+		self
+	}
+
+	/// Sets `t[key]` to the function `f`, where `t` is the table,
+	/// replacing any existing value.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_function(&mut self, key: &CStr, f: Func) -> &mut Self {
+		self.lua.push_function(f);
+		self.lua.set_field(self.stack_pos, key);
+		self
+	}
+
+	/// Gets or creates the nested namespace `path` in the table, like [`Lua::namespace`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// such as when a part of `path` exists but is not a table.
+	pub fn namespace(&mut self, path: &CStr) -> Namespace<'_> {
+		self.lua.push_value(self.stack_pos);
+		self.lua.namespace_in_table(path)
+	}
+
+	/// Leaves the table on the stack, and returns its absolute stack position.
+	pub fn keep(self) -> StackPos {
+		let stack_pos = self.stack_pos;
+		core::mem::forget(self);
+		stack_pos
+	}
+}
+
+impl Deref for Namespace<'_> {
+	type Target = Lua;
+	fn deref(&self) -> &Self::Target {
+		self.lua
+	}
+}
+impl DerefMut for Namespace<'_> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.lua
+	}
+}
+
+impl Drop for Namespace<'_> {
+	fn drop(&mut self) {
+		self.lua.set_top((self.stack_pos - 1) as c_uint);
+	}
+}
+
+/// Functions for sharing global tables between binary modules.
+impl Lua {
+	/// Gets or creates the global table `path`, and returns a view of it for populating it.
+	/// 
+	/// `path` may contain dots to refer to nested tables, such as `myorg.net`,
+	/// where each table is only created if it doesn't exist already.
+	/// This way, binary modules and Lua scripts which share a namespace
+	/// can be loaded in any order without replacing each other's tables.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// such as when a part of `path` exists but is not a table.
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::prelude::*;
+	/// 
+	/// extern "C-unwind" fn connect(cx: LuaCtx<'_>) -> LuaRets {
+	///     let _ = cx;
+	///     LuaRets::ZERO
+	/// }
+	/// 
+	/// struct Net;
+	/// impl LuaModule for Net {
+	///     fn open(&mut self, lua: &mut Lua) {
+	///         // Another binary module may have created `myorg` already.
+	///         lua.namespace(c"myorg.net")
+	///             .set(c"VERSION", 2)
+	///             .set_default(c"timeout", 30)
+	///             .set_function(c"connect", connect);
+	///     }
+	/// }
+	/// ```
+	pub fn namespace(&mut self, path: &CStr) -> Namespace<'_> {
+		self.push_globals();
+		self.namespace_in_table(path)
+	}
+
+	/// Replaces the table at the top of the stack with the nested namespace `path` in it.
+	fn namespace_in_table(&mut self, path: &CStr) -> Namespace<'_> {
+		let stack_pos = self.top() as StackPos;
+		let bytes = path.to_bytes();
+		let mut end = 0;
+		for part in bytes.split(|&b| b == b'.') {
+			end += part.len();
+			self.push_string(part);
+			self.get_table(-2);
+			if self.is_type(-1, StdType::Nil) {
+				self.pop(1);
+				self.create_table();
+				self.push_string(part);
+				self.push_value(-2);
+				self.set_table(-4);
+			} else if !self.is_type(-1, StdType::Table) {
+				let mut message = CStrBuf::new();
+				let path = str::from_utf8(&bytes[..end]).unwrap_or("?");
+				let _ = write!(message, "namespace `{path}` already exists, but is not a table");
+				self.throw_error(message.as_c_str())
+			}
+			self.remove(-2);
+			end += 1;
+		}
+		Namespace {
+			lua: self,
+			stack_pos,
+		}
+	}
+}
@@ -0,0 +1,150 @@
+use crate::source::Vector;
+
+use super::{
+	super::{
+		Lua, Number, StackPos,
+	},
+	call_method, set_value_method,
+	get_vector_method, set_vector_method,
+	get_number_method, set_number_method,
+};
+
+std_type_handle! {
+	/// Handle to damage information (`CTakeDamageInfo`) on the Lua stack,
+	/// which is passed to damage hooks and `Entity:TakeDamageInfo`.
+	DamageInfo = DamageInfo;
+	check: check_damage_info;
+	test: test_damage_info;
+}
+
+/// Functions for constructing damage information.
+impl Lua {
+	/// Pushes empty damage information created with the global `DamageInfo()` function,
+	/// and returns a handle to it.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// including if `DamageInfo()` doesn't return damage information.
+	pub fn create_damage_info(&mut self) -> DamageInfo<'_> {
+		self.get_global(c"DamageInfo");
+		self.call(0, 1);
+		self.check_damage_info(-1)
+	}
+}
+
+impl DamageInfo<'_> {
+	/// Returns the amount of damage, as returned by `CTakeDamageInfo:GetDamage()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn damage(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"GetDamage")
+	}
+
+	/// Sets the amount of damage with `CTakeDamageInfo:SetDamage(damage)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_damage(&mut self, damage: Number) {
+		set_number_method(self.lua, self.stack_pos, c"SetDamage", damage)
+	}
+
+	/// Adds to the amount of damage with `CTakeDamageInfo:AddDamage(damage)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn add_damage(&mut self, damage: Number) {
+		set_number_method(self.lua, self.stack_pos, c"AddDamage", damage)
+	}
+
+	/// Multiplies the amount of damage by `scale` with `CTakeDamageInfo:ScaleDamage(scale)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn scale_damage(&mut self, scale: Number) {
+		set_number_method(self.lua, self.stack_pos, c"ScaleDamage", scale)
+	}
+
+	/// Returns the `DMG_*` flags of the damage, as returned by `CTakeDamageInfo:GetDamageType()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn damage_type(&mut self) -> u32 {
+		get_number_method(self.lua, self.stack_pos, c"GetDamageType") as u32
+	}
+
+	/// Sets the `DMG_*` flags of the damage with `CTakeDamageInfo:SetDamageType(ty)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_damage_type(&mut self, ty: u32) {
+		set_number_method(self.lua, self.stack_pos, c"SetDamageType", ty as Number)
+	}
+
+	/// Returns the force of the damage, as returned by `CTakeDamageInfo:GetDamageForce()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn damage_force(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetDamageForce")
+	}
+
+	/// Sets the force of the damage with `CTakeDamageInfo:SetDamageForce(force)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_damage_force(&mut self, force: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetDamageForce", force)
+	}
+
+	/// Returns the position where the damage was done,
+	/// as returned by `CTakeDamageInfo:GetDamagePosition()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn damage_position(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetDamagePosition")
+	}
+
+	/// Sets the position where the damage was done with `CTakeDamageInfo:SetDamagePosition(pos)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_damage_position(&mut self, pos: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetDamagePosition", pos)
+	}
+
+	/// Pushes the attacker, as returned by `CTakeDamageInfo:GetAttacker()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_attacker(&mut self) {
+		call_method(self.lua, self.stack_pos, c"GetAttacker", |_| 0, 1)
+	}
+
+	/// Sets the attacker to the entity at `attacker` with `CTakeDamageInfo:SetAttacker(attacker)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_attacker(&mut self, attacker: StackPos) {
+		set_value_method(self.lua, self.stack_pos, c"SetAttacker", attacker)
+	}
+
+	/// Pushes the inflictor, as returned by `CTakeDamageInfo:GetInflictor()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_inflictor(&mut self) {
+		call_method(self.lua, self.stack_pos, c"GetInflictor", |_| 0, 1)
+	}
+
+	/// Sets the inflictor to the entity at `inflictor` with `CTakeDamageInfo:SetInflictor(inflictor)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_inflictor(&mut self, inflictor: StackPos) {
+		set_value_method(self.lua, self.stack_pos, c"SetInflictor", inflictor)
+	}
+}
@@ -0,0 +1,213 @@
+use core::ffi::CStr;
+
+use crate::source::{
+	QAngle, Vector,
+};
+
+use super::{
+	super::{
+		Lua, Number, StackPos,
+	},
+	call_method, set_value_method,
+	get_vector_method, set_vector_method,
+	get_number_method, set_number_method,
+};
+
+std_type_handle! {
+	/// Handle to effect data (`CEffectData`) on the Lua stack,
+	/// which describes an effect to dispatch with [`EffectData::dispatch`].
+	EffectData = EffectData;
+	check: check_effect_data;
+	test: test_effect_data;
+}
+
+/// Functions for constructing effect data.
+impl Lua {
+	/// Pushes empty effect data created with the global `EffectData()` function,
+	/// and returns a handle to it.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// including if `EffectData()` doesn't return effect data.
+	pub fn create_effect_data(&mut self) -> EffectData<'_> {
+		self.get_global(c"EffectData");
+		self.call(0, 1);
+		self.check_effect_data(-1)
+	}
+}
+
+impl EffectData<'_> {
+	/// Dispatches the effect `name` with this data using `util.Effect(name, data)`.
+	/// 
+	/// On the server, the effect is sent to the players in the Potentially Audible Set of its origin,
+	/// or to the players in the recipient filter at `recipients` if it is `Some`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn dispatch(&mut self, name: &CStr, recipients: Option<StackPos>) {
+		let recipients = recipients.map(|pos| self.lua.abs_index(pos));
+		self.lua.get_global_field(c"util", c"Effect");
+		self.lua.push_c_string(name);
+		self.lua.push_value(self.stack_pos);
+		if let Some(recipients) = recipients {
+			// `allowOverride`, which is the default.
+			self.lua.push_bool(true);
+			self.lua.push_value(recipients);
+			self.lua.call(4, 0);
+		} else {
+			self.lua.call(2, 0);
+		}
+	}
+
+	/// Returns the origin of the effect, as returned by `CEffectData:GetOrigin()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn origin(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetOrigin")
+	}
+
+	/// Sets the origin of the effect with `CEffectData:SetOrigin(origin)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_origin(&mut self, origin: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetOrigin", origin)
+	}
+
+	/// Returns the start of the effect, as returned by `CEffectData:GetStart()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn start(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetStart")
+	}
+
+	/// Sets the start of the effect with `CEffectData:SetStart(start)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_start(&mut self, start: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetStart", start)
+	}
+
+	/// Returns the normal of the effect, as returned by `CEffectData:GetNormal()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn normal(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetNormal")
+	}
+
+	/// Sets the normal of the effect with `CEffectData:SetNormal(normal)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_normal(&mut self, normal: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetNormal", normal)
+	}
+
+	/// Returns the angles of the effect, as returned by `CEffectData:GetAngles()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn angles(&mut self) -> QAngle {
+		call_method(self.lua, self.stack_pos, c"GetAngles", |_| 0, 1);
+		let angles = *self.lua.get_angle(-1);
+		self.lua.pop(1);
+		angles
+	}
+
+	/// Sets the angles of the effect with `CEffectData:SetAngles(angles)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_angles(&mut self, angles: &QAngle) {
+		call_method(self.lua, self.stack_pos, c"SetAngles", |lua| {
+			lua.push_angle(angles);
+			1
+		}, 0)
+	}
+
+	/// Returns the scale of the effect, as returned by `CEffectData:GetScale()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn scale(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"GetScale")
+	}
+
+	/// Sets the scale of the effect with `CEffectData:SetScale(scale)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_scale(&mut self, scale: Number) {
+		set_number_method(self.lua, self.stack_pos, c"SetScale", scale)
+	}
+
+	/// Returns the magnitude of the effect, as returned by `CEffectData:GetMagnitude()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn magnitude(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"GetMagnitude")
+	}
+
+	/// Sets the magnitude of the effect with `CEffectData:SetMagnitude(magnitude)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_magnitude(&mut self, magnitude: Number) {
+		set_number_method(self.lua, self.stack_pos, c"SetMagnitude", magnitude)
+	}
+
+	/// Returns the radius of the effect, as returned by `CEffectData:GetRadius()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn radius(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"GetRadius")
+	}
+
+	/// Sets the radius of the effect with `CEffectData:SetRadius(radius)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_radius(&mut self, radius: Number) {
+		set_number_method(self.lua, self.stack_pos, c"SetRadius", radius)
+	}
+
+	/// Returns the flags of the effect, as returned by `CEffectData:GetFlags()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn flags(&mut self) -> u32 {
+		get_number_method(self.lua, self.stack_pos, c"GetFlags") as u32
+	}
+
+	/// Sets the flags of the effect with `CEffectData:SetFlags(flags)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_flags(&mut self, flags: u32) {
+		set_number_method(self.lua, self.stack_pos, c"SetFlags", flags as Number)
+	}
+
+	/// Pushes the entity of the effect, as returned by `CEffectData:GetEntity()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_entity(&mut self) {
+		call_method(self.lua, self.stack_pos, c"GetEntity", |_| 0, 1)
+	}
+
+	/// Sets the entity of the effect to the one at `entity` with `CEffectData:SetEntity(entity)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_entity(&mut self, entity: StackPos) {
+		set_value_method(self.lua, self.stack_pos, c"SetEntity", entity)
+	}
+}
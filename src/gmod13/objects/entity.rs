@@ -0,0 +1,293 @@
+use core::{
+	error::Error,
+	ffi::{
+		CStr, c_uint,
+	},
+	fmt,
+};
+
+use crate::source::{
+	QAngle, Vector,
+};
+
+use super::{
+	super::{
+		Lua, StackPos, StdType,
+	},
+	call_method, is_valid, set_value_method,
+	get_vector_method, set_vector_method,
+};
+
+std_type_handle! {
+	/// Handle to an entity (`Entity`) on the Lua stack,
+	/// which may also be a player, weapon, NPC or vehicle.
+	Entity = Entity;
+	check: check_entity;
+	test: test_entity;
+}
+
+/// Error returned when an entity can't be created with [`Lua::create_entity`] or [`Lua::build_entity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CreateEntityError {
+	/// `ents.Create` doesn't exist, such as when the Lua state is not of the server realm.
+	Unavailable,
+	/// `ents.Create` returned `NULL`, since there is no entity class with the given name.
+	InvalidClass,
+	/// The entity was removed while it was being spawned.
+	RemovedOnSpawn,
+}
+
+impl Error for CreateEntityError {}
+impl fmt::Display for CreateEntityError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Unavailable => "entities can only be created on the server",
+			Self::InvalidClass => "entity class doesn't exist",
+			Self::RemovedOnSpawn => "entity was removed while spawning",
+		})
+	}
+}
+
+/// Functions for creating entities.
+impl Lua {
+	/// Creates an entity of the class `class`, spawns it and activates it,
+	/// and returns a handle to it, which is left on the top of the stack.
+	/// 
+	/// See [`build_entity`](Self::build_entity) for setting properties of the entity before it's spawned.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// If an error is returned, then nothing is left on the stack.
+	pub fn create_entity(&mut self, class: &CStr) -> Result<Entity<'_>, CreateEntityError> {
+		self.build_entity(class)?.spawn()
+	}
+
+	/// Creates an entity of the class `class` with `ents.Create(class)`,
+	/// and returns a builder which sets its properties before it's spawned.
+	/// 
+	/// The entity is left on the top of the stack.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// If an error is returned, then nothing is left on the stack.
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::prelude::*;
+	/// use gmbm::gmod13::objects::CreateEntityError;
+	/// 
+	/// /// Spawns a crate at `pos`, which can't be moved by players.
+	/// fn spawn_crate(lua: &mut Lua, pos: &SeVector) -> Result<(), CreateEntityError> {
+	///     lua.build_entity(c"prop_physics")?
+	///         .model(c"models/props_junk/wood_crate001a.mdl")
+	///         .pos(pos)
+	///         // Motion is disabled on spawn.
+	///         .key_value(c"spawnflags", c"8")
+	///         .spawn()?;
+	///     lua.pop(1);
+	///     Ok(())
+	/// }
+	/// ```
+	pub fn build_entity(&mut self, class: &CStr) -> Result<EntityBuilder<'_>, CreateEntityError> {
+		self.get_global_field(c"ents", c"Create");
+		if !self.is_type(-1, StdType::Function) {
+			self.pop(1);
+			return Err(CreateEntityError::Unavailable)
+		}
+		self.push_c_string(class);
+		self.call(1, 1);
+		if !self.is_type(-1, StdType::Entity) || !is_valid(self, -1) {
+			self.pop(1);
+			return Err(CreateEntityError::InvalidClass)
+		}
+		Ok(EntityBuilder {
+			entity: self.check_entity(-1),
+		})
+	}
+}
+
+impl Entity<'_> {
+	/// Returns `true` if the entity is valid, as returned by `Entity:IsValid()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn is_valid(&mut self) -> bool {
+		is_valid(self.lua, self.stack_pos)
+	}
+
+	/// Returns the position of the entity, as returned by `Entity:GetPos()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn pos(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetPos")
+	}
+
+	/// Sets the position of the entity with `Entity:SetPos(pos)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_pos(&mut self, pos: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetPos", pos)
+	}
+
+	/// Returns the angles of the entity, as returned by `Entity:GetAngles()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn angles(&mut self) -> QAngle {
+		call_method(self.lua, self.stack_pos, c"GetAngles", |_| 0, 1);
+		let angles = *self.lua.get_angle(-1);
+		self.lua.pop(1);
+		angles
+	}
+
+	/// Sets the angles of the entity with `Entity:SetAngles(angles)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_angles(&mut self, angles: &QAngle) {
+		call_method(self.lua, self.stack_pos, c"SetAngles", |lua| {
+			lua.push_angle(angles);
+			1
+		}, 0)
+	}
+
+	/// Sets the model of the entity with `Entity:SetModel(model)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_model(&mut self, model: &CStr) {
+		call_method(self.lua, self.stack_pos, c"SetModel", |lua| {
+			lua.push_c_string(model);
+			1
+		}, 0)
+	}
+
+	/// Sets the key-value `key` of the entity to `value` with `Entity:SetKeyValue(key, value)`,
+	/// like the key-values of entities placed in a map.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_key_value(&mut self, key: &CStr, value: &CStr) {
+		call_method(self.lua, self.stack_pos, c"SetKeyValue", |lua| {
+			lua.push_c_string(key);
+			lua.push_c_string(value);
+			2
+		}, 0)
+	}
+
+	/// Sets the owner of the entity to the entity at `owner` with `Entity:SetOwner(owner)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_owner(&mut self, owner: StackPos) {
+		set_value_method(self.lua, self.stack_pos, c"SetOwner", owner)
+	}
+
+	/// Spawns the entity with `Entity:Spawn()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn spawn(&mut self) {
+		call_method(self.lua, self.stack_pos, c"Spawn", |_| 0, 0)
+	}
+
+	/// Activates the entity with `Entity:Activate()`,
+	/// which is usually done right after it's spawned.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn activate(&mut self) {
+		call_method(self.lua, self.stack_pos, c"Activate", |_| 0, 0)
+	}
+
+	/// Removes the entity with `Entity:Remove()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn remove(&mut self) {
+		call_method(self.lua, self.stack_pos, c"Remove", |_| 0, 0)
+	}
+}
+
+/// Builder of an entity which hasn't been spawned yet, as returned by [`Lua::build_entity`].
+/// 
+/// Properties which are set with this builder are set before the entity is spawned,
+/// so they're already in effect when the entity initializes itself.
+/// If the builder is dropped without calling [`spawn`](Self::spawn),
+/// then the entity is left on the stack, but never spawned.
+pub struct EntityBuilder<'a> {
+	entity: Entity<'a>,
+}
+
+impl<'a> EntityBuilder<'a> {
+	/// Returns a handle to the entity, for setting properties that this builder doesn't have methods for.
+	pub const fn entity(&mut self) -> &mut Entity<'a> {
+		&mut self.entity
+	}
+
+	/// Sets the position of the entity.
+	/// 
+	/// See [`Entity::set_pos`].
+	pub fn pos(mut self, pos: &Vector) -> Self {
+		self.entity.set_pos(pos);
+		self
+	}
+
+	/// Sets the angles of the entity.
+	/// 
+	/// See [`Entity::set_angles`].
+	pub fn angles(mut self, angles: &QAngle) -> Self {
+		self.entity.set_angles(angles);
+		self
+	}
+
+	/// Sets the model of the entity.
+	/// 
+	/// See [`Entity::set_model`].
+	pub fn model(mut self, model: &CStr) -> Self {
+		self.entity.set_model(model);
+		self
+	}
+
+	/// Sets the key-value `key` of the entity to `value`.
+	/// 
+	/// See [`Entity::set_key_value`].
+	pub fn key_value(mut self, key: &CStr, value: &CStr) -> Self {
+		self.entity.set_key_value(key, value);
+		self
+	}
+
+	/// Sets the owner of the entity to the entity at `owner`.
+	/// 
+	/// See [`Entity::set_owner`].
+	pub fn owner(mut self, owner: StackPos) -> Self {
+		self.entity.set_owner(owner);
+		self
+	}
+
+	/// Spawns and activates the entity, and returns a handle to it.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// If the entity was removed while it was being spawned, such as by a hook,
+	/// then [`CreateEntityError::RemovedOnSpawn`] is returned, and the entity is popped from the stack.
+	pub fn spawn(self) -> Result<Entity<'a>, CreateEntityError> {
+		let mut entity = self.entity;
+		entity.spawn();
+		if !entity.is_valid() {
+			entity.lua.set_top((entity.stack_pos - 1) as c_uint);
+			return Err(CreateEntityError::RemovedOnSpawn)
+		}
+		entity.activate();
+		Ok(entity)
+	}
+}
@@ -0,0 +1,165 @@
+use core::ffi::{
+	CStr, c_uint,
+};
+
+use crate::source::Vector;
+
+use super::{
+	super::{
+		Lua, LuaString, Number, StackPos, StdType,
+	},
+	call_method, get_number_method,
+};
+
+std_type_handle! {
+	/// Handle to a material (`IMaterial`) on the Lua stack.
+	Material = Material;
+	check: check_material;
+	test: test_material;
+}
+
+std_type_handle! {
+	/// Handle to a texture (`ITexture`) on the Lua stack.
+	Texture = Texture;
+	check: check_texture;
+	test: test_texture;
+}
+
+impl Material<'_> {
+	/// Returns the name of the material, as returned by `IMaterial:GetName()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn name(&mut self) -> Option<LuaString<'_>> {
+		call_method(self.lua, self.stack_pos, c"GetName", |_| 0, 1);
+		LuaString::from_top(self.lua)
+	}
+
+	/// Returns `true` if the material failed to load, as returned by `IMaterial:IsError()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn is_error(&mut self) -> bool {
+		call_method(self.lua, self.stack_pos, c"IsError", |_| 0, 1);
+		let is_error = self.lua.get_bool(-1);
+		self.lua.pop(1);
+		is_error
+	}
+
+	/// Pushes the texture of the material parameter `key` and returns a handle to it,
+	/// as returned by `IMaterial:GetTexture(key)`,
+	/// or returns `None` if the parameter is not a texture.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn texture(&mut self, key: &CStr) -> Option<Texture<'_>> {
+		self.call_with_key(c"GetTexture", key, |_| 0, 1);
+		if !self.lua.is_type(-1, StdType::Texture) {
+			self.lua.pop(1);
+			return None
+		}
+		self.lua.test_texture(-1)
+	}
+
+	/// Sets the material parameter `key` to the texture at `texture` with `IMaterial:SetTexture(key, texture)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_texture(&mut self, key: &CStr, texture: StackPos) {
+		let texture = self.lua.abs_index(texture);
+		self.call_with_key(c"SetTexture", key, |lua| {
+			lua.push_value(texture);
+			1
+		}, 0)
+	}
+
+	/// Returns the material parameter `key` as a number, as returned by `IMaterial:GetFloat(key)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn float(&mut self, key: &CStr) -> Number {
+		self.call_with_key(c"GetFloat", key, |_| 0, 1);
+		let n = self.lua.get_number(-1);
+		self.lua.pop(1);
+		n
+	}
+
+	/// Sets the material parameter `key` to a number with `IMaterial:SetFloat(key, n)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_float(&mut self, key: &CStr, n: Number) {
+		self.call_with_key(c"SetFloat", key, |lua| {
+			lua.push_number(n);
+			1
+		}, 0)
+	}
+
+	/// Returns the material parameter `key` as a [`Vector`], as returned by `IMaterial:GetVector(key)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn vector(&mut self, key: &CStr) -> Vector {
+		self.call_with_key(c"GetVector", key, |_| 0, 1);
+		let vector = *self.lua.get_vector(-1);
+		self.lua.pop(1);
+		vector
+	}
+
+	/// Sets the material parameter `key` to a [`Vector`] with `IMaterial:SetVector(key, vector)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_vector(&mut self, key: &CStr, vector: &Vector) {
+		self.call_with_key(c"SetVector", key, |lua| {
+			lua.push_vector(vector);
+			1
+		}, 0)
+	}
+
+	/// Calls the method `name` with `key` as the first argument, followed by the arguments pushed by `push_args`.
+	fn call_with_key<F: FnOnce(&mut Lua) -> c_uint>(&mut self, name: &CStr, key: &CStr, push_args: F, n_results: c_uint) {
+		call_method(self.lua, self.stack_pos, name, |lua| {
+			lua.push_c_string(key);
+			push_args(lua) + 1
+		}, n_results)
+	}
+}
+
+impl Texture<'_> {
+	/// Returns the name of the texture, as returned by `ITexture:GetName()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn name(&mut self) -> Option<LuaString<'_>> {
+		call_method(self.lua, self.stack_pos, c"GetName", |_| 0, 1);
+		LuaString::from_top(self.lua)
+	}
+
+	/// Returns `true` if the texture failed to load, as returned by `ITexture:IsError()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn is_error(&mut self) -> bool {
+		call_method(self.lua, self.stack_pos, c"IsError", |_| 0, 1);
+		let is_error = self.lua.get_bool(-1);
+		self.lua.pop(1);
+		is_error
+	}
+
+	/// Returns the width of the texture, as returned by `ITexture:Width()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn width(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"Width")
+	}
+
+	/// Returns the height of the texture, as returned by `ITexture:Height()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn height(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"Height")
+	}
+}
@@ -0,0 +1,151 @@
+//! Typed handles to Garry's Mod objects on the Lua stack.
+
+use core::ffi::{
+	CStr,
+	c_uint,
+};
+
+use crate::source::Vector;
+
+use super::{
+	Lua, Number, StackPos,
+};
+
+/// Defines a handle type for values of a [`StdType`],
+/// along with functions on [`Lua`] to obtain it.
+macro_rules! std_type_handle {
+	{
+		$(#[$attr:meta])*
+		$Handle:ident = $ty:ident;
+		check: $check:ident;
+		test: $test:ident;
+	} => {
+		$(#[$attr])*
+		pub struct $Handle<'a> {
+			lua: &'a mut $crate::gmod13::Lua,
+			stack_pos: $crate::gmod13::StackPos,
+		}
+
+		impl<'a> $Handle<'a> {
+			/// Returns the absolute position of the object on the stack.
+			pub const fn stack_pos(&self) -> $crate::gmod13::StackPos {
+				self.stack_pos
+			}
+
+			/// Returns the [`Lua`](crate::gmod13::Lua) state that the object is in.
+			pub const fn lua(&mut self) -> &mut $crate::gmod13::Lua {
+				self.lua
+			}
+
+			/// Converts this handle into the [`Lua`](crate::gmod13::Lua) state that the object is in.
+			pub const fn into_lua(self) -> &'a mut $crate::gmod13::Lua {
+				self.lua
+			}
+		}
+
+		impl $crate::gmod13::Lua {
+			#[doc = ::core::concat!(
+				"Returns a [`", ::core::stringify!($Handle), "`] handle to the value at `arg`.\n\n",
+				"# Errors\n",
+				"The inner Lua state may raise an [error](crate::errors) if the value is not of the expected type.",
+			)]
+			pub fn $check(&mut self, arg: $crate::gmod13::StackPos) -> $Handle<'_> {
+				self.check_type(arg, $crate::gmod13::StdType::$ty);
+				let stack_pos = self.abs_index(arg);
+				$Handle {
+					lua: self,
+					stack_pos,
+				}
+			}
+
+			#[doc = ::core::concat!(
+				"Returns a [`", ::core::stringify!($Handle), "`] handle to the value at `stack_pos`,\n",
+				"or `None` if the value is not of the expected type.",
+			)]
+			pub fn $test(&mut self, stack_pos: $crate::gmod13::StackPos) -> Option<$Handle<'_>> {
+				if !self.is_type(stack_pos, $crate::gmod13::StdType::$ty) {
+					return None
+				}
+				let stack_pos = self.abs_index(stack_pos);
+				Some($Handle {
+					lua: self,
+					stack_pos,
+				})
+			}
+		}
+	};
+}
+
+mod phys_obj;
+pub use phys_obj::*;
+mod material;
+pub use material::*;
+mod user_msg;
+pub use user_msg::*;
+mod recipient_filter;
+pub use recipient_filter::*;
+mod damage_info;
+pub use damage_info::*;
+mod effect_data;
+pub use effect_data::*;
+mod entity;
+pub use entity::*;
+
+/// Calls the method `name` of the object at `stack_pos` with the arguments pushed by `push_args`,
+/// keeping `n_results` return values on the stack.
+fn call_method<F: FnOnce(&mut Lua) -> c_uint>(
+	lua: &mut Lua, stack_pos: StackPos, name: &CStr, push_args: F, n_results: c_uint,
+) {
+	lua.push_self_method(stack_pos, name);
+	let n_args = push_args(lua);
+	lua.call(n_args + 1, n_results);
+}
+
+/// Returns `true` if calling the method `IsValid` on the object at `stack_pos` returns a truthy value.
+fn is_valid(lua: &mut Lua, stack_pos: StackPos) -> bool {
+	call_method(lua, stack_pos, c"IsValid", |_| 0, 1);
+	let is_valid = lua.get_bool(-1);
+	lua.pop(1);
+	is_valid
+}
+
+/// Returns the [`Vector`] returned by calling the method `name` of the object at `stack_pos`.
+fn get_vector_method(lua: &mut Lua, stack_pos: StackPos, name: &CStr) -> Vector {
+	call_method(lua, stack_pos, name, |_| 0, 1);
+	let vector = *lua.get_vector(-1);
+	lua.pop(1);
+	vector
+}
+
+/// Calls the method `name` of the object at `stack_pos` with `vector` as the only argument.
+fn set_vector_method(lua: &mut Lua, stack_pos: StackPos, name: &CStr, vector: &Vector) {
+	call_method(lua, stack_pos, name, |lua| {
+		lua.push_vector(vector);
+		1
+	}, 0)
+}
+
+/// Returns the [`Number`] returned by calling the method `name` of the object at `stack_pos`.
+fn get_number_method(lua: &mut Lua, stack_pos: StackPos, name: &CStr) -> Number {
+	call_method(lua, stack_pos, name, |_| 0, 1);
+	let n = lua.get_number(-1);
+	lua.pop(1);
+	n
+}
+
+/// Calls the method `name` of the object at `stack_pos` with `n` as the only argument.
+fn set_number_method(lua: &mut Lua, stack_pos: StackPos, name: &CStr, n: Number) {
+	call_method(lua, stack_pos, name, |lua| {
+		lua.push_number(n);
+		1
+	}, 0)
+}
+
+/// Calls the method `name` of the object at `stack_pos` with the value at `value` as the only argument.
+fn set_value_method(lua: &mut Lua, stack_pos: StackPos, name: &CStr, value: StackPos) {
+	let value = lua.abs_index(value);
+	call_method(lua, stack_pos, name, |lua| {
+		lua.push_value(value);
+		1
+	}, 0)
+}
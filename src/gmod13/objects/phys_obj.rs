@@ -0,0 +1,100 @@
+use crate::source::Vector;
+
+use super::{
+	super::Number,
+	call_method, is_valid,
+	get_vector_method, set_vector_method,
+	get_number_method, set_number_method,
+};
+
+std_type_handle! {
+	/// Handle to a physics object (`PhysObj`) on the Lua stack.
+	PhysObj = PhysObj;
+	check: check_phys_obj;
+	test: test_phys_obj;
+}
+
+impl PhysObj<'_> {
+	/// Returns `true` if the physics object is valid, as returned by `PhysObj:IsValid()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn is_valid(&mut self) -> bool {
+		is_valid(self.lua, self.stack_pos)
+	}
+
+	/// Returns the position of the physics object, as returned by `PhysObj:GetPos()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn pos(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetPos")
+	}
+
+	/// Sets the position of the physics object with `PhysObj:SetPos(pos)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_pos(&mut self, pos: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetPos", pos)
+	}
+
+	/// Returns the velocity of the physics object, as returned by `PhysObj:GetVelocity()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn velocity(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"GetVelocity")
+	}
+
+	/// Sets the velocity of the physics object with `PhysObj:SetVelocity(velocity)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_velocity(&mut self, velocity: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"SetVelocity", velocity)
+	}
+
+	/// Applies `force` to the center of mass of the physics object with `PhysObj:ApplyForceCenter(force)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn apply_force_center(&mut self, force: &Vector) {
+		set_vector_method(self.lua, self.stack_pos, c"ApplyForceCenter", force)
+	}
+
+	/// Returns the mass of the physics object, as returned by `PhysObj:GetMass()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn mass(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"GetMass")
+	}
+
+	/// Sets the mass of the physics object with `PhysObj:SetMass(mass)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_mass(&mut self, mass: Number) {
+		set_number_method(self.lua, self.stack_pos, c"SetMass", mass)
+	}
+
+	/// Enables or disables motion of the physics object with `PhysObj:EnableMotion(enable)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn enable_motion(&mut self, enable: bool) {
+		call_method(self.lua, self.stack_pos, c"EnableMotion", |lua| {
+			lua.push_bool(enable);
+			1
+		}, 0)
+	}
+
+	/// Wakes the physics object up with `PhysObj:Wake()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn wake(&mut self) {
+		call_method(self.lua, self.stack_pos, c"Wake", |_| 0, 0)
+	}
+}
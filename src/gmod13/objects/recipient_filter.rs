@@ -0,0 +1,104 @@
+use crate::source::Vector;
+
+use super::{
+	super::{
+		Lua, Number, StackPos,
+	},
+	call_method, get_number_method, set_value_method,
+};
+
+std_type_handle! {
+	/// Handle to a recipient filter (`CRecipientFilter`) on the Lua stack,
+	/// which selects the players that a message or effect is sent to.
+	/// 
+	/// The filter can be passed to Lua functions which accept one by its [stack position](Self::stack_pos),
+	/// such as with [`Recipients::At`](crate::gmod13::message::Recipients::At).
+	RecipientFilter = RecipientFilter;
+	check: check_recipient_filter;
+	test: test_recipient_filter;
+}
+
+/// Functions for constructing recipient filters.
+impl Lua {
+	/// Pushes an empty recipient filter created with the global `RecipientFilter()` function,
+	/// and returns a handle to it.
+	/// 
+	/// Recipient filters can only be created on the server.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// including if `RecipientFilter()` doesn't return a recipient filter.
+	pub fn create_recipient_filter(&mut self) -> RecipientFilter<'_> {
+		self.get_global(c"RecipientFilter");
+		self.call(0, 1);
+		self.check_recipient_filter(-1)
+	}
+}
+
+impl RecipientFilter<'_> {
+	/// Adds all players to the filter with `CRecipientFilter:AddAllPlayers()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn add_all_players(&mut self) {
+		call_method(self.lua, self.stack_pos, c"AddAllPlayers", |_| 0, 0)
+	}
+
+	/// Removes all players from the filter with `CRecipientFilter:RemoveAllPlayers()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn remove_all_players(&mut self) {
+		call_method(self.lua, self.stack_pos, c"RemoveAllPlayers", |_| 0, 0)
+	}
+
+	/// Adds the player at `player` to the filter with `CRecipientFilter:AddPlayer(player)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn add_player(&mut self, player: StackPos) {
+		set_value_method(self.lua, self.stack_pos, c"AddPlayer", player)
+	}
+
+	/// Removes the player at `player` from the filter with `CRecipientFilter:RemovePlayer(player)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn remove_player(&mut self, player: StackPos) {
+		set_value_method(self.lua, self.stack_pos, c"RemovePlayer", player)
+	}
+
+	/// Adds all players in the Potentially Visible Set of `pos` to the filter
+	/// with `CRecipientFilter:AddPVS(pos)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn add_pvs(&mut self, pos: &Vector) {
+		call_method(self.lua, self.stack_pos, c"AddPVS", |lua| {
+			lua.push_vector(pos);
+			1
+		}, 0)
+	}
+
+	/// Adds all players in the Potentially Audible Set of `pos` to the filter
+	/// with `CRecipientFilter:AddPAS(pos)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn add_pas(&mut self, pos: &Vector) {
+		call_method(self.lua, self.stack_pos, c"AddPAS", |lua| {
+			lua.push_vector(pos);
+			1
+		}, 0)
+	}
+
+	/// Returns the number of players in the filter, as returned by `CRecipientFilter:GetCount()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn count(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"GetCount")
+	}
+}
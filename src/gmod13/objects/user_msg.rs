@@ -0,0 +1,119 @@
+use crate::source::{
+	QAngle, Vector,
+};
+
+use super::{
+	super::{
+		LuaString, Number,
+	},
+	call_method, get_vector_method, get_number_method,
+};
+
+std_type_handle! {
+	/// Handle to a user message (`bf_read`) on the Lua stack,
+	/// which is passed to hooks of the deprecated `usermessage` library.
+	/// 
+	/// New code should prefer the `net` library,
+	/// which [`MessageReader`](crate::gmod13::message::MessageReader) can read from transparently.
+	UserMsg = UserMsg;
+	check: check_user_msg;
+	test: test_user_msg;
+}
+
+impl UserMsg<'_> {
+	/// Reads an angle from the message with `bf_read:ReadAngle()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_angle(&mut self) -> QAngle {
+		call_method(self.lua, self.stack_pos, c"ReadAngle", |_| 0, 1);
+		let angle = *self.lua.get_angle(-1);
+		self.lua.pop(1);
+		angle
+	}
+
+	/// Reads a boolean from the message with `bf_read:ReadBool()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_bool(&mut self) -> bool {
+		call_method(self.lua, self.stack_pos, c"ReadBool", |_| 0, 1);
+		let b = self.lua.get_bool(-1);
+		self.lua.pop(1);
+		b
+	}
+
+	/// Reads a signed 8-bit integer from the message with `bf_read:ReadChar()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_char(&mut self) -> i8 {
+		get_number_method(self.lua, self.stack_pos, c"ReadChar") as i8
+	}
+
+	/// Reads a signed 16-bit integer from the message with `bf_read:ReadShort()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_short(&mut self) -> i16 {
+		get_number_method(self.lua, self.stack_pos, c"ReadShort") as i16
+	}
+
+	/// Reads a signed 32-bit integer from the message with `bf_read:ReadLong()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_long(&mut self) -> i32 {
+		get_number_method(self.lua, self.stack_pos, c"ReadLong") as i32
+	}
+
+	/// Reads a floating-point number from the message with `bf_read:ReadFloat()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_float(&mut self) -> Number {
+		get_number_method(self.lua, self.stack_pos, c"ReadFloat")
+	}
+
+	/// Reads a string from the message with `bf_read:ReadString()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_string(&mut self) -> Option<LuaString<'_>> {
+		call_method(self.lua, self.stack_pos, c"ReadString", |_| 0, 1);
+		LuaString::from_top(self.lua)
+	}
+
+	/// Reads a vector from the message with `bf_read:ReadVector()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_vector(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"ReadVector")
+	}
+
+	/// Reads a normalized vector from the message with `bf_read:ReadVectorNormal()`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_vector_normal(&mut self) -> Vector {
+		get_vector_method(self.lua, self.stack_pos, c"ReadVectorNormal")
+	}
+
+	/// Reads an entity from the message with `bf_read:ReadEntity()`,
+	/// pushing it onto the stack.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn read_entity(&mut self) {
+		call_method(self.lua, self.stack_pos, c"ReadEntity", |_| 0, 1)
+	}
+
+	/// Rewinds the message to the start with `bf_read:Reset()`, so that it can be read again.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn reset(&mut self) {
+		call_method(self.lua, self.stack_pos, c"Reset", |_| 0, 0)
+	}
+}
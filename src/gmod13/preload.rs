@@ -0,0 +1,115 @@
+use core::ffi::CStr;
+
+use super::{
+	func::{
+		Ctx, Func, Rets,
+	},
+	Lua, StdType,
+};
+
+/// Field of the registry which holds the `require` function that [`require_with_preload`] replaced.
+const ORIGINAL_REQUIRE_FIELD: &CStr = c"gmbm.require";
+
+/// Lua function `(name: string) -> any`,
+/// which returns `package.loaded[name]` if it's not `nil`,
+/// or calls the loader in `package.preload[name]` and stores its result in `package.loaded[name]`,
+/// or calls the original `require` otherwise.
+extern "C-unwind" fn require_with_preload(cx: Ctx<'_>) -> Rets {
+	let lua = cx.lua();
+	lua.check_string(1);
+	lua.set_top(1);
+	let loaded = lua.namespace(c"package.loaded").keep();
+	let preload = lua.namespace(c"package.preload").keep();
+
+	lua.push_value(1);
+	lua.get_table(loaded);
+	if !lua.is_type(-1, StdType::Nil) {
+		return Rets::new(1)
+	}
+	lua.pop(1);
+
+	lua.push_value(1);
+	lua.get_table(preload);
+	if lua.is_type(-1, StdType::Function) {
+		lua.push_value(1);
+		lua.call(1, 1);
+		if lua.is_type(-1, StdType::Nil) {
+			// Like in `require`, a loader which returns nothing marks the module as loaded.
+			lua.pop(1);
+			lua.push_bool(true);
+		}
+		lua.push_value(1);
+		lua.push_value(-2);
+		lua.set_table(loaded);
+		return Rets::new(1)
+	}
+	lua.pop(1);
+
+	lua.push_registry();
+	lua.get_field(-1, ORIGINAL_REQUIRE_FIELD);
+	lua.push_value(1);
+	lua.call(1, 1);
+	Rets::new(1)
+}
+
+/// Functions for registering submodules which are loaded with `require`.
+impl Lua {
+	/// Registers `loader` as the loader of the module `name` in `package.preload`,
+	/// so that `require(name)` in Lua calls `loader` with `name` the first time,
+	/// and returns the value that it returned, which is kept in `package.loaded`.
+	/// 
+	/// This allows the tables of submodules, such as `mymod.sub`,
+	/// to be created only when they're needed, instead of in [`Module::open`](super::Module::open).
+	/// 
+	/// The `require` of Garry's Mod doesn't look in `package.preload`,
+	/// so the first time this is called in a Lua state,
+	/// the global `require` is replaced with a function which does,
+	/// and which calls the original `require` for every other module.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::prelude::*;
+	/// 
+	/// /// Loader of `mymod.sub`, which is only called when it's required.
+	/// extern "C-unwind" fn load_sub(cx: LuaCtx<'_>) -> LuaRets {
+	///     let lua = cx.lua();
+	///     lua_table!(lua => {
+	///         answer = 42,
+	///     });
+	///     LuaRets::new(1)
+	/// }
+	/// 
+	/// struct MyMod;
+	/// impl LuaModule for MyMod {
+	///     fn open(&mut self, lua: &mut Lua) {
+	///         lua.preload_module(c"mymod.sub", load_sub);
+	///     }
+	/// }
+	/// ```
+	pub fn preload_module(&mut self, name: &CStr, loader: Func) {
+		self.install_require_with_preload();
+		self.namespace(c"package.preload").set_function(name, loader);
+	}
+
+	/// Replaces the global `require` with [`require_with_preload`], if that hasn't been done yet.
+	fn install_require_with_preload(&mut self) {
+		self.push_registry();
+		self.get_field(-1, ORIGINAL_REQUIRE_FIELD);
+		let is_installed = !self.is_type(-1, StdType::Nil);
+		self.pop(1);
+		if !is_installed {
+			self.get_global(c"require");
+			self.set_field(-2, ORIGINAL_REQUIRE_FIELD);
+			self.push_globals();
+			self.push_function(require_with_preload);
+			self.set_field(-2, c"require");
+			self.pop(1);
+		}
+		self.pop(1);
+	}
+}
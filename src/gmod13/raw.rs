@@ -2,7 +2,7 @@
 
 use core::{
 	ffi::{
-		c_int, c_char, c_uint, c_void, c_double,
+		c_int, c_char, c_uchar, c_uint, c_void, c_double,
 	},
 	ptr::NonNull,
 };
@@ -26,6 +26,13 @@ pub enum Special {
 
 /// LuaJIT state structure provided by
 /// the same Garry's Mod version that uses `gmod13_open` and `gmod13_close` functions for binary modules.
+/// 
+/// # Layout
+/// Garry's Mod stores a pointer to its [`LuaBase`] right after the `lua_State` structure of LuaJIT,
+/// at [`LUABASE_OFFSET`](Self::LUABASE_OFFSET), which is selected at compile time by the pointer width.
+/// [`Lua::from_state_ptr_checked`](super::Lua::from_state_ptr_checked)
+/// also checks that the header starts like a LuaJIT thread with [`has_thread_header`](Self::has_thread_header),
+/// so a state with a different layout is rejected instead of having the wrong pointer read from it.
 #[derive(Debug)]
 #[repr(C)]
 pub struct LuaState {
@@ -40,6 +47,51 @@ pub struct LuaState {
 	pub luabase: NonNull<LuaBase>,
 }
 
+impl LuaState {
+	/// Offset of [`luabase`](Self::luabase) in bytes from the start of the state.
+	pub const LUABASE_OFFSET: usize = core::mem::offset_of!(Self, luabase);
+
+	/// Offset of the `gct` byte of the `GCHeader` of LuaJIT in bytes from the start of the state,
+	/// which comes after the `nextgc` reference and the `marked` byte.
+	#[cfg(target_pointer_width = "32")]
+	const GCT_OFFSET: usize = 4 + 1;
+	#[cfg(target_pointer_width = "64")]
+	const GCT_OFFSET: usize = 8 + 1;
+
+	/// Value of the `gct` byte of LuaJIT threads, which is `~LJ_TTHREAD`.
+	const THREAD_GCT: u8 = 6;
+
+	/// Returns `true` if the header of the state at `ptr` has the type of a LuaJIT thread,
+	/// as every `lua_State` does.
+	/// 
+	/// # Safety
+	/// `ptr` must be valid for reads of a [`LuaState`].
+	pub const unsafe fn has_thread_header(ptr: *const Self) -> bool {
+		let gct = unsafe { ptr.cast::<u8>().add(Self::GCT_OFFSET).read() };
+		gct == Self::THREAD_GCT
+	}
+}
+
+// Catches accidental changes to the size of the header, which would make every binary module read the wrong pointer.
+#[cfg(target_pointer_width = "32")]
+const _: () = assert!(LuaState::LUABASE_OFFSET == 72);
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(LuaState::LUABASE_OFFSET == 120);
+
+#[cfg(feature = "testing")]
+impl LuaState {
+	/// Returns a new state that has no LuaJIT state in its header, only a pointer to `luabase`,
+	/// and the type of a LuaJIT thread.
+	pub(crate) const fn detached(luabase: NonNull<LuaBase>) -> Self {
+		let mut header = [0; _];
+		header[Self::GCT_OFFSET] = Self::THREAD_GCT;
+		Self {
+			_ignore_this_common_lua_header: header,
+			luabase,
+		}
+	}
+}
+
 /// Type of C (or native) functions that can be executed by Lua.
 pub type CFunc = unsafe extern "C-unwind" fn (state: *mut LuaState) -> c_int;
 
@@ -117,3 +169,47 @@ pub type Number = c_double;
 
 /// Integer type used internally to identify Lua types.
 pub type RawType = c_int;
+
+/// Header of full userdata which represents a value of a [`Type`](super::Type) other than the standard Lua ones,
+/// as created by [`Lua::push_user_type_raw`](super::Lua::push_user_type_raw).
+/// 
+/// Garry's Mod reads the type of such userdata from this header,
+/// and C++ binary modules conventionally start their own userdata with it,
+/// so userdata that follows this convention can be shared between binary modules.
+/// Userdata created for [user types](super::user_types) also starts with this header.
+#[doc(alias = "GarrysMod::Lua::UserData")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct UserData {
+	/// Pointer to the value of the userdata.
+	pub data: *mut c_void,
+	/// Type of the userdata, truncated to a byte.
+	pub ty: c_uchar,
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use core::ptr::null_mut;
+
+	use crate::gmod13::{
+		testing::MockLua,
+		Lua,
+	};
+
+	use super::*;
+
+	#[test]
+	fn checked_state_ptr() {
+		let mut mock = MockLua::new();
+		let ptr = mock.as_state_ptr();
+		assert!(unsafe { LuaState::has_thread_header(ptr) });
+		assert!(unsafe { Lua::from_state_ptr_checked(ptr) }.is_some());
+		assert!(unsafe { Lua::from_state_ptr_checked(null_mut()) }.is_none());
+
+		// A header which isn't a LuaJIT thread has a different layout, so its pointer isn't read.
+		let mut other = LuaState::detached(NonNull::dangling());
+		other._ignore_this_common_lua_header = [0; _];
+		assert!(!unsafe { LuaState::has_thread_header(&raw const other) });
+		assert!(unsafe { Lua::from_state_ptr_checked(&raw mut other) }.is_none());
+	}
+}
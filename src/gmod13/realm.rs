@@ -0,0 +1,231 @@
+use core::ffi::CStr;
+
+use super::{
+	func::Func,
+	Lua, StackPos, Module,
+	after_push,
+};
+
+/// Realm that a Lua state belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Realm {
+	/// Server-side Lua state.
+	Server,
+	/// Client-side Lua state.
+	Client,
+	/// Lua state of the main menu.
+	Menu,
+}
+
+impl Realm {
+	/// Returns the name of the global variable which is `true` in this realm.
+	pub const fn global_name(self) -> &'static CStr {
+		match self {
+			Self::Server => c"SERVER",
+			Self::Client => c"CLIENT",
+			Self::Menu => c"MENU_DLL",
+		}
+	}
+
+	/// Returns the error message for whenever this realm is expected.
+	pub const fn expected_err(self) -> &'static CStr {
+		match self {
+			Self::Server => c"this can only be used in the server realm",
+			Self::Client => c"this can only be used in the client realm",
+			Self::Menu => c"this can only be used in the menu realm",
+		}
+	}
+
+	/// Returns the error message for whenever a binary module is loaded outside of this realm.
+	pub const fn module_err(self) -> &'static CStr {
+		match self {
+			Self::Server => c"this binary module can only be loaded in the server realm (its file name should start with `gmsv_`)",
+			Self::Client => c"this binary module can only be loaded in the client realm (its file name should start with `gmcl_`)",
+			Self::Menu => c"this binary module can only be loaded in the menu realm (its file name should start with `gmcl_`)",
+		}
+	}
+}
+
+/// What [`RealmOnly`] does when its binary module is loaded in another realm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OtherRealm {
+	/// Raise an error which names the expected realm.
+	Error,
+	/// Do nothing, leaving the inner module unopened.
+	Skip,
+}
+
+/// [`Module`] which only opens the inner module if it's loaded in the given [`Realm`].
+/// 
+/// Dependencies and saved state of the inner module are only checked and restored in that realm,
+/// and it is only closed if it was opened.
+/// 
+/// This is used by [`gmod13_module!`](crate::gmod13_module) with the `server only:` syntax and the like,
+/// and can also wrap single entries of a bracketed list of modules.
+#[derive(Debug)]
+pub struct RealmOnly<M> {
+	realm: Realm,
+	other_realm: OtherRealm,
+	opened: bool,
+	module: M,
+}
+
+impl<M> RealmOnly<M> {
+	/// Returns a wrapper for `module` which is only opened in `realm`,
+	/// and does `other_realm` in other realms.
+	pub const fn new(realm: Realm, other_realm: OtherRealm, module: M) -> Self {
+		Self {
+			realm, other_realm,
+			opened: false,
+			module,
+		}
+	}
+
+	/// Returns the realm that the inner module is opened in.
+	pub const fn realm(&self) -> Realm {
+		self.realm
+	}
+
+	/// Returns `true` if the inner module is currently open.
+	pub const fn is_open(&self) -> bool {
+		self.opened
+	}
+
+	/// Returns a reference to the inner module.
+	pub const fn get(&self) -> &M {
+		&self.module
+	}
+
+	/// Returns a mutable reference to the inner module.
+	pub const fn get_mut(&mut self) -> &mut M {
+		&mut self.module
+	}
+}
+
+impl<M: Module> Module for RealmOnly<M> {
+	fn open(&mut self, lua: &mut Lua) {
+		if !lua.is_realm(self.realm) {
+			match self.other_realm {
+				OtherRealm::Error => lua.throw_error(self.realm.module_err()),
+				OtherRealm::Skip => return,
+			}
+		}
+		// Dependencies are checked here instead of in `requires`,
+		// since they may not exist in other realms.
+		lua.check_dependencies(self.module.requires());
+		// The state of the inner module is saved and restored under its own key.
+		lua.restore_module_state(&mut self.module);
+		self.module.open(lua);
+		self.opened = true;
+	}
+
+	fn close(&mut self, lua: &mut Lua) {
+		if self.opened {
+			self.opened = false;
+			self.module.close(lua);
+			lua.save_module_state(&mut self.module);
+		}
+	}
+}
+
+/// Functions for detecting the realm of the Lua state.
+impl Lua {
+	/// Returns `true` if the Lua state belongs to the given [`Realm`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn is_realm(&mut self, realm: Realm) -> bool {
+		self.push_globals();
+		self.get_field(-1, realm.global_name());
+		let is_realm = self.get_bool(-1);
+		self.pop(2);
+		is_realm
+	}
+
+	/// Returns the [`Realm`] that the Lua state belongs to,
+	/// or `None` if it couldn't be determined.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn realm(&mut self) -> Option<Realm> {
+		// `MENU_DLL` is checked first, just in case other realm globals are defined in the menu state.
+		[Realm::Menu, Realm::Server, Realm::Client].into_iter().find(|&realm| self.is_realm(realm))
+	}
+
+	/// Returns `true` if the Lua state is server-side.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	#[inline]
+	pub fn is_server(&mut self) -> bool {
+		self.is_realm(Realm::Server)
+	}
+
+	/// Returns `true` if the Lua state is client-side.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	#[inline]
+	pub fn is_client(&mut self) -> bool {
+		self.is_realm(Realm::Client)
+	}
+
+	/// Returns `true` if the Lua state is the one of the main menu.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	#[inline]
+	pub fn is_menu(&mut self) -> bool {
+		self.is_realm(Realm::Menu)
+	}
+
+	/// Throws an error if the Lua state does not belong to the given [`Realm`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_realm(&mut self, realm: Realm) {
+		if !self.is_realm(realm) {
+			self.throw_error(realm.expected_err())
+		}
+	}
+
+	/// Sets `t[key]` to the function `f` if the Lua state belongs to the given [`Realm`],
+	/// where `t` is the value at `stack_pos`,
+	/// returning `true` if the function was set.
+	/// 
+	/// If the Lua state belongs to another realm, this does nothing.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_function_in_realm(&mut self, realm: Realm, stack_pos: StackPos, key: &CStr, f: Func) -> bool {
+		if !self.is_realm(realm) {
+			return false
+		}
+
+		self.push_function(f);
+		self.set_field(after_push(stack_pos, 1), key);
+		true
+	}
+
+	/// Sets `t[key]` to the function `f` only if the Lua state is server-side.
+	/// 
+	/// See [`Lua::set_function_in_realm`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	#[inline]
+	pub fn set_server_function(&mut self, stack_pos: StackPos, key: &CStr, f: Func) -> bool {
+		self.set_function_in_realm(Realm::Server, stack_pos, key, f)
+	}
+
+	/// Sets `t[key]` to the function `f` only if the Lua state is client-side.
+	/// 
+	/// See [`Lua::set_function_in_realm`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	#[inline]
+	pub fn set_client_function(&mut self, stack_pos: StackPos, key: &CStr, f: Func) -> bool {
+		self.set_function_in_realm(Realm::Client, stack_pos, key, f)
+	}
+}
@@ -0,0 +1,134 @@
+use core::{
+	ffi::CStr,
+	fmt,
+};
+
+use super::{
+	Lua, Module, Number, StackPos, StdType,
+};
+
+/// Name of the registry field which holds the table of saved module states.
+const STATES_FIELD: &CStr = c"gmbm_module_states";
+
+/// Writer for the state saved by [`Module::save_state`],
+/// which collects bytes into a table of Lua strings that is joined when saving is done.
+pub struct StateWriter<'a> {
+	lua: &'a mut Lua,
+	table: StackPos,
+	len: usize,
+}
+
+impl StateWriter<'_> {
+	/// Returns the [`Lua`] state that the state is being saved into.
+	pub const fn lua(&mut self) -> &mut Lua {
+		self.lua
+	}
+
+	/// Appends `bytes` to the saved state.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn write(&mut self, bytes: &[u8]) {
+		if bytes.is_empty() {
+			return
+		}
+
+		self.len += 1;
+		self.lua.push_number(self.len as Number);
+		self.lua.push_string(bytes);
+		self.lua.raw_set(self.table);
+	}
+}
+
+impl fmt::Write for StateWriter<'_> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		self.write(s.as_bytes());
+		Ok(())
+	}
+}
+
+/// Functions for preserving module state across reloads.
+impl Lua {
+	/// Saves the state of `module` with [`Module::save_state`]
+	/// into the registry under [`Module::state_key`],
+	/// or does nothing if the module has no key.
+	/// 
+	/// This is called after [`Module::close`]
+	/// by the functions exported with [`gmod13_module_with!`](crate::gmod13_module_with).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn save_module_state<M: Module + ?Sized>(&mut self, module: &mut M) {
+		if module.state_key().is_none() {
+			return
+		}
+
+		self.create_table();
+		let table = self.abs_index(-1);
+		let mut writer = StateWriter {
+			lua: self,
+			table,
+			len: 0,
+		};
+		module.save_state(&mut writer);
+		let len = writer.len;
+
+		if len == 0 {
+			self.pop(1);
+			return
+		}
+
+		// Join the chunks with `table.concat`.
+		self.get_global_field(c"table", c"concat");
+		self.insert(-2);
+		self.call(1, 1);
+
+		let Some(key) = module.state_key() else { return };
+		self.push_states_table();
+		self.push_string(key);
+		self.push_value(-3);
+		self.raw_set(-3); // states[key] = state
+		self.pop(2);
+	}
+
+	/// Restores the state of `module` with [`Module::restore_state`]
+	/// if it has been saved before with [`Lua::save_module_state`],
+	/// removing the saved state afterwards.
+	/// 
+	/// This is called before [`Module::open`]
+	/// by the functions exported with [`gmod13_module_with!`](crate::gmod13_module_with).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn restore_module_state<M: Module + ?Sized>(&mut self, module: &mut M) {
+		let Some(key) = module.state_key() else { return };
+		self.push_states_table();
+		self.push_string(key);
+		self.push_value(-1);
+		self.raw_get(-3); // states[key]
+		if self.is_type(-1, StdType::String) {
+			// The string is kept alive by the states table until `states[key]` is cleared after the call.
+			let state = self.get_string(-1).unwrap_or_default();
+			module.restore_state(self, state);
+			self.pop(1);
+			self.push_nil();
+			self.raw_set(-3); // states[key] = nil
+			self.pop(1);
+		} else {
+			self.pop(3);
+		}
+	}
+
+	/// Pushes the table of saved module states, creating it if it doesn't exist.
+	fn push_states_table(&mut self) {
+		self.push_registry();
+		self.get_field(-1, STATES_FIELD);
+		if !self.is_type(-1, StdType::Table) {
+			self.pop(1);
+			self.create_table();
+			self.push_value(-1);
+			self.set_field(-3, STATES_FIELD);
+		}
+		self.remove(-2);
+	}
+}
@@ -0,0 +1,152 @@
+//! Marshalling of database rows into the table shape that Lua code expects from query results,
+//! independent of any specific database driver.
+//! 
+//! Results are pushed as a sequence of rows,
+//! where each row is a table which maps column names to values, like the one returned by `sql.Query`.
+//! `NULL` values are left out of their rows, so they read as `nil`.
+//! 
+//! # Examples
+//! ```no_run
+//! use gmbm::gmod13::{
+//!     rows::Column,
+//!     Lua,
+//! };
+//! 
+//! fn push_players(lua: &mut Lua, players: &[(&str, i64, Option<f64>)]) {
+//!     lua.push_rows(players.iter().map(|&(name, kills, rating)| [
+//!         ("name", Column::from(name)),
+//!         ("kills", Column::from(kills)),
+//!         ("rating", Column::from(rating)),
+//!     ]));
+//! }
+//! ```
+
+use super::{
+	Lua, Number, ToLua,
+};
+
+/// Value of a column in a database row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column<'a> {
+	/// `NULL`, which is left out of the row table.
+	Null,
+	/// Integer, which is pushed as a [`Number`],
+	/// and may lose precision if it is outside of [`MIN_SAFE_INTEGER`](super::MIN_SAFE_INTEGER)`..=`[`MAX_SAFE_INTEGER`](super::MAX_SAFE_INTEGER).
+	Integer(i64),
+	/// Floating-point number.
+	Float(Number),
+	/// Text or binary data, which is pushed as a string.
+	Bytes(&'a [u8]),
+}
+
+impl Column<'_> {
+	/// Returns `true` if the value is [`Column::Null`].
+	pub const fn is_null(&self) -> bool {
+		matches!(self, Self::Null)
+	}
+}
+
+impl ToLua for Column<'_> {
+	fn push_to(self, lua: &mut Lua) {
+		match self {
+			Self::Null => lua.push_nil(),
+			Self::Integer(i) => lua.push_number(i as Number),
+			Self::Float(n) => lua.push_number(n),
+			Self::Bytes(bytes) => lua.push_string(bytes),
+		}
+	}
+}
+
+impl From<i64> for Column<'_> {
+	fn from(value: i64) -> Self {
+		Self::Integer(value)
+	}
+}
+
+impl From<i32> for Column<'_> {
+	fn from(value: i32) -> Self {
+		Self::Integer(value as _)
+	}
+}
+
+impl From<Number> for Column<'_> {
+	fn from(value: Number) -> Self {
+		Self::Float(value)
+	}
+}
+
+impl From<bool> for Column<'_> {
+	fn from(value: bool) -> Self {
+		Self::Integer(value as _)
+	}
+}
+
+impl<'a> From<&'a [u8]> for Column<'a> {
+	fn from(value: &'a [u8]) -> Self {
+		Self::Bytes(value)
+	}
+}
+
+impl<'a> From<&'a str> for Column<'a> {
+	fn from(value: &'a str) -> Self {
+		Self::Bytes(value.as_bytes())
+	}
+}
+
+impl<'a, T: Into<Column<'a>>> From<Option<T>> for Column<'a> {
+	fn from(value: Option<T>) -> Self {
+		value.map(Into::into).unwrap_or(Self::Null)
+	}
+}
+
+/// Functions for pushing database query results.
+impl Lua {
+	/// Pushes a table which contains a table for each row in `rows`,
+	/// mapping the name of every non-`NULL` column to its value,
+	/// and returns the number of rows.
+	/// 
+	/// The table is empty if there are no rows.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_rows<'a, I, R, N>(&mut self, rows: I) -> usize
+	where
+		I: IntoIterator<Item = R>,
+		R: IntoIterator<Item = (N, Column<'a>)>,
+		N: AsRef<[u8]>,
+	{
+		self.create_table();
+		let mut n_rows = 0;
+		for row in rows {
+			n_rows += 1;
+			self.push_number(n_rows as Number);
+			self.push_row(row);
+			self.set_table(-3);
+		}
+		n_rows
+	}
+
+	/// Pushes a table which maps the name of every non-`NULL` column in `row` to its value.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_row<'a, R, N>(&mut self, row: R)
+	where
+		R: IntoIterator<Item = (N, Column<'a>)>,
+		N: AsRef<[u8]>,
+	{
+		self.create_table();
+		for (name, value) in row {
+			if value.is_null() {
+				continue
+			}
+			self.push_string(name);
+			value.push_to(self);
+			self.set_table(-3);
+		}
+	}
+}
@@ -0,0 +1,214 @@
+//! Runtime support for binary modules which don't link to the standard library,
+//! which must otherwise provide their own panic handler and global allocator.
+//! 
+//! [`gmod13_runtime!`](crate::gmod13_runtime) defines both in the binary module crate:
+//! - the panic handler raises a Lua error with the panic message in the Lua state
+//!   that the binary module was opened in, so it shows up in the game console,
+//!   if the panic happened on the thread that owns that state;
+//! - the global allocator is [`MallocAlloc`], which allocates with the C library.
+//! 
+//! # Examples
+//! ```ignore
+//! #![no_std]
+//! 
+//! use gmbm::prelude::*;
+//! 
+//! struct Hello;
+//! impl LuaModule for Hello {
+//!     fn open(&mut self, _: &mut Lua) {}
+//! }
+//! 
+//! gmod13_module!(Hello = Hello);
+//! gmbm::gmod13_runtime!();
+//! ```
+
+use core::{
+	alloc::{
+		GlobalAlloc, Layout,
+	},
+	ffi::c_void,
+	fmt::Write,
+	panic::PanicInfo,
+	ptr::{
+		null_mut, NonNull,
+	},
+	sync::atomic::{
+		AtomicPtr, AtomicUsize, Ordering,
+	},
+};
+
+use super::{
+	chunks::CStrBuf,
+	thread::current_thread_id,
+	Lua, LuaHandle,
+};
+
+/// Lua state that panics are reported to.
+static PANIC_STATE: AtomicPtr<Lua> = AtomicPtr::new(null_mut());
+/// Identifier of the thread that owns [`PANIC_STATE`].
+static PANIC_OWNER: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the Lua state that panics are reported to by [`panic()`].
+/// 
+/// This is called with the state that the binary module is opened in
+/// by [`Lua::report_panics`].
+/// The current thread is recorded as the owner of the state,
+/// since the state can't be used from any other thread.
+pub fn set_panic_state(lua: &mut Lua) {
+	PANIC_OWNER.store(current_thread_id(), Ordering::Relaxed);
+	PANIC_STATE.store(lua, Ordering::Release);
+}
+
+/// Stops reporting panics to `lua` if it is the Lua state set with [`set_panic_state`].
+/// 
+/// This is called when the binary module is closed
+/// by [`Lua::stop_reporting_panics`].
+pub fn clear_panic_state(lua: &mut Lua) {
+	let _ = PANIC_STATE.compare_exchange(lua, null_mut(), Ordering::Relaxed, Ordering::Relaxed);
+}
+
+/// Panic handler which raises a Lua error with the panic message
+/// in the state set with [`set_panic_state`],
+/// or crashes the game if there is no such state,
+/// or if the panic happened on a thread other than the one that owns it.
+/// 
+/// This is the panic handler defined by [`gmod13_runtime!`](crate::gmod13_runtime).
+pub fn panic(info: &PanicInfo<'_>) -> ! {
+	let lua = NonNull::new(PANIC_STATE.load(Ordering::Acquire));
+	if let Some(mut lua) = lua && PANIC_OWNER.load(Ordering::Relaxed) == current_thread_id() {
+		let mut message = CStrBuf::new();
+		let _ = write!(message, "{info}");
+		// SAFETY: The panic happened on the thread that owns the state,
+		// which is cleared before the state is closed.
+		let mut handle = unsafe { LuaHandle::new(lua.as_mut()) };
+		handle.with(|lua| lua.throw_error(message.as_c_str()));
+	}
+	abort()
+}
+
+/// Stops the process without unwinding.
+fn abort() -> ! {
+	#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+	unsafe {
+		core::arch::asm!("ud2", options(noreturn))
+	}
+	#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+	loop {
+		core::hint::spin_loop()
+	}
+}
+
+#[cfg_attr(all(unix, not(target_os = "macos")), link(name = "c"))]
+#[cfg_attr(windows, link(name = "msvcrt"))]
+unsafe extern "C" {
+	fn malloc(size: usize) -> *mut c_void;
+	fn calloc(count: usize, size: usize) -> *mut c_void;
+	fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+	fn free(ptr: *mut c_void);
+}
+
+/// Alignment of every allocation made by `malloc`.
+const MALLOC_ALIGN: usize = if cfg!(target_pointer_width = "64") { 16 } else { 8 };
+
+/// Global allocator which allocates memory with the `malloc` family of functions of the C library
+/// that the game itself uses.
+/// 
+/// Allocations with an alignment greater than what `malloc` guarantees are over-allocated,
+/// with a pointer to the original allocation stored just before the aligned one.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct MallocAlloc;
+
+unsafe impl GlobalAlloc for MallocAlloc {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		if layout.align() <= MALLOC_ALIGN {
+			return unsafe { malloc(layout.size()) }.cast()
+		}
+		unsafe { alloc_overaligned(layout, false) }
+	}
+
+	unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+		if layout.align() <= MALLOC_ALIGN {
+			return unsafe { calloc(1, layout.size()) }.cast()
+		}
+		unsafe { alloc_overaligned(layout, true) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		if layout.align() <= MALLOC_ALIGN {
+			return unsafe { free(ptr.cast()) }
+		}
+		unsafe { free(ptr.cast::<*mut c_void>().sub(1).read()) }
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		if layout.align() <= MALLOC_ALIGN {
+			return unsafe { realloc(ptr.cast(), new_size) }.cast()
+		}
+
+		let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+		let new_ptr = unsafe { self.alloc(new_layout) };
+		if !new_ptr.is_null() {
+			unsafe {
+				new_ptr.copy_from_nonoverlapping(ptr, layout.size().min(new_size));
+				self.dealloc(ptr, layout);
+			}
+		}
+		new_ptr
+	}
+}
+
+/// Allocates memory for `layout` with `malloc`, aligning it by hand.
+unsafe fn alloc_overaligned(layout: Layout, zeroed: bool) -> *mut u8 {
+	// There is always room for the original pointer before the aligned one,
+	// since the alignment is greater than that of `malloc`.
+	let Some(size) = layout.size().checked_add(layout.align()) else {
+		return null_mut()
+	};
+	let original = if zeroed {
+		unsafe { calloc(1, size) }
+	} else {
+		unsafe { malloc(size) }
+	};
+	if original.is_null() {
+		return null_mut()
+	}
+
+	let offset = layout.align() - (original as usize & (layout.align() - 1));
+	unsafe {
+		let aligned = original.cast::<u8>().add(offset);
+		aligned.cast::<*mut c_void>().sub(1).write(original);
+		aligned
+	}
+}
+
+/// Defines a panic handler and a global allocator for a binary module which doesn't link to the standard library,
+/// with [`runtime::panic`](crate::gmod13::runtime::panic) and [`MallocAlloc`](crate::gmod13::runtime::MallocAlloc).
+/// 
+/// `gmod13_runtime!(panic_handler)` or `gmod13_runtime!(global_allocator)` only defines one of them.
+#[macro_export]
+macro_rules! gmod13_runtime {
+	() => {
+		$crate::gmod13_runtime!(panic_handler);
+		$crate::gmod13_runtime!(global_allocator);
+	};
+
+	(panic_handler) => {
+		const _: () = {
+			#[panic_handler]
+			fn gmod13_panic(info: &::core::panic::PanicInfo<'_>) -> ! {
+				$crate::gmod13::runtime::panic(info)
+			}
+		};
+	};
+
+	(global_allocator) => {
+		#[global_allocator]
+		static GMOD13_GLOBAL_ALLOCATOR: $crate::gmod13::runtime::MallocAlloc = $crate::gmod13::runtime::MallocAlloc;
+	};
+
+	($($whatever:tt)*) => {
+		::core::compile_error! {
+			"expected nothing, `panic_handler` or `global_allocator`"
+		}
+	};
+}
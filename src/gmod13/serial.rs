@@ -0,0 +1,422 @@
+//! Compact binary encoding of Lua values,
+//! which can be used to persist or transmit nested tables
+//! without the lossiness of JSON for binary strings, non-string keys and large integers.
+//! 
+//! `nil`, booleans, numbers, strings and tables are supported.
+//! Tables may have keys of any supported type.
+//! Tables which are referenced more than once are encoded once for every reference,
+//! while tables which contain themselves are rejected.
+//! Metatables are not encoded.
+//! 
+//! # Format
+//! An encoded value starts with [`FORMAT_VERSION`], followed by the value itself,
+//! which is a tag byte followed by its data:
+//! - `0`: `nil`.
+//! - `1`: `false`.
+//! - `2`: `true`.
+//! - `3`: integer in the range of [`MIN_SAFE_INTEGER`](super::MIN_SAFE_INTEGER)`..=`[`MAX_SAFE_INTEGER`](super::MAX_SAFE_INTEGER),
+//!   as a zigzag-encoded LEB128 varint.
+//! - `4`: any other number, as a little-endian IEEE 754 double.
+//! - `5`: string, as its length in a LEB128 varint, followed by its bytes.
+//! - `6`: table, as a sequence of keys each followed by their value, terminated by `0`.
+//! 
+//! # Examples
+//! ```no_run
+//! use gmbm::gmod13::Lua;
+//! 
+//! fn round_trip(lua: &mut Lua) {
+//!     let mut bytes = Vec::new();
+//!     lua.serialize_value(-1, &mut |chunk: &[u8]| bytes.extend_from_slice(chunk))
+//!         .expect("value should be serializable");
+//!     lua.push_deserialized(&bytes).expect("encoding should be valid");
+//! }
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::{
+	error::Error,
+	fmt,
+};
+
+use super::{
+	number_to_i53,
+	Lua, Number, StackPos, StdType, Type,
+};
+
+/// Version of the encoding, which is the first byte of every encoded value.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Maximum depth of nested tables that can be encoded or decoded.
+pub const MAX_DEPTH: usize = 64;
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_NUMBER: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_TABLE: u8 = 6;
+const TAG_END: u8 = TAG_NIL;
+
+/// Trait for destinations of encoded bytes.
+/// 
+/// This is implemented for closures which accept byte slices,
+/// and for `Vec<u8>` with the `alloc` feature.
+pub trait Output {
+	/// Writes `bytes` to the end of the output.
+	fn write_bytes(&mut self, bytes: &[u8]);
+}
+
+impl<F: FnMut(&[u8])> Output for F {
+	fn write_bytes(&mut self, bytes: &[u8]) {
+		self(bytes)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl Output for Vec<u8> {
+	fn write_bytes(&mut self, bytes: &[u8]) {
+		self.extend_from_slice(bytes)
+	}
+}
+
+/// Error returned when a Lua value can't be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SerializeError {
+	/// A value of this type was found, which can't be encoded.
+	Unsupported(Type),
+	/// A table contains itself.
+	Cycle,
+	/// Tables are nested deeper than [`MAX_DEPTH`].
+	TooDeep,
+}
+
+impl Error for SerializeError {}
+impl fmt::Display for SerializeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Unsupported(ty) => write!(f, "values of type {ty} can't be serialized"),
+			Self::Cycle => f.write_str("table contains itself"),
+			Self::TooDeep => f.write_str("tables are nested too deeply"),
+		}
+	}
+}
+
+/// Error returned when bytes can't be decoded as a Lua value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeserializeError {
+	/// The encoding has a version other than [`FORMAT_VERSION`].
+	UnsupportedVersion(u8),
+	/// The bytes end in the middle of a value.
+	UnexpectedEnd,
+	/// A value has an unknown or unsupported tag.
+	InvalidTag(u8),
+	/// A length or integer doesn't fit its type.
+	InvalidVarint,
+	/// A table key is `nil` or NaN, which can't be used as keys.
+	InvalidKey,
+	/// Tables are nested deeper than [`MAX_DEPTH`].
+	TooDeep,
+	/// There are bytes after the end of the value.
+	TrailingBytes,
+}
+
+impl Error for DeserializeError {}
+impl fmt::Display for DeserializeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::UnsupportedVersion(version) => write!(f, "unsupported format version {version}"),
+			Self::UnexpectedEnd => f.write_str("unexpected end of input"),
+			Self::InvalidTag(tag) => write!(f, "invalid tag {tag}"),
+			Self::InvalidVarint => f.write_str("varint is out of range"),
+			Self::InvalidKey => f.write_str("table key is nil or NaN"),
+			Self::TooDeep => f.write_str("tables are nested too deeply"),
+			Self::TrailingBytes => f.write_str("trailing bytes after value"),
+		}
+	}
+}
+
+/// Functions for encoding Lua values as bytes.
+impl Lua {
+	/// Encodes the value at `stack_pos` into `out`
+	/// in the format described in the [module documentation](crate::gmod13::serial).
+	/// 
+	/// If an error is returned, then `out` may contain part of the encoding.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn serialize_value<O: Output + ?Sized>(&mut self, stack_pos: StackPos, out: &mut O) -> Result<(), SerializeError> {
+		let value = self.abs_index(stack_pos);
+		let top = self.top();
+		// Set of the tables that are currently being encoded.
+		self.create_table();
+		let visiting = self.abs_index(-1);
+
+		out.write_bytes(&[FORMAT_VERSION]);
+		let result = self.serialize_at(value, visiting, out, 0);
+		self.set_top(top);
+		result
+	}
+
+	/// Decodes `bytes`, which must contain exactly one value encoded with [`serialize_value`](Self::serialize_value),
+	/// and pushes the value onto the stack.
+	/// 
+	/// If an error is returned, then nothing is pushed.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_deserialized(&mut self, bytes: &[u8]) -> Result<(), DeserializeError> {
+		let mut reader = Reader {
+			bytes,
+		};
+		let version = reader.byte()?;
+		if version != FORMAT_VERSION {
+			return Err(DeserializeError::UnsupportedVersion(version))
+		}
+
+		let top = self.top();
+		let tag = reader.byte()?;
+		let mut result = self.deserialize_tagged(tag, &mut reader, 0);
+		if result.is_ok() && !reader.bytes.is_empty() {
+			result = Err(DeserializeError::TrailingBytes);
+		}
+		if result.is_err() {
+			self.set_top(top);
+		}
+		result
+	}
+
+	fn serialize_at<O: Output + ?Sized>(
+		&mut self, pos: StackPos, visiting: StackPos, out: &mut O, depth: usize,
+	) -> Result<(), SerializeError> {
+		let ty = self.get_type(pos);
+		match ty.to_std() {
+			Some(StdType::Nil) => out.write_bytes(&[TAG_NIL]),
+			Some(StdType::Bool) => out.write_bytes(&[if self.get_bool(pos) { TAG_TRUE } else { TAG_FALSE }]),
+			Some(StdType::Number) => write_number(out, self.get_number(pos)),
+			Some(StdType::String) => {
+				let bytes = self.get_string(pos).unwrap_or_default();
+				out.write_bytes(&[TAG_STRING]);
+				write_varint(out, bytes.len() as u64);
+				out.write_bytes(bytes);
+			}
+			Some(StdType::Table) => {
+				if depth == MAX_DEPTH {
+					return Err(SerializeError::TooDeep)
+				}
+
+				self.push_value(pos);
+				self.raw_get(visiting);
+				let is_visiting = self.get_bool(-1);
+				self.pop(1);
+				if is_visiting {
+					return Err(SerializeError::Cycle)
+				}
+				self.push_value(pos);
+				self.push_bool(true);
+				self.raw_set(visiting);
+
+				out.write_bytes(&[TAG_TABLE]);
+				self.push_nil();
+				while self.next(pos) != 0 {
+					let key = self.abs_index(-2);
+					let value = self.abs_index(-1);
+					self.serialize_at(key, visiting, out, depth + 1)?;
+					self.serialize_at(value, visiting, out, depth + 1)?;
+					self.pop(1);
+				}
+				out.write_bytes(&[TAG_END]);
+
+				self.push_value(pos);
+				self.push_nil();
+				self.raw_set(visiting);
+			}
+			_ => return Err(SerializeError::Unsupported(ty)),
+		}
+		Ok(())
+	}
+
+	fn deserialize_tagged(&mut self, tag: u8, reader: &mut Reader<'_>, depth: usize) -> Result<(), DeserializeError> {
+		match tag {
+			TAG_NIL => self.push_nil(),
+			TAG_FALSE => self.push_bool(false),
+			TAG_TRUE => self.push_bool(true),
+			TAG_INTEGER => {
+				let i = zigzag_decode(reader.varint()?);
+				if number_to_i53(i as Number) != Some(i) {
+					return Err(DeserializeError::InvalidVarint)
+				}
+				self.push_number(i as Number)
+			}
+			TAG_NUMBER => {
+				let bytes = reader.take(size_of::<Number>())?;
+				// The slice always has the right length.
+				self.push_number(Number::from_le_bytes(bytes.try_into().unwrap_or_default()))
+			}
+			TAG_STRING => {
+				let len = usize::try_from(reader.varint()?).map_err(|_| DeserializeError::InvalidVarint)?;
+				let bytes = reader.take(len)?;
+				self.push_string(bytes)
+			}
+			TAG_TABLE => {
+				if depth == MAX_DEPTH {
+					return Err(DeserializeError::TooDeep)
+				}
+
+				self.create_table();
+				loop {
+					let key_tag = reader.byte()?;
+					if key_tag == TAG_END {
+						break
+					}
+					self.deserialize_tagged(key_tag, reader, depth + 1)?;
+					// The tag of `nil` also ends the table, but the key is checked anyway,
+					// since `raw_set` would raise an error for it.
+					let is_nil = self.is_type(-1, StdType::Nil);
+					if is_nil || (self.is_type(-1, StdType::Number) && self.get_number(-1).is_nan()) {
+						return Err(DeserializeError::InvalidKey)
+					}
+					let value_tag = reader.byte()?;
+					self.deserialize_tagged(value_tag, reader, depth + 1)?;
+					self.raw_set(-3);
+				}
+			}
+			_ => return Err(DeserializeError::InvalidTag(tag)),
+		}
+		Ok(())
+	}
+}
+
+/// Writes `n` with the smallest encoding that represents it exactly.
+fn write_number<O: Output + ?Sized>(out: &mut O, n: Number) {
+	// Negative zero would be decoded as positive zero if it were encoded as an integer.
+	match number_to_i53(n) {
+		Some(i) if !(i == 0 && n.is_sign_negative()) => {
+			out.write_bytes(&[TAG_INTEGER]);
+			write_varint(out, zigzag_encode(i));
+		}
+		_ => {
+			out.write_bytes(&[TAG_NUMBER]);
+			out.write_bytes(&n.to_le_bytes());
+		}
+	}
+}
+
+fn write_varint<O: Output + ?Sized>(out: &mut O, mut n: u64) {
+	let mut buf = [0; 10];
+	let mut len = 0;
+	loop {
+		let byte = (n & 0x7f) as u8;
+		n >>= 7;
+		if n == 0 {
+			buf[len] = byte;
+			len += 1;
+			break
+		}
+		buf[len] = byte | 0x80;
+		len += 1;
+	}
+	out.write_bytes(&buf[..len]);
+}
+
+const fn zigzag_encode(i: i64) -> u64 {
+	((i << 1) ^ (i >> 63)) as u64
+}
+
+const fn zigzag_decode(n: u64) -> i64 {
+	((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+/// Cursor over encoded bytes.
+struct Reader<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn byte(&mut self) -> Result<u8, DeserializeError> {
+		let (&byte, rest) = self.bytes.split_first().ok_or(DeserializeError::UnexpectedEnd)?;
+		self.bytes = rest;
+		Ok(byte)
+	}
+
+	fn take(&mut self, n: usize) -> Result<&'a [u8], DeserializeError> {
+		let (taken, rest) = self.bytes.split_at_checked(n).ok_or(DeserializeError::UnexpectedEnd)?;
+		self.bytes = rest;
+		Ok(taken)
+	}
+
+	fn varint(&mut self) -> Result<u64, DeserializeError> {
+		let mut n: u64 = 0;
+		for shift in (0..64).step_by(7) {
+			let byte = self.byte()?;
+			let bits = u64::from(byte & 0x7f);
+			if shift == 63 && bits > 1 {
+				return Err(DeserializeError::InvalidVarint)
+			}
+			n |= bits << shift;
+			if byte & 0x80 == 0 {
+				return Ok(n)
+			}
+		}
+		Err(DeserializeError::InvalidVarint)
+	}
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use crate::gmod13::testing::MockLua;
+
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.create_table();
+		lua.push_number(1.5);
+		lua.push_string(b"\0bytes");
+		lua.set_table(-3);
+		lua.push_bool(true);
+		lua.create_table();
+		lua.set_table(-3);
+
+		let mut bytes = Vec::new();
+		lua.serialize_value(-1, &mut |chunk: &[u8]| bytes.extend_from_slice(chunk)).unwrap();
+		lua.push_deserialized(&bytes).unwrap();
+
+		lua.push_number(1.5);
+		lua.get_table(-2);
+		assert_eq!(lua.get_string(-1), Some(&b"\0bytes"[..]));
+		lua.push_bool(true);
+		lua.get_table(-3);
+		assert!(lua.is_type(-1, StdType::Table));
+	}
+
+	#[test]
+	fn invalid_keys() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		let mut nan_key = Vec::from([FORMAT_VERSION, TAG_TABLE, TAG_NUMBER]);
+		nan_key.extend_from_slice(&Number::NAN.to_le_bytes());
+		nan_key.extend_from_slice(&[TAG_TRUE, TAG_END]);
+		assert_eq!(lua.push_deserialized(&nan_key), Err(DeserializeError::InvalidKey));
+		assert_eq!(lua.top(), 0);
+	}
+
+	#[test]
+	fn malformed_input() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		assert_eq!(lua.push_deserialized(&[]), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_deserialized(&[0]), Err(DeserializeError::UnsupportedVersion(0)));
+		assert_eq!(lua.push_deserialized(&[FORMAT_VERSION, 9]), Err(DeserializeError::InvalidTag(9)));
+		assert_eq!(lua.push_deserialized(&[FORMAT_VERSION, TAG_TABLE, TAG_TRUE]), Err(DeserializeError::UnexpectedEnd));
+		assert_eq!(lua.push_deserialized(&[FORMAT_VERSION, TAG_NIL, TAG_NIL]), Err(DeserializeError::TrailingBytes));
+		assert_eq!(lua.top(), 0);
+	}
+}
@@ -0,0 +1,201 @@
+//! Bridge to the `sql` library of Garry's Mod, which operates on the local SQLite database.
+
+use core::{
+	error::Error,
+	ffi::CStr,
+	fmt::{
+		self, Write,
+	},
+};
+
+use super::{
+	Lua, Number, StackPos, StdType,
+};
+
+/// Maximum number of bytes of an error message stored in [`SqlError`].
+pub const ERROR_CAPACITY: usize = 96;
+
+/// Error returned by `sql.LastError()` after a failed query.
+/// 
+/// The message is stored inline, truncated to [`ERROR_CAPACITY`] bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SqlError {
+	buf: [u8; ERROR_CAPACITY],
+	len: usize,
+}
+
+impl SqlError {
+	fn new(message: &[u8]) -> Self {
+		let len = message.len().min(ERROR_CAPACITY);
+		let mut buf = [0; ERROR_CAPACITY];
+		buf[..len].copy_from_slice(&message[..len]);
+		Self {
+			buf, len,
+		}
+	}
+
+	/// Returns the (possibly truncated) error message.
+	pub fn message(&self) -> &[u8] {
+		&self.buf[..self.len]
+	}
+}
+
+impl Error for SqlError {}
+impl fmt::Display for SqlError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for chunk in self.message().utf8_chunks() {
+			f.write_str(chunk.valid())?;
+			if !chunk.invalid().is_empty() {
+				f.write_char(char::REPLACEMENT_CHARACTER)?;
+			}
+		}
+		Ok(())
+	}
+}
+impl fmt::Debug for SqlError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("SqlError").field(&self.message().escape_ascii()).finish()
+	}
+}
+
+/// Rows returned by [`Lua::sql_query`].
+/// 
+/// The result of the query stays on the stack at [`stack_pos`](Self::stack_pos).
+pub struct Rows<'a> {
+	lua: &'a mut Lua,
+	stack_pos: StackPos,
+	len: usize,
+}
+
+impl Rows<'_> {
+	/// Returns the absolute position of the result of the query on the stack.
+	pub const fn stack_pos(&self) -> StackPos {
+		self.stack_pos
+	}
+
+	/// Returns the number of rows.
+	pub const fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Returns `true` if there are no rows.
+	pub const fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Pushes the row at `index`, starting from `0`, and returns it,
+	/// or returns `None` if it is out of bounds.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn row(&mut self, index: usize) -> Option<Row<'_>> {
+		if index >= self.len {
+			return None
+		}
+
+		self.lua.push_number((index + 1) as Number);
+		self.lua.raw_get(self.stack_pos);
+		let stack_pos = self.lua.abs_index(-1);
+		Some(Row {
+			lua: self.lua,
+			stack_pos,
+		})
+	}
+
+	/// Calls `f` for every row, in order.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn for_each<F: FnMut(&mut Row<'_>)>(&mut self, mut f: F) {
+		for index in 0..self.len {
+			if let Some(mut row) = self.row(index) {
+				f(&mut row);
+			}
+		}
+	}
+}
+
+/// Row of [`Rows`], which maps column names to values.
+/// 
+/// The row is removed from the stack when this is dropped.
+pub struct Row<'a> {
+	lua: &'a mut Lua,
+	stack_pos: StackPos,
+}
+
+impl Row<'_> {
+	/// Returns the value of `column` as a byte string,
+	/// or `None` if it is `NULL` or doesn't exist.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn get(&mut self, column: &CStr) -> Option<&[u8]> {
+		self.lua.get_field(self.stack_pos, column);
+		let value = if self.lua.is_type(-1, StdType::String) {
+			self.lua.get_string(-1)
+		} else {
+			None
+		};
+		// The string is still referenced by the row, so it is kept alive after popping it.
+		self.lua.pop(1);
+		value
+	}
+
+	/// Returns the value of `column` parsed as a [`Number`],
+	/// or `None` if it is `NULL`, doesn't exist, or isn't a valid number.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn get_number(&mut self, column: &CStr) -> Option<Number> {
+		let value = self.get(column)?;
+		core::str::from_utf8(value).ok()?.trim().parse().ok()
+	}
+}
+
+impl Drop for Row<'_> {
+	fn drop(&mut self) {
+		self.lua.remove(self.stack_pos)
+	}
+}
+
+/// Functions for using the local SQLite database.
+impl Lua {
+	/// Runs `query` with `sql.Query(query)`,
+	/// returning the resulting rows or the error from `sql.LastError()`.
+	/// 
+	/// The result of the query is pushed onto the stack.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn sql_query<Q: AsRef<[u8]>>(&mut self, query: Q) -> Result<Rows<'_>, SqlError> {
+		self.get_global_field(c"sql", c"Query");
+		self.push_string(query);
+		self.call(1, 1);
+
+		if self.is_type(-1, StdType::Bool) && !self.get_bool(-1) {
+			self.pop(1);
+			self.get_global_field(c"sql", c"LastError");
+			self.call(0, 1);
+			let error = SqlError::new(self.get_string(-1).unwrap_or_default());
+			self.pop(1);
+			return Err(error)
+		}
+
+		let len = self.table_len(-1);
+		let stack_pos = self.abs_index(-1);
+		Ok(Rows {
+			lua: self,
+			stack_pos, len,
+		})
+	}
+
+	/// Pushes `s` escaped and quoted for use in SQL queries, with `sql.SQLStr(s)`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_sql_str<S: AsRef<[u8]>>(&mut self, s: S) {
+		self.get_global_field(c"sql", c"SQLStr");
+		self.push_string(s);
+		self.call(1, 1);
+	}
+}
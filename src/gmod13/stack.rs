@@ -0,0 +1,353 @@
+use core::{
+	ffi::{
+		c_int, c_uint,
+	},
+	ops::Range,
+};
+
+use super::{
+	Lua, StackPos, Type,
+};
+
+/// Contiguous range of values on the stack, such as the arguments of a function.
+/// 
+/// The range is stored with absolute stack positions,
+/// so it keeps referring to the same values when more values are pushed onto the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StackRange {
+	start: StackPos,
+	len: c_uint,
+}
+
+impl StackRange {
+	/// Returns the range of `len` values starting at the absolute stack position `start`.
+	/// 
+	/// # Panics
+	/// Panics if `start` is not an absolute stack position, which must be at least `1`.
+	pub const fn new(start: StackPos, len: c_uint) -> Self {
+		assert!(start >= 1, "start of a stack range must be an absolute stack position");
+		Self {
+			start, len,
+		}
+	}
+
+	/// Returns the absolute stack position of the first value in the range.
+	pub const fn start(&self) -> StackPos {
+		self.start
+	}
+
+	/// Returns the absolute stack position just after the last value in the range.
+	pub const fn end(&self) -> StackPos {
+		self.start + self.len as StackPos
+	}
+
+	/// Returns the number of values in the range.
+	pub const fn len(&self) -> c_uint {
+		self.len
+	}
+
+	/// Returns `true` if the range doesn't contain any values.
+	pub const fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Returns `true` if the absolute stack position `stack_pos` is in the range.
+	pub const fn contains(&self, stack_pos: StackPos) -> bool {
+		stack_pos >= self.start && stack_pos < self.end()
+	}
+
+	/// Returns an iterator over the absolute stack positions in the range.
+	pub const fn positions(&self) -> Range<StackPos> {
+		self.start..self.end()
+	}
+}
+
+/// Recorded height of the stack, as returned by [`Lua::snapshot`].
+/// 
+/// This is used to check and restore the stack in routines which push several values in steps,
+/// such as when converting nested data to Lua values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StackMark {
+	top: c_uint,
+}
+
+impl StackMark {
+	/// Returns the number of values that were on the stack when the mark was made.
+	pub const fn top(&self) -> c_uint {
+		self.top
+	}
+}
+
+/// Functions for ranges of values on the stack.
+impl Lua {
+	/// Returns the range of values from `stack_pos` to the top of the stack, inclusive.
+	/// 
+	/// For example, in a function, `lua.range_from(2)` is the range of every argument after the first.
+	/// If `stack_pos` is just above the top of the stack, then the range is empty.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn range_from(&self, stack_pos: StackPos) -> StackRange {
+		let top = self.top() as StackPos;
+		let start = if stack_pos == top + 1 { stack_pos } else { self.abs_index(stack_pos) };
+		StackRange::new(start, (top + 1 - start).max(0) as c_uint)
+	}
+
+	/// Returns the range of the `n` values on top of the stack.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn range_top(&self, n: c_uint) -> StackRange {
+		self.debug_check_values(n);
+		StackRange::new(self.top() as StackPos + 1 - n as StackPos, n)
+	}
+
+	/// Pushes copies of the values in `from` onto the stack, in order,
+	/// and returns the number of values pushed.
+	/// 
+	/// This is useful for calling another function with the arguments of the current function.
+	/// Lua only guarantees that there is space for 20 more values on the stack in a function,
+	/// so large ranges may overflow the stack.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::gmod13::func::{
+	///     Ctx, Rets,
+	/// };
+	/// 
+	/// /// Calls `print` with every argument after the first, and returns the first argument.
+	/// extern "C-unwind" fn print_rest(cx: Ctx<'_>) -> Rets {
+	///     let lua = cx.lua();
+	///     let rest = lua.range_from(2);
+	///     lua.get_global(c"print");
+	///     let n = lua.forward_args(rest);
+	///     lua.call(n, 0);
+	///     lua.push_value(1);
+	///     Rets::new(1)
+	/// }
+	/// ```
+	pub fn forward_args(&mut self, from: StackRange) -> c_uint {
+		if let Some(last) = from.positions().last() {
+			self.debug_check_pos(last);
+		}
+		for stack_pos in from.positions() {
+			self.push_value(stack_pos);
+		}
+		from.len()
+	}
+}
+
+/// Functions for rearranging values on the stack.
+impl Lua {
+	/// Pops the value at the top of the stack, and replaces the value at `stack_pos` with it,
+	/// without shifting any other values.
+	/// 
+	/// `stack_pos` must refer to a value on the stack, and can't be a pseudo-index.
+	/// 
+	/// This method is not part of the public C++ API,
+	/// and is implemented with [`insert`](Self::insert) and [`remove`](Self::remove).
+	pub fn replace(&self, stack_pos: StackPos) {
+		self.debug_check_stack_pos(stack_pos);
+		let stack_pos = self.abs_index(stack_pos);
+		if stack_pos == self.top() as StackPos {
+			// The value replaces itself.
+			return self.pop(1)
+		}
+		self.insert(stack_pos);
+		self.remove(stack_pos + 1);
+	}
+
+	/// Rotates the values from `stack_pos` to the top of the stack by `n` positions towards the top,
+	/// or by `-n` positions towards the bottom if `n` is negative,
+	/// like `lua_rotate` in Lua 5.3.
+	/// 
+	/// For example, with the values `1 2 3 4` on the stack,
+	/// `rotate(2, 1)` results in `1 4 2 3`, and `rotate(2, -1)` results in `1 3 4 2`.
+	/// 
+	/// `stack_pos` must refer to a value on the stack, and can't be a pseudo-index.
+	/// 
+	/// This method is not part of the public C++ API,
+	/// and is implemented with [`insert`](Self::insert) and [`remove`](Self::remove).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn rotate(&self, stack_pos: StackPos, n: c_int) {
+		self.debug_check_stack_pos(stack_pos);
+		let stack_pos = self.abs_index(stack_pos);
+		let len = self.top() as c_int + 1 - stack_pos;
+		if len <= 0 {
+			return
+		}
+		let n = n.rem_euclid(len);
+		// Rotating by the shorter distance needs fewer moves.
+		if n <= len / 2 {
+			for _ in 0..n {
+				self.insert(stack_pos);
+			}
+		} else {
+			for _ in n..len {
+				self.push_value(stack_pos);
+				self.remove(stack_pos);
+			}
+		}
+	}
+}
+
+/// Functions for marking and restoring the height of the stack.
+impl Lua {
+	/// Returns a mark of the current height of the stack,
+	/// which can be used to check the values pushed after it and to pop them.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Examples
+	/// ```
+	/// use gmbm::prelude::*;
+	/// 
+	/// /// Pushes a table with a `name` field.
+	/// fn push_entry(lua: &mut Lua, name: &str) {
+	///     let mark = lua.snapshot();
+	///     lua.create_table();
+	///     lua.push_string(name);
+	///     lua.expect_since(mark, &[LuaStdType::Table, LuaStdType::String]);
+	///     lua.set_field(-2, c"name");
+	///     lua.expect_since(mark, &[LuaStdType::Table]);
+	/// }
+	/// ```
+	pub fn snapshot(&self) -> StackMark {
+		StackMark {
+			top: self.top(),
+		}
+	}
+
+	/// Returns the number of values that have been pushed above `mark`.
+	/// 
+	/// With the `debug-stack` feature, this raises an error if values below `mark` have been popped.
+	/// Otherwise, `0` is returned in that case.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn values_since(&self, mark: StackMark) -> c_uint {
+		self.debug_check_above(mark.top, 0);
+		self.top().saturating_sub(mark.top)
+	}
+
+	/// Checks that the values above `mark` have the types `types`, in order,
+	/// with the `debug-stack` feature, and does nothing otherwise.
+	/// 
+	/// Types can be given either as [`StdType`](super::StdType)s or as [`Type`]s,
+	/// such as those of user types.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// With the `debug-stack` feature, the inner Lua state will raise an [error](crate::errors)
+	/// if the values don't match `types`.
+	#[inline(always)]
+	pub fn expect_since<T: Copy + Into<Type>>(&self, mark: StackMark, types: &[T]) {
+		self.debug_check_types_above(mark.top, types)
+	}
+
+	/// Pops every value that has been pushed above `mark`.
+	/// 
+	/// With the `debug-stack` feature, this raises an error if values below `mark` have been popped,
+	/// instead of filling the stack up to `mark` with `nil`s.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn restore(&self, mark: StackMark) {
+		self.debug_check_above(mark.top, 0);
+		self.set_top(mark.top)
+	}
+
+	/// Pops every value that has been pushed above `mark`, except for the `n` values on top of the stack,
+	/// which are moved down to just above `mark`.
+	/// 
+	/// This is useful for returning results from a routine that needed temporary values below them.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// With the `debug-stack` feature, the inner Lua state will raise an [error](crate::errors)
+	/// if there are less than `n` values above `mark`.
+	pub fn restore_keeping(&self, mark: StackMark, n: c_uint) {
+		self.debug_check_above(mark.top, n);
+		let len = self.top().saturating_sub(mark.top);
+		for _ in n.min(len)..len {
+			self.remove((mark.top + 1) as StackPos);
+		}
+	}
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use alloc::vec::Vec;
+
+	use crate::gmod13::testing::MockLua;
+
+	use super::*;
+
+	/// Pushes the numbers from `1` to `n`.
+	fn push_numbers(lua: &mut Lua, n: u8) {
+		for i in 1..=n {
+			lua.push_number(i.into());
+		}
+	}
+
+	/// Returns the numbers on the stack, from bottom to top.
+	fn numbers(lua: &Lua) -> Vec<u8> {
+		(1..=lua.top() as StackPos).map(|stack_pos| lua.get_number(stack_pos) as u8).collect()
+	}
+
+	#[test]
+	fn ranges() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		push_numbers(lua, 4);
+
+		let rest = lua.range_from(2);
+		assert_eq!((rest.start(), rest.len()), (2, 3));
+		assert_eq!(lua.range_from(-1), StackRange::new(4, 1));
+		assert!(lua.range_from(5).is_empty());
+		assert_eq!(lua.range_top(2), StackRange::new(3, 2));
+
+		assert_eq!(lua.forward_args(rest), 3);
+		assert_eq!(numbers(lua), [1, 2, 3, 4, 2, 3, 4]);
+	}
+
+	#[test]
+	fn replace_and_rotate() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		push_numbers(lua, 4);
+
+		lua.rotate(2, 1);
+		assert_eq!(numbers(lua), [1, 4, 2, 3]);
+		lua.rotate(2, -1);
+		assert_eq!(numbers(lua), [1, 2, 3, 4]);
+		lua.rotate(1, 6);
+		assert_eq!(numbers(lua), [3, 4, 1, 2]);
+
+		lua.push_number(9.0);
+		lua.replace(1);
+		assert_eq!(numbers(lua), [9, 4, 1, 2]);
+		lua.replace(-1);
+		assert_eq!(numbers(lua), [9, 4, 1]);
+	}
+
+	#[test]
+	fn marks() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		push_numbers(lua, 1);
+
+		let mark = lua.snapshot();
+		push_numbers(lua, 3);
+		assert_eq!(lua.values_since(mark), 3);
+		lua.restore_keeping(mark, 1);
+		assert_eq!(numbers(lua), [1, 3]);
+		lua.restore(mark);
+		assert_eq!(numbers(lua), [1]);
+	}
+}
@@ -0,0 +1,173 @@
+//! Runtime checks of stack discipline, which are enabled by the `debug-stack` feature.
+//! 
+//! Without the feature, every `debug_*` check compiles down to nothing.
+
+use core::{
+	ffi::{
+		c_int, c_uint,
+	},
+	fmt::{
+		self, Write,
+	},
+};
+
+#[cfg(feature = "debug-stack")]
+use super::lua::is_relative;
+use super::{
+	chunks::CStrBuf,
+	func::Rets,
+	Lua, StackPos, Type,
+};
+
+/// Maximum number of values that a native function may have on its stack in LuaJIT,
+/// which is also the limit for the results of a call.
+const MAX_CALL_VALUES: i64 = 8000;
+
+/// Functions for checking stack discipline with the `debug-stack` feature.
+impl Lua {
+	/// Checks that `stack_pos` refers to a value on the stack or is a pseudo-index.
+	#[inline(always)]
+	pub(crate) fn debug_check_pos(&self, stack_pos: StackPos) {
+		#[cfg(feature = "debug-stack")]
+		{
+			let top = self.top() as StackPos;
+			let valid = if is_relative(stack_pos) {
+				-stack_pos <= top
+			} else {
+				stack_pos != 0 && stack_pos <= top
+			};
+			if !valid {
+				self.stack_violation(format_args!("stack position {stack_pos} is invalid with {top} values on the stack"))
+			}
+		}
+		#[cfg(not(feature = "debug-stack"))]
+		let _ = stack_pos;
+	}
+
+	/// Checks that `stack_pos` refers to a value on the stack, and is not a pseudo-index.
+	#[inline(always)]
+	pub(crate) fn debug_check_stack_pos(&self, stack_pos: StackPos) {
+		#[cfg(feature = "debug-stack")]
+		{
+			let top = self.top() as StackPos;
+			let valid = if is_relative(stack_pos) {
+				-stack_pos <= top
+			} else {
+				stack_pos > 0 && stack_pos <= top
+			};
+			if !valid {
+				self.stack_violation(format_args!("stack position {stack_pos} is not on the stack with {top} values on the stack"))
+			}
+		}
+		#[cfg(not(feature = "debug-stack"))]
+		let _ = stack_pos;
+	}
+
+	/// Checks that there are at least `n` values on the stack for an operation to pop.
+	#[inline(always)]
+	pub(crate) fn debug_check_values(&self, n: c_uint) {
+		#[cfg(feature = "debug-stack")]
+		{
+			let top = self.top();
+			if n > top {
+				self.stack_violation(format_args!("operation needs {n} values, but there are only {top} on the stack"))
+			}
+		}
+		#[cfg(not(feature = "debug-stack"))]
+		let _ = n;
+	}
+
+	/// Checks that `stack_pos` is valid, and that there are at least `n` values on top of it to pop.
+	#[inline(always)]
+	pub(crate) fn debug_check_pos_values(&self, stack_pos: StackPos, n: c_uint) {
+		self.debug_check_pos(stack_pos);
+		self.debug_check_values(n);
+	}
+
+	/// Checks that a function and `n_args` arguments are on the stack,
+	/// and that `n_results` results fit on the stack.
+	#[inline(always)]
+	pub(crate) fn debug_check_call(&self, n_args: c_uint, n_results: c_int) {
+		#[cfg(feature = "debug-stack")]
+		self.check_call(n_args, n_results);
+		#[cfg(not(feature = "debug-stack"))]
+		let _ = (n_args, n_results);
+	}
+
+	/// Raises an error unless a function and `n_args` arguments are on the stack,
+	/// and `n_results` results fit on the stack,
+	/// where `n_results` may be `-1` to keep every result.
+	pub(crate) fn check_call(&self, n_args: c_uint, n_results: c_int) {
+		let top = self.top();
+		if n_args >= top {
+			self.stack_violation(format_args!(
+				"call needs a function and {n_args} arguments, but there are only {top} values on the stack"
+			))
+		}
+		let top_after = (top - n_args - 1) as i64 + n_results as i64;
+		if n_results < -1 || top_after > MAX_CALL_VALUES {
+			self.stack_violation(format_args!(
+				"call requests {n_results} results, which don't fit on the stack"
+			))
+		}
+	}
+
+	/// Checks that there are at least `n` values above the absolute stack position `base`.
+	#[inline(always)]
+	pub(crate) fn debug_check_above(&self, base: c_uint, n: c_uint) {
+		#[cfg(feature = "debug-stack")]
+		{
+			let top = self.top();
+			if top < base || top - base < n {
+				let count = top as i64 - base as i64;
+				self.stack_violation(format_args!("expected at least {n} values above the mark, but there are {count}"))
+			}
+		}
+		#[cfg(not(feature = "debug-stack"))]
+		let _ = (base, n);
+	}
+
+	/// Checks that the values above the absolute stack position `base` have the types `types`, in order.
+	#[inline(always)]
+	pub(crate) fn debug_check_types_above<T: Copy + Into<Type>>(&self, base: c_uint, types: &[T]) {
+		#[cfg(feature = "debug-stack")]
+		{
+			let top = self.top();
+			let expected = types.len() as c_uint;
+			if top < base || top - base != expected {
+				let count = top as i64 - base as i64;
+				self.stack_violation(format_args!("expected {expected} values above the mark, but there are {count}"))
+			}
+			for (i, &ty) in types.iter().enumerate() {
+				let stack_pos = (base + 1) as StackPos + i as StackPos;
+				let (expected, actual) = (ty.into(), self.get_type(stack_pos));
+				if actual != expected {
+					self.stack_violation(format_args!("expected {expected} at stack position {stack_pos}, but found {actual}"))
+				}
+			}
+		}
+		#[cfg(not(feature = "debug-stack"))]
+		let _ = (base, types);
+	}
+
+	/// Checks that a native function returns at most as many values as there are on the stack.
+	#[inline(always)]
+	pub(crate) fn debug_check_rets(&self, rets: Rets) -> Rets {
+		#[cfg(feature = "debug-stack")]
+		{
+			let count = rets.count();
+			let top = self.top();
+			if count as c_uint > top {
+				self.stack_violation(format_args!("function returns {count} values, but there are only {top} on the stack"))
+			}
+		}
+		rets
+	}
+
+	#[cold]
+	fn stack_violation(&self, args: fmt::Arguments<'_>) -> ! {
+		let mut message = CStrBuf::new();
+		let _ = write!(message, "stack discipline violated: {args}");
+		self.throw_error(message.as_c_str())
+	}
+}
@@ -0,0 +1,527 @@
+use core::{
+	ffi::{
+		CStr,
+		c_int, c_uint,
+	},
+	fmt::{
+		self, Write,
+	},
+	mem::ManuallyDrop,
+	ops::Deref,
+};
+
+use super::{
+	chunks::{
+		CHUNK_SIZE, CStrBuf,
+	},
+	Lua, Number, StackPos, StdType, ToLuaMulti,
+};
+
+/// Lua string on the stack, which is removed from the stack when this is dropped.
+pub struct LuaString<'a> {
+	lua: &'a mut Lua,
+	stack_pos: StackPos,
+}
+
+impl<'a> LuaString<'a> {
+	/// Returns a handle to the string at the top of the stack,
+	/// or pops the value and returns `None` if it isn't a string.
+	pub(crate) fn from_top(lua: &'a mut Lua) -> Option<Self> {
+		if !lua.is_type(-1, StdType::String) {
+			lua.pop(1);
+			return None
+		}
+
+		let stack_pos = lua.abs_index(-1);
+		Some(Self {
+			lua, stack_pos,
+		})
+	}
+
+	/// Returns the absolute position of the string on the stack.
+	pub const fn stack_pos(&self) -> StackPos {
+		self.stack_pos
+	}
+
+	/// Returns the position of the string on the stack, leaving it there.
+	pub fn into_stack_pos(self) -> StackPos {
+		ManuallyDrop::new(self).stack_pos
+	}
+
+	/// Returns the bytes of the string.
+	pub fn as_bytes(&self) -> &[u8] {
+		self.lua.get_string(self.stack_pos).unwrap_or_default()
+	}
+}
+
+impl Deref for LuaString<'_> {
+	type Target = [u8];
+	fn deref(&self) -> &Self::Target {
+		self.as_bytes()
+	}
+}
+
+impl AsRef<[u8]> for LuaString<'_> {
+	fn as_ref(&self) -> &[u8] {
+		self.as_bytes()
+	}
+}
+
+impl fmt::Debug for LuaString<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "\"{}\"", self.as_bytes().escape_ascii())
+	}
+}
+
+impl Drop for LuaString<'_> {
+	fn drop(&mut self) {
+		self.lua.remove(self.stack_pos)
+	}
+}
+
+/// Builder of a Lua string which is too large, or too expensive, to collect on the Rust side.
+/// 
+/// Output is collected into a fixed-size buffer,
+/// and every time it is full, it is pushed onto the stack as a separate chunk.
+/// The chunks are joined with `table.concat` in [`finish`](Self::finish),
+/// so no allocation is required on the Rust side.
+/// 
+/// While the builder is alive, it owns the value at the top of the stack, if any.
+/// Values may be pushed above it temporarily,
+/// but they must be popped before anything is written to the builder.
+/// If the builder is dropped without calling `finish`, the output is discarded.
+/// 
+/// # Examples
+/// ```
+/// # use gmbm::prelude::*;
+/// # use gmbm::gmod13::LuaStringBuilder;
+/// use core::fmt::Write;
+/// 
+/// fn push_report(lua: &mut Lua, scores: &[(&str, u32)]) {
+///     let mut report = LuaStringBuilder::new(lua);
+///     for &(name, score) in scores {
+///         let _ = writeln!(report, "{name}: {score}");
+///     }
+///     report.finish();
+/// }
+/// ```
+pub struct LuaStringBuilder<'a> {
+	lua: &'a mut Lua,
+	buf: [u8; CHUNK_SIZE],
+	len: usize,
+	n_chunks: usize,
+}
+
+impl<'a> LuaStringBuilder<'a> {
+	/// Creates a new builder of an empty string, which pushes onto the stack of `lua`.
+	pub const fn new(lua: &'a mut Lua) -> Self {
+		Self {
+			lua,
+			buf: [0; CHUNK_SIZE],
+			len: 0,
+			n_chunks: 0,
+		}
+	}
+
+	/// Appends arbitrary bytes to the string, which don't have to be valid UTF-8.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_bytes(&mut self, mut bytes: &[u8]) {
+		// Large inputs are pushed as their own chunk, instead of being copied into the buffer first.
+		if bytes.len() >= CHUNK_SIZE {
+			self.flush();
+			self.push_chunk(bytes);
+			return
+		}
+
+		while !bytes.is_empty() {
+			let n = bytes.len().min(CHUNK_SIZE - self.len);
+			self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+			self.len += n;
+			bytes = &bytes[n..];
+
+			if self.len == CHUNK_SIZE {
+				self.flush();
+			}
+		}
+	}
+
+	/// Finishes the string, and leaves it at the top of the stack.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn finish(self) {
+		let mut this = ManuallyDrop::new(self);
+		this.flush();
+		match this.n_chunks {
+			0 => this.lua.push_string(""),
+			1 => {}
+			_ => {
+				this.lua.push_globals();
+				this.lua.get_field(-1, c"table");
+				this.lua.get_field(-1, c"concat");
+				this.lua.remove(-2);
+				this.lua.remove(-2);
+				this.lua.insert(-2);
+				this.lua.call(1, 1); // table.concat(t)
+			}
+		}
+	}
+
+	/// Pushes any buffered output as a chunk.
+	fn flush(&mut self) {
+		if self.len > 0 {
+			let buf = self.buf;
+			self.push_chunk(&buf[..self.len]);
+			self.len = 0;
+		}
+	}
+
+	fn push_chunk(&mut self, chunk: &[u8]) {
+		let lua = &mut *self.lua;
+		if self.n_chunks == 1 {
+			// There's more than one chunk, so move the first one into a table.
+			lua.create_table();
+			lua.insert(-2);
+			lua.push_number(1.0);
+			lua.insert(-2);
+			lua.raw_set(-3); // t[1] = chunk
+		}
+
+		if self.n_chunks == 0 {
+			lua.push_string(chunk);
+		} else {
+			lua.push_number((self.n_chunks + 1) as Number);
+			lua.push_string(chunk);
+			lua.raw_set(-3); // t[n] = chunk
+		}
+		self.n_chunks += 1;
+	}
+}
+
+impl Write for LuaStringBuilder<'_> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		self.push_bytes(s.as_bytes());
+		Ok(())
+	}
+}
+
+impl fmt::Debug for LuaStringBuilder<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("LuaStringBuilder")
+			.field("buffered", &self.buf[..self.len].escape_ascii())
+			.field("n_chunks", &self.n_chunks)
+			.finish()
+	}
+}
+
+impl Drop for LuaStringBuilder<'_> {
+	fn drop(&mut self) {
+		if self.n_chunks > 0 {
+			self.lua.pop(1);
+		}
+	}
+}
+
+/// Lua source of a function which concatenates all of its arguments with the `..` operator.
+const CONCAT_SOURCE: &CStr = c"return function(...) \
+	local n = select('#', ...) \
+	local s = select(n, ...) \
+	for i = n - 1, 1, -1 do s = (select(i, ...)) .. s end \
+	return s \
+end";
+
+/// Unique address used as the registry key for the function compiled from [`CONCAT_SOURCE`].
+static CONCAT_KEY: u8 = 0;
+
+/// Functions for building Lua strings.
+impl Lua {
+	/// Pushes the formatted `args` onto the stack as a Lua string.
+	/// 
+	/// The output is formatted into a fixed-size buffer, and is pushed in chunks which are joined with `table.concat`,
+	/// so no allocation is required on the Rust side.
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// fn push_position(lua: &mut Lua, x: LuaNumber, y: LuaNumber) {
+	///     lua.push_fmt(format_args!("({x}, {y})"));
+	/// }
+	/// ```
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_fmt(&mut self, args: fmt::Arguments<'_>) {
+		if let Some(s) = args.as_str() {
+			return self.push_string(s)
+		}
+
+		let mut builder = LuaStringBuilder::new(self);
+		let _ = builder.write_fmt(args);
+		builder.finish();
+	}
+
+	/// Pushes `bytes` onto the stack as a Lua string
+	/// with every magic character of Lua patterns escaped with `%`,
+	/// so that the string matches `bytes` literally when it is used as a pattern,
+	/// such as with `string.find` or `string.gsub`.
+	/// 
+	/// Nul bytes are escaped as `%z`, since they can't appear in patterns otherwise.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_pattern_escaped(&mut self, bytes: &[u8]) {
+		let mut builder = LuaStringBuilder::new(self);
+		for &byte in bytes {
+			match byte {
+				b'^' | b'$' | b'(' | b')' | b'%' | b'.' | b'[' | b']' | b'*' | b'+' | b'-' | b'?' => {
+					builder.push_bytes(&[b'%', byte])
+				}
+				0 => builder.push_bytes(b"%z"),
+				_ => builder.push_bytes(&[byte]),
+			}
+		}
+		builder.finish();
+	}
+
+	/// Pushes the result of `string.format(format, ...)` with the values of `args` as its arguments.
+	/// 
+	/// `format` must be a constant, so that user-provided bytes can never be interpreted as format specifiers,
+	/// and are only ever passed as arguments.
+	/// An error is raised without calling `string.format`
+	/// if the number of conversion specifiers in `format` is not the number of values in `args`.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Examples
+	/// ```no_run
+	/// # use gmbm::prelude::*;
+	/// fn push_greeting(lua: &mut Lua, name: &[u8], kills: LuaNumber) {
+	///     lua.push_format(c"%s has %d kills (%5.1f%%)", (name, kills, kills / 100.0));
+	/// }
+	/// ```
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_format<A: ToLuaMulti>(&mut self, format: &'static CStr, args: A) {
+		let expected = format_specifier_count(format.to_bytes());
+		if expected != A::LEN as usize {
+			let mut message = CStrBuf::new();
+			let _ = write!(message, "format string expects {expected} arguments, got {}", A::LEN);
+			self.throw_error(message.as_c_str())
+		}
+
+		self.get_global_field(c"string", c"format");
+		self.push_c_string(format);
+		args.push_all_to(self);
+		self.call(A::LEN + 1, 1);
+	}
+
+	/// Pops `n` values from the stack and pushes the result of concatenating them with the `..` operator,
+	/// following the usual Lua semantics (including numbers being converted to strings, and `__concat` metamethods).
+	/// 
+	/// If `n` is `1`, the value is left as-is on the stack.
+	/// If `n` is `0`, an empty string is pushed.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// It is emulated by calling a small Lua function compiled with `CompileString`,
+	/// which is cached in the registry.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn concat(&mut self, n: c_uint) {
+		match n {
+			0 => self.push_string(""),
+			1 => {}
+			_ => {
+				self.push_concat_function();
+				self.insert(-(n as c_int) - 1);
+				self.call(n, 1);
+			}
+		}
+	}
+
+	/// Pushes the concatenation of all byte strings in `parts` onto the stack as a Lua string,
+	/// without allocating a contiguous buffer for it on the Rust side.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_concat(&mut self, parts: &[&[u8]]) {
+		for part in parts {
+			self.push_string(part);
+		}
+		self.concat(parts.len() as _);
+	}
+
+	fn push_concat_function(&mut self) {
+		self.push_registry();
+		unsafe { self.push_light_userdata(&raw const CONCAT_KEY as *mut u8) }
+		self.raw_get(-2); // registry[CONCAT_KEY]
+		if self.is_type(-1, StdType::Function) {
+			self.remove(-2);
+			return
+		}
+		self.pop(1);
+
+		self.push_globals();
+		self.get_field(-1, c"CompileString");
+		self.remove(-2);
+		self.push_c_string(CONCAT_SOURCE);
+		self.push_string("gmbm");
+		self.call(2, 1); // CompileString(CONCAT_SOURCE, "gmbm")
+		self.call(0, 1);
+
+		unsafe { self.push_light_userdata(&raw const CONCAT_KEY as *mut u8) }
+		self.push_value(-2);
+		self.raw_set(-4); // registry[CONCAT_KEY] = f
+		self.remove(-2);
+	}
+}
+
+/// Functions for UTF-8 strings.
+impl Lua {
+	/// Pushes the given UTF-8 string onto the stack as a Lua string.
+	/// 
+	/// This is equivalent to [`push_string`](Self::push_string).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_str(&mut self, s: &str) {
+		self.push_string(s)
+	}
+
+	/// If the value at `stack_pos` is a string which is valid UTF-8, returns it.
+	/// Otherwise, throws an error,
+	/// which contains the position of the first invalid byte if the value is a string.
+	/// 
+	/// Like [`check_string`](Self::check_string), this converts a number at `stack_pos` to a string.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_utf8(&self, stack_pos: StackPos) -> &str {
+		// Negative positions are not argument numbers, so errors name the argument by its absolute position.
+		let arg_num = self.abs_index(stack_pos);
+		let Some(bytes) = self.get_string(stack_pos) else {
+			// The value is neither a string nor a number, so this raises the usual type error.
+			self.check_type(arg_num, StdType::String);
+			self.arg_error(arg_num, c"string expected")
+		};
+		match str::from_utf8(bytes) {
+			Ok(s) => s,
+			Err(e) => {
+				let mut message = CStrBuf::new();
+				// Positions in Lua strings start from `1`.
+				let _ = write!(message, "invalid UTF-8 at byte {}", e.valid_up_to() + 1);
+				self.arg_error(arg_num, message.as_c_str())
+			}
+		}
+	}
+}
+
+/// Returns the number of conversion specifiers in the `string.format` string `format`,
+/// which is the number of arguments that it expects.
+fn format_specifier_count(format: &[u8]) -> usize {
+	let mut count = 0;
+	let mut bytes = format.iter();
+	while let Some(&byte) = bytes.next() {
+		if byte != b'%' {
+			continue
+		}
+		// Skip flags, width and precision up to the conversion character.
+		match bytes.find(|&&b| !matches!(b, b'-' | b'+' | b' ' | b'#' | b'.' | b'0'..=b'9')) {
+			Some(b'%') | None => {}
+			Some(_) => count += 1,
+		}
+	}
+	count
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use crate::gmod13::testing::MockLua;
+
+	use super::*;
+
+	#[test]
+	fn builder() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		let mut builder = LuaStringBuilder::new(lua);
+		builder.push_bytes(b"a\0b");
+		let _ = write!(builder, "{}", 12);
+		builder.finish();
+		assert_eq!(lua.get_string(-1), Some(&b"a\0b12"[..]));
+
+		LuaStringBuilder::new(lua).finish();
+		assert_eq!(lua.get_string(-1), Some(&b""[..]));
+
+		// Dropping an unfinished builder leaves nothing behind.
+		lua.set_top(0);
+		LuaStringBuilder::new(lua).push_bytes(b"discarded");
+		assert_eq!(lua.top(), 0);
+	}
+
+	#[test]
+	fn pattern_escaped() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_pattern_escaped(b"1+1=2 (50%)\0");
+		assert_eq!(lua.get_string(-1), Some(&b"1%+1=2 %(50%%%)%z"[..]));
+	}
+
+	#[test]
+	fn utf8() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_str("héllo");
+		assert_eq!(lua.check_utf8(-1), "héllo");
+	}
+
+	#[test]
+	#[should_panic(expected = "invalid UTF-8 at byte 3")]
+	fn invalid_utf8() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_string(b"ok\xFF");
+		lua.check_utf8(1);
+	}
+
+	#[test]
+	#[should_panic(expected = "bad argument #2 (invalid UTF-8 at byte 3)")]
+	fn invalid_utf8_at_relative_position() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_str("ok");
+		lua.push_string(b"ok\xFF");
+		lua.check_utf8(-1);
+	}
+
+	#[test]
+	#[should_panic(expected = "bad argument #1 (string expected, got nil)")]
+	fn utf8_type_error_at_relative_position() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_nil();
+		lua.push_str("ok");
+		lua.check_utf8(-2);
+	}
+
+	#[test]
+	fn format_specifiers() {
+		assert_eq!(format_specifier_count(b"%s has %d kills (%5.1f%%)"), 3);
+		assert_eq!(format_specifier_count(b"100%% done"), 0);
+		assert_eq!(format_specifier_count(b"trailing %"), 0);
+	}
+
+	#[test]
+	#[should_panic(expected = "format string expects 2 arguments, got 1")]
+	fn format_argument_count() {
+		let mut mock = MockLua::new();
+		mock.lua().push_format(c"%s: %d", ("kills",));
+	}
+}
@@ -0,0 +1,277 @@
+use core::{
+	cmp::Ordering,
+	ffi::c_uint,
+	fmt::Write,
+	slice::from_raw_parts_mut,
+};
+
+use super::{
+	chunks::CStrBuf,
+	func::{
+		Ctx, Rets,
+	},
+	Lua, Number, StackPos, StdType,
+};
+
+/// Maximum depth of nested tables that [`Lua::deep_copy`] can copy.
+const MAX_COPY_DEPTH: usize = 200;
+
+/// Functions for protecting tables.
+impl Lua {
+	/// Makes the table at `stack_pos` read-only for Lua code,
+	/// so that assigning to any of its fields raises an error.
+	/// 
+	/// The fields of the table are moved into a hidden table,
+	/// which the table reads from through the `__index` of its new metatable.
+	/// The metatable also has a `__newindex` which raises an error,
+	/// and a `__metatable` field so that it can't be replaced or inspected with `getmetatable`.
+	/// 
+	/// Any existing metatable of the table is replaced.
+	/// Since the table itself is left empty, `pairs`, `next` and the length operator don't see its fields,
+	/// and `rawset` can still add new fields to it.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```no_run
+	/// use gmbm::gmod13::Lua;
+	/// 
+	/// fn push_config(lua: &mut Lua) {
+	///     lua.create_table();
+	///     lua.push_number(32.0);
+	///     lua.set_field(-2, c"MaxPlayers");
+	///     lua.freeze_table(-1);
+	/// }
+	/// ```
+	pub fn freeze_table(&mut self, stack_pos: StackPos) {
+		let table = self.abs_index(stack_pos);
+
+		self.create_table();
+		self.push_nil();
+		while self.next(table) != 0 {
+			self.push_value(-2);
+			self.insert(-2);
+			self.raw_set(-4);
+		}
+
+		// Fields are cleared by traversing the copy, since the table can't be modified while it is traversed.
+		self.push_nil();
+		while self.next(-2) != 0 {
+			self.pop(1);
+			self.push_value(-1);
+			self.push_nil();
+			self.raw_set(table);
+		}
+
+		self.create_table();
+		self.insert(-2);
+		self.set_field(-2, c"__index");
+		self.push_function(frozen_new_index);
+		self.set_field(-2, c"__newindex");
+		self.push_bool(false);
+		self.set_field(-2, c"__metatable");
+		self.set_metatable(table);
+	}
+}
+
+/// Functions for copying tables.
+impl Lua {
+	/// Pushes a deep copy of the table at `stack_pos`,
+	/// in which every table in its keys and values is also copied.
+	/// 
+	/// Tables which are referenced more than once, including tables which contain themselves,
+	/// are copied once, so the copy has the same structure as the original.
+	/// Other values, including userdata and functions, are shared with the original.
+	/// Fields are read and written without invoking metamethods.
+	/// 
+	/// If `copy_metatables` is `true`, then every copied table has the same metatable as its original.
+	/// Metatables themselves are not copied.
+	/// 
+	/// If the value at `stack_pos` isn't a table, then it is pushed as-is.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// including if tables are nested more than 200 times.
+	pub fn deep_copy(&mut self, stack_pos: StackPos, copy_metatables: bool) {
+		let original = self.abs_index(stack_pos);
+		// Map of the tables that have been copied to their copies.
+		self.create_table();
+		let copies = self.abs_index(-1);
+		self.push_copy(original, copies, copy_metatables, 0);
+		self.remove(copies);
+	}
+
+	fn push_copy(&mut self, pos: StackPos, copies: StackPos, copy_metatables: bool, depth: usize) {
+		if !self.is_type(pos, StdType::Table) {
+			return self.push_value(pos)
+		}
+
+		self.push_value(pos);
+		self.raw_get(copies);
+		if !self.is_type(-1, StdType::Nil) {
+			return
+		}
+		self.pop(1);
+
+		if depth == MAX_COPY_DEPTH {
+			self.throw_error(c"table is nested too deeply to copy")
+		}
+
+		self.create_table();
+		let copy = self.abs_index(-1);
+		self.push_value(pos);
+		self.push_value(copy);
+		self.raw_set(copies);
+
+		self.push_nil();
+		while self.next(pos) != 0 {
+			let key = self.abs_index(-2);
+			let value = self.abs_index(-1);
+			self.push_copy(key, copies, copy_metatables, depth + 1);
+			self.push_copy(value, copies, copy_metatables, depth + 1);
+			self.raw_set(copy);
+			self.pop(1);
+		}
+
+		if copy_metatables && self.get_metatable(pos) {
+			self.set_metatable(copy);
+		}
+	}
+}
+
+/// Functions for sorting tables.
+impl Lua {
+	/// Sorts the elements `1..=n` of the table at `stack_pos` in place,
+	/// where `n` is the length of the table.
+	/// 
+	/// If `comparator` is `Some`, then it is the position of a function
+	/// which is called like `comparator(a, b)` and returns `true` if `a` must come before `b`,
+	/// like the comparator of `table.sort`.
+	/// Otherwise, numbers and strings are compared with the `<` operator,
+	/// and an error is raised for any other values, including values of different types.
+	/// 
+	/// Unlike `table.sort`, which swaps elements in the table as it goes,
+	/// this sorts an array of indices with [`slice::sort_unstable_by`],
+	/// and then moves every element into place once.
+	/// The sort is not stable.
+	/// Fields are read and written without invoking metamethods.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Panics
+	/// May panic if `comparator` doesn't define a total order.
+	pub fn sort_table(&mut self, stack_pos: StackPos, comparator: Option<StackPos>) {
+		let table = self.abs_index(stack_pos);
+		let comparator = comparator.map(|pos| self.abs_index(pos));
+		let len = self.length_of(table).max(0) as c_uint;
+		if len < 2 {
+			return
+		}
+
+		// The indices are stored in userdata, so that no allocation is required on the Rust side,
+		// and so that they are freed even if a comparison raises an error.
+		let Some(size) = len.checked_mul(size_of::<c_uint>() as c_uint) else {
+			self.throw_error(c"table is too large to sort")
+		};
+		let indices_ptr = unsafe { self.new_userdata_raw(size) } as *mut c_uint;
+		if indices_ptr.is_null() {
+			self.throw_error(c"failed to allocate indices for sorting")
+		}
+		let indices_pos = self.abs_index(-1);
+		// SAFETY: Userdata is suitably aligned for any primitive type,
+		// and stays alive until it is removed from the stack below.
+		let indices = unsafe {
+			for i in 0..len {
+				indices_ptr.add(i as usize).write(i + 1);
+			}
+			from_raw_parts_mut(indices_ptr, len as usize)
+		};
+
+		indices.sort_unstable_by(|&a, &b| {
+			if self.sort_less_than(table, a, b, comparator) {
+				Ordering::Less
+			} else {
+				// Only `Less` is significant to the sort.
+				Ordering::Greater
+			}
+		});
+
+		self.create_table();
+		for (i, &index) in indices.iter().enumerate() {
+			self.push_number((i + 1) as Number);
+			self.push_number(index as Number);
+			self.raw_get(table);
+			self.raw_set(-3);
+		}
+		for i in 1..=len {
+			self.push_number(i as Number);
+			self.push_value(-1);
+			self.raw_get(-3);
+			self.raw_set(table);
+		}
+		self.pop(1);
+		self.remove(indices_pos);
+	}
+
+	/// Returns `true` if element `a` of the table at `table` must come before element `b`.
+	fn sort_less_than(&mut self, table: StackPos, a: c_uint, b: c_uint, comparator: Option<StackPos>) -> bool {
+		if let Some(comparator) = comparator {
+			self.push_value(comparator);
+		}
+		self.push_number(a as Number);
+		self.raw_get(table);
+		self.push_number(b as Number);
+		self.raw_get(table);
+
+		if comparator.is_some() {
+			self.call(2, 1);
+			let less = self.get_bool(-1);
+			self.pop(1);
+			return less
+		}
+
+		let less = match (self.get_type(-2).to_std(), self.get_type(-1).to_std()) {
+			(Some(StdType::Number), Some(StdType::Number)) => self.get_number(-2) < self.get_number(-1),
+			(Some(StdType::String), Some(StdType::String)) => self.get_string(-2) < self.get_string(-1),
+			_ => {
+				let mut message = CStrBuf::new();
+				let _ = write!(
+					message, "attempt to compare {} with {}",
+					self.get_type_name(self.get_type(-2)).to_bytes().escape_ascii(),
+					self.get_type_name(self.get_type(-1)).to_bytes().escape_ascii(),
+				);
+				self.throw_error(message.as_c_str())
+			}
+		};
+		self.pop(2);
+		less
+	}
+}
+
+/// Lua function `(t: table, comparator: function?)`,
+/// which sorts `t` in place like `table.sort` with [`Lua::sort_table`].
+pub extern "C-unwind" fn lua_sort(cx: Ctx<'_>) -> Rets {
+	let lua = cx.lua();
+	lua.check_type(1, StdType::Table);
+	let comparator = match lua.get_type(2).to_std() {
+		Some(StdType::None | StdType::Nil) => None,
+		_ => {
+			lua.check_type(2, StdType::Function);
+			Some(2)
+		}
+	};
+	lua.sort_table(1, comparator);
+	Rets::ZERO
+}
+
+extern "C-unwind" fn frozen_new_index(cx: Ctx<'_>) -> Rets {
+	cx.lua().throw_error(c"attempt to modify a read-only table")
+}
@@ -0,0 +1,247 @@
+//! Asynchronous work which runs on a background [Tokio](tokio) runtime,
+//! with results that are marshalled back to the thread that owns the Lua state.
+//! 
+//! Lua states may only be used from the thread that owns them,
+//! so futures never touch Lua directly.
+//! Instead, they queue closures with a [`MainThread`] handle,
+//! which are run by a `Think` hook that [`Lua::spawn`] adds,
+//! or by calling [`Lua::run_completions`] manually.
+//! 
+//! The runtime is created on first use,
+//! and must be shut down with [`shutdown`] in [`Module::close`](super::Module::close),
+//! since its threads would otherwise keep running after the binary module is unloaded.
+//! 
+//! # Examples
+//! ```no_run
+//! use gmbm::gmod13::{
+//!     task,
+//!     Lua,
+//! };
+//! 
+//! async fn fetch_motd() -> Result<String, &'static str> {
+//!     Ok("Welcome!".into())
+//! }
+//! 
+//! fn push_motd(lua: &mut Lua) {
+//!     // Pushes a `LuaPromise`, which Lua code can use like `promise:Then(print)`.
+//!     lua.spawn(fetch_motd());
+//! }
+//! 
+//! fn close(lua: &mut Lua) {
+//!     task::shutdown(lua);
+//! }
+//! ```
+
+extern crate std;
+
+use alloc::{
+	boxed::Box,
+	vec::Vec,
+};
+use core::{
+	fmt::Write,
+	future::Future,
+};
+use std::sync::{
+	Mutex, PoisonError,
+};
+
+use tokio::runtime::{
+	Builder, Handle, Runtime,
+};
+
+use super::{
+	chunks::CStrBuf,
+	convert::ToLua,
+	func::{
+		Ctx, Rets,
+	},
+	Lua, Ref, StdType,
+};
+
+/// Closure queued to run on the thread that owns a Lua state.
+type Completion = Box<dyn FnOnce(&mut Lua) + Send>;
+
+static RUNTIME: Mutex<Option<Runtime>> = Mutex::new(None);
+
+/// Queued completions, along with the [`MainThread`] that they should run on.
+static COMPLETIONS: Mutex<Vec<(MainThread, Completion)>> = Mutex::new(Vec::new());
+
+/// References to the promises of spawned futures which haven't been settled yet,
+/// along with the [`MainThread`] that they belong to.
+static PENDING: Mutex<Vec<(MainThread, Ref)>> = Mutex::new(Vec::new());
+
+/// Returns a handle to the background runtime, creating it if it isn't running.
+/// 
+/// # Panics
+/// Panics if the runtime can't be created.
+pub fn handle() -> Handle {
+	let mut runtime = RUNTIME.lock().unwrap_or_else(PoisonError::into_inner);
+	runtime.get_or_insert_with(|| {
+		Builder::new_multi_thread()
+			.enable_all()
+			.build()
+			.expect("failed to create Tokio runtime")
+	}).handle().clone()
+}
+
+/// Shuts down the background runtime without waiting for its tasks to finish,
+/// discards all queued completions,
+/// frees the references to the promises of `lua` which haven't been settled,
+/// and removes the `Think` hook added by [`Lua::spawn`].
+/// 
+/// This should be called when the last Lua state which uses the runtime is closed.
+/// The runtime is created again if it is used afterwards.
+/// 
+/// # Errors
+/// The inner Lua state may raise an [error](crate::errors).
+pub fn shutdown(lua: &mut Lua) {
+	let runtime = RUNTIME.lock().unwrap_or_else(PoisonError::into_inner).take();
+	if let Some(runtime) = runtime {
+		runtime.shutdown_background();
+	}
+	COMPLETIONS.lock().unwrap_or_else(PoisonError::into_inner).clear();
+
+	let main_thread = lua.main_thread();
+	let pending = core::mem::take(&mut *PENDING.lock().unwrap_or_else(PoisonError::into_inner));
+	for (owner, lua_ref) in pending {
+		// References of other Lua states can't be freed from here, and those states should already be closed.
+		if owner == main_thread {
+			lua.free_ref(lua_ref);
+		}
+	}
+
+	lua.remove_completion_hook();
+}
+
+/// Handle to the thread that owns a Lua state,
+/// which can be sent to other threads to queue work that needs the Lua state.
+/// 
+/// A handle is obtained with [`Lua::main_thread`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MainThread {
+	lua: usize,
+}
+
+impl MainThread {
+	/// Queues `f` to be called with the Lua state on the thread that owns it,
+	/// the next time that [`Lua::run_completions`] is called.
+	pub fn queue<F: FnOnce(&mut Lua) + Send + 'static>(self, f: F) {
+		COMPLETIONS.lock().unwrap_or_else(PoisonError::into_inner).push((self, Box::new(f)));
+	}
+}
+
+/// Functions for running asynchronous work on a background runtime.
+impl Lua {
+	/// Returns a handle which queues work for this Lua state from other threads.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn main_thread(&self) -> MainThread {
+		MainThread {
+			lua: self as *const Self as usize,
+		}
+	}
+
+	/// Runs all closures queued for this Lua state with [`MainThread::queue`], in order.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// Closures which haven't been run yet stay queued in that case.
+	pub fn run_completions(&mut self) {
+		let main_thread = self.main_thread();
+		// Each closure is removed from the queue only when it's about to run,
+		// so that the rest stay queued if it raises an error.
+		while let Some(f) = take_next(main_thread) {
+			f(self);
+		}
+	}
+
+	/// Spawns `future` on the background runtime,
+	/// and pushes a [`LuaPromise`](super::user_types::LuaPromise)
+	/// which is resolved with its output, or rejected with its error, on the main thread.
+	/// 
+	/// This also adds a `Think` hook which calls [`Lua::run_completions`] every tick.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Panics
+	/// Panics if the runtime can't be created.
+	pub fn spawn<F, T, E>(&mut self, future: F)
+	where
+		F: Future<Output = Result<T, E>> + Send + 'static,
+		T: ToLua + Send + 'static,
+		E: ToLua + Send + 'static,
+	{
+		self.add_completion_hook();
+		let promise = self.create_promise();
+		let main_thread = self.main_thread();
+		PENDING.lock().unwrap_or_else(PoisonError::into_inner).push((main_thread, promise.lua_ref()));
+		handle().spawn(async move {
+			let result = future.await;
+			main_thread.queue(move |lua| {
+				untrack(main_thread, promise.lua_ref());
+				match result {
+					Ok(value) => promise.resolve(lua, move |lua| value.push_to(lua)),
+					Err(error) => promise.reject(lua, move |lua| error.push_to(lua)),
+				}
+			});
+		});
+	}
+
+	/// Adds the `Think` hook which runs completions for this binary module, replacing it if it already exists.
+	fn add_completion_hook(&mut self) {
+		self.get_global_field(c"hook", c"Add");
+		self.push_c_string(c"Think");
+		self.push_c_string(completion_hook_name().as_c_str());
+		self.push_function(run_completions_hook);
+		self.call(3, 0);
+	}
+
+	/// Removes the `Think` hook which runs completions for this binary module,
+	/// unless the `hook` library has already been unloaded.
+	fn remove_completion_hook(&mut self) {
+		self.get_global(c"hook");
+		if !self.is_type(-1, StdType::Table) {
+			self.pop(1);
+			return
+		}
+		self.get_field(-1, c"Remove");
+		self.remove(-2);
+		self.push_c_string(c"Think");
+		self.push_c_string(completion_hook_name().as_c_str());
+		self.call(2, 0);
+	}
+}
+
+/// Returns the name of the `Think` hook which runs completions for this binary module.
+fn completion_hook_name() -> CStrBuf {
+	// The hook is unique to each binary module, since each one has its own queue.
+	let mut name = CStrBuf::new();
+	let _ = write!(name, "gmbm.completions.{:p}", &COMPLETIONS);
+	name
+}
+
+/// Stops tracking the promise at `lua_ref`, since it's about to be settled.
+fn untrack(main_thread: MainThread, lua_ref: Ref) {
+	let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+	if let Some(i) = pending.iter().position(move |entry| *entry == (main_thread, lua_ref)) {
+		pending.swap_remove(i);
+	}
+}
+
+/// Removes and returns the first completion queued for `main_thread`.
+fn take_next(main_thread: MainThread) -> Option<Completion> {
+	let mut queue = COMPLETIONS.lock().unwrap_or_else(PoisonError::into_inner);
+	let i = queue.iter().position(move |(owner, _)| *owner == main_thread)?;
+	Some(queue.remove(i).1)
+}
+
+extern "C-unwind" fn run_completions_hook(cx: Ctx<'_>) -> Rets {
+	cx.lua().run_completions();
+	Rets::ZERO
+}
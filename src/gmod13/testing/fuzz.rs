@@ -0,0 +1,217 @@
+//! Deterministic drivers which run sequences of safe [`Lua`] operations against a [`MockLua`],
+//! asserting that the stack stays balanced and keeps the expected types after every operation.
+//! 
+//! [`run`] interprets arbitrary bytes as operations,
+//! so it can be used directly as the body of a `cargo fuzz` target,
+//! like the `stack` target in the `fuzz` directory of the repository.
+//! [`run_seeded`] generates the bytes from a seed instead, which is useful in regular tests.
+//! 
+//! # Examples
+//! ```
+//! use gmbm::gmod13::testing::fuzz;
+//! 
+//! for seed in 0..16 {
+//!     fuzz::run_seeded(seed, 256);
+//! }
+//! ```
+
+use alloc::vec::Vec;
+use core::ffi::c_uint;
+
+use super::{
+	super::{
+		Lua, StackPos, StdType,
+	},
+	MockLua,
+};
+
+/// Maximum number of values that operations will keep on the stack.
+pub const MAX_STACK: usize = 64;
+
+/// Runs the operations encoded in `data` against a new [`MockLua`].
+/// 
+/// # Panics
+/// Panics if the stack of the state doesn't match the expected one after any operation.
+pub fn run(data: &[u8]) {
+	let mut mock = MockLua::new();
+	let lua = mock.lua();
+	let mut model = Model::default();
+
+	let mut bytes = data.iter().copied();
+	while let Some(op) = bytes.next() {
+		let arg = bytes.next().unwrap_or_default();
+		step(lua, &mut model, op, arg);
+		model.check(lua);
+	}
+}
+
+/// Runs `steps` operations generated from `seed` against a new [`MockLua`].
+/// 
+/// # Panics
+/// Panics if the stack of the state doesn't match the expected one after any operation.
+pub fn run_seeded(seed: u64, steps: usize) {
+	// SplitMix64, which is good enough to pick operations.
+	let mut state = seed;
+	let mut next = move || {
+		state = state.wrapping_add(0x9E3779B97F4A7C15);
+		let mut z = state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+		z ^ (z >> 31)
+	};
+
+	let data: Vec<u8> = (0..steps).flat_map(move |_| next().to_le_bytes()[..2].to_vec()).collect();
+	run(&data)
+}
+
+/// Expected types of the values on the stack, from bottom to top.
+#[derive(Default)]
+struct Model {
+	stack: Vec<StdType>,
+}
+
+impl Model {
+	fn len(&self) -> c_uint {
+		self.stack.len() as _
+	}
+
+	/// Returns a valid negative stack position derived from `arg`, or `None` if the stack is empty.
+	fn relative(&self, arg: u8) -> Option<StackPos> {
+		let len = self.stack.len();
+		(len > 0).then(|| -((arg as usize % len) as StackPos) - 1)
+	}
+
+	/// Returns the index in the model of the relative stack position `pos`.
+	fn index(&self, pos: StackPos) -> usize {
+		(self.stack.len() as StackPos + pos) as usize
+	}
+
+	fn push(&mut self, ty: StdType) {
+		self.stack.push(ty)
+	}
+
+	/// Asserts that the stack of `lua` matches the model.
+	fn check(&self, lua: &Lua) {
+		assert_eq!(lua.top(), self.len(), "stack size");
+		for (i, &ty) in self.stack.iter().enumerate() {
+			let pos = i as StackPos + 1;
+			assert_eq!(lua.get_type(pos), ty, "type at {pos}");
+		}
+	}
+}
+
+/// Runs the operation selected by `op` with the argument `arg`, and updates `model` accordingly.
+fn step(lua: &mut Lua, model: &mut Model, op: u8, arg: u8) {
+	let full = model.stack.len() >= MAX_STACK;
+	match op % 16 {
+		0 if !full => {
+			lua.push_nil();
+			model.push(StdType::Nil)
+		}
+		1 if !full => {
+			lua.push_bool(arg & 1 != 0);
+			model.push(StdType::Bool)
+		}
+		2 if !full => {
+			lua.push_number(arg as _);
+			model.push(StdType::Number)
+		}
+		3 if !full => {
+			lua.push_string([b'k', arg]);
+			model.push(StdType::String)
+		}
+		4 if !full => {
+			lua.create_table();
+			model.push(StdType::Table)
+		}
+		5 => {
+			let n = (arg as c_uint).min(model.len());
+			lua.pop(n);
+			model.stack.truncate(model.stack.len() - n as usize);
+		}
+		6 => {
+			let top = arg as c_uint % (MAX_STACK as c_uint + 1);
+			lua.set_top(top);
+			model.stack.resize(top as _, StdType::Nil);
+		}
+		7 => {
+			let top = arg as c_uint % (MAX_STACK as c_uint + 1);
+			lua.drain_to(top);
+			model.stack.truncate(top as _);
+		}
+		8 if !full => if let Some(pos) = model.relative(arg) {
+			lua.push_value(pos);
+			model.push(model.stack[model.index(pos)]);
+		}
+		9 => if let Some(pos) = model.relative(arg) {
+			lua.insert(pos);
+			let i = model.index(pos);
+			let ty = model.stack.pop().unwrap();
+			model.stack.insert(i, ty);
+		}
+		10 => if let Some(pos) = model.relative(arg) {
+			lua.remove(pos);
+			model.stack.remove(model.index(pos));
+		}
+		11 => if let Some(pos) = model.relative(arg) {
+			let abs = lua.abs_index(pos);
+			assert_eq!(abs, model.index(pos) as StackPos + 1, "absolute index of {pos}");
+			assert_eq!(lua.get_type(abs), model.stack[abs as usize - 1], "type at {abs}");
+		}
+		12 => {
+			// Set `t.k = v` for the table `t` under the value `v`.
+			if let [.., StdType::Table, _] = model.stack[..] {
+				lua.set_field(-2, c"k");
+				model.stack.pop();
+			}
+		}
+		13 if !full => {
+			// Push `t.k` for the table `t` on top, which is a value set by op 12 or `nil`.
+			if let Some(StdType::Table) = model.stack.last() {
+				lua.get_field(-1, c"k");
+				let ty = lua.get_type(-1).to_std().expect("field should have a standard type");
+				model.push(ty);
+			}
+		}
+		14 => {
+			// Convert the number on top to a string in place.
+			if let Some(StdType::Number) = model.stack.last() {
+				assert!(lua.get_string(-1).is_some(), "number should convert to a string");
+				*model.stack.last_mut().unwrap() = StdType::String;
+			}
+		}
+		15 if !full => {
+			lua.push_globals();
+			model.push(StdType::Table)
+		}
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn seeded() {
+		for seed in 0..64 {
+			run_seeded(seed, 512);
+		}
+	}
+
+	#[test]
+	fn every_operation() {
+		// Fill the stack past its limit first, so that every operation also runs on a full stack.
+		let fill = (0..MAX_STACK as u8 + 1).flat_map(|i| [4, i]);
+		let ops = (0..=u8::MAX).flat_map(|op| [op, op.wrapping_mul(7)]);
+		let data: Vec<u8> = fill.chain(ops.clone()).chain(ops).collect();
+		run(&data);
+	}
+
+	#[test]
+	fn truncated_input() {
+		run(&[]);
+		run(&[2]);
+		run(&[2, 1, 8]);
+	}
+}
@@ -0,0 +1,1166 @@
+//! Implementation of the Garry's Mod Lua API on top of a real LuaJIT state.
+//! 
+//! Unlike [`MockLua`](super::MockLua), this runs the actual LuaJIT interpreter,
+//! so garbage collection, references, metatables and protected calls behave exactly like they do in the game.
+//! This requires linking to the system `luajit-5.1` library.
+
+use alloc::{
+	boxed::Box,
+	ffi::CString,
+	vec::Vec,
+};
+use core::{
+	cell::Cell,
+	ffi::{
+		CStr,
+		c_char, c_int, c_uint, c_void,
+	},
+	mem::zeroed,
+	ptr::{
+		NonNull, null_mut,
+	},
+};
+
+use crate::source::{
+	Vector, QAngle,
+};
+
+use super::super::{
+	func::Func,
+	CallError, CFunc, Lua, LuaBase, LuaBaseVt, LuaState,
+	Number, RawRef, RawType, Special, StackPos, StdType,
+};
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct lua_State {
+	_private: [u8; 0],
+}
+
+#[allow(non_camel_case_types)]
+type lua_CFunction = unsafe extern "C-unwind" fn(l: *mut lua_State) -> c_int;
+
+#[link(name = "luajit-5.1")]
+unsafe extern "C-unwind" {
+	fn luaL_newstate() -> *mut lua_State;
+	fn luaL_openlibs(l: *mut lua_State);
+	fn lua_close(l: *mut lua_State);
+
+	fn lua_gettop(l: *mut lua_State) -> c_int;
+	fn lua_settop(l: *mut lua_State, idx: c_int);
+	fn lua_pushvalue(l: *mut lua_State, idx: c_int);
+	fn lua_remove(l: *mut lua_State, idx: c_int);
+	fn lua_insert(l: *mut lua_State, idx: c_int);
+
+	fn lua_type(l: *mut lua_State, idx: c_int) -> c_int;
+	fn lua_equal(l: *mut lua_State, idx1: c_int, idx2: c_int) -> c_int;
+	fn lua_rawequal(l: *mut lua_State, idx1: c_int, idx2: c_int) -> c_int;
+	fn lua_tonumber(l: *mut lua_State, idx: c_int) -> Number;
+	fn lua_toboolean(l: *mut lua_State, idx: c_int) -> c_int;
+	fn lua_tolstring(l: *mut lua_State, idx: c_int, len: *mut usize) -> *const c_char;
+	fn lua_objlen(l: *mut lua_State, idx: c_int) -> usize;
+	fn lua_tocfunction(l: *mut lua_State, idx: c_int) -> Option<lua_CFunction>;
+	fn lua_touserdata(l: *mut lua_State, idx: c_int) -> *mut c_void;
+
+	fn lua_pushnil(l: *mut lua_State);
+	fn lua_pushnumber(l: *mut lua_State, n: Number);
+	fn lua_pushlstring(l: *mut lua_State, s: *const c_char, len: usize);
+	fn lua_pushstring(l: *mut lua_State, s: *const c_char);
+	fn lua_pushcclosure(l: *mut lua_State, f: lua_CFunction, n: c_int);
+	fn lua_pushboolean(l: *mut lua_State, b: c_int);
+	fn lua_pushlightuserdata(l: *mut lua_State, p: *mut c_void);
+
+	fn lua_gettable(l: *mut lua_State, idx: c_int);
+	fn lua_getfield(l: *mut lua_State, idx: c_int, k: *const c_char);
+	fn lua_rawget(l: *mut lua_State, idx: c_int);
+	fn lua_rawgeti(l: *mut lua_State, idx: c_int, n: c_int);
+	fn lua_createtable(l: *mut lua_State, narr: c_int, nrec: c_int);
+	fn lua_newuserdata(l: *mut lua_State, size: usize) -> *mut c_void;
+	fn lua_getmetatable(l: *mut lua_State, idx: c_int) -> c_int;
+	fn lua_getupvalue(l: *mut lua_State, funcindex: c_int, n: c_int) -> *const c_char;
+
+	fn lua_settable(l: *mut lua_State, idx: c_int);
+	fn lua_setfield(l: *mut lua_State, idx: c_int, k: *const c_char);
+	fn lua_rawset(l: *mut lua_State, idx: c_int);
+	fn lua_setmetatable(l: *mut lua_State, idx: c_int) -> c_int;
+
+	fn lua_call(l: *mut lua_State, nargs: c_int, nresults: c_int);
+	fn lua_pcall(l: *mut lua_State, nargs: c_int, nresults: c_int, errfunc: c_int) -> c_int;
+	fn luaL_loadstring(l: *mut lua_State, s: *const c_char) -> c_int;
+
+	fn lua_error(l: *mut lua_State) -> !;
+	fn lua_next(l: *mut lua_State, idx: c_int) -> c_int;
+
+	fn luaL_argerror(l: *mut lua_State, numarg: c_int, extramsg: *const c_char) -> !;
+	fn luaL_typerror(l: *mut lua_State, narg: c_int, tname: *const c_char) -> !;
+	fn luaL_checknumber(l: *mut lua_State, narg: c_int) -> Number;
+	fn luaL_checklstring(l: *mut lua_State, narg: c_int, len: *mut usize) -> *const c_char;
+	fn luaL_ref(l: *mut lua_State, t: c_int) -> c_int;
+	fn luaL_unref(l: *mut lua_State, t: c_int, r: c_int);
+}
+
+const LUA_REGISTRYINDEX: c_int = -10000;
+const LUA_ENVIRONINDEX: c_int = -10001;
+const LUA_GLOBALSINDEX: c_int = -10002;
+
+const LUA_TUSERDATA: c_int = 7;
+
+/// Number of upvalues that [`trampoline`] reserves before the upvalues of the native function.
+const RESERVED_UPVALUES: c_int = 2;
+
+/// First [`RawType`] assigned to metatables created with `CreateMetaTable`.
+const FIRST_CUSTOM_TYPE: RawType = StdType::SurfaceInfo as RawType + 1;
+
+/// Header of userdata created by Garry's Mod.
+#[repr(C)]
+struct UserData {
+	data: *mut c_void,
+	ty: u8,
+}
+
+/// Garry's Mod Lua API implemented on top of a real LuaJIT state.
+/// 
+/// Native functions are pushed as LuaJIT closures which store the function and a pointer to this structure
+/// in their first upvalues,
+/// and upvalue indices are adjusted accordingly.
+#[repr(C)]
+pub struct LuaJit {
+	// INVARIANT: This is the first field, so that a pointer to it is also a pointer to `LuaJit`.
+	base: LuaBase,
+	state: LuaState,
+	main: *mut lua_State,
+	/// Thread that is currently running native code.
+	current: Cell<*mut lua_State>,
+	type_names: Vec<(RawType, CString)>,
+	next_type: RawType,
+	zero_vector: Vector,
+	zero_angle: QAngle,
+}
+
+impl LuaJit {
+	/// Creates a new LuaJIT state with the standard libraries opened.
+	/// 
+	/// # Panics
+	/// Panics if LuaJIT fails to allocate the state.
+	pub fn new() -> Box<Self> {
+		let main = unsafe { luaL_newstate() };
+		assert!(!main.is_null(), "failed to create LuaJIT state");
+		unsafe { luaL_openlibs(main) };
+
+		let mut jit = Box::new(Self {
+			base: LuaBase {
+				vtable: &VTABLE,
+			},
+			state: LuaState::detached(NonNull::dangling()),
+			main,
+			current: Cell::new(main),
+			type_names: Vec::new(),
+			next_type: FIRST_CUSTOM_TYPE,
+			zero_vector: unsafe { zeroed() },
+			zero_angle: unsafe { zeroed() },
+		});
+		jit.state.luabase = NonNull::from(&mut jit.base);
+		jit
+	}
+
+	/// Returns the [`Lua`] interface to this state.
+	pub fn lua(&mut self) -> &mut Lua {
+		unsafe { Lua::from_state_ptr(self.as_state_ptr()) }
+	}
+
+	/// Returns a pointer to the [`LuaState`] of this state,
+	/// which can be passed to `gmod13_*` entrypoints and native functions.
+	pub fn as_state_ptr(&mut self) -> *mut LuaState {
+		&raw mut self.state
+	}
+
+	/// Sets the global variable `name` to the native function `f`.
+	pub fn set_global_function(&mut self, name: &CStr, f: Func) {
+		let lua = self.lua();
+		lua.push_globals();
+		lua.push_function(f);
+		lua.set_field(-2, name);
+		lua.pop(1);
+	}
+
+	/// Runs `code` as a Lua chunk in a protected call.
+	/// 
+	/// On failure, the error message is left on the stack, like with [`Lua::pcall`].
+	pub fn exec(&mut self, code: &CStr) -> Result<(), CallError> {
+		let l = self.current.get();
+		let status = unsafe {
+			match luaL_loadstring(l, code.as_ptr()) {
+				0 => lua_pcall(l, 0, 0, 0),
+				status => status,
+			}
+		};
+		if status == 0 {
+			Ok(())
+		} else {
+			Err(CallError)
+		}
+	}
+
+	fn type_name(&mut self, ty: RawType) -> *const c_char {
+		if let Some((_, name)) = self.type_names.iter().find(move |(t, _)| *t == ty) {
+			return name.as_ptr()
+		}
+
+		let name = match StdType::from_raw(ty) {
+			Some(std) => CString::new(std.name()).unwrap(),
+			None => CString::from(c"UserData"),
+		};
+		self.type_names.push((ty, name));
+		self.type_names.last().unwrap().1.as_ptr()
+	}
+
+	fn raw_type(&self, idx: c_int) -> RawType {
+		let l = self.current.get();
+		let ty = unsafe { lua_type(l, idx) };
+		if ty == LUA_TUSERDATA && unsafe { lua_objlen(l, idx) } >= size_of::<UserData>() {
+			unsafe { (*lua_touserdata(l, idx).cast::<UserData>()).ty as RawType }
+		} else {
+			ty
+		}
+	}
+
+	/// Pushes the metatable registered for `ty`, returning `false` and pushing nothing if there is none.
+	fn push_type_metatable(&mut self, ty: RawType) -> bool {
+		let l = self.current.get();
+		let name = self.type_name(ty);
+		unsafe {
+			lua_getfield(l, LUA_REGISTRYINDEX, name);
+			if lua_type(l, -1) == StdType::Table as c_int {
+				true
+			} else {
+				lua_settop(l, -2);
+				false
+			}
+		}
+	}
+
+	fn create_metatable(&mut self, name: &CStr, ty: Option<RawType>) -> RawType {
+		let l = self.current.get();
+		unsafe {
+			lua_getfield(l, LUA_REGISTRYINDEX, name.as_ptr());
+			if lua_type(l, -1) == StdType::Table as c_int {
+				lua_getfield(l, -1, c"MetaID".as_ptr());
+				let ty = lua_tonumber(l, -1) as RawType;
+				lua_settop(l, -2);
+				return ty
+			}
+			lua_settop(l, -2);
+		}
+
+		let ty = ty.unwrap_or_else(|| {
+			let ty = self.next_type;
+			self.next_type += 1;
+			ty
+		});
+		self.type_names.retain(move |(t, _)| *t != ty);
+		self.type_names.push((ty, name.into()));
+
+		unsafe {
+			lua_createtable(l, 0, 2);
+			lua_pushstring(l, name.as_ptr());
+			lua_setfield(l, -2, c"MetaName".as_ptr());
+			lua_pushnumber(l, ty as Number);
+			lua_setfield(l, -2, c"MetaID".as_ptr());
+			lua_pushvalue(l, -1);
+			lua_setfield(l, LUA_REGISTRYINDEX, name.as_ptr());
+		}
+		ty
+	}
+
+	/// Pushes a new userdata with the Garry's Mod header, which holds a copy of `value`.
+	fn push_boxed<T>(&mut self, value: *const T, ty: StdType) {
+		let l = self.current.get();
+		unsafe {
+			let ud = lua_newuserdata(l, size_of::<UserData>() + size_of::<T>()).cast::<UserData>();
+			let data = ud.add(1).cast::<T>();
+			data.write(value.read());
+			ud.write(UserData {
+				data: data.cast(),
+				ty: ty as u8,
+			});
+		}
+		if self.push_type_metatable(ty.to_raw()) {
+			unsafe { lua_setmetatable(l, -2) };
+		}
+	}
+
+	/// Returns the data of the userdata at `idx`, if it is of type `ty`.
+	fn boxed_data(&self, idx: c_int, ty: StdType) -> Option<*mut c_void> {
+		if self.raw_type(idx) == ty.to_raw() {
+			Some(unsafe { (*lua_touserdata(self.current.get(), idx).cast::<UserData>()).data })
+		} else {
+			None
+		}
+	}
+}
+
+impl Drop for LuaJit {
+	fn drop(&mut self) {
+		unsafe { lua_close(self.main) }
+	}
+}
+
+impl core::fmt::Debug for LuaJit {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("LuaJit").field("main", &self.main).finish_non_exhaustive()
+	}
+}
+
+/// Calls the native function stored in the first upvalue
+/// with the [`LuaState`] of the [`LuaJit`] stored in the second upvalue.
+unsafe extern "C-unwind" fn trampoline(l: *mut lua_State) -> c_int {
+	struct Restore<'a> {
+		current: &'a Cell<*mut lua_State>,
+		prev: *mut lua_State,
+	}
+	impl Drop for Restore<'_> {
+		fn drop(&mut self) {
+			self.current.set(self.prev)
+		}
+	}
+
+	let f: CFunc = unsafe { core::mem::transmute(lua_touserdata(l, LUA_GLOBALSINDEX - 1)) };
+	let jit = unsafe { &mut *lua_touserdata(l, LUA_GLOBALSINDEX - 2).cast::<LuaJit>() };
+	let _restore = Restore {
+		prev: jit.current.replace(l),
+		current: &jit.current,
+	};
+	unsafe { f(&raw mut jit.state) }
+}
+
+/// Returns the [`LuaJit`] that `this` points to.
+/// 
+/// # Safety
+/// `this` must point to the `base` field of a live [`LuaJit`].
+unsafe fn jit<'a>(this: *mut LuaBase) -> &'a mut LuaJit {
+	unsafe { &mut *this.cast::<LuaJit>() }
+}
+
+/// Returns the current thread of the [`LuaJit`] that `this` points to.
+unsafe fn cur(this: *mut LuaBase) -> *mut lua_State {
+	unsafe { jit(this) }.current.get()
+}
+
+/// Converts a Garry's Mod stack position to a LuaJIT one, skipping the reserved upvalues.
+const fn idx(stack_pos: StackPos) -> c_int {
+	if stack_pos < LUA_GLOBALSINDEX {
+		stack_pos - RESERVED_UPVALUES
+	} else {
+		stack_pos
+	}
+}
+
+unsafe extern "C-unwind" fn jit_top(this: *mut LuaBase) -> c_int {
+	unsafe { lua_gettop(cur(this)) }
+}
+
+unsafe extern "C-unwind" fn jit_push(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_pushvalue(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_pop(this: *mut LuaBase, amt: c_int) {
+	unsafe { lua_settop(cur(this), -amt - 1) }
+}
+
+unsafe extern "C-unwind" fn jit_get_table(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_gettable(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_get_field(this: *mut LuaBase, stack_pos: StackPos, name: *const c_char) {
+	unsafe { lua_getfield(cur(this), idx(stack_pos), name) }
+}
+
+unsafe extern "C-unwind" fn jit_set_field(this: *mut LuaBase, stack_pos: StackPos, name: *const c_char) {
+	unsafe { lua_setfield(cur(this), idx(stack_pos), name) }
+}
+
+unsafe extern "C-unwind" fn jit_create_table(this: *mut LuaBase) {
+	unsafe { lua_createtable(cur(this), 0, 0) }
+}
+
+unsafe extern "C-unwind" fn jit_set_table(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_settable(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_set_meta_table(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_setmetatable(cur(this), idx(stack_pos)) };
+}
+
+unsafe extern "C-unwind" fn jit_get_meta_table(this: *mut LuaBase, stack_pos: StackPos) -> bool {
+	unsafe { lua_getmetatable(cur(this), idx(stack_pos)) != 0 }
+}
+
+unsafe extern "C-unwind" fn jit_call(this: *mut LuaBase, n_args: c_int, n_results: c_int) {
+	unsafe { lua_call(cur(this), n_args, n_results) }
+}
+
+unsafe extern "C-unwind" fn jit_pcall(this: *mut LuaBase, n_args: c_int, n_results: c_int, error_func: c_int) -> c_int {
+	unsafe { lua_pcall(cur(this), n_args, n_results, error_func) }
+}
+
+unsafe extern "C-unwind" fn jit_equal(this: *mut LuaBase, a: StackPos, b: StackPos) -> c_int {
+	unsafe { lua_equal(cur(this), idx(a), idx(b)) }
+}
+
+unsafe extern "C-unwind" fn jit_raw_equal(this: *mut LuaBase, a: StackPos, b: StackPos) -> c_int {
+	unsafe { lua_rawequal(cur(this), idx(a), idx(b)) }
+}
+
+unsafe extern "C-unwind" fn jit_insert(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_insert(cur(this), stack_pos) }
+}
+
+unsafe extern "C-unwind" fn jit_remove(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_remove(cur(this), stack_pos) }
+}
+
+unsafe extern "C-unwind" fn jit_next(this: *mut LuaBase, stack_pos: StackPos) -> c_int {
+	unsafe { lua_next(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_new_userdata(this: *mut LuaBase, size: c_uint) -> *mut c_void {
+	unsafe { lua_newuserdata(cur(this), size as _) }
+}
+
+unsafe extern "C-unwind" fn jit_throw_error(this: *mut LuaBase, error: *const c_char) -> ! {
+	let l = unsafe { cur(this) };
+	unsafe {
+		lua_pushstring(l, error);
+		lua_error(l)
+	}
+}
+
+unsafe extern "C-unwind" fn jit_check_type(this: *mut LuaBase, stack_pos: StackPos, ty: RawType) {
+	let jit = unsafe { jit(this) };
+	if jit.raw_type(idx(stack_pos)) != ty {
+		let name = jit.type_name(ty);
+		unsafe { luaL_typerror(jit.current.get(), stack_pos, name) }
+	}
+}
+
+unsafe extern "C-unwind" fn jit_arg_error(this: *mut LuaBase, arg_num: c_int, message: *const c_char) -> ! {
+	unsafe { luaL_argerror(cur(this), arg_num, message) }
+}
+
+unsafe extern "C-unwind" fn jit_raw_get(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_rawget(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_raw_set(this: *mut LuaBase, stack_pos: StackPos) {
+	unsafe { lua_rawset(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_get_string(this: *mut LuaBase, stack_pos: StackPos, out_len: *mut c_uint) -> *const c_char {
+	let mut len = 0;
+	let ptr = unsafe { lua_tolstring(cur(this), idx(stack_pos), &mut len) };
+	if !out_len.is_null() {
+		unsafe { out_len.write(len as _) }
+	}
+	ptr
+}
+
+unsafe extern "C-unwind" fn jit_get_number(this: *mut LuaBase, stack_pos: StackPos) -> Number {
+	unsafe { lua_tonumber(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_get_bool(this: *mut LuaBase, stack_pos: StackPos) -> bool {
+	unsafe { lua_toboolean(cur(this), idx(stack_pos)) != 0 }
+}
+
+unsafe extern "C-unwind" fn jit_get_c_function(this: *mut LuaBase, stack_pos: StackPos) -> Option<CFunc> {
+	let l = unsafe { cur(this) };
+	let stack_pos = idx(stack_pos);
+	let f = unsafe { lua_tocfunction(l, stack_pos) };
+	if !f.is_some_and(move |f| core::ptr::fn_addr_eq(f, trampoline as lua_CFunction)) {
+		return None
+	}
+	unsafe {
+		lua_getupvalue(l, stack_pos, 1);
+		let f = lua_touserdata(l, -1);
+		lua_settop(l, -2);
+		core::mem::transmute::<*mut c_void, Option<CFunc>>(f)
+	}
+}
+
+unsafe extern "C-unwind" fn jit_get_userdata(this: *mut LuaBase, stack_pos: StackPos) -> *mut c_void {
+	unsafe { lua_touserdata(cur(this), idx(stack_pos)) }
+}
+
+unsafe extern "C-unwind" fn jit_push_nil(this: *mut LuaBase) {
+	unsafe { lua_pushnil(cur(this)) }
+}
+
+unsafe extern "C-unwind" fn jit_push_string(this: *mut LuaBase, val: *const c_char, len: c_int) {
+	let l = unsafe { cur(this) };
+	if len == 0 {
+		unsafe { lua_pushstring(l, val) }
+	} else {
+		unsafe { lua_pushlstring(l, val, len as _) }
+	}
+}
+
+unsafe extern "C-unwind" fn jit_push_number(this: *mut LuaBase, val: Number) {
+	unsafe { lua_pushnumber(cur(this), val) }
+}
+
+unsafe extern "C-unwind" fn jit_push_bool(this: *mut LuaBase, val: bool) {
+	unsafe { lua_pushboolean(cur(this), val as _) }
+}
+
+unsafe extern "C-unwind" fn jit_push_c_function(this: *mut LuaBase, val: CFunc) {
+	unsafe { jit_push_c_closure(this, val, 0) }
+}
+
+unsafe extern "C-unwind" fn jit_push_c_closure(this: *mut LuaBase, val: CFunc, n_upvalues: c_int) {
+	let l = unsafe { cur(this) };
+	unsafe {
+		lua_pushlightuserdata(l, val as *mut c_void);
+		lua_insert(l, -n_upvalues - 1);
+		lua_pushlightuserdata(l, this.cast());
+		lua_insert(l, -n_upvalues - 1);
+		lua_pushcclosure(l, trampoline, n_upvalues + RESERVED_UPVALUES);
+	}
+}
+
+unsafe extern "C-unwind" fn jit_push_userdata(this: *mut LuaBase, val: *mut c_void) {
+	unsafe { lua_pushlightuserdata(cur(this), val) }
+}
+
+unsafe extern "C-unwind" fn jit_reference_create(this: *mut LuaBase) -> RawRef {
+	unsafe { luaL_ref(cur(this), LUA_REGISTRYINDEX) }
+}
+
+unsafe extern "C-unwind" fn jit_reference_free(this: *mut LuaBase, i: RawRef) {
+	unsafe { luaL_unref(cur(this), LUA_REGISTRYINDEX, i) }
+}
+
+unsafe extern "C-unwind" fn jit_reference_push(this: *mut LuaBase, i: RawRef) {
+	unsafe { lua_rawgeti(cur(this), LUA_REGISTRYINDEX, i) }
+}
+
+unsafe extern "C-unwind" fn jit_push_special(this: *mut LuaBase, special: c_int) {
+	let idx = match special {
+		s if s == Special::Registry as c_int => LUA_REGISTRYINDEX,
+		s if s == Special::Env as c_int => LUA_ENVIRONINDEX,
+		_ => LUA_GLOBALSINDEX,
+	};
+	unsafe { lua_pushvalue(cur(this), idx) }
+}
+
+unsafe extern "C-unwind" fn jit_is_type(this: *mut LuaBase, stack_pos: StackPos, ty: RawType) -> bool {
+	unsafe { jit(this) }.raw_type(idx(stack_pos)) == ty
+}
+
+unsafe extern "C-unwind" fn jit_get_type(this: *mut LuaBase, stack_pos: StackPos) -> RawType {
+	unsafe { jit(this) }.raw_type(idx(stack_pos))
+}
+
+unsafe extern "C-unwind" fn jit_get_type_name(this: *mut LuaBase, ty: RawType) -> *const c_char {
+	unsafe { jit(this) }.type_name(ty)
+}
+
+unsafe extern "C-unwind" fn jit_create_meta_table_type(this: *mut LuaBase, name: *const c_char, ty: RawType) {
+	unsafe { jit(this) }.create_metatable(unsafe { CStr::from_ptr(name) }, Some(ty));
+}
+
+unsafe extern "C-unwind" fn jit_check_string(this: *mut LuaBase, stack_pos: StackPos) -> *const c_char {
+	unsafe { luaL_checklstring(cur(this), stack_pos, null_mut()) }
+}
+
+unsafe extern "C-unwind" fn jit_check_number(this: *mut LuaBase, stack_pos: StackPos) -> Number {
+	unsafe { luaL_checknumber(cur(this), stack_pos) }
+}
+
+unsafe extern "C-unwind" fn jit_obj_len(this: *mut LuaBase, stack_pos: StackPos) -> c_int {
+	unsafe { lua_objlen(cur(this), idx(stack_pos)) as _ }
+}
+
+unsafe extern "C-unwind" fn jit_get_angle(this: *mut LuaBase, stack_pos: StackPos) -> NonNull<QAngle> {
+	let jit = unsafe { jit(this) };
+	match jit.boxed_data(idx(stack_pos), StdType::Angle) {
+		Some(data) => unsafe { NonNull::new_unchecked(data.cast()) },
+		None => NonNull::from(&jit.zero_angle),
+	}
+}
+
+unsafe extern "C-unwind" fn jit_get_vector(this: *mut LuaBase, stack_pos: StackPos) -> NonNull<Vector> {
+	let jit = unsafe { jit(this) };
+	match jit.boxed_data(idx(stack_pos), StdType::Vector) {
+		Some(data) => unsafe { NonNull::new_unchecked(data.cast()) },
+		None => NonNull::from(&jit.zero_vector),
+	}
+}
+
+unsafe extern "C-unwind" fn jit_push_angle(this: *mut LuaBase, val: *const QAngle) {
+	unsafe { jit(this) }.push_boxed(val, StdType::Angle)
+}
+
+unsafe extern "C-unwind" fn jit_push_vector(this: *mut LuaBase, val: *const Vector) {
+	unsafe { jit(this) }.push_boxed(val, StdType::Vector)
+}
+
+unsafe extern "C-unwind" fn jit_set_state(this: *mut LuaBase, l: *mut LuaState) {
+	// Only the `LuaState` of this structure can be passed here, which doesn't refer to a different thread.
+	let _ = (this, l);
+}
+
+unsafe extern "C-unwind" fn jit_create_meta_table(this: *mut LuaBase, name: *const c_char) -> c_int {
+	unsafe { jit(this) }.create_metatable(unsafe { CStr::from_ptr(name) }, None)
+}
+
+unsafe extern "C-unwind" fn jit_push_meta_table(this: *mut LuaBase, ty: RawType) -> bool {
+	unsafe { jit(this) }.push_type_metatable(ty)
+}
+
+unsafe extern "C-unwind" fn jit_push_user_type(this: *mut LuaBase, data: *mut c_void, ty: RawType) {
+	let jit = unsafe { jit(this) };
+	let l = jit.current.get();
+	unsafe {
+		lua_newuserdata(l, size_of::<UserData>()).cast::<UserData>().write(UserData {
+			data,
+			ty: ty as u8,
+		});
+	}
+	if jit.push_type_metatable(ty) {
+		unsafe { lua_setmetatable(l, -2) };
+	}
+}
+
+unsafe extern "C-unwind" fn jit_set_user_type(this: *mut LuaBase, stack_pos: StackPos, data: *mut c_void) {
+	let ud = unsafe { lua_touserdata(cur(this), idx(stack_pos)) }.cast::<UserData>();
+	if !ud.is_null() {
+		unsafe { (*ud).data = data }
+	}
+}
+
+static VTABLE: LuaBaseVt = LuaBaseVt {
+	top: jit_top,
+	push: jit_push,
+	pop: jit_pop,
+	get_table: jit_get_table,
+	get_field: jit_get_field,
+	set_field: jit_set_field,
+	create_table: jit_create_table,
+	set_table: jit_set_table,
+	set_meta_table: jit_set_meta_table,
+	get_meta_table: jit_get_meta_table,
+	call: jit_call,
+	pcall: jit_pcall,
+	equal: jit_equal,
+	raw_equal: jit_raw_equal,
+	insert: jit_insert,
+	remove: jit_remove,
+	next: jit_next,
+	new_userdata: jit_new_userdata,
+	throw_error: jit_throw_error,
+	check_type: jit_check_type,
+	arg_error: jit_arg_error,
+	raw_get: jit_raw_get,
+	raw_set: jit_raw_set,
+	get_string: jit_get_string,
+	get_number: jit_get_number,
+	get_bool: jit_get_bool,
+	get_c_function: jit_get_c_function,
+	get_userdata: jit_get_userdata,
+	push_nil: jit_push_nil,
+	push_string: jit_push_string,
+	push_number: jit_push_number,
+	push_bool: jit_push_bool,
+	push_c_function: jit_push_c_function,
+	push_c_closure: jit_push_c_closure,
+	push_userdata: jit_push_userdata,
+	reference_create: jit_reference_create,
+	reference_free: jit_reference_free,
+	reference_push: jit_reference_push,
+	push_special: jit_push_special,
+	is_type: jit_is_type,
+	get_type: jit_get_type,
+	get_type_name: jit_get_type_name,
+	create_meta_table_type: jit_create_meta_table_type,
+	check_string: jit_check_string,
+	check_number: jit_check_number,
+	obj_len: jit_obj_len,
+	get_angle: jit_get_angle,
+	get_vector: jit_get_vector,
+	push_angle: jit_push_angle,
+	push_vector: jit_push_vector,
+	set_state: jit_set_state,
+	create_meta_table: jit_create_meta_table,
+	push_meta_table: jit_push_meta_table,
+	push_user_type: jit_push_user_type,
+	set_user_type: jit_set_user_type,
+};
+
+#[cfg(test)]
+mod tests {
+	use alloc::{
+		boxed::Box,
+		string::String,
+	};
+	use core::{
+		ffi::{
+			CStr, c_int,
+		},
+		mem::MaybeUninit,
+		sync::atomic::{
+			AtomicUsize, Ordering,
+		},
+	};
+
+	use crate::{
+		gmod13::{
+			func::{
+				Func, Ctx, Rets,
+			},
+			user_types::{
+				MethodFuncCtx, SelfCtx, UserType, UserTypeBase, UserTypeBoxed,
+			},
+			Lua, LuaState, Module, StateWriter, StdType,
+		},
+		gmod13_module, gmod13_type,
+	};
+
+	use super::{
+		luaL_newstate, lua_close, LuaJit,
+	};
+
+	/// Calls `f` in a protected call, and returns the error message if it raised an error.
+	fn protected(jit: &mut LuaJit, f: Func) -> Result<(), String> {
+		let lua = jit.lua();
+		lua.push_function(f);
+		let result = lua.pcall(0, 0, 0);
+		result.map_err(move |_| {
+			let message = String::from_utf8_lossy(lua.get_string(-1).unwrap_or_default()).into_owned();
+			lua.pop(1);
+			message
+		})
+	}
+
+	/// Runs `code`, panicking with the error message if it raised an error.
+	fn exec(jit: &mut LuaJit, code: &CStr) {
+		if jit.exec(code).is_err() {
+			let lua = jit.lua();
+			panic!("{}", String::from_utf8_lossy(lua.get_string(-1).unwrap_or_default()))
+		}
+	}
+
+	/// Pops the value on top of the stack into the global variable `name`.
+	fn set_global(lua: &mut Lua, name: &CStr) {
+		lua.push_globals();
+		lua.insert(-2);
+		lua.set_field(-2, name);
+		lua.pop(1);
+	}
+
+	struct Small {
+		_value: u32,
+	}
+	gmod13_type!(Small);
+	impl UserType for Small {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	struct Large {
+		_value: u64,
+	}
+	gmod13_type!(Large);
+	impl UserType for Large {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	struct SharedName;
+	unsafe impl UserTypeBase for SharedName {
+		const ID: &'static CStr = c"Shared";
+		const EXPECTED_ERR: &'static CStr = c"Shared expected";
+	}
+	impl UserType for SharedName {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	#[test]
+	fn register_collisions() {
+		extern "C-unwind" fn register_small(cx: Ctx<'_>) -> Rets {
+			cx.lua().register_named::<Small>(c"Shared");
+			Rets::new(1)
+		}
+		extern "C-unwind" fn register_large(cx: Ctx<'_>) -> Rets {
+			cx.lua().register_named::<Large>(c"Shared");
+			Rets::new(1)
+		}
+		extern "C-unwind" fn register_shared(cx: Ctx<'_>) -> Rets {
+			cx.lua().register::<SharedName>();
+			Rets::new(1)
+		}
+
+		let mut jit = LuaJit::new();
+		assert_eq!(protected(&mut jit, register_small), Ok(()));
+		assert_eq!(protected(&mut jit, register_small), Ok(()));
+		assert!(protected(&mut jit, register_large).unwrap_err().contains("different layout"));
+		assert!(protected(&mut jit, register_shared).unwrap_err().contains("different registry key"));
+		assert_eq!(jit.lua().top(), 0);
+	}
+
+	static TRACKED_COLLECTS: AtomicUsize = AtomicUsize::new(0);
+	static TRACKED_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+	struct Tracked;
+	gmod13_type!(Tracked);
+	impl UserType for Tracked {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+		unsafe fn collect(&mut self, _: SelfCtx<'_, Self>) {
+			TRACKED_COLLECTS.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+	impl Drop for Tracked {
+		fn drop(&mut self) {
+			TRACKED_DROPS.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	#[test]
+	fn gc_collects_values_and_refs_keep_them_alive() {
+		let mut jit = LuaJit::new();
+		let lua = jit.lua();
+		lua.push_auto_user_type(Tracked);
+		lua.push_auto_user_type(Tracked);
+		let kept = lua.create_ref();
+		lua.pop(2);
+
+		exec(&mut jit, c"collectgarbage()");
+		assert_eq!(TRACKED_COLLECTS.load(Ordering::Relaxed), 1);
+		assert_eq!(TRACKED_DROPS.load(Ordering::Relaxed), 1);
+
+		let lua = jit.lua();
+		lua.push_ref(kept);
+		assert!(lua.is_type(-1, lua.user_type_of::<Tracked>()));
+		lua.pop(1);
+		lua.free_ref(kept);
+
+		exec(&mut jit, c"collectgarbage()");
+		assert_eq!(TRACKED_COLLECTS.load(Ordering::Relaxed), 2);
+		assert_eq!(TRACKED_DROPS.load(Ordering::Relaxed), 2);
+	}
+
+	struct Resource {
+		value: u32,
+		drops: &'static AtomicUsize,
+	}
+	gmod13_type!(Resource);
+	impl UserType for Resource {
+		fn init_metatable(mut cx: SelfCtx<'_, Self>) {
+			cx.push_value(-1);
+			cx.set_field(-2, c"__index");
+			cx.push_method(resource_take);
+			cx.set_field(-2, c"Take");
+			cx.push_method(resource_destroy);
+			cx.set_field(-2, c"Destroy");
+			cx.push_method(resource_get);
+			cx.set_field(-2, c"Get");
+			cx.push_method(resource_hold);
+			cx.set_field(-2, c"Hold");
+		}
+	}
+	impl Drop for Resource {
+		fn drop(&mut self) {
+			self.drops.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	extern "C-unwind" fn resource_take(cx: MethodFuncCtx<'_, Resource>) -> Rets {
+		let mut lua = cx.lua();
+		let value = lua.take_self();
+		lua.push_number(value.value as _);
+		Rets::new(1)
+	}
+
+	extern "C-unwind" fn resource_destroy(cx: MethodFuncCtx<'_, Resource>) -> Rets {
+		cx.lua().destroy_self();
+		Rets::ZERO
+	}
+
+	extern "C-unwind" fn resource_get(cx: MethodFuncCtx<'_, Resource>) -> Rets {
+		let lua = cx.lua();
+		let value = lua.borrow().value;
+		lua.push_number(value as _);
+		Rets::new(1)
+	}
+
+	/// `resource:Hold(f)`, which calls `f` while `resource` is mutably borrowed.
+	extern "C-unwind" fn resource_hold(cx: MethodFuncCtx<'_, Resource>) -> Rets {
+		let mut lua = cx.lua();
+		let ty = lua.self_ty();
+		let _guard = unsafe { lua.check_ud_borrow_mut::<Resource>(ty, 1) };
+		lua.push_value(2);
+		lua.call(0, 0);
+		Rets::ZERO
+	}
+
+	#[test]
+	fn take_self_and_destroy() {
+		static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+		let mut jit = LuaJit::new();
+		let lua = jit.lua();
+		lua.push_auto_user_type(Resource { value: 1, drops: &DROPS });
+		set_global(lua, c"taken");
+		lua.push_auto_user_type(Resource { value: 2, drops: &DROPS });
+		set_global(lua, c"destroyed");
+
+		exec(&mut jit, cr#"
+			assert(taken:Take() == 1)
+			local ok, err = pcall(taken.Get, taken)
+			assert(not ok and err:find("already destroyed"), err)
+			ok, err = pcall(taken.Take, taken)
+			assert(not ok and err:find("already destroyed"), err)
+
+			assert(destroyed:Get() == 2)
+			destroyed:Destroy()
+			ok, err = pcall(destroyed.Destroy, destroyed)
+			assert(not ok and err:find("already destroyed"), err)
+
+			taken, destroyed = nil, nil
+			collectgarbage()
+		"#);
+		assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+	}
+
+	#[test]
+	fn borrow_conflicts() {
+		static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+		let mut jit = LuaJit::new();
+		let lua = jit.lua();
+		lua.push_auto_user_type(Resource { value: 3, drops: &DROPS });
+		set_global(lua, c"held");
+
+		exec(&mut jit, cr#"
+			local ok, err = pcall(held.Hold, held, function() return held:Get() end)
+			assert(not ok and err:find("already mutably borrowed"), err)
+			local ok, err = pcall(held.Hold, held, function() return held:Take() end)
+			assert(not ok and err:find("already borrowed"), err)
+		"#);
+	}
+
+	static UNIT_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+	struct Unit;
+	gmod13_type!(Unit);
+	impl UserType for Unit {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+	impl Drop for Unit {
+		fn drop(&mut self) {
+			UNIT_DROPS.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	static BIG_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+	#[repr(align(64))]
+	struct Big {
+		bytes: [u8; 4096],
+	}
+	gmod13_type!(Big);
+	impl UserType for Big {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+	impl UserTypeBoxed for Big {}
+	impl Drop for Big {
+		fn drop(&mut self) {
+			BIG_DROPS.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	static ALIGNED_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+	/// Type which doesn't fit in userdata, so it's boxed even though it isn't a [`UserTypeBoxed`].
+	#[repr(align(128))]
+	struct Aligned(u8);
+	gmod13_type!(Aligned);
+	impl UserType for Aligned {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+	impl Drop for Aligned {
+		fn drop(&mut self) {
+			ALIGNED_DROPS.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	#[test]
+	fn zero_sized_and_boxed_types() {
+		let mut jit = LuaJit::new();
+		let lua = jit.lua();
+		lua.push_auto_user_type(Unit);
+		lua.push_auto_user_type(Unit);
+		let ty = lua.user_type_of::<Unit>();
+		assert!(unsafe { lua.test_ud_ptr::<Unit>(ty, -1) }.is_some());
+		assert!(!lua.raw_equal(-1, -2));
+		lua.pop(2);
+
+		let ty = lua.register_boxed::<Big>();
+		lua.pop(1);
+		let mut big = Box::new(Big { bytes: [0; 4096] });
+		big.bytes[4095] = 7;
+		unsafe { lua.push_user_type_boxed(ty, big) };
+		let ptr = unsafe { lua.test_ud_ptr::<Big>(ty, -1) }.unwrap();
+		assert!(ptr.as_ptr().is_aligned());
+		assert_eq!(unsafe { ptr.as_ref() }.bytes[4095], 7);
+		assert!(unsafe { lua.test_ud_header(ty, -1).unwrap().as_ref() }.boxed);
+		lua.pop(1);
+
+		let value = lua.push_auto_user_type(Aligned(3)).unwrap();
+		assert!((value as *mut Aligned).is_aligned());
+		assert_eq!(value.0, 3);
+		let ty = lua.user_type_of::<Aligned>();
+		assert!(unsafe { lua.test_ud_header(ty, -1).unwrap().as_ref() }.boxed);
+		lua.pop(1);
+
+		exec(&mut jit, c"collectgarbage()");
+		assert_eq!(UNIT_DROPS.load(Ordering::Relaxed), 2);
+		assert_eq!(BIG_DROPS.load(Ordering::Relaxed), 1);
+		assert_eq!(ALIGNED_DROPS.load(Ordering::Relaxed), 1);
+	}
+
+	#[test]
+	fn pcall_errors() {
+		extern "C-unwind" fn fail(cx: Ctx<'_>) -> Rets {
+			cx.lua().throw_error(c"native failure")
+		}
+		extern "C-unwind" fn check_arg(cx: Ctx<'_>) -> Rets {
+			let lua = cx.lua();
+			lua.check_type(1, StdType::Table);
+			Rets::ZERO
+		}
+
+		extern "C-unwind" fn check_long_name(cx: Ctx<'_>) -> Rets {
+			let lua = cx.lua();
+			lua.type_error(1, LONG_NAME)
+		}
+		// Longer than the 256-byte buffer that other error messages are formatted into.
+		const LONG_NAME: &CStr = c"gmbm::tests::LongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongLongName";
+
+		let mut jit = LuaJit::new();
+		assert!(protected(&mut jit, fail).unwrap_err().contains("native failure"));
+		let message = protected(&mut jit, check_long_name).unwrap_err();
+		assert!(message.contains(LONG_NAME.to_str().unwrap()), "{message}");
+		assert!(message.ends_with("expected, got no value)"), "{message}");
+		assert!(protected(&mut jit, check_arg).unwrap_err().contains("bad argument #1"));
+
+		let lua = jit.lua();
+		lua.push_number(1.0);
+		assert!(jit.exec(c"error('script failure')").is_err());
+		let lua = jit.lua();
+		assert!(lua.get_string(-1).is_some_and(|s| s.ends_with(b"script failure")));
+		lua.pop(1);
+		assert_eq!(lua.top(), 1);
+
+		jit.set_global_function(c"fail", fail);
+		exec(&mut jit, c"assert(not pcall(fail))");
+	}
+
+	static OPENED_MODULES: AtomicUsize = AtomicUsize::new(0);
+	static CLOSED_MODULES: AtomicUsize = AtomicUsize::new(0);
+
+	/// Module which records when it's opened and closed, or raises an error when it's opened if `fail` is `true`.
+	struct Recorder {
+		fail: bool,
+	}
+
+	impl Module for Recorder {
+		fn open(&mut self, lua: &mut Lua) {
+			if self.fail {
+				lua.throw_error(c"failed to open")
+			}
+			OPENED_MODULES.fetch_add(1, Ordering::Relaxed);
+		}
+
+		fn close(&mut self, _: &mut Lua) {
+			CLOSED_MODULES.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	gmod13_module!([
+		Recorder = Recorder { fail: false },
+		Recorder = Recorder { fail: true },
+		Recorder = Recorder { fail: false },
+	]);
+
+	unsafe extern "C-unwind" {
+		fn gmod13_open(state: *mut LuaState) -> c_int;
+		fn gmod13_close(state: *mut LuaState) -> c_int;
+	}
+
+	#[test]
+	fn combined_modules_close_opened_ones() {
+		let mut jit = LuaJit::new();
+		let lua = jit.lua();
+		lua.push_c_function(gmod13_open);
+		assert!(lua.pcall(0, 0, 0).is_err());
+		lua.pop(1);
+		assert_eq!(OPENED_MODULES.load(Ordering::Relaxed), 1);
+
+		lua.push_c_function(gmod13_close);
+		assert!(lua.pcall(0, 0, 0).is_ok());
+		assert_eq!(CLOSED_MODULES.load(Ordering::Relaxed), 1);
+	}
+
+	/// Module which saves its counter under `key` when it's reloaded.
+	struct Counter {
+		key: Option<&'static str>,
+		count: u8,
+	}
+
+	impl Module for Counter {
+		fn open(&mut self, _: &mut Lua) {}
+
+		fn state_key(&self) -> Option<&str> {
+			self.key
+		}
+
+		fn save_state(&mut self, state: &mut StateWriter<'_>) {
+			state.write(&[self.count]);
+		}
+
+		fn restore_state(&mut self, _: &Lua, state: &[u8]) {
+			self.count = state[0];
+		}
+	}
+
+	#[test]
+	fn module_state_survives_reload() {
+		let mut jit = LuaJit::new();
+		let lua = jit.lua();
+		lua.save_module_state(&mut Counter { key: Some("gmbm::tests::counter"), count: 7 });
+		lua.save_module_state(&mut Counter { key: None, count: 9 });
+		assert_eq!(lua.top(), 0);
+
+		let mut unkeyed = Counter { key: None, count: 0 };
+		lua.restore_module_state(&mut unkeyed);
+		assert_eq!(unkeyed.count, 0);
+
+		let mut keyed = Counter { key: Some("gmbm::tests::counter"), count: 0 };
+		lua.restore_module_state(&mut keyed);
+		assert_eq!(keyed.count, 7);
+		// The saved state is removed once it's restored.
+		keyed.count = 0;
+		lua.restore_module_state(&mut keyed);
+		assert_eq!(keyed.count, 0);
+		assert_eq!(lua.top(), 0);
+	}
+
+	#[test]
+	fn thread_header_of_real_states() {
+		let l = unsafe { luaL_newstate() };
+		assert!(!l.is_null());
+		assert!(unsafe { LuaState::has_thread_header(l.cast()) });
+		unsafe { lua_close(l) };
+
+		let zeroed = MaybeUninit::<LuaState>::zeroed();
+		assert!(!unsafe { LuaState::has_thread_header(zeroed.as_ptr()) });
+	}
+}
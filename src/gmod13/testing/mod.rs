@@ -0,0 +1,1129 @@
+//! In-memory implementation of the Garry's Mod Lua API,
+//! which allows exercising module code in tests and benchmarks without launching the game.
+//! 
+//! [`MockLua`] implements the virtual function table of [`LuaBase`] in pure Rust,
+//! with a fake stack and tables backed by a simple list of key-value pairs.
+//! Only native functions can be called,
+//! and the global table starts out empty,
+//! so any Lua library functions used by the code under test must be provided with
+//! [`MockLua::set_global_function`].
+//! 
+//! Errors are raised as Rust panics, which are caught by [`Lua::pcall`].
+//! Values are never garbage-collected, so `__gc` metamethods are never called.
+//! With the `luajit` feature, `LuaJit` provides the same interface on top of a real LuaJIT state instead.
+//! 
+//! # Examples
+//! ```
+//! use gmbm::{gmod13::testing::MockLua, prelude::*};
+//! 
+//! let mut mock = MockLua::new();
+//! let lua = mock.lua();
+//! lua.push_globals();
+//! lua.push_string("Hello, tests!");
+//! lua.set_field(-2, c"GREETING");
+//! lua.pop(1);
+//! 
+//! lua.get_global(c"GREETING");
+//! assert_eq!(lua.get_string(-1), Some(&b"Hello, tests!"[..]));
+//! ```
+
+extern crate std;
+
+use alloc::{
+	boxed::Box,
+	ffi::CString,
+	format,
+	rc::Rc,
+	string::String,
+	vec,
+	vec::Vec,
+};
+use core::{
+	cell::RefCell,
+	ffi::{
+		CStr,
+		c_char, c_int, c_uint, c_void,
+	},
+	mem::{
+		MaybeUninit, zeroed,
+	},
+	ptr::{
+		NonNull, null, null_mut,
+	},
+};
+use std::panic::{
+	AssertUnwindSafe, catch_unwind, resume_unwind,
+};
+
+use crate::source::{
+	Vector, QAngle,
+};
+
+use super::{
+	func::{
+		Func, to_c_func,
+	},
+	CFunc, Lua, LuaBase, LuaBaseVt, LuaState,
+	Number, RawRef, RawType, StackPos, StdType,
+};
+
+pub mod fuzz;
+
+#[cfg(feature = "luajit")]
+mod luajit;
+#[cfg(feature = "luajit")]
+pub use luajit::*;
+
+/// Pseudo-index of the registry.
+const REGISTRY_INDEX: StackPos = -10000;
+/// Pseudo-index of the environment table.
+const ENVIRON_INDEX: StackPos = -10001;
+/// Pseudo-index of the global table.
+const GLOBALS_INDEX: StackPos = -10002;
+
+/// Value returned by `pcall` for runtime errors.
+const LUA_ERRRUN: c_int = 2;
+
+/// Value for `n_results` which keeps every returned value.
+const LUA_MULTRET: c_int = -1;
+
+/// First [`RawType`] assigned to metatables created with `CreateMetaTable`.
+const FIRST_CUSTOM_TYPE: RawType = StdType::SurfaceInfo as RawType + 1;
+
+type TableRef = Rc<RefCell<Table>>;
+
+#[derive(Clone)]
+enum Value {
+	Nil,
+	Bool(bool),
+	Number(Number),
+	/// Contents of the string, followed by a nul terminator.
+	String(Rc<[u8]>),
+	Table(TableRef),
+	Function(Rc<Function>),
+	LightUserData(*mut c_void),
+	UserData(Rc<UserData>),
+	Vector(Rc<Vector>),
+	Angle(Rc<QAngle>),
+}
+
+impl Value {
+	fn string(bytes: &[u8]) -> Self {
+		let mut string = Vec::with_capacity(bytes.len() + 1);
+		string.extend_from_slice(bytes);
+		string.push(0);
+		Self::String(string.into())
+	}
+
+	fn new_table() -> Self {
+		Self::Table(TableRef::default())
+	}
+
+	const fn is_nil(&self) -> bool {
+		matches!(self, Self::Nil)
+	}
+
+	const fn is_truthy(&self) -> bool {
+		!matches!(self, Self::Nil | Self::Bool(false))
+	}
+
+	fn raw_type(&self) -> RawType {
+		let ty = match self {
+			Self::Nil => StdType::Nil,
+			Self::Bool(..) => StdType::Bool,
+			Self::Number(..) => StdType::Number,
+			Self::String(..) => StdType::String,
+			Self::Table(..) => StdType::Table,
+			Self::Function(..) => StdType::Function,
+			Self::LightUserData(..) => StdType::LightUserData,
+			Self::UserData(ud) => return ud.raw_type(),
+			Self::Vector(..) => StdType::Vector,
+			Self::Angle(..) => StdType::Angle,
+		};
+		ty.to_raw()
+	}
+
+	fn raw_eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::Nil, Self::Nil) => true,
+			(Self::Bool(a), Self::Bool(b)) => a == b,
+			(Self::Number(a), Self::Number(b)) => a == b,
+			(Self::String(a), Self::String(b)) => a == b,
+			(Self::Table(a), Self::Table(b)) => Rc::ptr_eq(a, b),
+			(Self::Function(a), Self::Function(b)) => Rc::ptr_eq(a, b),
+			(Self::LightUserData(a), Self::LightUserData(b)) => a == b,
+			(Self::UserData(a), Self::UserData(b)) => Rc::ptr_eq(a, b),
+			(Self::Vector(a), Self::Vector(b)) => Rc::ptr_eq(a, b),
+			(Self::Angle(a), Self::Angle(b)) => Rc::ptr_eq(a, b),
+			_ => false,
+		}
+	}
+
+	/// Returns the contents of the string, or the number converted to a string.
+	fn to_bytes(&self) -> Option<Rc<[u8]>> {
+		match self {
+			Self::String(s) => Some(s.clone()),
+			Self::Number(n) => match Self::string(format!("{n}").as_bytes()) {
+				Self::String(s) => Some(s),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+
+	fn to_number(&self) -> Option<Number> {
+		match self {
+			Self::Number(n) => Some(*n),
+			Self::String(s) => core::str::from_utf8(&s[..s.len() - 1]).ok()?.trim().parse().ok(),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Default)]
+struct Table {
+	entries: Vec<(Value, Value)>,
+	metatable: Option<TableRef>,
+}
+
+impl Table {
+	fn position(&self, key: &Value) -> Option<usize> {
+		self.entries.iter().position(move |(k, _)| k.raw_eq(key))
+	}
+
+	fn get(&self, key: &Value) -> Value {
+		self.position(key).map(|i| self.entries[i].1.clone()).unwrap_or(Value::Nil)
+	}
+
+	fn get_field(&self, key: &[u8]) -> Value {
+		self.get(&Value::string(key))
+	}
+
+	fn set(&mut self, key: Value, value: Value) {
+		match (self.position(&key), value.is_nil()) {
+			(Some(i), true) => {
+				self.entries.remove(i);
+			}
+			(Some(i), false) => self.entries[i].1 = value,
+			(None, true) => {}
+			(None, false) => self.entries.push((key, value)),
+		}
+	}
+
+	fn border(&self) -> usize {
+		let mut n = 0;
+		while !self.get(&Value::Number((n + 1) as Number)).is_nil() {
+			n += 1;
+		}
+		n
+	}
+}
+
+struct Function {
+	f: CFunc,
+	upvalues: RefCell<Vec<Value>>,
+}
+
+struct UserData {
+	data: Box<[MaybeUninit<u64>]>,
+	size: usize,
+	metatable: RefCell<Option<TableRef>>,
+}
+
+impl UserData {
+	fn new(size: usize) -> Self {
+		Self {
+			data: vec![MaybeUninit::zeroed(); size.div_ceil(size_of::<u64>())].into_boxed_slice(),
+			size,
+			metatable: RefCell::new(None),
+		}
+	}
+
+	fn as_ptr(&self) -> *mut c_void {
+		self.data.as_ptr() as *mut c_void
+	}
+
+	/// Returns the type stored in the header of the userdata, like Garry's Mod does.
+	fn raw_type(&self) -> RawType {
+		if self.size > size_of::<*mut c_void>() {
+			unsafe { *self.as_ptr().cast::<u8>().add(size_of::<*mut c_void>()) as RawType }
+		} else {
+			StdType::UserData.to_raw()
+		}
+	}
+}
+
+struct Frame {
+	/// Index of the first argument in the stack.
+	base: usize,
+	function: Option<Rc<Function>>,
+}
+
+struct Inner {
+	stack: Vec<Value>,
+	frames: Vec<Frame>,
+	globals: TableRef,
+	registry: TableRef,
+	refs: Vec<Option<Value>>,
+	type_names: Vec<(RawType, CString)>,
+	type_metatables: Vec<(RawType, TableRef)>,
+	next_type: RawType,
+	zero_vector: Vector,
+	zero_angle: QAngle,
+}
+
+impl Inner {
+	fn frame(&self) -> &Frame {
+		self.frames.last().unwrap()
+	}
+
+	fn top(&self) -> usize {
+		self.stack.len() - self.frame().base
+	}
+
+	/// Returns the index of `pos` in the stack, if it refers to a stack slot.
+	fn index(&self, pos: StackPos) -> Option<usize> {
+		let base = self.frame().base;
+		if pos > 0 {
+			let i = base + pos as usize - 1;
+			(i < self.stack.len()).then_some(i)
+		} else if pos < 0 && pos > REGISTRY_INDEX {
+			let i = self.stack.len() as isize + pos as isize;
+			(i >= base as isize).then_some(i as usize)
+		} else {
+			None
+		}
+	}
+
+	fn get_opt(&self, pos: StackPos) -> Option<Value> {
+		match pos {
+			REGISTRY_INDEX => Some(Value::Table(self.registry.clone())),
+			ENVIRON_INDEX | GLOBALS_INDEX => Some(Value::Table(self.globals.clone())),
+			pos if pos < GLOBALS_INDEX => {
+				let n = (GLOBALS_INDEX - 1 - pos) as usize;
+				let function = self.frame().function.as_ref()?;
+				let upvalues = function.upvalues.borrow();
+				upvalues.get(n).cloned()
+			}
+			pos => self.index(pos).map(|i| self.stack[i].clone()),
+		}
+	}
+
+	fn get(&self, pos: StackPos) -> Value {
+		self.get_opt(pos).unwrap_or(Value::Nil)
+	}
+
+	fn set(&mut self, pos: StackPos, value: Value) {
+		if pos < GLOBALS_INDEX {
+			let n = (GLOBALS_INDEX - 1 - pos) as usize;
+			if let Some(function) = self.frame().function.as_ref()
+				&& let Some(upvalue) = function.upvalues.borrow_mut().get_mut(n)
+			{
+				*upvalue = value;
+			}
+		} else if let Some(i) = self.index(pos) {
+			self.stack[i] = value;
+		}
+	}
+
+	fn push(&mut self, value: Value) {
+		self.stack.push(value)
+	}
+
+	fn pop(&mut self) -> Value {
+		if self.top() > 0 {
+			self.stack.pop().unwrap()
+		} else {
+			Value::Nil
+		}
+	}
+
+	fn metatable_of(&self, value: &Value) -> Option<TableRef> {
+		match value {
+			Value::Table(t) => t.borrow().metatable.clone(),
+			Value::UserData(ud) => ud.metatable.borrow().clone(),
+			value => self.type_metatable(value.raw_type()),
+		}
+	}
+
+	fn type_metatable(&self, ty: RawType) -> Option<TableRef> {
+		self.type_metatables.iter().find(move |(t, _)| *t == ty).map(|(_, mt)| mt.clone())
+	}
+
+	fn type_name(&mut self, ty: RawType) -> *const c_char {
+		if let Some((_, name)) = self.type_names.iter().find(move |(t, _)| *t == ty) {
+			return name.as_ptr()
+		}
+
+		let name = match StdType::from_raw(ty) {
+			Some(std) => CString::new(std.name()).unwrap(),
+			None => CString::from(c"UserData"),
+		};
+		self.type_names.push((ty, name));
+		self.type_names.last().unwrap().1.as_ptr()
+	}
+
+	fn type_name_of(&mut self, value: &Value) -> String {
+		let name = self.type_name(value.raw_type());
+		unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()
+	}
+
+	fn create_metatable(&mut self, name: &CStr, ty: Option<RawType>) -> RawType {
+		let existing = self.registry.borrow().get_field(name.to_bytes());
+		if let Value::Table(mt) = existing {
+			let ty = mt.borrow().get_field(b"MetaID").to_number().unwrap_or_default() as RawType;
+			self.push(Value::Table(mt));
+			return ty
+		}
+
+		let ty = ty.unwrap_or_else(|| {
+			let ty = self.next_type;
+			self.next_type += 1;
+			ty
+		});
+
+		let mt = TableRef::default();
+		{
+			let mut t = mt.borrow_mut();
+			t.set(Value::string(b"MetaName"), Value::string(name.to_bytes()));
+			t.set(Value::string(b"MetaID"), Value::Number(ty as Number));
+		}
+		self.registry.borrow_mut().set(Value::string(name.to_bytes()), Value::Table(mt.clone()));
+		self.type_metatables.retain(move |(t, _)| *t != ty);
+		self.type_metatables.push((ty, mt.clone()));
+		self.type_names.retain(move |(t, _)| *t != ty);
+		self.type_names.push((ty, name.into()));
+		self.push(Value::Table(mt));
+		ty
+	}
+}
+
+/// In-memory Lua state which implements the Garry's Mod Lua API for testing.
+/// 
+/// See the [module-level documentation](self) for its limitations.
+#[repr(C)]
+pub struct MockLua {
+	// INVARIANT: This is the first field, so that a pointer to it is also a pointer to `MockLua`.
+	base: LuaBase,
+	state: LuaState,
+	inner: Inner,
+}
+
+impl MockLua {
+	/// Creates a new, empty Lua state.
+	/// 
+	/// The state is boxed, since the [`LuaState`] passed to native functions points into it.
+	pub fn new() -> Box<Self> {
+		let mut mock = Box::new(Self {
+			base: LuaBase {
+				vtable: &VTABLE,
+			},
+			state: LuaState::detached(NonNull::dangling()),
+			inner: Inner {
+				stack: Vec::new(),
+				frames: vec![Frame {
+					base: 0,
+					function: None,
+				}],
+				globals: TableRef::default(),
+				registry: TableRef::default(),
+				refs: Vec::new(),
+				type_names: Vec::new(),
+				type_metatables: Vec::new(),
+				next_type: FIRST_CUSTOM_TYPE,
+				zero_vector: unsafe { zeroed() },
+				zero_angle: unsafe { zeroed() },
+			},
+		});
+		mock.state.luabase = NonNull::from(&mut mock.base);
+		mock
+	}
+
+	/// Returns the [`Lua`] interface to this state.
+	pub fn lua(&mut self) -> &mut Lua {
+		unsafe { Lua::from_state_ptr(self.as_state_ptr()) }
+	}
+
+	/// Returns a pointer to the [`LuaState`] of this state,
+	/// which can be passed to `gmod13_*` entrypoints and native functions.
+	pub fn as_state_ptr(&mut self) -> *mut LuaState {
+		&raw mut self.state
+	}
+
+	/// Sets the global variable `name` to the native function `f`.
+	pub fn set_global_function(&mut self, name: &CStr, f: Func) {
+		self.set_global_c_function(name, to_c_func(f))
+	}
+
+	/// Sets the global variable `name` to the native function `f`.
+	pub fn set_global_c_function(&mut self, name: &CStr, f: CFunc) {
+		let function = Value::Function(Rc::new(Function {
+			f,
+			upvalues: RefCell::new(Vec::new()),
+		}));
+		self.inner.globals.borrow_mut().set(Value::string(name.to_bytes()), function);
+	}
+}
+
+impl core::fmt::Debug for MockLua {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("MockLua").field("top", &self.inner.top()).finish_non_exhaustive()
+	}
+}
+
+/// Returns the mutable state of the [`MockLua`] that `this` points to.
+/// 
+/// # Safety
+/// `this` must point to the `base` field of a live [`MockLua`],
+/// and the returned reference must not be held across calls to native functions.
+unsafe fn inner<'a>(this: *mut LuaBase) -> &'a mut Inner {
+	unsafe { &mut (*this.cast::<MockLua>()).inner }
+}
+
+fn throw(message: &str) -> ! {
+	panic!("{message}")
+}
+
+fn arg_error(n: c_int, message: &str) -> ! {
+	panic!("bad argument #{n} ({message})")
+}
+
+fn type_error(m: &mut Inner, n: c_int, expected: &str) -> ! {
+	let got = match m.get_opt(n) {
+		Some(value) => m.type_name_of(&value),
+		None => String::from("no value"),
+	};
+	arg_error(n, &format!("{expected} expected, got {got}"))
+}
+
+/// Calls the value below the top `n_args` values with them as arguments, like `lua_call`.
+unsafe fn call_value(this: *mut LuaBase, n_args: usize, n_results: c_int) {
+	let (function, func_idx) = {
+		let m = unsafe { inner(this) };
+		if m.top() < n_args + 1 {
+			throw("attempt to call with too few values on the stack")
+		}
+		let func_idx = m.stack.len() - n_args - 1;
+		let function = match m.stack[func_idx].clone() {
+			Value::Function(function) => function,
+			value => {
+				let handler = m.metatable_of(&value).map(|mt| mt.borrow().get_field(b"__call"));
+				if let Some(Value::Function(handler)) = handler {
+					m.stack.insert(func_idx, Value::Function(handler.clone()));
+					handler
+				} else {
+					let name = m.type_name_of(&value);
+					throw(&format!("attempt to call a {name} value"))
+				}
+			}
+		};
+		m.frames.push(Frame {
+			base: func_idx + 1,
+			function: Some(function.clone()),
+		});
+		(function, func_idx)
+	};
+
+	let state = unsafe { &raw mut (*this.cast::<MockLua>()).state };
+	let n = unsafe { (function.f)(state) }.max(0) as usize;
+
+	let m = unsafe { inner(this) };
+	m.frames.pop();
+	let n = n.min(m.stack.len() - func_idx - 1);
+	let mut results = m.stack.split_off(m.stack.len() - n);
+	m.stack.truncate(func_idx);
+	if n_results != LUA_MULTRET {
+		results.resize(n_results.max(0) as usize, Value::Nil);
+	}
+	m.stack.extend(results);
+}
+
+/// Calls `function` with `args`, returning its first result.
+unsafe fn call_metamethod(this: *mut LuaBase, function: Value, args: &[Value]) -> Value {
+	{
+		let m = unsafe { inner(this) };
+		m.push(function);
+		m.stack.extend_from_slice(args);
+	}
+	unsafe { call_value(this, args.len(), 1) };
+	unsafe { inner(this) }.stack.pop().unwrap()
+}
+
+/// Returns `object[key]`, respecting `__index`.
+unsafe fn index(this: *mut LuaBase, mut object: Value, key: Value) -> Value {
+	loop {
+		let m = unsafe { inner(this) };
+		let metatable = if let Value::Table(t) = &object {
+			let value = t.borrow().get(&key);
+			if !value.is_nil() {
+				return value
+			}
+			t.borrow().metatable.clone()
+		} else {
+			m.metatable_of(&object)
+		};
+
+		let handler = metatable.map(|mt| mt.borrow().get_field(b"__index")).unwrap_or(Value::Nil);
+		match handler {
+			Value::Nil if matches!(object, Value::Table(..)) => return Value::Nil,
+			Value::Nil => {
+				let name = m.type_name_of(&object);
+				throw(&format!("attempt to index a {name} value"))
+			}
+			Value::Function(..) => return unsafe { call_metamethod(this, handler, &[object, key]) },
+			handler => object = handler,
+		}
+	}
+}
+
+/// Does `object[key] = value`, respecting `__newindex`.
+unsafe fn new_index(this: *mut LuaBase, mut object: Value, key: Value, value: Value) {
+	loop {
+		let m = unsafe { inner(this) };
+		let metatable = if let Value::Table(t) = &object {
+			if key.is_nil() {
+				throw("table index is nil")
+			}
+			let exists = t.borrow().position(&key).is_some();
+			let metatable = t.borrow().metatable.clone();
+			let handler = metatable.as_ref().map(|mt| mt.borrow().get_field(b"__newindex"));
+			if exists || handler.as_ref().is_none_or(Value::is_nil) {
+				t.borrow_mut().set(key, value);
+				return
+			}
+			metatable
+		} else {
+			m.metatable_of(&object)
+		};
+
+		let handler = metatable.map(|mt| mt.borrow().get_field(b"__newindex")).unwrap_or(Value::Nil);
+		match handler {
+			Value::Nil => {
+				let name = m.type_name_of(&object);
+				throw(&format!("attempt to index a {name} value"))
+			}
+			Value::Function(..) => {
+				unsafe { call_metamethod(this, handler, &[object, key, value]) };
+				return
+			}
+			handler => object = handler,
+		}
+	}
+}
+
+fn pos_to_bytes(m: &mut Inner, pos: StackPos) -> Option<Rc<[u8]>> {
+	let value = m.get(pos);
+	let bytes = value.to_bytes()?;
+	if let Value::Number(..) = value {
+		// Like Lua, convert the number on the stack in place.
+		m.set(pos, Value::String(bytes.clone()));
+	}
+	Some(bytes)
+}
+
+unsafe extern "C-unwind" fn mock_top(this: *mut LuaBase) -> c_int {
+	unsafe { inner(this) }.top() as _
+}
+
+unsafe extern "C-unwind" fn mock_push(this: *mut LuaBase, stack_pos: StackPos) {
+	let m = unsafe { inner(this) };
+	let value = m.get(stack_pos);
+	m.push(value)
+}
+
+unsafe extern "C-unwind" fn mock_pop(this: *mut LuaBase, amt: c_int) {
+	let m = unsafe { inner(this) };
+	let len = m.stack.len() - (amt.max(0) as usize).min(m.top());
+	m.stack.truncate(len)
+}
+
+unsafe extern "C-unwind" fn mock_get_table(this: *mut LuaBase, stack_pos: StackPos) {
+	let (object, key) = {
+		let m = unsafe { inner(this) };
+		let object = m.get(stack_pos);
+		(object, m.pop())
+	};
+	let value = unsafe { index(this, object, key) };
+	unsafe { inner(this) }.push(value)
+}
+
+unsafe extern "C-unwind" fn mock_get_field(this: *mut LuaBase, stack_pos: StackPos, name: *const c_char) {
+	let object = unsafe { inner(this) }.get(stack_pos);
+	let key = Value::string(unsafe { CStr::from_ptr(name) }.to_bytes());
+	let value = unsafe { index(this, object, key) };
+	unsafe { inner(this) }.push(value)
+}
+
+unsafe extern "C-unwind" fn mock_set_field(this: *mut LuaBase, stack_pos: StackPos, name: *const c_char) {
+	let (object, value) = {
+		let m = unsafe { inner(this) };
+		let object = m.get(stack_pos);
+		(object, m.pop())
+	};
+	let key = Value::string(unsafe { CStr::from_ptr(name) }.to_bytes());
+	unsafe { new_index(this, object, key, value) }
+}
+
+unsafe extern "C-unwind" fn mock_create_table(this: *mut LuaBase) {
+	unsafe { inner(this) }.push(Value::new_table())
+}
+
+unsafe extern "C-unwind" fn mock_set_table(this: *mut LuaBase, stack_pos: StackPos) {
+	let (object, key, value) = {
+		let m = unsafe { inner(this) };
+		let object = m.get(stack_pos);
+		let value = m.pop();
+		(object, m.pop(), value)
+	};
+	unsafe { new_index(this, object, key, value) }
+}
+
+unsafe extern "C-unwind" fn mock_set_meta_table(this: *mut LuaBase, stack_pos: StackPos) {
+	let m = unsafe { inner(this) };
+	let object = m.get(stack_pos);
+	let metatable = match m.pop() {
+		Value::Table(mt) => Some(mt),
+		Value::Nil => None,
+		_ => throw("metatable must be a table or nil"),
+	};
+	match object {
+		Value::Table(t) => t.borrow_mut().metatable = metatable,
+		Value::UserData(ud) => *ud.metatable.borrow_mut() = metatable,
+		object => {
+			let ty = object.raw_type();
+			m.type_metatables.retain(move |(t, _)| *t != ty);
+			if let Some(mt) = metatable {
+				m.type_metatables.push((ty, mt));
+			}
+		}
+	}
+}
+
+unsafe extern "C-unwind" fn mock_get_meta_table(this: *mut LuaBase, stack_pos: StackPos) -> bool {
+	let m = unsafe { inner(this) };
+	let object = m.get(stack_pos);
+	if let Some(mt) = m.metatable_of(&object) {
+		m.push(Value::Table(mt));
+		true
+	} else {
+		false
+	}
+}
+
+unsafe extern "C-unwind" fn mock_call(this: *mut LuaBase, n_args: c_int, n_results: c_int) {
+	unsafe { call_value(this, n_args.max(0) as _, n_results) }
+}
+
+unsafe extern "C-unwind" fn mock_pcall(this: *mut LuaBase, n_args: c_int, n_results: c_int, error_func: c_int) -> c_int {
+	let _ = error_func;
+	let (n_frames, func_idx) = {
+		let m = unsafe { inner(this) };
+		(m.frames.len(), m.stack.len().saturating_sub(n_args.max(0) as usize + 1))
+	};
+
+	let result = catch_unwind(AssertUnwindSafe(move || unsafe { call_value(this, n_args.max(0) as _, n_results) }));
+	let Err(payload) = result else {
+		return 0
+	};
+
+	let message = if let Some(message) = payload.downcast_ref::<String>() {
+		message.as_bytes()
+	} else if let Some(message) = payload.downcast_ref::<&str>() {
+		message.as_bytes()
+	} else {
+		resume_unwind(payload)
+	};
+
+	let m = unsafe { inner(this) };
+	m.frames.truncate(n_frames);
+	m.stack.truncate(func_idx);
+	m.push(Value::string(message));
+	LUA_ERRRUN
+}
+
+unsafe extern "C-unwind" fn mock_equal(this: *mut LuaBase, a: StackPos, b: StackPos) -> c_int {
+	let (a, b, handler) = {
+		let m = unsafe { inner(this) };
+		let (a, b) = (m.get(a), m.get(b));
+		if a.raw_eq(&b) {
+			return 1
+		}
+		if !matches!((&a, &b), (Value::Table(..), Value::Table(..)) | (Value::UserData(..), Value::UserData(..))) {
+			return 0
+		}
+
+		let handler_of = |value: &Value| m.metatable_of(value).map(|mt| mt.borrow().get_field(b"__eq"));
+		match (handler_of(&a), handler_of(&b)) {
+			(Some(ha), Some(hb)) if !ha.is_nil() && ha.raw_eq(&hb) => (a, b, ha),
+			_ => return 0,
+		}
+	};
+	unsafe { call_metamethod(this, handler, &[a, b]) }.is_truthy() as _
+}
+
+unsafe extern "C-unwind" fn mock_raw_equal(this: *mut LuaBase, a: StackPos, b: StackPos) -> c_int {
+	let m = unsafe { inner(this) };
+	m.get(a).raw_eq(&m.get(b)) as _
+}
+
+unsafe extern "C-unwind" fn mock_insert(this: *mut LuaBase, stack_pos: StackPos) {
+	let m = unsafe { inner(this) };
+	if let Some(i) = m.index(stack_pos) {
+		let value = m.pop();
+		m.stack.insert(i, value);
+	}
+}
+
+unsafe extern "C-unwind" fn mock_remove(this: *mut LuaBase, stack_pos: StackPos) {
+	let m = unsafe { inner(this) };
+	if let Some(i) = m.index(stack_pos) {
+		m.stack.remove(i);
+	}
+}
+
+unsafe extern "C-unwind" fn mock_next(this: *mut LuaBase, stack_pos: StackPos) -> c_int {
+	let m = unsafe { inner(this) };
+	let Value::Table(t) = m.get(stack_pos) else {
+		type_error(m, stack_pos, "table")
+	};
+	let key = m.pop();
+	let t = t.borrow();
+	let start = if key.is_nil() {
+		0
+	} else {
+		match t.position(&key) {
+			Some(i) => i + 1,
+			None => throw("invalid key to 'next'"),
+		}
+	};
+
+	if let Some((k, v)) = t.entries.get(start) {
+		let (k, v) = (k.clone(), v.clone());
+		m.push(k);
+		m.push(v);
+		1
+	} else {
+		0
+	}
+}
+
+unsafe extern "C-unwind" fn mock_new_userdata(this: *mut LuaBase, size: c_uint) -> *mut c_void {
+	let ud = Rc::new(UserData::new(size as _));
+	let ptr = ud.as_ptr();
+	unsafe { inner(this) }.push(Value::UserData(ud));
+	ptr
+}
+
+unsafe extern "C-unwind" fn mock_throw_error(this: *mut LuaBase, error: *const c_char) -> ! {
+	let _ = this;
+	throw(&unsafe { CStr::from_ptr(error) }.to_string_lossy())
+}
+
+unsafe extern "C-unwind" fn mock_check_type(this: *mut LuaBase, stack_pos: StackPos, ty: RawType) {
+	let m = unsafe { inner(this) };
+	let actual = m.get_opt(stack_pos).map(|value| value.raw_type()).unwrap_or(StdType::None.to_raw());
+	if actual != ty {
+		let expected = unsafe { CStr::from_ptr(m.type_name(ty)) }.to_string_lossy().into_owned();
+		type_error(m, stack_pos, &expected)
+	}
+}
+
+unsafe extern "C-unwind" fn mock_arg_error(this: *mut LuaBase, arg_num: c_int, message: *const c_char) -> ! {
+	let _ = this;
+	arg_error(arg_num, &unsafe { CStr::from_ptr(message) }.to_string_lossy())
+}
+
+unsafe extern "C-unwind" fn mock_raw_get(this: *mut LuaBase, stack_pos: StackPos) {
+	let m = unsafe { inner(this) };
+	let Value::Table(t) = m.get(stack_pos) else {
+		type_error(m, stack_pos, "table")
+	};
+	let key = m.pop();
+	let value = t.borrow().get(&key);
+	m.push(value)
+}
+
+unsafe extern "C-unwind" fn mock_raw_set(this: *mut LuaBase, stack_pos: StackPos) {
+	let m = unsafe { inner(this) };
+	let Value::Table(t) = m.get(stack_pos) else {
+		type_error(m, stack_pos, "table")
+	};
+	let value = m.pop();
+	let key = m.pop();
+	if key.is_nil() {
+		throw("table index is nil")
+	}
+	t.borrow_mut().set(key, value)
+}
+
+unsafe extern "C-unwind" fn mock_get_string(this: *mut LuaBase, stack_pos: StackPos, out_len: *mut c_uint) -> *const c_char {
+	let m = unsafe { inner(this) };
+	let Some(bytes) = pos_to_bytes(m, stack_pos) else {
+		return null()
+	};
+	if !out_len.is_null() {
+		unsafe { out_len.write((bytes.len() - 1) as _) }
+	}
+	// The string is kept alive by the stack slot, which now holds it.
+	bytes.as_ptr().cast()
+}
+
+unsafe extern "C-unwind" fn mock_get_number(this: *mut LuaBase, stack_pos: StackPos) -> Number {
+	unsafe { inner(this) }.get(stack_pos).to_number().unwrap_or_default()
+}
+
+unsafe extern "C-unwind" fn mock_get_bool(this: *mut LuaBase, stack_pos: StackPos) -> bool {
+	unsafe { inner(this) }.get(stack_pos).is_truthy()
+}
+
+unsafe extern "C-unwind" fn mock_get_c_function(this: *mut LuaBase, stack_pos: StackPos) -> Option<CFunc> {
+	match unsafe { inner(this) }.get(stack_pos) {
+		Value::Function(function) => Some(function.f),
+		_ => None,
+	}
+}
+
+unsafe extern "C-unwind" fn mock_get_userdata(this: *mut LuaBase, stack_pos: StackPos) -> *mut c_void {
+	match unsafe { inner(this) }.get(stack_pos) {
+		Value::UserData(ud) => ud.as_ptr(),
+		Value::LightUserData(ptr) => ptr,
+		_ => null_mut(),
+	}
+}
+
+unsafe extern "C-unwind" fn mock_push_nil(this: *mut LuaBase) {
+	unsafe { inner(this) }.push(Value::Nil)
+}
+
+unsafe extern "C-unwind" fn mock_push_string(this: *mut LuaBase, val: *const c_char, len: c_int) {
+	let bytes = if len == 0 {
+		unsafe { CStr::from_ptr(val) }.to_bytes()
+	} else {
+		unsafe { core::slice::from_raw_parts(val.cast(), len as _) }
+	};
+	unsafe { inner(this) }.push(Value::string(bytes))
+}
+
+unsafe extern "C-unwind" fn mock_push_number(this: *mut LuaBase, val: Number) {
+	unsafe { inner(this) }.push(Value::Number(val))
+}
+
+unsafe extern "C-unwind" fn mock_push_bool(this: *mut LuaBase, val: bool) {
+	unsafe { inner(this) }.push(Value::Bool(val))
+}
+
+unsafe extern "C-unwind" fn mock_push_c_function(this: *mut LuaBase, val: CFunc) {
+	unsafe { mock_push_c_closure(this, val, 0) }
+}
+
+unsafe extern "C-unwind" fn mock_push_c_closure(this: *mut LuaBase, val: CFunc, n_upvalues: c_int) {
+	let m = unsafe { inner(this) };
+	let n_upvalues = (n_upvalues.max(0) as usize).min(m.top());
+	let upvalues = m.stack.split_off(m.stack.len() - n_upvalues);
+	m.push(Value::Function(Rc::new(Function {
+		f: val,
+		upvalues: RefCell::new(upvalues),
+	})))
+}
+
+unsafe extern "C-unwind" fn mock_push_userdata(this: *mut LuaBase, val: *mut c_void) {
+	unsafe { inner(this) }.push(Value::LightUserData(val))
+}
+
+unsafe extern "C-unwind" fn mock_reference_create(this: *mut LuaBase) -> RawRef {
+	let m = unsafe { inner(this) };
+	let value = m.pop();
+	if let Some(i) = m.refs.iter().position(Option::is_none) {
+		m.refs[i] = Some(value);
+		i as RawRef + 1
+	} else {
+		m.refs.push(Some(value));
+		m.refs.len() as RawRef
+	}
+}
+
+unsafe extern "C-unwind" fn mock_reference_free(this: *mut LuaBase, i: RawRef) {
+	let m = unsafe { inner(this) };
+	if let Some(slot) = usize::try_from(i - 1).ok().and_then(|i| m.refs.get_mut(i)) {
+		*slot = None;
+	}
+}
+
+unsafe extern "C-unwind" fn mock_reference_push(this: *mut LuaBase, i: RawRef) {
+	let m = unsafe { inner(this) };
+	let value = usize::try_from(i - 1).ok()
+		.and_then(|i| m.refs.get(i).cloned().flatten())
+		.unwrap_or(Value::Nil);
+	m.push(value)
+}
+
+unsafe extern "C-unwind" fn mock_push_special(this: *mut LuaBase, special: c_int) {
+	let m = unsafe { inner(this) };
+	let table = if special == super::Special::Registry as c_int {
+		m.registry.clone()
+	} else {
+		m.globals.clone()
+	};
+	m.push(Value::Table(table))
+}
+
+unsafe extern "C-unwind" fn mock_is_type(this: *mut LuaBase, stack_pos: StackPos, ty: RawType) -> bool {
+	unsafe { mock_get_type(this, stack_pos) == ty }
+}
+
+unsafe extern "C-unwind" fn mock_get_type(this: *mut LuaBase, stack_pos: StackPos) -> RawType {
+	unsafe { inner(this) }.get_opt(stack_pos).map(|value| value.raw_type()).unwrap_or(StdType::None.to_raw())
+}
+
+unsafe extern "C-unwind" fn mock_get_type_name(this: *mut LuaBase, ty: RawType) -> *const c_char {
+	unsafe { inner(this) }.type_name(ty)
+}
+
+unsafe extern "C-unwind" fn mock_create_meta_table_type(this: *mut LuaBase, name: *const c_char, ty: RawType) {
+	unsafe { inner(this) }.create_metatable(unsafe { CStr::from_ptr(name) }, Some(ty));
+}
+
+unsafe extern "C-unwind" fn mock_check_string(this: *mut LuaBase, stack_pos: StackPos) -> *const c_char {
+	let m = unsafe { inner(this) };
+	match pos_to_bytes(m, stack_pos) {
+		Some(bytes) => bytes.as_ptr().cast(),
+		None => type_error(m, stack_pos, "string"),
+	}
+}
+
+unsafe extern "C-unwind" fn mock_check_number(this: *mut LuaBase, stack_pos: StackPos) -> Number {
+	let m = unsafe { inner(this) };
+	match m.get(stack_pos).to_number() {
+		Some(n) => n,
+		None => type_error(m, stack_pos, "number"),
+	}
+}
+
+unsafe extern "C-unwind" fn mock_obj_len(this: *mut LuaBase, stack_pos: StackPos) -> c_int {
+	let len = match unsafe { inner(this) }.get(stack_pos) {
+		Value::String(s) => s.len() - 1,
+		Value::Table(t) => t.borrow().border(),
+		Value::UserData(ud) => ud.size,
+		_ => 0,
+	};
+	len as _
+}
+
+unsafe extern "C-unwind" fn mock_get_angle(this: *mut LuaBase, stack_pos: StackPos) -> NonNull<QAngle> {
+	let m = unsafe { inner(this) };
+	match m.get(stack_pos) {
+		// The angle is kept alive by the stack slot.
+		Value::Angle(angle) => NonNull::from(&*angle),
+		_ => NonNull::from(&m.zero_angle),
+	}
+}
+
+unsafe extern "C-unwind" fn mock_get_vector(this: *mut LuaBase, stack_pos: StackPos) -> NonNull<Vector> {
+	let m = unsafe { inner(this) };
+	match m.get(stack_pos) {
+		// The vector is kept alive by the stack slot.
+		Value::Vector(vector) => NonNull::from(&*vector),
+		_ => NonNull::from(&m.zero_vector),
+	}
+}
+
+unsafe extern "C-unwind" fn mock_push_angle(this: *mut LuaBase, val: *const QAngle) {
+	unsafe { inner(this) }.push(Value::Angle(Rc::new(unsafe { val.read() })))
+}
+
+unsafe extern "C-unwind" fn mock_push_vector(this: *mut LuaBase, val: *const Vector) {
+	unsafe { inner(this) }.push(Value::Vector(Rc::new(unsafe { val.read() })))
+}
+
+unsafe extern "C-unwind" fn mock_set_state(this: *mut LuaBase, l: *mut LuaState) {
+	let _ = (this, l);
+}
+
+unsafe extern "C-unwind" fn mock_create_meta_table(this: *mut LuaBase, name: *const c_char) -> c_int {
+	unsafe { inner(this) }.create_metatable(unsafe { CStr::from_ptr(name) }, None)
+}
+
+unsafe extern "C-unwind" fn mock_push_meta_table(this: *mut LuaBase, ty: RawType) -> bool {
+	let m = unsafe { inner(this) };
+	if let Some(mt) = m.type_metatable(ty) {
+		m.push(Value::Table(mt));
+		true
+	} else {
+		false
+	}
+}
+
+unsafe extern "C-unwind" fn mock_push_user_type(this: *mut LuaBase, data: *mut c_void, ty: RawType) {
+	let ud = UserData::new(size_of::<*mut c_void>() * 2);
+	unsafe {
+		ud.as_ptr().cast::<*mut c_void>().write(data);
+		ud.as_ptr().cast::<u8>().add(size_of::<*mut c_void>()).write(ty as u8);
+	}
+	let m = unsafe { inner(this) };
+	*ud.metatable.borrow_mut() = m.type_metatable(ty);
+	m.push(Value::UserData(Rc::new(ud)))
+}
+
+unsafe extern "C-unwind" fn mock_set_user_type(this: *mut LuaBase, stack_pos: StackPos, data: *mut c_void) {
+	if let Value::UserData(ud) = unsafe { inner(this) }.get(stack_pos) {
+		unsafe { ud.as_ptr().cast::<*mut c_void>().write(data) }
+	}
+}
+
+static VTABLE: LuaBaseVt = LuaBaseVt {
+	top: mock_top,
+	push: mock_push,
+	pop: mock_pop,
+	get_table: mock_get_table,
+	get_field: mock_get_field,
+	set_field: mock_set_field,
+	create_table: mock_create_table,
+	set_table: mock_set_table,
+	set_meta_table: mock_set_meta_table,
+	get_meta_table: mock_get_meta_table,
+	call: mock_call,
+	pcall: mock_pcall,
+	equal: mock_equal,
+	raw_equal: mock_raw_equal,
+	insert: mock_insert,
+	remove: mock_remove,
+	next: mock_next,
+	new_userdata: mock_new_userdata,
+	throw_error: mock_throw_error,
+	check_type: mock_check_type,
+	arg_error: mock_arg_error,
+	raw_get: mock_raw_get,
+	raw_set: mock_raw_set,
+	get_string: mock_get_string,
+	get_number: mock_get_number,
+	get_bool: mock_get_bool,
+	get_c_function: mock_get_c_function,
+	get_userdata: mock_get_userdata,
+	push_nil: mock_push_nil,
+	push_string: mock_push_string,
+	push_number: mock_push_number,
+	push_bool: mock_push_bool,
+	push_c_function: mock_push_c_function,
+	push_c_closure: mock_push_c_closure,
+	push_userdata: mock_push_userdata,
+	reference_create: mock_reference_create,
+	reference_free: mock_reference_free,
+	reference_push: mock_reference_push,
+	push_special: mock_push_special,
+	is_type: mock_is_type,
+	get_type: mock_get_type,
+	get_type_name: mock_get_type_name,
+	create_meta_table_type: mock_create_meta_table_type,
+	check_string: mock_check_string,
+	check_number: mock_check_number,
+	obj_len: mock_obj_len,
+	get_angle: mock_get_angle,
+	get_vector: mock_get_vector,
+	push_angle: mock_push_angle,
+	push_vector: mock_push_vector,
+	set_state: mock_set_state,
+	create_meta_table: mock_create_meta_table,
+	push_meta_table: mock_push_meta_table,
+	push_user_type: mock_push_user_type,
+	set_user_type: mock_set_user_type,
+};
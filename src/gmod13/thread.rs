@@ -0,0 +1,24 @@
+//! Identification of threads, which is available in `no_std` modules.
+
+/// Returns an identifier of the current thread,
+/// which is different from those of all other running threads.
+pub(crate) fn current_thread_id() -> usize {
+	#[cfg(windows)]
+	let id = unsafe { GetCurrentThreadId() as usize };
+	#[cfg(unix)]
+	let id = unsafe { pthread_self() };
+	id
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+	fn GetCurrentThreadId() -> u32;
+}
+
+// `pthread_t` is an integer on Linux and a pointer on macOS, both of which fit in a `usize`.
+#[cfg(unix)]
+#[cfg_attr(not(target_os = "macos"), link(name = "pthread"))]
+unsafe extern "C" {
+	fn pthread_self() -> usize;
+}
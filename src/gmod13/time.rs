@@ -0,0 +1,112 @@
+use core::{
+	ffi::CStr,
+	ops::{
+		Add, Sub,
+	},
+	time::Duration,
+};
+
+use super::{
+	Lua, Number,
+};
+
+/// Point in time measured with `SysTime`, which is monotonic and has high precision.
+/// 
+/// This is similar to `std::time::Instant`, but is available in `no_std` modules.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Instant {
+	secs: Number,
+}
+
+impl Instant {
+	/// Returns the current point in time.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn now(lua: &mut Lua) -> Self {
+		Self {
+			secs: lua.sys_time(),
+		}
+	}
+
+	/// Returns the time elapsed since `earlier`, or zero if `earlier` is later than this.
+	pub fn duration_since(&self, earlier: Self) -> Duration {
+		Duration::try_from_secs_f64(self.secs - earlier.secs).unwrap_or_default()
+	}
+
+	/// Returns the time elapsed since this point in time.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn elapsed(&self, lua: &mut Lua) -> Duration {
+		Self::now(lua).duration_since(*self)
+	}
+
+	/// Returns the number of seconds since an arbitrary point in time, as returned by `SysTime`.
+	pub const fn as_secs_f64(&self) -> Number {
+		self.secs
+	}
+}
+
+impl Add<Duration> for Instant {
+	type Output = Self;
+	fn add(self, rhs: Duration) -> Self::Output {
+		Self {
+			secs: self.secs + rhs.as_secs_f64(),
+		}
+	}
+}
+
+impl Sub<Duration> for Instant {
+	type Output = Self;
+	fn sub(self, rhs: Duration) -> Self::Output {
+		Self {
+			secs: self.secs - rhs.as_secs_f64(),
+		}
+	}
+}
+
+impl Sub for Instant {
+	type Output = Duration;
+	fn sub(self, rhs: Self) -> Self::Output {
+		self.duration_since(rhs)
+	}
+}
+
+/// Functions for reading the clocks of the engine.
+impl Lua {
+	/// Returns the current simulation time in seconds with `CurTime`,
+	/// which is synchronized between the server and clients and is affected by `host_timescale`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn cur_time(&mut self) -> Number {
+		self.call_time_function(c"CurTime")
+	}
+
+	/// Returns a high-precision, monotonic time in seconds with `SysTime`,
+	/// which is suitable for profiling.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn sys_time(&mut self) -> Number {
+		self.call_time_function(c"SysTime")
+	}
+
+	/// Returns the time in seconds since the game was started with `RealTime`,
+	/// which is not affected by pausing or `host_timescale`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn real_time(&mut self) -> Number {
+		self.call_time_function(c"RealTime")
+	}
+
+	fn call_time_function(&mut self, name: &CStr) -> Number {
+		self.get_global(name);
+		self.call(0, 1);
+		let time = self.get_number(-1);
+		self.pop(1);
+		time
+	}
+}
@@ -0,0 +1,161 @@
+//! Typed wrapper for `util.TraceLine`.
+
+use core::ffi::CStr;
+
+use crate::source::Vector;
+
+use super::{
+	Lua, Number, Ref, StackPos, StdType,
+};
+
+/// Parameters of a line trace, which are converted into the trace structure passed to `util.TraceLine`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceRequest {
+	/// Position where the trace starts.
+	pub start: Vector,
+	/// Position where the trace ends.
+	pub end: Vector,
+	/// `MASK_*` enumeration of contents to hit, or `None` for the default `MASK_SOLID`.
+	pub mask: Option<u32>,
+	/// `COLLISION_GROUP_*` enumeration to trace with, or `None` for the default.
+	pub collision_group: Option<i32>,
+	/// Whether the world should be ignored.
+	pub ignore_world: bool,
+	/// Stack position of the entity, table of entities or function to use as the filter.
+	pub filter: Option<StackPos>,
+}
+
+impl TraceRequest {
+	/// Returns a request to trace from `start` to `end` with default options.
+	pub const fn new(start: Vector, end: Vector) -> Self {
+		Self {
+			start, end,
+			mask: None,
+			collision_group: None,
+			ignore_world: false,
+			filter: None,
+		}
+	}
+}
+
+/// Result of a line trace, decoded from the table returned by `util.TraceLine`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceResult {
+	/// Whether the trace hit anything.
+	pub hit: bool,
+	/// Whether the trace hit the world.
+	pub hit_world: bool,
+	/// Whether the trace hit the skybox.
+	pub hit_sky: bool,
+	/// Whether the trace hit anything other than the world.
+	pub hit_non_world: bool,
+	/// Whether the trace started inside of a solid.
+	pub start_solid: bool,
+	/// Whether the trace never left a solid.
+	pub all_solid: bool,
+	/// Fraction of the distance between the start and end positions that was travelled.
+	pub fraction: Number,
+	/// Position where the trace stopped.
+	pub hit_pos: Vector,
+	/// Normal of the surface that was hit.
+	pub hit_normal: Vector,
+	/// Direction of the trace, normalized.
+	pub normal: Vector,
+	/// `HITGROUP_*` enumeration of the part of the entity that was hit.
+	pub hit_group: i32,
+	/// `MAT_*` enumeration of the material that was hit.
+	pub mat_type: i32,
+	/// Reference to the valid entity that was hit, if there is one,
+	/// which must be freed with [`Lua::free_ref`].
+	pub entity: Option<Ref>,
+}
+
+/// Functions for tracing lines through the world.
+impl Lua {
+	/// Performs a line trace with `util.TraceLine`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn trace_line(&mut self, request: &TraceRequest) -> TraceResult {
+		let filter = request.filter.map(|filter| self.abs_index(filter));
+
+		self.get_global_field(c"util", c"TraceLine");
+		self.create_table();
+		self.push_vector(&request.start);
+		self.set_field(-2, c"start");
+		self.push_vector(&request.end);
+		self.set_field(-2, c"endpos");
+		if let Some(mask) = request.mask {
+			self.push_number(mask as _);
+			self.set_field(-2, c"mask");
+		}
+		if let Some(collision_group) = request.collision_group {
+			self.push_number(collision_group as _);
+			self.set_field(-2, c"collisiongroup");
+		}
+		if request.ignore_world {
+			self.push_bool(true);
+			self.set_field(-2, c"ignoreworld");
+		}
+		if let Some(filter) = filter {
+			self.push_value(filter);
+			self.set_field(-2, c"filter");
+		}
+		self.call(1, 1);
+
+		let result = TraceResult {
+			hit: self.get_bool_field(c"Hit"),
+			hit_world: self.get_bool_field(c"HitWorld"),
+			hit_sky: self.get_bool_field(c"HitSky"),
+			hit_non_world: self.get_bool_field(c"HitNonWorld"),
+			start_solid: self.get_bool_field(c"StartSolid"),
+			all_solid: self.get_bool_field(c"AllSolid"),
+			fraction: self.get_number_field(c"Fraction"),
+			hit_pos: self.get_vector_field(c"HitPos"),
+			hit_normal: self.get_vector_field(c"HitNormal"),
+			normal: self.get_vector_field(c"Normal"),
+			hit_group: self.get_number_field(c"HitGroup") as _,
+			mat_type: self.get_number_field(c"MatType") as _,
+			entity: self.get_entity_ref_field(c"Entity"),
+		};
+		self.pop(1);
+		result
+	}
+
+	fn get_bool_field(&mut self, key: &CStr) -> bool {
+		self.get_field(-1, key);
+		let b = self.get_bool(-1);
+		self.pop(1);
+		b
+	}
+
+	fn get_number_field(&mut self, key: &CStr) -> Number {
+		self.get_field(-1, key);
+		let n = self.get_number(-1);
+		self.pop(1);
+		n
+	}
+
+	fn get_vector_field(&mut self, key: &CStr) -> Vector {
+		self.get_field(-1, key);
+		let vector = *self.get_vector(-1);
+		self.pop(1);
+		vector
+	}
+
+	/// Returns a reference to the valid entity in the field `key` of the table at the top of the stack.
+	fn get_entity_ref_field(&mut self, key: &CStr) -> Option<Ref> {
+		self.get_field(-1, key);
+		if self.is_type(-1, StdType::Entity) {
+			self.push_self_method(-1, c"IsValid");
+			self.call(1, 1);
+			let is_valid = self.get_bool(-1);
+			self.pop(1);
+			if is_valid {
+				return Some(self.create_ref())
+			}
+		}
+		self.pop(1);
+		None
+	}
+}
@@ -1,3 +1,5 @@
+use core::fmt;
+
 use super::RawType;
 
 /// Pre-defined type in Garry's Mod Lua.
@@ -54,9 +56,89 @@ pub enum StdType {
 }
 
 impl StdType {
+	/// All pre-defined types, in order of their numeric values.
+	pub const ALL: [Self; 45] = [
+		Self::None, Self::Nil, Self::Bool, Self::LightUserData, Self::Number,
+		Self::String, Self::Table, Self::Function, Self::UserData, Self::Thread,
+		Self::Entity, Self::Vector, Self::Angle, Self::PhysObj, Self::Save,
+		Self::Restore, Self::DamageInfo, Self::EffectData, Self::MoveData, Self::RecipientFilter,
+		Self::UserCmd, Self::ScriptedVehicle, Self::Material, Self::Panel, Self::Particle,
+		Self::ParticleEmitter, Self::Texture, Self::UserMsg, Self::ConVar, Self::IMesh,
+		Self::Matrix, Self::Sound, Self::PixelVisHandle, Self::DLight, Self::Video,
+		Self::File, Self::Locomotion, Self::Path, Self::NavArea, Self::SoundHandle,
+		Self::NavLadder, Self::ParticleSystem, Self::ProjectedTexture, Self::PhysCollide, Self::SurfaceInfo,
+	];
+
 	pub const fn to_raw(self) -> RawType {
 		self as _
 	}
+
+	/// Returns the [`StdType`] with the numeric value `raw`, if there is one.
+	pub const fn from_raw(raw: RawType) -> Option<Self> {
+		let index = raw as isize - Self::None as isize;
+		if index >= 0 && (index as usize) < Self::ALL.len() {
+			Some(Self::ALL[index as usize])
+		} else {
+			None
+		}
+	}
+
+	/// Returns the name of this type, as returned by the Lua `type` function.
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::None => "no value",
+			Self::Nil => "nil",
+			Self::Bool => "boolean",
+			Self::LightUserData => "lightuserdata",
+			Self::Number => "number",
+			Self::String => "string",
+			Self::Table => "table",
+			Self::Function => "function",
+			Self::UserData => "userdata",
+			Self::Thread => "thread",
+			Self::Entity => "Entity",
+			Self::Vector => "Vector",
+			Self::Angle => "Angle",
+			Self::PhysObj => "PhysObj",
+			Self::Save => "ISave",
+			Self::Restore => "IRestore",
+			Self::DamageInfo => "CTakeDamageInfo",
+			Self::EffectData => "CEffectData",
+			Self::MoveData => "CMoveData",
+			Self::RecipientFilter => "CRecipientFilter",
+			Self::UserCmd => "CUserCmd",
+			Self::ScriptedVehicle => "ScriptedVehicle",
+			Self::Material => "IMaterial",
+			Self::Panel => "Panel",
+			Self::Particle => "CLuaParticle",
+			Self::ParticleEmitter => "CLuaEmitter",
+			Self::Texture => "ITexture",
+			Self::UserMsg => "bf_read",
+			Self::ConVar => "ConVar",
+			Self::IMesh => "IMesh",
+			Self::Matrix => "VMatrix",
+			Self::Sound => "CSoundPatch",
+			Self::PixelVisHandle => "pixelvis_handle_t",
+			Self::DLight => "dlight_t",
+			Self::Video => "IVideoWriter",
+			Self::File => "File",
+			Self::Locomotion => "CLuaLocomotion",
+			Self::Path => "PathFollower",
+			Self::NavArea => "CNavArea",
+			Self::SoundHandle => "IGModAudioChannel",
+			Self::NavLadder => "CNavLadder",
+			Self::ParticleSystem => "CNewParticleEffect",
+			Self::ProjectedTexture => "ProjectedTexture",
+			Self::PhysCollide => "PhysCollide",
+			Self::SurfaceInfo => "SurfaceInfo",
+		}
+	}
+}
+
+impl fmt::Display for StdType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
 }
 
 /// Type returned by the Garry's Mod Lua API.
@@ -70,6 +152,12 @@ impl Type {
 		Self(ty.to_raw())
 	}
 
+	/// Returns the [`StdType`] that this type represents,
+	/// or `None` if it is a custom type.
+	pub const fn to_std(self) -> Option<StdType> {
+		StdType::from_raw(self.0)
+	}
+
 	/// Returns `true` if this type is the specified [`StdType`].
 	pub const fn is_std(self, ty: StdType) -> bool {
 		self.0 == ty.to_raw()
@@ -92,3 +180,13 @@ impl PartialEq<Type> for StdType {
 		other.is_std(*self)
 	}
 }
+
+impl fmt::Display for Type {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some(ty) = self.to_std() {
+			f.write_str(ty.name())
+		} else {
+			write!(f, "custom type {}", self.0)
+		}
+	}
+}
@@ -0,0 +1,144 @@
+//! Unicode-aware case folding and normalization of Lua strings,
+//! for modules which process chat messages or other text written by players.
+//! 
+//! Garry's Mod treats strings as bytes,
+//! so text that looks the same may be encoded differently,
+//! and `string.lower` only changes ASCII letters.
+//! The functions in this module operate on strings which are valid UTF-8,
+//! and push their results directly onto the stack with a [`LuaStringBuilder`].
+//! 
+//! Case folding is implemented with the full lowercase mapping of [`char::to_lowercase`],
+//! which matches the Unicode case folding of almost all characters.
+//! 
+//! # Examples
+//! ```no_run
+//! use gmbm::gmod13::Lua;
+//! 
+//! fn open(lua: &mut Lua) {
+//!     // Adds `utf8x.NFC`, `utf8x.FoldCase` and `utf8x.EqualIgnoreCase`.
+//!     lua.push_globals();
+//!     lua.create_table();
+//!     lua.set_unicode_functions(-1);
+//!     lua.set_field(-2, c"utf8x");
+//!     lua.pop(1);
+//! }
+//! ```
+
+use core::fmt::Write;
+
+use unicode_normalization::UnicodeNormalization;
+pub use unicode_normalization::is_nfc;
+
+use super::{
+	func::{
+		Ctx, Rets,
+	},
+	Lua, LuaStringBuilder, StackPos,
+};
+
+/// Returns `true` if `a` and `b` are equal when compared case-insensitively,
+/// and regardless of how their characters are composed.
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+	fold_case(a).eq(fold_case(b))
+}
+
+/// Returns the characters of `s` with their case folded,
+/// in Normalization Form D (NFD).
+fn fold_case(s: &str) -> impl Iterator<Item = char> + '_ {
+	s.nfd().flat_map(char::to_lowercase).nfd()
+}
+
+/// Functions for Unicode-aware text processing.
+impl Lua {
+	/// Pushes `s` in Normalization Form C (NFC) onto the stack as a Lua string.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_nfc(&mut self, s: &str) {
+		if is_nfc(s) {
+			return self.push_string(s)
+		}
+		self.push_chars(s.nfc())
+	}
+
+	/// Pushes `s` with its case folded, in Normalization Form C (NFC), onto the stack as a Lua string,
+	/// so that strings which only differ in case push the same string.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_case_folded(&mut self, s: &str) {
+		self.push_chars(fold_case(s).nfc())
+	}
+
+	/// Sets the fields `NFC`, `FoldCase` and `EqualIgnoreCase` of the table at `stack_pos`
+	/// to [`lua_nfc`], [`lua_fold_case`] and [`lua_eq_ignore_case`].
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_unicode_functions(&mut self, stack_pos: StackPos) {
+		let table = self.abs_index(stack_pos);
+		self.push_function(lua_nfc);
+		self.set_field(table, c"NFC");
+		self.push_function(lua_fold_case);
+		self.set_field(table, c"FoldCase");
+		self.push_function(lua_eq_ignore_case);
+		self.set_field(table, c"EqualIgnoreCase");
+	}
+
+	fn push_chars<I: Iterator<Item = char>>(&mut self, chars: I) {
+		let mut builder = LuaStringBuilder::new(self);
+		for c in chars {
+			let _ = builder.write_char(c);
+		}
+		builder.finish();
+	}
+
+	/// Returns the UTF-8 string at argument `arg_num`,
+	/// which stays valid for as long as the argument is on the stack.
+	fn check_utf8_arg<'a>(&self, arg_num: StackPos) -> &'a str {
+		let s: *const str = self.check_utf8(arg_num);
+		// SAFETY: The string is referenced by the stack, and won't be collected while it's there.
+		unsafe { &*s }
+	}
+}
+
+/// Lua function `(s: string) -> string`,
+/// which returns `s` in Normalization Form C (NFC).
+/// 
+/// Raises an error if `s` isn't valid UTF-8.
+pub extern "C-unwind" fn lua_nfc(cx: Ctx<'_>) -> Rets {
+	let lua = cx.lua();
+	let s = lua.check_utf8_arg(1);
+	lua.push_nfc(s);
+	Rets::new(1)
+}
+
+/// Lua function `(s: string) -> string`,
+/// which returns `s` with its case folded, in Normalization Form C (NFC).
+/// 
+/// Raises an error if `s` isn't valid UTF-8.
+pub extern "C-unwind" fn lua_fold_case(cx: Ctx<'_>) -> Rets {
+	let lua = cx.lua();
+	let s = lua.check_utf8_arg(1);
+	lua.push_case_folded(s);
+	Rets::new(1)
+}
+
+/// Lua function `(a: string, b: string) -> boolean`,
+/// which returns `true` if `a` and `b` are equal when compared case-insensitively,
+/// and regardless of how their characters are composed.
+/// 
+/// Raises an error if either string isn't valid UTF-8.
+pub extern "C-unwind" fn lua_eq_ignore_case(cx: Ctx<'_>) -> Rets {
+	let lua = cx.lua();
+	let a = lua.check_utf8_arg(1);
+	let b = lua.check_utf8_arg(2);
+	lua.push_bool(eq_ignore_case(a, b));
+	Rets::new(1)
+}
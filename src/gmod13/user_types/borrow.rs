@@ -0,0 +1,258 @@
+use core::{
+	fmt,
+	marker::PhantomData,
+	ops::{
+		Deref, DerefMut,
+	},
+	ptr::NonNull,
+};
+
+use super::{
+	super::{
+		Lua, Type, StackPos,
+	},
+	UserType, RawUd, SelfCtx,
+	check_ud_header, test_ud_header_of,
+};
+
+/// Borrow state of a user type value stored in [`RawUd`].
+/// 
+/// Positive values count shared borrows, and [`BORROWED_MUT`] indicates an exclusive borrow.
+pub type BorrowFlag = isize;
+
+/// [`BorrowFlag`] of a value which is not borrowed.
+pub const UNBORROWED: BorrowFlag = 0;
+
+/// [`BorrowFlag`] of a value which is mutably borrowed.
+pub const BORROWED_MUT: BorrowFlag = -1;
+
+/// Shared borrow of a user type value, which is released when dropped.
+/// 
+/// If a Lua error is raised while this guard is alive,
+/// the borrow may never be released.
+pub struct UdRef<'a, T> {
+	header: NonNull<RawUd>,
+	value: NonNull<T>,
+	_life: PhantomData<&'a T>,
+}
+
+impl<T> Deref for UdRef<'_, T> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		unsafe { self.value.as_ref() }
+	}
+}
+
+impl<T> Drop for UdRef<'_, T> {
+	fn drop(&mut self) {
+		unsafe { (*self.header.as_ptr()).borrow -= 1 }
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for UdRef<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		T::fmt(self, f)
+	}
+}
+
+/// Exclusive borrow of a user type value, which is released when dropped.
+/// 
+/// If a Lua error is raised while this guard is alive,
+/// the borrow may never be released.
+pub struct UdRefMut<'a, T> {
+	header: NonNull<RawUd>,
+	value: NonNull<T>,
+	_life: PhantomData<&'a mut T>,
+}
+
+impl<T> Deref for UdRefMut<'_, T> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		unsafe { self.value.as_ref() }
+	}
+}
+impl<T> DerefMut for UdRefMut<'_, T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		unsafe { self.value.as_mut() }
+	}
+}
+
+impl<T> Drop for UdRefMut<'_, T> {
+	fn drop(&mut self) {
+		unsafe { (*self.header.as_ptr()).borrow = UNBORROWED }
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for UdRefMut<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		T::fmt(self, f)
+	}
+}
+
+/// Functions for borrow-checked access to user types.
+impl Lua {
+	/// Returns a shared borrow of the value of type `T` at `arg`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the argument is not `T`, or if it is currently mutably borrowed.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	/// 
+	/// The returned guard must not outlive the userdata at `arg`.
+	pub unsafe fn check_ud_borrow<'a, T: UserType>(&self, ty: Type, arg: StackPos) -> UdRef<'a, T> {
+		let header = unsafe { check_ud_header::<T>(self, ty, arg) };
+		unsafe { borrow_header(self, header) }
+	}
+
+	/// Returns a shared borrow of the value of type `T` at `stack_pos`,
+	/// or [`None`] if it is not `T` or has already been destroyed.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the value is currently mutably borrowed.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	/// 
+	/// The returned guard must not outlive the userdata at `stack_pos`.
+	pub unsafe fn test_ud_borrow<'a, T: UserType>(&self, ty: Type, stack_pos: StackPos) -> Option<UdRef<'a, T>> {
+		let header = unsafe { test_ud_header_of::<T>(self, ty, stack_pos)? };
+		if unsafe { header.as_ref() }.data.is_null() {
+			return None
+		}
+		Some(unsafe { borrow_header(self, header) })
+	}
+
+	/// Returns an exclusive borrow of the value of type `T` at `arg`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the argument is not `T`, or if it is currently borrowed.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	/// 
+	/// The returned guard must not outlive the userdata at `arg`.
+	pub unsafe fn check_ud_borrow_mut<'a, T: UserType>(&self, ty: Type, arg: StackPos) -> UdRefMut<'a, T> {
+		let header = unsafe { check_ud_header::<T>(self, ty, arg) };
+		let ud = unsafe { &mut *header.as_ptr() };
+		if ud.borrow != UNBORROWED {
+			self.throw_error(c"userdata is already borrowed")
+		}
+		ud.borrow = BORROWED_MUT;
+
+		UdRefMut {
+			header,
+			value: unsafe { NonNull::new_unchecked(ud.data.cast()) },
+			_life: PhantomData,
+		}
+	}
+}
+
+/// Adds a shared borrow to the userdata with the given `header`.
+/// 
+/// # Safety
+/// `header` must point to the header of userdata created for `T`, the value of which has not been destroyed yet.
+unsafe fn borrow_header<'a, T>(lua: &Lua, header: NonNull<RawUd>) -> UdRef<'a, T> {
+	let ud = unsafe { &mut *header.as_ptr() };
+	if ud.borrow == BORROWED_MUT {
+		lua.throw_error(c"userdata is already mutably borrowed")
+	}
+	ud.borrow += 1;
+
+	UdRef {
+		header,
+		value: unsafe { NonNull::new_unchecked(ud.data.cast()) },
+		_life: PhantomData,
+	}
+}
+
+impl<T: UserType> SelfCtx<'_, T> {
+	/// Returns a shared borrow of `self` as a `T`.
+	/// 
+	/// The borrow is checked at runtime,
+	/// so it remains sound even if Lua re-enters a method on the same value.
+	/// The guard borrows the context, so the Lua state can't be used mutably while it is alive.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, or if it is currently mutably borrowed.
+	pub fn borrow(&self) -> UdRef<'_, T> {
+		unsafe { self.check_ud_borrow(self.self_ty(), 1) }
+	}
+
+	/// Returns an exclusive borrow of `self` as a `T`.
+	/// 
+	/// The borrow is checked at runtime,
+	/// so it remains sound even if Lua re-enters a method on the same value.
+	/// The guard borrows the context, so the Lua state can't be used mutably while it is alive;
+	/// use [`with_self`](Self::with_self) to update the value and then call into Lua.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, or if it is currently borrowed.
+	pub fn borrow_mut(&self) -> UdRefMut<'_, T> {
+		unsafe { self.check_ud_borrow_mut(self.self_ty(), 1) }
+	}
+
+	/// Calls `f` with an exclusive borrow of `self` as a `T`, and returns its result.
+	/// 
+	/// Unlike with [`borrow_mut`](Self::borrow_mut), the Lua state can't be used while `f` is called,
+	/// so the borrow is always released before Lua gets to run code again,
+	/// such as with [`Lua::call`], which may re-enter a method on the same value.
+	/// The borrow is also released if `f` panics.
+	/// 
+	/// This is the safest way to access `self` in methods which also call into Lua:
+	/// read or update what's needed in `f`, and call into Lua after it returns.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, or if it is currently borrowed.
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// struct Counter {
+	///     count: u32,
+	/// }
+	/// gmod13_type!(Counter);
+	/// impl LuaUserType for Counter {
+	///     fn init_metatable(mut cx: LuaSelfCtx<'_, Self>) {
+	///         cx.push_value(-1);
+	///         cx.set_field(-2, c"__index");
+	///         // `counter:Increment(callback)`
+	///         cx.push_method(gmod13_method!(Counter => mut lua => {
+	///             let count = lua.with_self(|this| {
+	///                 this.count += 1;
+	///                 this.count
+	///             });
+	///             // `self` isn't borrowed anymore, so `callback` may use the counter too.
+	///             lua.push_value(2);
+	///             lua.push_number(count as LuaNumber);
+	///             lua.call(1, 0);
+	///             0
+	///         }));
+	///         cx.set_field(-2, c"Increment");
+	///     }
+	/// }
+	/// ```
+	pub fn with_self<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+		let mut this = self.borrow_mut();
+		f(&mut this)
+	}
+
+	/// Calls `f` with a shared borrow of `self` as a `T`, and returns its result.
+	/// 
+	/// Like with [`with_self`](Self::with_self), the Lua state can't be used while `f` is called,
+	/// so the borrow is always released before Lua gets to run code again.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, or if it is currently mutably borrowed.
+	pub fn with_self_ref<R>(&mut self, f: impl FnOnce(&T) -> R) -> R {
+		let this = self.borrow();
+		f(&this)
+	}
+}
@@ -0,0 +1,115 @@
+use alloc::boxed::Box;
+use core::{
+	mem::MaybeUninit,
+	ptr::NonNull,
+};
+
+use super::{
+	super::{
+		Lua, Type,
+	},
+	UserType, RawUd,
+	TypeKey, register_with_key, init_userdata,
+};
+
+/// Marker trait for [`UserType`]s which are stored in a heap allocation,
+/// with the Lua userdata only holding a pointer to it.
+/// 
+/// Boxed user types are not subject to the size and alignment requirements of
+/// types which are stored directly in Lua userdata,
+/// and are cheap to move into Lua regardless of their size.
+pub trait UserTypeBoxed: UserType {}
+
+/// Functions for handling boxed user types.
+impl Lua {
+	/// Creates the metatable for `T`, initializes it and returns the [`Type`] associated with it.
+	/// 
+	/// Unlike [`register`](Self::register), this always sets `__gc` on the metatable,
+	/// so that the heap allocation of every boxed value is freed.
+	/// 
+	/// # Errors
+	/// See [`register`](Self::register).
+	pub fn register_boxed<T: UserTypeBoxed>(&mut self) -> Type {
+		register_with_key::<T>(self, TypeKey::Static(T::ID), T::LAYOUT_HASH, true)
+	}
+
+	/// Allocates a heap allocation for `T`, initializes it with `init`
+	/// and pushes userdata pointing to it.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`,
+	/// which must have been registered with [`register_boxed`](Self::register_boxed).
+	/// 
+	/// `init` must initialize the given value.
+	pub unsafe fn create_user_type_boxed<'a, T: UserTypeBoxed, F: FnOnce(&mut MaybeUninit<T>)>(
+		&mut self, ty: Type, init: F,
+	) -> Option<&'a mut T> {
+		let mut value = Box::<T>::new_uninit();
+		init(&mut value);
+		unsafe { self.push_user_type_boxed(ty, value.assume_init()) }
+	}
+
+	/// Like [`create_user_type_boxed`](Self::create_user_type_boxed), but `init` may fail,
+	/// in which case the heap allocation is freed, the error is returned and nothing is pushed.
+	/// 
+	/// Returns `Ok(None)` if the userdata couldn't be allocated.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`,
+	/// which must have been registered with [`register_boxed`](Self::register_boxed).
+	/// 
+	/// `init` must initialize the given value if it returns `Ok`.
+	pub unsafe fn try_create_user_type_boxed<'a, T: UserTypeBoxed, E, F: FnOnce(&mut MaybeUninit<T>) -> Result<(), E>>(
+		&mut self, ty: Type, init: F,
+	) -> Result<Option<&'a mut T>, E> {
+		let mut value = Box::<T>::new_uninit();
+		init(&mut value)?;
+		Ok(unsafe { self.push_user_type_boxed(ty, value.assume_init()) })
+	}
+
+	/// Pushes userdata pointing to the heap allocation of `value`.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`,
+	/// which must have been registered with [`register_boxed`](Self::register_boxed).
+	pub unsafe fn push_user_type_boxed<'a, T: UserTypeBoxed>(&mut self, ty: Type, value: Box<T>) -> Option<&'a mut T> {
+		unsafe { push_boxed(self, ty, value) }
+	}
+}
+
+/// Pushes userdata pointing to the heap allocation of `value`,
+/// with the [`boxed`](RawUd::boxed) flag set in its header.
+/// 
+/// # Safety
+/// `ty` must be the correct type identifier for `T`,
+/// and its metatable must have a `__gc` metamethod that frees the heap allocation.
+pub(super) unsafe fn push_boxed<'a, T: UserType>(lua: &mut Lua, ty: Type, value: Box<T>) -> Option<&'a mut T> {
+	let ud: *mut RawUd = unsafe {
+		lua.new_userdata_raw(size_of::<RawUd>() as _).cast()
+	};
+	if ud.is_null() {
+		return None
+	}
+
+	let value_ptr = Box::into_raw(value);
+	unsafe {
+		init_userdata(lua, ud, value_ptr, ty, true);
+		Some(&mut *value_ptr)
+	}
+}
+
+/// Drops and deallocates the boxed value at `ptr`.
+/// 
+/// # Safety
+/// `ptr` must have been created by [`Box::into_raw`], and must not be used afterwards.
+pub(super) unsafe fn drop_boxed<T>(ptr: NonNull<T>) {
+	unsafe { drop(Box::from_raw(ptr.as_ptr())) }
+}
+
+/// Moves the boxed value at `ptr` out of its heap allocation and deallocates it.
+/// 
+/// # Safety
+/// `ptr` must have been created by [`Box::into_raw`], and must not be used afterwards.
+pub(super) unsafe fn take_boxed<T>(ptr: NonNull<T>) -> T {
+	unsafe { *Box::from_raw(ptr.as_ptr()) }
+}
@@ -0,0 +1,376 @@
+use alloc::boxed::Box;
+use core::{
+	fmt::Write,
+	ptr::NonNull,
+	time::Duration,
+};
+
+use crate::gmod13_type;
+
+use super::{
+	super::{
+		chunks::CStrBuf,
+		func::{
+			Ctx, Rets,
+		},
+		Instant, Lua, Number, Ref, StackPos, StdType,
+	},
+	MethodFuncCtx, SelfCtx, UserType,
+};
+
+/// Trait for heavy work which is performed in small steps,
+/// so that it can be spread across multiple ticks with [`Lua::spawn_chunked`]
+/// instead of freezing the game until it's done.
+/// 
+/// # Examples
+/// ```no_run
+/// use core::time::Duration;
+/// use gmbm::gmod13::{
+///     user_types::ChunkedWork,
+///     Lua, Number,
+/// };
+/// 
+/// /// Sums the numbers from `1` to `n`, one number per step.
+/// struct Sum {
+///     i: u64,
+///     n: u64,
+///     sum: u64,
+/// }
+/// 
+/// impl ChunkedWork for Sum {
+///     fn step(&mut self, _: &mut Lua) -> bool {
+///         self.i += 1;
+///         self.sum += self.i;
+///         self.i == self.n
+///     }
+/// 
+///     fn progress(&self) -> Number {
+///         self.i as Number / self.n as Number
+///     }
+/// 
+///     fn push_result(&mut self, lua: &mut Lua) {
+///         lua.push_number(self.sum as Number);
+///     }
+/// }
+/// 
+/// fn push_sum(lua: &mut Lua) {
+///     // Pushes a `LuaChunkedTask`, which Lua code can use like `task:OnComplete(print)`.
+///     lua.spawn_chunked(Sum { i: 0, n: 100_000_000, sum: 0 }, Duration::from_millis(2));
+/// }
+/// ```
+pub trait ChunkedWork: 'static {
+	/// Performs the next step of the work, returning `true` if all of it is done.
+	/// 
+	/// Steps should be small, since the time budget of a tick is only checked between them.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// in which case the work is cancelled.
+	fn step(&mut self, lua: &mut Lua) -> bool;
+
+	/// Returns how much of the work is done, from `0.0` to `1.0`.
+	fn progress(&self) -> Number;
+
+	/// Pushes the result of the work onto the stack.
+	/// 
+	/// This is called once, after [`step`](Self::step) returns `true`.
+	/// By default, `nil` is pushed.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	fn push_result(&mut self, lua: &mut Lua) {
+		lua.push_nil()
+	}
+}
+
+/// Handle to [`ChunkedWork`] which is performed across multiple ticks by a `Think` hook,
+/// giving heavy native computations a progress and completion callback interface in Lua.
+/// 
+/// In Lua, `task:OnProgress(callback)` calls `callback(progress)` after every tick that the work runs in,
+/// and `task:OnComplete(callback)` calls `callback(result)` once the work is done.
+/// Both methods replace the previous callback and return the task itself, so they can be chained.
+/// `task:GetProgress()` returns how much of the work is done, from `0` to `1`,
+/// `task:IsRunning()` returns `true` if the work is neither done nor cancelled,
+/// and `task:Cancel()` stops the work without calling the completion callback.
+/// 
+/// Values of this type are created with [`Lua::spawn_chunked`].
+/// Running tasks are kept alive by the hook, even if Lua code doesn't reference them.
+pub struct LuaChunkedTask {
+	work: Option<Box<dyn ChunkedWork>>,
+	budget: Duration,
+	state: ChunkedState,
+	progress: Number,
+	on_progress: Option<Ref>,
+	on_complete: Option<Ref>,
+}
+
+gmod13_type!(LuaChunkedTask);
+
+/// State of a [`LuaChunkedTask`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkedState {
+	/// The work is still being performed.
+	Running,
+	/// All of the work is done.
+	Done,
+	/// The work has been cancelled.
+	Cancelled,
+}
+
+impl LuaChunkedTask {
+	/// Returns the current state of the task.
+	pub const fn state(&self) -> ChunkedState {
+		self.state
+	}
+
+	/// Returns how much of the work was done as of the last tick, from `0.0` to `1.0`.
+	pub const fn progress(&self) -> Number {
+		self.progress
+	}
+
+	/// Returns the maximum time that the work is performed for in every tick.
+	pub const fn budget(&self) -> Duration {
+		self.budget
+	}
+}
+
+impl UserType for LuaChunkedTask {
+	fn init_metatable(mut cx: SelfCtx<'_, Self>) {
+		cx.push_value(-1);
+		cx.set_field(-2, c"__index");
+
+		cx.push_method(task_on_progress);
+		cx.set_field(-2, c"OnProgress");
+		cx.push_method(task_on_complete);
+		cx.set_field(-2, c"OnComplete");
+		cx.push_method(task_get_progress);
+		cx.set_field(-2, c"GetProgress");
+		cx.push_method(task_is_running);
+		cx.set_field(-2, c"IsRunning");
+		cx.push_method(task_cancel);
+		cx.set_field(-2, c"Cancel");
+	}
+
+	unsafe fn collect(&mut self, cx: SelfCtx<'_, Self>) {
+		for lua_ref in [self.on_progress, self.on_complete].into_iter().flatten() {
+			cx.free_ref(lua_ref);
+		}
+	}
+}
+
+/// Functions for spreading heavy work across multiple ticks.
+impl Lua {
+	/// Pushes a new [`LuaChunkedTask`] onto the stack,
+	/// registering the type first if needed,
+	/// which performs `work` for at most `budget` in every tick until it's done.
+	/// 
+	/// This also adds a `Think` hook which runs all tasks of this binary module,
+	/// and removes itself once none of them are running.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn spawn_chunked<W: ChunkedWork>(&mut self, work: W, budget: Duration) {
+		self.push_auto_user_type(LuaChunkedTask {
+			work: Some(Box::new(work)),
+			budget,
+			state: ChunkedState::Running,
+			progress: 0.0,
+			on_progress: None,
+			on_complete: None,
+		});
+		let task = self.abs_index(-1);
+
+		let name = hook_name();
+		self.push_registry();
+		self.get_field(-1, name.as_c_str());
+		if !self.is_type(-1, StdType::Table) {
+			self.pop(1);
+			self.create_table();
+			self.push_value(-1);
+			self.set_field(-3, name.as_c_str());
+
+			self.get_global_field(c"hook", c"Add");
+			self.push_c_string(c"Think");
+			self.push_c_string(name.as_c_str());
+			self.push_function(run_chunked_hook);
+			self.call(3, 0);
+		}
+
+		self.push_value(task);
+		self.push_bool(true);
+		self.raw_set(-3);
+		self.pop(2);
+	}
+
+	/// Runs every running task of this binary module for one tick,
+	/// and removes the `Think` hook if none of them are running afterwards.
+	fn run_chunked_tick(&mut self) {
+		let name = hook_name();
+		self.push_registry();
+		self.get_field(-1, name.as_c_str());
+		if !self.is_type(-1, StdType::Table) {
+			self.pop(2);
+			return
+		}
+		let tasks = self.abs_index(-1);
+
+		// The tasks are copied to an array first,
+		// since callbacks may spawn new tasks while they're being run.
+		self.create_table();
+		let snapshot = self.abs_index(-1);
+		let mut n = 0;
+		self.push_nil();
+		while self.next(tasks) != 0 {
+			self.pop(1);
+			n += 1;
+			self.push_number(n as Number);
+			self.push_value(-2);
+			self.raw_set(snapshot);
+		}
+
+		let ty = self.user_type_of::<LuaChunkedTask>();
+		for i in 1..=n {
+			self.push_number(i as Number);
+			self.raw_get(snapshot);
+			if let Some(ptr) = unsafe { self.test_ud_ptr::<LuaChunkedTask>(ty, -1) } {
+				let task = self.abs_index(-1);
+				self.run_chunked_task(ptr, task, tasks);
+			}
+			self.pop(1);
+		}
+		self.pop(1);
+
+		self.push_nil();
+		if self.next(tasks) != 0 {
+			self.pop(4);
+			return
+		}
+		self.push_nil();
+		self.set_field(-3, name.as_c_str());
+		self.pop(2);
+
+		self.get_global_field(c"hook", c"Remove");
+		self.push_c_string(c"Think");
+		self.push_c_string(name.as_c_str());
+		self.call(2, 0);
+	}
+
+	/// Performs the work of the task at `task` for one tick, and calls its callbacks,
+	/// removing it from the table of running tasks at `tasks` if it's no longer running.
+	fn run_chunked_task(&mut self, ptr: NonNull<LuaChunkedTask>, task: StackPos, tasks: StackPos) {
+		// The task is kept alive by the stack while its work is performed and its callbacks run,
+		// but it may be cancelled by either of them.
+		let (work, budget) = {
+			let this = unsafe { &mut *ptr.as_ptr() };
+			(this.work.take(), this.budget)
+		};
+		// The work is missing if a previous step raised an error.
+		let Some(mut work) = work else {
+			unsafe { &mut *ptr.as_ptr() }.state = ChunkedState::Cancelled;
+			return self.remove_chunked_task(task, tasks)
+		};
+
+		let start = Instant::now(self);
+		let done = loop {
+			if work.step(self) {
+				break true
+			}
+			if start.elapsed(self) >= budget {
+				break false
+			}
+		};
+
+		let this = unsafe { &mut *ptr.as_ptr() };
+		if this.state != ChunkedState::Running {
+			return self.remove_chunked_task(task, tasks)
+		}
+
+		if !done {
+			this.progress = work.progress();
+			this.work = Some(work);
+			if let Some(on_progress) = this.on_progress {
+				let progress = this.progress;
+				self.push_ref(on_progress);
+				self.push_number(progress);
+				self.call(1, 0);
+			}
+			return
+		}
+
+		this.state = ChunkedState::Done;
+		this.progress = 1.0;
+		let on_complete = this.on_complete;
+		self.remove_chunked_task(task, tasks);
+		if let Some(on_complete) = on_complete {
+			self.push_ref(on_complete);
+			work.push_result(self);
+			self.call(1, 0);
+		}
+	}
+
+	fn remove_chunked_task(&mut self, task: StackPos, tasks: StackPos) {
+		self.push_value(task);
+		self.push_nil();
+		self.raw_set(tasks);
+	}
+}
+
+/// Returns the name of the `Think` hook which runs tasks for this binary module,
+/// which is also the name of the registry field with the table of running tasks.
+fn hook_name() -> CStrBuf {
+	// The hook is unique to each binary module, since each one has its own copy of this function.
+	let mut name = CStrBuf::new();
+	let _ = write!(name, "gmbm.chunked.{:p}", run_chunked_hook as extern "C-unwind" fn(Ctx<'_>) -> Rets);
+	name
+}
+
+extern "C-unwind" fn run_chunked_hook(cx: Ctx<'_>) -> Rets {
+	cx.lua().run_chunked_tick();
+	Rets::ZERO
+}
+
+/// Replaces the callback in `slot` with the function at argument `2`, and returns the task.
+fn set_callback(mut lua: SelfCtx<'_, LuaChunkedTask>, slot: fn(&mut LuaChunkedTask) -> &mut Option<Ref>) -> Rets {
+	lua.check_self();
+	lua.check_type(2, StdType::Function);
+	lua.push_value(2);
+	let callback = lua.create_ref();
+	if let Some(previous) = slot(lua.check_self_mut()).replace(callback) {
+		lua.free_ref(previous);
+	}
+	lua.push_value(1);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn task_on_progress(cx: MethodFuncCtx<'_, LuaChunkedTask>) -> Rets {
+	set_callback(cx.lua(), move |task| &mut task.on_progress)
+}
+
+extern "C-unwind" fn task_on_complete(cx: MethodFuncCtx<'_, LuaChunkedTask>) -> Rets {
+	set_callback(cx.lua(), move |task| &mut task.on_complete)
+}
+
+extern "C-unwind" fn task_get_progress(cx: MethodFuncCtx<'_, LuaChunkedTask>) -> Rets {
+	let lua = cx.lua();
+	lua.push_number(lua.check_self().progress);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn task_is_running(cx: MethodFuncCtx<'_, LuaChunkedTask>) -> Rets {
+	let lua = cx.lua();
+	lua.push_bool(lua.check_self().state == ChunkedState::Running);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn task_cancel(cx: MethodFuncCtx<'_, LuaChunkedTask>) -> Rets {
+	let mut lua = cx.lua();
+	let task = lua.check_self_mut();
+	if task.state == ChunkedState::Running {
+		task.state = ChunkedState::Cancelled;
+		// The work is dropped by the hook instead if it's currently being performed.
+		task.work = None;
+	}
+	Rets::ZERO
+}
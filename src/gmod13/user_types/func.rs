@@ -1,4 +1,7 @@
 use core::{
+	ffi::{
+		CStr, c_int,
+	},
 	marker::PhantomData,
 	mem::transmute,
 	ops::{
@@ -12,9 +15,10 @@ use super::{
 		func::{
 			Func, Ctx, Rets,
 		},
-		Lua, Type,
+		Lua, Type, Number, StackPos, StdType,
 	},
-	UserType,
+	UserType, UdRef, UdRefMut,
+	test_ud_header_of,
 };
 
 /// Context for function calls with a `self` of type `T`.
@@ -67,12 +71,196 @@ impl<'a, T: UserType> SelfCtx<'a, T> {
 
 	/// Pushes the given method function onto the stack.
 	/// 
+	/// The [`Type`] for `T` is stored in an upvalue of the function,
+	/// so it is available through [`self_ty`](Self::self_ty) without a registry lookup.
+	/// 
 	/// # Errors
 	/// The inner Lua state may raise an [error](crate::errors).
 	pub fn push_method(&mut self, f: MethodFunc<T>) {
 		self.lua.push_bits(self.ty.0 as _);
 		self.lua.push_closure(to_func(f), 1)
 	}
+
+	/// Pushes `value` onto the stack as a new userdata of type `T`.
+	/// 
+	/// This is the fast path for constructing user types:
+	/// unlike [`Lua::user_type_of`], it doesn't need to look up the [`Type`] in the registry.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_new(&mut self, value: T) -> Option<&mut T> {
+		let ty = self.ty;
+		unsafe { self.lua.push_user_type(ty, value) }
+	}
+}
+
+/// Functions for accessing arguments after `self`.
+/// 
+/// Argument `n` refers to the `n`-th argument after `self`, starting from `1`,
+/// which is at stack position `n + 1`.
+/// When a method is called with `:`, Lua reports errors with the same numbering,
+/// so errors raised by these functions refer to the argument number seen by the caller.
+impl<T: UserType> SelfCtx<'_, T> {
+	/// Returns the stack position of argument `n` after `self`.
+	pub const fn arg(&self, n: c_int) -> StackPos {
+		n + 1
+	}
+
+	/// Returns the number at argument `n` after `self`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the argument is not a number.
+	pub fn check_arg_number(&self, n: c_int) -> Number {
+		self.check_number(self.arg(n))
+	}
+
+	/// Returns the string at argument `n` after `self`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the argument is not a string.
+	pub fn check_arg_string(&self, n: c_int) -> &CStr {
+		self.check_string(self.arg(n))
+	}
+
+	/// Returns a shared borrow of the user type value `U` at argument `n` after `self`.
+	/// 
+	/// The [`Type`] of `U` is looked up with [`Lua::user_type_of`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if `U` is not registered, if the argument is not `U`, or if it is currently mutably borrowed.
+	pub fn check_arg_ud<U: UserType>(&self, n: c_int) -> UdRef<'_, U> {
+		let ty = self.user_type_of::<U>();
+		unsafe { self.check_ud_borrow(ty, self.arg(n)) }
+	}
+
+	/// Returns an exclusive borrow of the user type value `U` at argument `n` after `self`.
+	/// 
+	/// The [`Type`] of `U` is looked up with [`Lua::user_type_of`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if `U` is not registered, if the argument is not `U`, or if it is currently borrowed.
+	pub fn check_arg_ud_mut<U: UserType>(&self, n: c_int) -> UdRefMut<'_, U> {
+		let ty = self.user_type_of::<U>();
+		unsafe { self.check_ud_borrow_mut(ty, self.arg(n)) }
+	}
+
+	/// Throws an error related to argument `n` after `self` and ceases execution of the function.
+	pub fn arg_error_after_self(&self, n: c_int, message: &CStr) -> ! {
+		self.arg_error(self.arg(n), message)
+	}
+}
+
+/// Which operand of a binary metamethod is `self`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+	/// `self` is the left operand, at stack position `1`.
+	Left,
+	/// `self` is the right operand, at stack position `2`.
+	Right,
+}
+
+impl Side {
+	/// Returns the stack position of `self`.
+	pub const fn self_pos(self) -> StackPos {
+		match self {
+			Self::Left => 1,
+			Self::Right => 2,
+		}
+	}
+
+	/// Returns the stack position of the other operand.
+	pub const fn other_pos(self) -> StackPos {
+		match self {
+			Self::Left => 2,
+			Self::Right => 1,
+		}
+	}
+}
+
+/// Operands of a binary metamethod, where at least one of them is `T`.
+/// 
+/// `self` stays borrowed until this is dropped.
+/// 
+/// See [`SelfCtx::check_operands`].
+pub struct Operands<'a, T> {
+	lua: &'a Lua,
+	side: Side,
+	this: UdRef<'a, T>,
+}
+
+impl<'a, T> Operands<'a, T> {
+	/// Returns which operand is `self`.
+	pub const fn side(&self) -> Side {
+		self.side
+	}
+
+	/// Returns a reference to `self` as a `T`.
+	pub fn this(&self) -> &T {
+		&self.this
+	}
+
+	/// Returns the stack position of the other operand.
+	pub const fn other_pos(&self) -> StackPos {
+		self.side.other_pos()
+	}
+
+	/// Returns the other operand if it is a number.
+	pub fn other_number(&self) -> Option<Number> {
+		let pos = self.other_pos();
+		if self.lua.is_type(pos, StdType::Number) {
+			Some(self.lua.get_number(pos))
+		} else {
+			None
+		}
+	}
+
+	/// Returns a shared borrow of the other operand if it is a value of the user type `U`.
+	/// 
+	/// If both operands are `T`, this can be used with `U = T` to get the other value.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the other operand is currently mutably borrowed.
+	pub fn other_ud<U: UserType>(&self) -> Option<UdRef<'a, U>> {
+		let ty = self.lua.try_user_type_of::<U>()?;
+		unsafe { self.lua.test_ud_borrow(ty, self.other_pos()) }
+	}
+}
+
+/// Functions for binary metamethods, like `__add` or `__eq`,
+/// which may receive `self` as either operand.
+impl<T: UserType> SelfCtx<'_, T> {
+	/// Returns the operands of a binary metamethod,
+	/// figuring out which of the first two arguments is `self`,
+	/// and borrows `self`.
+	/// 
+	/// An operand is only considered to be `self` if it is a userdata created for `T`,
+	/// rather than for another Rust type registered under the same metatable.
+	/// If both operands are `T`, `self` is the left operand.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if neither of the operands is `T`, if `self` has already been destroyed,
+	/// or if it is currently mutably borrowed.
+	pub fn check_operands(&self) -> Operands<'_, T> {
+		let side = if unsafe { test_ud_header_of::<T>(self, self.ty, 1) }.is_some() {
+			Side::Left
+		} else if unsafe { test_ud_header_of::<T>(self, self.ty, 2) }.is_some() {
+			Side::Right
+		} else {
+			self.arg_error(1, T::EXPECTED_ERR)
+		};
+
+		Operands {
+			lua: self.lua,
+			side,
+			this: unsafe { self.check_ud_borrow(self.ty, side.self_pos()) },
+		}
+	}
 }
 
 impl<T> Deref for SelfCtx<'_, T> {
@@ -0,0 +1,249 @@
+use core::ffi::CStr;
+
+use super::{
+	super::{
+		Lua, Number, StackPos, StdType, Type,
+	},
+	SelfCtx, UserType,
+	UNBORROWED,
+	test_ud_header_of, user_type_gc,
+};
+
+/// Field of the registry which holds the tables of tracked instances, keyed by [`Type`].
+const INSTANCES_FIELD: &CStr = c"gmbm_instances";
+
+/// Pushes the weak table of tracked instances of `ty`, creating it if `create` is `true`,
+/// and returns `true`, or returns `false` and pushes nothing if it doesn't exist.
+fn push_instances_table(lua: &mut Lua, ty: Type, create: bool) -> bool {
+	lua.push_registry();
+	lua.get_field(-1, INSTANCES_FIELD);
+	if !lua.is_type(-1, StdType::Table) {
+		lua.pop(1);
+		if !create {
+			lua.pop(1);
+			return false
+		}
+		lua.create_table();
+		lua.push_value(-1);
+		lua.set_field(-3, INSTANCES_FIELD);
+	}
+	lua.remove(-2);
+
+	lua.push_number(ty.0 as Number);
+	lua.raw_get(-2);
+	if !lua.is_type(-1, StdType::Table) {
+		lua.pop(1);
+		if !create {
+			lua.pop(1);
+			return false
+		}
+		// Instances are keys of a table with weak keys, so tracking them doesn't keep them alive.
+		lua.create_table();
+		lua.create_table();
+		lua.push_string("k");
+		lua.set_field(-2, c"__mode");
+		lua.set_metatable(-2);
+		lua.push_number(ty.0 as Number);
+		lua.push_value(-2);
+		lua.raw_set(-4);
+	}
+	lua.remove(-2);
+	true
+}
+
+/// Adds the userdata at the top of the stack to the tracked instances of `ty`.
+pub(super) fn track_instance(lua: &mut Lua, ty: Type) {
+	push_instances_table(lua, ty, true);
+	lua.push_value(-2);
+	lua.push_bool(true);
+	lua.raw_set(-3);
+	lua.pop(1);
+}
+
+/// Functions for visiting tracked instances of user types.
+impl Lua {
+	/// Calls `f` with every live instance of `T`,
+	/// if `T` has [`TRACK_INSTANCES`](UserType::TRACK_INSTANCES) set and has been [`register`](Self::register)ed.
+	/// 
+	/// See [`for_each_instance_of`](Self::for_each_instance_of).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// struct Handle {
+	///     open: bool,
+	/// }
+	/// gmod13_type!(Handle);
+	/// impl LuaUserType for Handle {
+	///     const TRACK_INSTANCES: bool = true;
+	///     fn init_metatable(_: LuaSelfCtx<'_, Self>) {}
+	/// }
+	/// 
+	/// /// Marks every handle as closed, even if Lua still holds references to it.
+	/// fn close_handles(lua: &mut Lua) {
+	///     lua.for_each_instance::<Handle>(|_, handle| handle.open = false);
+	/// }
+	/// ```
+	pub fn for_each_instance<T: UserType>(&mut self, f: impl FnMut(&mut Lua, &mut T)) {
+		if let Some(ty) = self.try_user_type_of::<T>() {
+			unsafe { self.for_each_instance_of(ty, f) }
+		}
+	}
+
+	/// Calls `f` with every live instance of `T` with the given [`Type`],
+	/// if `T` has [`TRACK_INSTANCES`](UserType::TRACK_INSTANCES) set.
+	/// 
+	/// Each instance is on the top of the stack while `f` is called with it,
+	/// and is exclusively borrowed like with [`check_ud_borrow_mut`](Self::check_ud_borrow_mut).
+	/// Instances that have been destroyed, but not collected yet, are skipped.
+	/// Instances created by `f` are not visited.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors),
+	/// such as when an instance is currently borrowed.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	pub unsafe fn for_each_instance_of<T: UserType>(&mut self, ty: Type, mut f: impl FnMut(&mut Lua, &mut T)) {
+		if !T::TRACK_INSTANCES {
+			return
+		}
+
+		visit_instances(self, ty, move |lua, stack_pos| {
+			let is_live = unsafe { test_ud_header_of::<T>(lua, ty, stack_pos) }
+				.is_some_and(move |header| !unsafe { header.as_ref() }.data.is_null());
+			if is_live {
+				let mut value = unsafe { lua.check_ud_borrow_mut::<T>(ty, stack_pos) };
+				f(lua, &mut value);
+			}
+		})
+	}
+}
+
+/// Functions for finalizing user types before a binary module is unloaded.
+impl Lua {
+	/// Destroys every live instance of `T`, and stops Lua from calling into the binary module to finalize it,
+	/// if `T` has been [`register`](Self::register)ed.
+	/// 
+	/// See [`finalize_instances_of`](Self::finalize_instances_of).
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// struct Connection;
+	/// gmod13_type!(Connection);
+	/// impl LuaUserType for Connection {
+	///     const TRACK_INSTANCES: bool = true;
+	///     fn init_metatable(_: LuaSelfCtx<'_, Self>) {}
+	/// }
+	/// 
+	/// struct Session;
+	/// gmod13_type!(Session);
+	/// impl LuaUserType for Session {
+	///     const TRACK_INSTANCES: bool = true;
+	///     fn init_metatable(_: LuaSelfCtx<'_, Self>) {}
+	/// }
+	/// 
+	/// struct Net;
+	/// impl LuaModule for Net {
+	///     fn open(&mut self, _: &mut Lua) {}
+	/// 
+	///     fn close(&mut self, lua: &mut Lua) {
+	///         // Sessions use connections when they're destroyed, so they're destroyed first.
+	///         lua.finalize_instances::<Session>();
+	///         lua.finalize_instances::<Connection>();
+	///     }
+	/// }
+	/// ```
+	pub fn finalize_instances<T: UserType>(&mut self) {
+		if let Some(ty) = self.try_user_type_of::<T>() {
+			unsafe { self.finalize_instances_of::<T>(ty) }
+		}
+	}
+
+	/// Destroys every live instance of `T` with the given [`Type`] like the `__gc` metamethod would,
+	/// if `T` has [`TRACK_INSTANCES`](UserType::TRACK_INSTANCES) set,
+	/// and then removes `__gc` from the metatable of `T`.
+	/// 
+	/// This is meant to be called in [`Module::close`](crate::gmod13::Module::close),
+	/// since instances may otherwise be finalized after the binary module has been unloaded,
+	/// calling into code that no longer exists.
+	/// Destroyed instances raise an error when they're used, like after [`SelfCtx::destroy_self`].
+	/// Types are finalized in the order this is called for them,
+	/// so types whose [`UserType::collect`] uses instances of another type should be finalized first.
+	/// 
+	/// Instances which aren't tracked, or which are currently borrowed, are not destroyed,
+	/// and their values are leaked instead of being dropped.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	pub unsafe fn finalize_instances_of<T: UserType>(&mut self, ty: Type) {
+		if T::TRACK_INSTANCES {
+			visit_instances(self, ty, move |lua, stack_pos| {
+				let can_destroy = unsafe { test_ud_header_of::<T>(lua, ty, stack_pos) }
+					.is_some_and(move |header| {
+						let header = unsafe { header.as_ref() };
+						!header.data.is_null() && header.borrow == UNBORROWED
+					});
+				if can_destroy {
+					// The value is destroyed by calling the finalizer, so `UserType::collect` sees the usual stack.
+					unsafe { SelfCtx::<T>::new(lua, ty) }.push_method(user_type_gc::<T>);
+					lua.push_value(stack_pos);
+					lua.call(1, 0);
+				}
+			});
+		}
+
+		if self.push_metatable(ty) {
+			self.push_nil();
+			self.set_field(-2, c"__gc");
+			self.pop(1);
+		}
+	}
+}
+
+/// Calls `f` with the absolute stack position of every tracked instance of `ty`,
+/// which is at the top of the stack when `f` is called.
+/// 
+/// Instances created by `f` are not visited, and values that `f` leaves on the stack are popped.
+fn visit_instances(lua: &mut Lua, ty: Type, mut f: impl FnMut(&mut Lua, StackPos)) {
+	if !push_instances_table(lua, ty, false) {
+		return
+	}
+
+	// The instances are copied into a sequence first,
+	// since new keys must not be added to a table while it's traversed with `next`.
+	lua.create_table();
+	let mut len = 0;
+	lua.push_nil();
+	while lua.next(-3) != 0 {
+		lua.pop(1);
+		len += 1;
+		lua.push_number(len as Number);
+		lua.push_value(-2);
+		lua.raw_set(-4);
+	}
+	lua.remove(-2);
+
+	let list = lua.abs_index(-1);
+	for i in 1..=len {
+		lua.push_number(i as Number);
+		lua.raw_get(list);
+		f(lua, list + 1);
+		lua.set_top(list as _);
+	}
+	lua.pop(1);
+}
@@ -0,0 +1,184 @@
+use core::{
+	ffi::c_int,
+	fmt::Write,
+};
+
+use crate::gmod13_type;
+
+use super::{
+	super::{
+		chunks::CStrBuf,
+		func::Rets,
+		number_to_i53,
+		Lua, Number, StackPos, StdType,
+	},
+	MethodFuncCtx, SelfCtx, UserType,
+};
+
+/// 64-bit signed integer stored in Lua as userdata,
+/// for values that a [`Number`] can't represent exactly, like SteamIDs or database IDs.
+/// 
+/// In Lua, values of this type support arithmetic with each other and with integral numbers,
+/// comparisons with each other, `tostring`, and a `ToNumber` method which converts them to a (possibly inexact) number.
+/// Arithmetic wraps around on overflow, like it does in C.
+/// 
+/// The type is registered the first time it is pushed with [`Lua::push_int64`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Int64(pub i64);
+
+gmod13_type!(Int64);
+
+impl UserType for Int64 {
+	fn init_metatable(mut cx: SelfCtx<'_, Self>) {
+		cx.push_value(-1);
+		cx.set_field(-2, c"__index");
+
+		cx.push_method(int64_tostring);
+		cx.set_field(-2, c"__tostring");
+		cx.push_method(int64_to_number);
+		cx.set_field(-2, c"ToNumber");
+
+		cx.push_method(int64_add);
+		cx.set_field(-2, c"__add");
+		cx.push_method(int64_sub);
+		cx.set_field(-2, c"__sub");
+		cx.push_method(int64_mul);
+		cx.set_field(-2, c"__mul");
+		cx.push_method(int64_div);
+		cx.set_field(-2, c"__div");
+		cx.push_method(int64_mod);
+		cx.set_field(-2, c"__mod");
+		cx.push_method(int64_unm);
+		cx.set_field(-2, c"__unm");
+
+		cx.set_eq();
+		cx.push_method(int64_lt);
+		cx.set_field(-2, c"__lt");
+		cx.push_method(int64_le);
+		cx.set_field(-2, c"__le");
+	}
+}
+
+/// Functions for 64-bit integers stored as [`Int64`] userdata.
+impl Lua {
+	/// Pushes `i` onto the stack as an [`Int64`],
+	/// registering the type first if needed.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn push_int64(&mut self, i: i64) {
+		self.push_auto_user_type(Int64(i));
+	}
+
+	/// Returns the integer at `stack_pos` if it is an [`Int64`] or an integral Lua number,
+	/// or `None` otherwise.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn get_int64(&self, stack_pos: StackPos) -> Option<i64> {
+		if self.is_type(stack_pos, StdType::Number) {
+			return number_to_i53(self.get_number(stack_pos))
+		}
+		let ty = self.try_user_type_of::<Int64>()?;
+		unsafe { self.test_ud::<Int64>(ty, stack_pos) }.map(move |i| i.0)
+	}
+
+	/// Returns the integer at `arg` if it is an [`Int64`] or an integral Lua number.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the argument is neither.
+	pub fn check_int64(&self, arg: c_int) -> i64 {
+		match self.get_int64(arg) {
+			Some(i) => i,
+			None => self.arg_error(arg, c"Int64 or integer expected"),
+		}
+	}
+}
+
+extern "C-unwind" fn int64_tostring(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	let mut lua = cx.lua();
+	let mut buf = CStrBuf::new();
+	let _ = write!(buf, "{}", lua.check_self().0);
+	lua.push_c_string(buf.as_c_str());
+	Rets::new(1)
+}
+
+extern "C-unwind" fn int64_to_number(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	let lua = cx.lua();
+	lua.push_number(lua.check_self().0 as Number);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn int64_unm(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	let mut lua = cx.lua();
+	let i = lua.check_self().0;
+	lua.push_new(Int64(i.wrapping_neg()));
+	Rets::new(1)
+}
+
+/// Applies `op` to the operands of a binary metamethod,
+/// where either one may be a number, and pushes the result.
+fn binary_op(mut lua: SelfCtx<'_, Int64>, op: fn(i64, i64) -> Option<i64>) -> Rets {
+	let (this, other_pos) = {
+		let operands = lua.check_operands();
+		(operands.this().0, operands.other_pos())
+	};
+	let other = lua.check_int64(other_pos);
+	let (a, b) = if other_pos == 2 {
+		(this, other)
+	} else {
+		(other, this)
+	};
+
+	match op(a, b) {
+		Some(i) => lua.push_new(Int64(i)),
+		None => lua.throw_error(c"attempt to divide an Int64 by zero"),
+	};
+	Rets::new(1)
+}
+
+extern "C-unwind" fn int64_add(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	binary_op(cx.lua(), move |a, b| Some(a.wrapping_add(b)))
+}
+
+extern "C-unwind" fn int64_sub(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	binary_op(cx.lua(), move |a, b| Some(a.wrapping_sub(b)))
+}
+
+extern "C-unwind" fn int64_mul(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	binary_op(cx.lua(), move |a, b| Some(a.wrapping_mul(b)))
+}
+
+extern "C-unwind" fn int64_div(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	binary_op(cx.lua(), move |a, b| (b != 0).then(move || a.wrapping_div(b)))
+}
+
+extern "C-unwind" fn int64_mod(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	// Like Lua, the result has the same sign as the divisor.
+	binary_op(cx.lua(), move |a, b| (b != 0).then(move || {
+		let r = a.wrapping_rem(b);
+		if r != 0 && (r < 0) != (b < 0) { r + b } else { r }
+	}))
+}
+
+/// Compares the operands of `__lt` or `__le`, which Lua only calls when both are [`Int64`].
+fn compare(lua: SelfCtx<'_, Int64>, cmp: fn(&i64, &i64) -> bool) -> Rets {
+	let ty = lua.self_ty();
+	let a = unsafe { lua.check_ud::<Int64>(ty, 1) }.0;
+	let b = unsafe { lua.check_ud::<Int64>(ty, 2) }.0;
+	lua.push_bool(cmp(&a, &b));
+	Rets::new(1)
+}
+
+extern "C-unwind" fn int64_lt(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	compare(cx.lua(), i64::lt)
+}
+
+extern "C-unwind" fn int64_le(cx: MethodFuncCtx<'_, Int64>) -> Rets {
+	compare(cx.lua(), i64::le)
+}
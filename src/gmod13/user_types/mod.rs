@@ -1,42 +1,142 @@
 //! Traits for implementing user types.
 
 use core::{
+	any::type_name,
+	convert::Infallible,
 	ffi::{
 		CStr,
-		c_void, c_uchar,
+		c_void, c_uchar, c_uint,
 	},
 	mem::{
 		MaybeUninit, needs_drop,
 	},
-	ptr::NonNull,
+	ptr::{
+		NonNull, null_mut,
+	},
 };
 
 use super::{
 	func::Rets,
-	Lua,
+	Lua, Bits,
 	Type, StdType, RawType,
-	StackPos,
+	StackPos, UserData,
 };
 
 mod func;
 pub use func::*;
+mod borrow;
+pub use borrow::*;
+mod int64;
+pub use int64::*;
+mod promise;
+pub use promise::*;
+mod singleton;
+mod instances;
+
+#[cfg(feature = "alloc")]
+mod boxed;
+#[cfg(feature = "alloc")]
+pub use boxed::*;
+#[cfg(feature = "alloc")]
+mod chunked;
+#[cfg(feature = "alloc")]
+pub use chunked::*;
 
 /// Base trait for [`UserType`] that will typically be implemented with [`gmod13_type!`](crate::gmod13_type!).
 /// 
 /// # Safety
 /// `ID` must *uniquely* (on a best-effort basis) identify the implementing type,
-/// the type *must not* be generic,
-/// it must have an alignment `<= 8`
-/// and size `<= (c_uint::MAX as usize) - size_of::<RawUd>()`.
+/// and the type *must not* be generic unless it is only registered with [`Lua::register_named`].
+/// 
+/// Types which are stored directly in Lua userdata
+/// (as opposed to boxed ones)
+/// must additionally have an alignment `<=` [`MAX_ALIGN`]
+/// and a [`userdata_size`] `<= (c_uint::MAX as usize)`.
+/// With the `alloc` feature, [`Lua::create_user_type`] boxes values of types that don't meet these requirements,
+/// and otherwise they are asserted at compile time.
 pub unsafe trait UserTypeBase: Sized {
 	/// Name of the metatable associated with this Rust type.
 	const ID: &'static CStr;
 	/// Error message for whenever a value of this type is expected.
 	const EXPECTED_ERR: &'static CStr;
+	/// Hash of the name and layout of this Rust type,
+	/// used to detect incompatible types that share the same [`ID`](Self::ID).
+	const LAYOUT_HASH: Bits = layout_hash::<Self>(Self::ID);
+}
+
+/// Returns a hash of `id` and the layout of `T`, suitable for [`UserTypeBase::LAYOUT_HASH`].
+pub const fn layout_hash<T>(id: &CStr) -> Bits {
+	const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+	const FNV_PRIME: u64 = 0x100000001b3;
+
+	const fn feed(mut hash: u64, bytes: &[u8]) -> u64 {
+		let mut i = 0;
+		while i < bytes.len() {
+			hash ^= bytes[i] as u64;
+			hash = hash.wrapping_mul(FNV_PRIME);
+			i += 1;
+		}
+		hash
+	}
+
+	let mut hash = feed(FNV_OFFSET, id.to_bytes());
+	hash = feed(hash, &(size_of::<T>() as u64).to_le_bytes());
+	hash = feed(hash, &(align_of::<T>() as u64).to_le_bytes());
+	hash = feed(hash, &[needs_drop::<T>() as u8]);
+	hash as Bits
 }
 
 /// Trait for Rust types that can be sent to and returned from Lua.
+/// 
+/// # Trait objects
+/// Multiple Rust implementations can share one Lua-facing type
+/// by implementing this trait for a boxed trait object,
+/// whose methods then dispatch through the vtable.
+/// The box itself is stored directly in the userdata, and is dropped by `__gc`.
+/// 
+/// ```
+/// # use gmbm::prelude::*;
+/// trait Shape {
+///     fn area(&self) -> f64;
+/// }
+/// 
+/// struct Square(f64);
+/// impl Shape for Square {
+///     fn area(&self) -> f64 { self.0 * self.0 }
+/// }
+/// 
+/// struct Circle(f64);
+/// impl Shape for Circle {
+///     fn area(&self) -> f64 { core::f64::consts::PI * self.0 * self.0 }
+/// }
+/// 
+/// gmod13_type!(Box<dyn Shape>);
+/// impl LuaUserType for Box<dyn Shape> {
+///     fn init_metatable(mut cx: LuaSelfCtx<'_, Self>) {
+///         cx.push_value(-1);
+///         cx.set_field(-2, c"__index");
+///         cx.push_method(gmod13_method!(Box<dyn Shape> => lua => {
+///             let area = lua.borrow().area();
+///             lua.push_number(area);
+///             1
+///         }));
+///         cx.set_field(-2, c"Area");
+///     }
+/// }
+/// 
+/// fn push_shapes(lua: &mut Lua) {
+///     lua.push_auto_user_type::<Box<dyn Shape>>(Box::new(Square(2.0)));
+///     lua.push_auto_user_type::<Box<dyn Shape>>(Box::new(Circle(1.0)));
+/// }
+/// ```
 pub trait UserType: UserTypeBase {
+	/// Whether instances of this type are tracked in a table with weak keys,
+	/// so that they can be visited with [`Lua::for_each_instance`].
+	/// 
+	/// Tracking adds a few table operations whenever an instance is created,
+	/// but doesn't keep instances alive.
+	const TRACK_INSTANCES: bool = false;
+
 	/// Initializes the Lua type's metatable on the top of the stack,
 	/// given its associated [`Type`].
 	/// 
@@ -79,38 +179,238 @@ macro_rules! gmod13_type {
 					)
 				};
 			}
-
-			const _ASSERT_USER_TYPE_BASE_ALIGNMENT: () = ::core::assert!(
-				::core::mem::align_of::<$Type>() <= 8,
-				::core::concat! {
-					'`', ::core::stringify! {$Type}, "` does not meet alignment requirement for `UserTypeBase`"
-				}
-			);
-			const _ASSERT_USER_TYPE_BASE_SIZE: () = ::core::assert!(
-				::core::mem::size_of::<$Type>()
-					<= (::core::ffi::c_uint::MAX as usize) - ::core::mem::size_of::<$crate::gmod13::user_types::RawUd>(),
-				::core::concat! {
-					'`', ::core::stringify! {$Type}, "` does not meet size requirement for `UserTypeBase`"
-				}
-			);
 		};
 	};
 }
 
+/// Maximum alignment of types that implement [`UserTypeBase`].
+pub const MAX_ALIGN: usize = 64;
+
+/// Alignment that Lua guarantees for userdata allocations.
+const USERDATA_ALIGN: usize = 8;
+
+/// Returns the size of the userdata allocated for a value of type `T`,
+/// including the [`RawUd`] header and any padding needed to align the value.
+/// 
+/// Values of zero-sized types are not stored in the userdata at all,
+/// so only the header is allocated for them.
+pub const fn userdata_size<T>() -> usize {
+	size_of::<RawUd>() + value_padding::<T>() + size_of::<T>()
+}
+
+/// Returns `true` if values of type `T` can be stored directly in Lua userdata,
+/// which requires an alignment `<=` [`MAX_ALIGN`] and a [`userdata_size`] `<= (c_uint::MAX as usize)`.
+pub const fn fits_userdata<T>() -> bool {
+	align_of::<T>() <= MAX_ALIGN && userdata_size::<T>() <= c_uint::MAX as usize
+}
+
+/// Returns `true` if the metatable of `T` needs a `__gc` metamethod,
+/// either to drop values of `T` or to free the heap allocations of values that don't [fit](fits_userdata) in userdata.
+const fn needs_gc<T>() -> bool {
+	needs_drop::<T>() || (cfg!(feature = "alloc") && !fits_userdata::<T>())
+}
+
+/// Returns the maximum amount of padding needed between the [`RawUd`] header and a value of type `T`.
+const fn value_padding<T>() -> usize {
+	if size_of::<T>() == 0 {
+		return 0
+	}
+	let header_align = 1 << size_of::<RawUd>().trailing_zeros();
+	let base_align = if header_align < USERDATA_ALIGN { header_align } else { USERDATA_ALIGN };
+	align_of::<T>().saturating_sub(base_align)
+}
+
 extern "C-unwind" fn user_type_gc<T: UserType>(cx: MethodFuncCtx<'_, T>) -> Rets {
 	let cx = cx.lua();
-	
-	let mut this = cx.check_self_ptr();
+
+	// Values that have already been destroyed explicitly are skipped.
+	if let Some(header) = unsafe { test_ud_header_of::<T>(&cx, cx.self_ty(), 1) }
+		&& !unsafe { header.as_ref() }.data.is_null()
+	{
+		unsafe { destroy_value(header, cx) }
+	}
+
+	Rets::ZERO
+}
+
+extern "C-unwind" fn user_type_eq<T: UserType + PartialEq>(cx: MethodFuncCtx<'_, T>) -> Rets {
+	let cx = cx.lua();
+	let ty = cx.self_ty();
+
+	// Lua only calls `__eq` for two userdata values that share this metamethod,
+	// but either of them may have been destroyed already.
+	let eq = match unsafe { (cx.test_ud_borrow::<T>(ty, 1), cx.test_ud_borrow::<T>(ty, 2)) } {
+		(Some(a), Some(b)) => *a == *b,
+		_ => false,
+	};
+	cx.push_bool(eq);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn user_type_copy<T: UserType + Clone>(cx: MethodFuncCtx<'_, T>) -> Rets {
+	cx.lua().push_clone();
+	Rets::new(1)
+}
+
+impl<T: UserType + Clone> SelfCtx<'_, T> {
+	/// Pushes a clone of `self` onto the stack as a new userdata of type `T`.
+	/// 
+	/// `self` is only borrowed while it is cloned, so it may not be mutably borrowed at the same time.
+	/// Like [`push_new`](Self::push_new), this doesn't need to look up the [`Type`] in the registry.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, or if it is currently mutably borrowed.
+	pub fn push_clone(&mut self) -> Option<&mut T> {
+		let value = self.borrow().clone();
+		self.push_new(value)
+	}
+
+	/// Sets the field `name` in the metatable on top of the stack to a method
+	/// which returns a clone of `self` as a new userdata,
+	/// like `__copy` or `Copy`.
+	/// 
+	/// This is meant to be called in [`UserType::init_metatable`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_copy(&mut self, name: &CStr) {
+		self.push_method(user_type_copy::<T>);
+		self.set_field(-2, name);
+	}
+}
+
+impl<T: UserType + PartialEq> SelfCtx<'_, T> {
+	/// Sets `__eq` in the metatable on top of the stack to compare values with [`PartialEq`],
+	/// so that `==` in Lua compares the Rust values instead of the userdata identities.
+	/// 
+	/// This is meant to be called in [`UserType::init_metatable`].
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn set_eq(&mut self) {
+		self.push_method(user_type_eq::<T>);
+		self.set_field(-2, c"__eq");
+	}
+}
+
+/// Runs [`UserType::collect`] and drops the value of the userdata with the given `header`,
+/// marking it as destroyed.
+/// 
+/// # Safety
+/// `header` must point to the header of userdata created for `T`, the value of which has not been destroyed yet.
+unsafe fn destroy_value<T: UserType>(header: NonNull<RawUd>, cx: SelfCtx<'_, T>) {
+	let ud = header.as_ptr();
 	unsafe {
+		let mut this = NonNull::new_unchecked((*ud).data.cast::<T>());
+		#[cfg(feature = "alloc")]
+		let is_boxed = (*ud).boxed;
+
 		T::collect(this.as_mut(), cx);
+		(*ud).data = null_mut();
+
+		#[cfg(feature = "alloc")]
+		if is_boxed {
+			boxed::drop_boxed(this);
+			return
+		}
 		this.drop_in_place();
 	}
+}
 
-	Rets::ZERO
+/// Moves the value out of the userdata with the given `header`,
+/// marking it as destroyed.
+/// 
+/// # Safety
+/// `header` must point to the header of userdata created for `T`, the value of which has not been destroyed yet.
+unsafe fn take_value<T: UserType>(header: NonNull<RawUd>) -> T {
+	let ud = header.as_ptr();
+	unsafe {
+		let this = NonNull::new_unchecked((*ud).data.cast::<T>());
+		#[cfg(feature = "alloc")]
+		let is_boxed = (*ud).boxed;
+
+		(*ud).data = null_mut();
+
+		#[cfg(feature = "alloc")]
+		if is_boxed {
+			return boxed::take_boxed(this)
+		}
+		this.read()
+	}
+}
+
+/// Returns the header of the userdata at `stack_pos` if it was created for `T`,
+/// as checked by [`Lua::test_ud_header`] and the [`layout_hash`](RawUd::layout_hash) of the header.
+/// 
+/// # Safety
+/// `ty` must be the correct type identifier for `T`.
+unsafe fn test_ud_header_of<T: UserType>(lua: &Lua, ty: Type, stack_pos: StackPos) -> Option<NonNull<RawUd>> {
+	let header = unsafe { lua.test_ud_header(ty, stack_pos)? };
+	(unsafe { header.as_ref() }.layout_hash == T::LAYOUT_HASH).then_some(header)
+}
+
+/// Returns the header of the userdata of type `T` at `arg`, the value of which has not been destroyed yet.
+/// 
+/// # Safety
+/// `ty` must be the correct type identifier for `T`.
+unsafe fn check_ud_header<T: UserType>(lua: &Lua, ty: Type, arg: StackPos) -> NonNull<RawUd> {
+	match unsafe { test_ud_header_of::<T>(lua, ty, arg) } {
+		Some(header) if unsafe { header.as_ref() }.data.is_null() => {
+			lua.arg_error(arg, c"object already destroyed")
+		}
+		Some(header) => header,
+		None => lua.arg_error(arg, T::EXPECTED_ERR),
+	}
+}
+
+impl<T: UserType> SelfCtx<'_, T> {
+	/// Moves `self` out of its userdata, marking it as destroyed.
+	/// 
+	/// Subsequent accesses to the userdata will raise an error instead of operating on a moved-out value,
+	/// and [`UserType::collect`] will not be called for it.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, if it has already been destroyed, or if it is currently borrowed.
+	pub fn take_self(&mut self) -> T {
+		let header = unsafe { check_unborrowed_self(self) };
+		unsafe { take_value(header) }
+	}
+
+	/// Destroys `self` like the `__gc` metamethod would, marking it as destroyed.
+	/// 
+	/// This is useful for implementing methods like `:Close()` or `:Destroy()`.
+	/// Subsequent accesses to the userdata will raise an error instead of operating on a dropped value.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors)
+	/// if the `self` argument is not `T`, if it has already been destroyed, or if it is currently borrowed.
+	pub fn destroy_self(self) {
+		let header = unsafe { check_unborrowed_self(&self) };
+		unsafe { destroy_value(header, self) }
+	}
+}
+
+/// # Safety
+/// The [`Type`] of `cx` must be the correct type identifier for `T`.
+unsafe fn check_unborrowed_self<T: UserType>(cx: &SelfCtx<'_, T>) -> NonNull<RawUd> {
+	let header = unsafe { check_ud_header::<T>(cx, cx.self_ty(), 1) };
+	if unsafe { header.as_ref() }.borrow != UNBORROWED {
+		cx.throw_error(c"userdata is already borrowed")
+	}
+	header
 }
 
 /// Functions for handling user types.
 impl Lua {
+	/// Allocates userdata for `T`, initializes its value with `init` and pushes it.
+	/// 
+	/// With the `alloc` feature, values of types which don't [fit](fits_userdata) in userdata
+	/// are stored in a heap allocation, like the values of boxed user types.
+	/// 
+	/// Returns `None` if the userdata couldn't be allocated.
+	/// 
 	/// # Safety
 	/// `ty` must be the correct type identifier for `T`.
 	/// 
@@ -118,33 +418,70 @@ impl Lua {
 	pub unsafe fn create_user_type<'a, T: UserType, F: FnOnce(&mut MaybeUninit<T>)>(
 		&mut self, ty: Type, init: F,
 	) -> Option<&'a mut T> {
-		struct RawUdOf<T> {
-			pub ud: RawUd,
-			pub value: T,
+		let result = unsafe {
+			self.try_create_user_type::<T, Infallible, _>(ty, move |value| {
+				init(value);
+				Ok(())
+			})
+		};
+		match result {
+			Ok(value) => value,
+		}
+	}
+
+	/// Like [`create_user_type`](Self::create_user_type), but `init` may fail,
+	/// in which case the error is returned and nothing is left on the stack.
+	/// 
+	/// Returns `Ok(None)` if the userdata couldn't be allocated.
+	/// 
+	/// The userdata is allocated before `init` is called,
+	/// and is left for the garbage collector if `init` fails.
+	/// It has no metatable at that point, so the partially initialized value is never dropped.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	/// 
+	/// `init` must initialize the given value if it returns `Ok`.
+	pub unsafe fn try_create_user_type<'a, T: UserType, E, F: FnOnce(&mut MaybeUninit<T>) -> Result<(), E>>(
+		&mut self, ty: Type, init: F,
+	) -> Result<Option<&'a mut T>, E> {
+		#[cfg(feature = "alloc")]
+		if !fits_userdata::<T>() {
+			let mut value = alloc::boxed::Box::<T>::new_uninit();
+			init(&mut value)?;
+			return Ok(unsafe { boxed::push_boxed(self, ty, value.assume_init()) })
+		}
+		#[cfg(not(feature = "alloc"))]
+		const {
+			assert!(align_of::<T>() <= MAX_ALIGN, "type does not meet alignment requirement for userdata");
+			assert!(userdata_size::<T>() <= c_uint::MAX as usize, "type does not meet size requirement for userdata");
 		}
 
-		let ud: *mut RawUdOf<T> = unsafe {
-			self.new_userdata_raw(size_of::<RawUdOf<T>>() as _).cast()
+		let ud: *mut RawUd = unsafe {
+			self.new_userdata_raw(userdata_size::<T>() as _).cast()
 		};
 		if ud.is_null() {
-			return None
+			return Ok(None)
 		}
 
-		let raw_ty = ty.0;
 		let value_ptr = unsafe {
-			let value_ptr = &raw mut (*ud).value;
-			init(&mut *(value_ptr as *mut MaybeUninit<_>));
-			(*ud).ud.data = value_ptr as _;
-			(*ud).ud.ty = raw_ty as _;
-			(*ud).ud.rust_ty = raw_ty;
+			let value_ptr = if size_of::<T>() == 0 {
+				// Zero-sized values don't need any storage, only a well-aligned pointer.
+				NonNull::<T>::dangling().as_ptr()
+			} else {
+				// `userdata_size` reserves enough padding after the header to align the value manually.
+				let base = ud.add(1).cast::<u8>();
+				base.add(base.align_offset(align_of::<T>())).cast::<T>()
+			};
+			if let Err(e) = init(&mut *(value_ptr as *mut MaybeUninit<_>)) {
+				self.pop(1);
+				return Err(e)
+			}
+			init_userdata(self, ud, value_ptr, ty, false);
 			value_ptr
 		};
 
-		if self.push_metatable(ty) {
-			self.set_metatable(-2);
-		}
-
-		unsafe { Some(&mut *value_ptr) }
+		unsafe { Ok(Some(&mut *value_ptr)) }
 	}
 
 	/// # Safety
@@ -153,28 +490,71 @@ impl Lua {
 		unsafe { self.create_user_type(ty, move |init| { init.write(value); }) }
 	}
 
+	/// Pushes a clone of `value` onto the stack as a new userdata of type `T`.
+	/// 
+	/// This is useful for returning a snapshot of a value without moving ownership of it into Lua.
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	pub unsafe fn push_user_type_clone<'a, T: UserType + Clone>(&mut self, ty: Type, value: &T) -> Option<&'a mut T> {
+		let value = value.clone();
+		unsafe { self.push_user_type(ty, value) }
+	}
+
+	/// Pushes `value` onto the stack as a new userdata of type `T`,
+	/// [`register`](Self::register)ing `T` first if it hasn't been registered in this Lua state yet.
+	/// 
+	/// This removes the need to register every type in [`Module::open`](super::Module::open)
+	/// before any code path can push it, at the cost of a registry lookup on every call.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// See [`register`](Self::register).
+	pub fn push_auto_user_type<T: UserType>(&mut self, value: T) -> Option<&mut T> {
+		let ty = if let Some(ty) = self.try_user_type_of::<T>() {
+			ty
+		} else {
+			let ty = self.register::<T>();
+			self.pop(1);
+			ty
+		};
+		unsafe { self.push_user_type(ty, value) }
+	}
+
 	/// # Safety
 	/// `ty` must be the correct type identifier for `T`.
 	pub unsafe fn test_ud_ptr<T: UserType>(&self, ty: Type, stack_pos: StackPos) -> Option<NonNull<T>> {
+		let ud = unsafe { test_ud_header_of::<T>(self, ty, stack_pos)?.as_ref() };
+		NonNull::new(ud.data.cast::<T>())
+	}
+
+	/// Returns a pointer to the [`RawUd`] header of the userdata at `stack_pos`
+	/// if it is of the given [`Type`] and has the [`RAW_UD_MAGIC`] of userdata created by this crate.
+	/// 
+	/// This doesn't check the [`layout_hash`](RawUd::layout_hash) of the header,
+	/// which the functions that return values of a specific Rust type do.
+	/// 
+	/// # Safety
+	/// `ty` must be a type identifier for userdata created by this crate.
+	pub unsafe fn test_ud_header(&self, ty: Type, stack_pos: StackPos) -> Option<NonNull<RawUd>> {
 		if !self.is_type(stack_pos, ty) {
 			return None
 		}
 
-		let ud = unsafe { self.get_userdata(stack_pos).cast::<RawUd>().as_ref()? };
-		if ud.ty != ty.0 as _ && ud.rust_ty != ty.0 {
+		let ud = NonNull::new(self.get_userdata(stack_pos).cast::<RawUd>())?;
+		let header = unsafe { ud.as_ref() };
+		if header.magic != RAW_UD_MAGIC || (header.ty != ty.0 as _ && header.rust_ty != ty.0) {
 			return None
 		}
 
-		NonNull::new(ud.data.cast::<T>())
+		Some(ud)
 	}
 
 	/// # Safety
 	/// `ty` must be the correct type identifier for `T`.
 	pub unsafe fn check_ud_ptr<T: UserType>(&self, ty: Type, arg: StackPos) -> NonNull<T> {
-		match unsafe { self.test_ud_ptr(ty, arg) } {
-			Some(t) => t,
-			None => self.arg_error(arg, T::EXPECTED_ERR),
-		}
+		let header = unsafe { check_ud_header::<T>(self, ty, arg) };
+		unsafe { NonNull::new_unchecked(header.as_ref().data.cast()) }
 	}
 
 	/// # Safety
@@ -197,57 +577,593 @@ impl Lua {
 
 	/// # Safety
 	/// `ty` must be the correct type identifier for `T`.
+	/// 
+	/// Note that Lua may re-enter functions which access the same userdata,
+	/// in which case more than one mutable reference to it may exist.
+	/// Consider using [`check_ud_borrow_mut`](Self::check_ud_borrow_mut) instead.
 	pub unsafe fn check_ud_mut<T: UserType>(&mut self, ty: Type, arg: StackPos) -> &mut T {
 		unsafe { self.check_ud_ptr(ty, arg).as_mut() }
 	}
 
+	/// Creates the metatable for `T`, initializes it and returns the [`Type`] associated with it.
+	/// 
+	/// If `T` has already been registered in this Lua state,
+	/// the existing metatable is pushed and its [`Type`] is returned without initializing it again.
+	/// 
+	/// # Errors
+	/// This function will raise an [error](crate::errors)
+	/// if a metatable with the name [`T::ID`](UserTypeBase::ID) already exists,
+	/// but was not created by this function for `T`,
+	/// such as when it was created by another binary module or a script,
+	/// or for a Rust type with a different [`LAYOUT_HASH`](UserTypeBase::LAYOUT_HASH).
 	pub fn register<T: UserType>(&mut self) -> Type {
-		let ty = self.create_metatable(T::ID);
-		let ty_raw = ty.0 as _;
-
-		self.push_registry();
-		push_registry_key::<T>(self);
-		self.push_bits(ty_raw);
-		self.raw_set(-3); // registry[key] = ty
-		self.pop(1);
-
-		let mut cx = unsafe { SelfCtx::new(self, ty) };
-		if needs_drop::<T>() {
-			cx.push_method(user_type_gc::<T>);
-			cx.set_field(-2, c"__gc");
-		}
-		T::init_metatable(cx);
+		register_with_key::<T>(self, TypeKey::Static(T::ID), T::LAYOUT_HASH, needs_gc::<T>())
+	}
 
-		ty
+	/// Creates the metatable for `T` with the given `name`, initializes it
+	/// and returns the [`Type`] associated with it.
+	/// 
+	/// Unlike [`register`](Self::register), the [`Type`] is associated with `name` instead of [`T::ID`](UserTypeBase::ID),
+	/// which allows registering each instantiation of a generic type under a distinct name.
+	/// Use [`user_type_of_named`](Self::user_type_of_named) to retrieve it later.
+	/// 
+	/// If `T` has already been registered under `name` in this Lua state,
+	/// the existing metatable is pushed and its [`Type`] is returned without initializing it again.
+	/// 
+	/// # Errors
+	/// This function will raise an [error](crate::errors)
+	/// if a metatable with the name `name` already exists,
+	/// but was not created by this function for `T`,
+	/// such as when it was registered for another instantiation of the same generic type.
+	pub fn register_named<T: UserType>(&mut self, name: &CStr) -> Type {
+		register_with_key::<T>(self, TypeKey::Named(name), layout_hash::<T>(name), needs_gc::<T>())
 	}
 
 	/// Returns the [`Type`] of the Lua user type associated with `T`.
 	/// 
+	/// This function looks up the [`Type`] in the registry on every call.
+	/// In hot paths, such as constructors, prefer functions pushed with [`SelfCtx::push_method`],
+	/// which store the [`Type`] in an upvalue and can use [`SelfCtx::push_new`].
+	/// 
 	/// # Errors
 	/// This function will raise an [error](crate::errors)
 	/// if `T` has not been [`register`](Self::register)ed.
 	pub fn user_type_of<T: UserType>(&self) -> Type {
-		self.push_registry();
-		push_registry_key::<T>(self);
-		self.raw_get(-2); // registry[key]
+		push_static_type::<T>(self);
+		pop_registered_type(self)
+	}
 
-		if !self.is_type(-1, StdType::Number) {
-			self.throw_error(c"type does not have an associated type ID in this Lua state")
+	/// Returns the [`Type`] of the Lua user type associated with `T`,
+	/// or `None` if `T` has not been [`register`](Self::register)ed.
+	pub fn try_user_type_of<T: UserType>(&self) -> Option<Type> {
+		push_static_type::<T>(self);
+		try_pop_registered_type(self)
+	}
+
+	/// Returns `true` if `T` has been [`register`](Self::register)ed in this Lua state.
+	pub fn is_registered<T: UserType>(&self) -> bool {
+		self.try_user_type_of::<T>().is_some()
+	}
+
+	/// Returns a context for the user type `T` with the given [`Type`],
+	/// which is usually the one returned by [`register`](Self::register).
+	/// 
+	/// This can be used to push functions outside of [`UserType::init_metatable`]
+	/// that have fast access to the [`Type`], like constructors.
+	/// 
+	/// # Examples
+	/// ```
+	/// # use gmbm::prelude::*;
+	/// # struct MyType;
+	/// # gmod13_type!(MyType);
+	/// # impl LuaUserType for MyType { fn init_metatable(_: LuaSelfCtx<'_, Self>) {} }
+	/// fn open(lua: &mut Lua) {
+	///     let ty = lua.register::<MyType>();
+	///     lua.pop(1);
+	/// 
+	///     lua.push_globals();
+	///     unsafe { lua.user_type_ctx::<MyType>(ty) }.push_method(gmod13_method!(MyType => mut lua => {
+	///         lua.push_new(MyType);
+	///         1
+	///     }));
+	///     lua.set_field(-2, c"MyType");
+	///     lua.pop(1);
+	/// }
+	/// ```
+	/// 
+	/// # Safety
+	/// `ty` must be the correct type identifier for `T`.
+	pub unsafe fn user_type_ctx<T: UserType>(&mut self, ty: Type) -> SelfCtx<'_, T> {
+		unsafe { SelfCtx::new(self, ty) }
+	}
+
+	/// Returns the [`Type`] of the Lua user type registered with [`register_named`](Self::register_named)
+	/// under the given `name`.
+	/// 
+	/// # Errors
+	/// This function will raise an [error](crate::errors)
+	/// if no type has been registered under `name`.
+	pub fn user_type_of_named(&mut self, name: &CStr) -> Type {
+		TypeKey::Named(name).push_value(self);
+		pop_registered_type(self)
+	}
+}
+
+/// Writes the [`RawUd`] header of the userdata at the top of the stack
+/// and sets its metatable to the one associated with `ty`.
+/// 
+/// # Safety
+/// `ud` must point to the header of the userdata at the top of the stack,
+/// and `data` must point to an initialized value of the type associated with `ty`,
+/// which must have been created by [`Box::into_raw`](alloc::boxed::Box::into_raw) if `boxed` is `true`.
+unsafe fn init_userdata<T: UserType>(lua: &mut Lua, ud: *mut RawUd, data: *mut T, ty: Type, boxed: bool) {
+	let raw_ty = ty.0;
+	unsafe {
+		ud.write(RawUd {
+			data: data as _,
+			ty: raw_ty as _,
+			boxed,
+			rust_ty: raw_ty,
+			magic: RAW_UD_MAGIC,
+			layout_hash: T::LAYOUT_HASH,
+			borrow: UNBORROWED,
+		});
+	}
+
+	if lua.push_metatable(ty) {
+		lua.set_metatable(-2);
+	}
+	if T::TRACK_INSTANCES {
+		instances::track_instance(lua, ty);
+	}
+}
+
+fn register_with_key<T: UserType>(lua: &mut Lua, key: TypeKey<'_>, layout_hash: Bits, needs_gc: bool) -> Type {
+	check_registration::<T>(lua, key, layout_hash);
+
+	key.push_value(lua);
+	if let Some(ty) = try_pop_registered_type(lua) {
+		if !lua.push_metatable(ty) {
+			lua.throw_error(c"metatable of registered type is missing")
+		}
+		if needs_gc {
+			// The type may have been registered inline before, without a finalizer.
+			lua.get_field(-1, c"__gc");
+			let has_gc = !lua.is_type(-1, StdType::Nil);
+			lua.pop(1);
+			if !has_gc {
+				let mut cx = unsafe { SelfCtx::<T>::new(lua, ty) };
+				cx.push_method(user_type_gc::<T>);
+				cx.set_field(-2, c"__gc");
+			}
 		}
+		return ty
+	}
+
+	let ty = lua.create_metatable(key.name());
+
+	lua.push_bits(layout_hash);
+	lua.set_field(-2, LAYOUT_HASH_FIELD);
+	lua.push_string(type_name::<T>());
+	lua.set_field(-2, RUST_TYPE_FIELD);
+
+	lua.push_bits(ty.0 as _);
+	key.set_value(lua);
+
+	let mut cx = unsafe { SelfCtx::new(lua, ty) };
+	if needs_gc {
+		cx.push_method(user_type_gc::<T>);
+		cx.set_field(-2, c"__gc");
+	}
+	T::init_metatable(cx);
+
+	ty
+}
+
+/// Pops a [`Type`] previously stored with [`TypeKey::set_value`] from the stack.
+fn pop_registered_type(lua: &Lua) -> Type {
+	if let Some(ty) = try_pop_registered_type(lua) {
+		ty
+	} else {
+		lua.throw_error(c"type does not have an associated type ID in this Lua state")
+	}
+}
+
+/// Pops a [`Type`] previously stored with [`TypeKey::set_value`] from the stack,
+/// or returns `None` if there is no such value.
+fn try_pop_registered_type(lua: &Lua) -> Option<Type> {
+	let ty = if lua.is_type(-1, StdType::Number) {
+		Some(Type(lua.get_bits(-1) as _))
+	} else {
+		None
+	};
+	lua.pop(1);
+	ty
+}
+
+/// Pushes the value stored under the static key of `T`.
+fn push_static_type<T: UserType>(lua: &Lua) {
+	lua.push_registry();
+	push_registry_key::<T>(lua);
+	lua.raw_get(-2); // registry[key]
+	lua.remove(-2);
+}
+
+/// Raises an error if the metatable for `key` already exists,
+/// but was not registered for `T` under `key` with the same `layout_hash`.
+fn check_registration<T: UserType>(lua: &mut Lua, key: TypeKey<'_>, layout_hash: Bits) {
+	key.push_value(lua);
+	let registered_here = lua.is_type(-1, StdType::Number);
+	lua.pop(1);
+
+	lua.push_registry();
+	lua.get_field(-1, key.name()); // registry[name]
+	if !lua.is_type(-1, StdType::Table) {
+		lua.pop(2);
+		return
+	}
+	if !registered_here {
+		// The metatable was created by another binary module, a script, or under another key.
+		lua.throw_error(c"metatable name is already in use by a type with a different registry key")
+	}
+
+	lua.get_field(-1, LAYOUT_HASH_FIELD);
+	let same_layout = lua.is_type(-1, StdType::Number) && lua.get_bits(-1) == layout_hash;
+	lua.pop(1);
+	if !same_layout {
+		lua.throw_error(c"metatable name is already in use by a type with a different layout")
+	}
 
-		let raw_ty = self.get_bits(-1);
-		self.pop(1);
-		Type(raw_ty as _)
+	// Types with the same layout may still be different types, such as instantiations of one generic type.
+	lua.get_field(-1, RUST_TYPE_FIELD);
+	let same_type = lua.get_string(-1) == Some(type_name::<T>().as_bytes());
+	lua.pop(1);
+	if !same_type {
+		lua.throw_error(c"metatable name is already in use by a different Rust type")
+	}
+	lua.pop(2);
+}
+
+/// Name of the metatable field which stores [`UserTypeBase::LAYOUT_HASH`].
+const LAYOUT_HASH_FIELD: &CStr = c"__gmbm_layout";
+/// Name of the metatable field which stores the [`type_name`] of the Rust type it was registered for.
+const RUST_TYPE_FIELD: &CStr = c"__gmbm_type";
+
+/// Key under which the [`Type`] of a registered user type is stored.
+#[derive(Clone, Copy)]
+enum TypeKey<'a> {
+	/// [`UserTypeBase::ID`], whose address is used as a key in the registry.
+	Static(&'static CStr),
+	/// Name given to [`Lua::register_named`],
+	/// which is used as a key in the table at `registry[NAMED_TYPES_KEY]`.
+	Named(&'a CStr),
+}
+
+impl<'a> TypeKey<'a> {
+	/// Returns the name of the metatable associated with this key.
+	const fn name(self) -> &'a CStr {
+		match self {
+			Self::Static(id) => id,
+			Self::Named(name) => name,
+		}
+	}
+
+	/// Pushes the value stored under this key, or `nil` if there is none.
+	fn push_value(self, lua: &mut Lua) {
+		lua.push_registry();
+		match self {
+			Self::Static(id) => {
+				push_static_key(lua, id);
+				lua.raw_get(-2); // registry[key]
+			}
+			Self::Named(name) => {
+				push_named_types_key(lua);
+				lua.raw_get(-2); // registry[NAMED_TYPES_KEY]
+				if lua.is_type(-1, StdType::Table) {
+					lua.push_c_string(name);
+					lua.raw_get(-2); // registry[NAMED_TYPES_KEY][name]
+					lua.remove(-2);
+				} else {
+					lua.pop(1);
+					lua.push_nil();
+				}
+			}
+		}
+		lua.remove(-2);
+	}
+
+	/// Pops a value from the stack and stores it under this key.
+	fn set_value(self, lua: &mut Lua) {
+		lua.push_registry();
+		match self {
+			Self::Static(id) => {
+				push_static_key(lua, id);
+				lua.push_value(-3);
+				lua.raw_set(-3); // registry[key] = value
+				lua.pop(2);
+			}
+			Self::Named(name) => {
+				push_named_types_key(lua);
+				lua.raw_get(-2); // registry[NAMED_TYPES_KEY]
+				if !lua.is_type(-1, StdType::Table) {
+					lua.pop(1);
+					lua.create_table();
+					push_named_types_key(lua);
+					lua.push_value(-2);
+					lua.raw_set(-4); // registry[NAMED_TYPES_KEY] = {}
+				}
+				lua.push_c_string(name);
+				lua.push_value(-4);
+				lua.raw_set(-3); // registry[NAMED_TYPES_KEY][name] = value
+				lua.pop(3);
+			}
+		}
 	}
 }
 
 fn push_registry_key<T: UserType>(lua: &Lua) {
-	unsafe { lua.push_light_userdata(T::ID.as_ptr() as *mut ()) }
+	push_static_key(lua, T::ID)
 }
 
+fn push_static_key(lua: &Lua, id: &'static CStr) {
+	unsafe { lua.push_light_userdata(id.as_ptr() as *mut ()) }
+}
+
+/// Unique address used as the registry key for the table of named user types.
+static NAMED_TYPES_KEY: u8 = 0;
+
+fn push_named_types_key(lua: &Lua) {
+	unsafe { lua.push_light_userdata(&raw const NAMED_TYPES_KEY as *mut u8) }
+}
+
+/// Value of [`RawUd::magic`] in userdata created by this crate.
+pub const RAW_UD_MAGIC: u32 = u32::from_le_bytes(*b"gmbm");
+
 /// Raw header for userdata allocated in a Lua state.
+/// 
+/// The header starts with the fields of [`UserData`],
+/// so userdata created for user types can be read by C++ binary modules,
+/// and Garry's Mod reads its [`Type`] like for any other userdata.
+/// See [`RawUd::user_data`].
+/// 
+/// Besides the [`Type`], each header records a [magic value](RAW_UD_MAGIC)
+/// and the [`LAYOUT_HASH`](UserTypeBase::LAYOUT_HASH) of the Rust type of its value,
+/// so that userdata which was forged by a script or created by another binary module
+/// is not mistaken for a value of a Rust type just because it has the same [`Type`].
+#[repr(C)]
 pub struct RawUd {
 	pub data: *mut c_void,
 	pub ty: c_uchar,
+	/// `true` if [`data`](Self::data) points to a separate heap allocation,
+	/// as created for boxed user types.
+	pub boxed: bool,
 	pub rust_ty: RawType,
+	/// [`RAW_UD_MAGIC`] if the userdata was created by this crate.
+	pub magic: u32,
+	/// [`UserTypeBase::LAYOUT_HASH`] of the Rust type of the value.
+	pub layout_hash: Bits,
+	/// Borrow state of the value, as used by [`UdRef`] and [`UdRefMut`].
+	pub borrow: BorrowFlag,
+}
+
+impl RawUd {
+	/// Returns the [`UserData`] header that this header starts with.
+	pub const fn user_data(&self) -> &UserData {
+		// SAFETY: `RawUd` starts with the same fields as `UserData`, as asserted below.
+		unsafe { &*(self as *const Self).cast() }
+	}
+}
+
+const _: () = {
+	use core::mem::offset_of;
+	assert!(offset_of!(RawUd, data) == offset_of!(UserData, data));
+	assert!(offset_of!(RawUd, ty) == offset_of!(UserData, ty));
+	assert!(align_of::<RawUd>() >= align_of::<UserData>());
+};
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+	use core::marker::PhantomData;
+
+	use crate::gmod13::{
+		func::Rets,
+		testing::MockLua,
+	};
+
+	use super::*;
+
+	struct Small {
+		_value: u32,
+	}
+	crate::gmod13_type!(Small);
+	impl UserType for Small {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	struct Large {
+		_value: u64,
+	}
+	crate::gmod13_type!(Large);
+	impl UserType for Large {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	/// Type whose metatable name is the same as the name that other types are registered under.
+	struct SharedName;
+	unsafe impl UserTypeBase for SharedName {
+		const ID: &'static CStr = c"Shared";
+		const EXPECTED_ERR: &'static CStr = c"Shared expected";
+	}
+	impl UserType for SharedName {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	#[test]
+	fn register_twice() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		let ty = lua.register::<Small>();
+		assert_eq!(lua.register::<Small>(), ty);
+		assert_eq!(lua.register_named::<Large>(c"Shared"), lua.register_named::<Large>(c"Shared"));
+		assert_eq!(lua.user_type_of::<Small>(), ty);
+	}
+
+	#[test]
+	#[should_panic(expected = "different registry key")]
+	fn same_name_different_key() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.register_named::<Small>(c"Shared");
+		lua.register::<SharedName>();
+	}
+
+	#[test]
+	#[should_panic(expected = "different registry key")]
+	fn same_name_as_script_metatable() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.create_metatable(c"Shared");
+		lua.pop(1);
+		lua.register::<SharedName>();
+	}
+
+	/// Generic type, each instantiation of which has the same layout.
+	struct Wrapper<T> {
+		_value: u32,
+		_marker: PhantomData<T>,
+	}
+	unsafe impl<T> UserTypeBase for Wrapper<T> {
+		const ID: &'static CStr = c"Wrapper";
+		const EXPECTED_ERR: &'static CStr = c"Wrapper expected";
+	}
+	impl<T> UserType for Wrapper<T> {
+		fn init_metatable(_: SelfCtx<'_, Self>) {}
+	}
+
+	#[test]
+	#[should_panic(expected = "different Rust type")]
+	fn same_name_same_layout_different_type() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.register_named::<Wrapper<u8>>(c"Wrapper");
+		lua.register_named::<Wrapper<i8>>(c"Wrapper");
+	}
+
+	#[test]
+	#[should_panic(expected = "different layout")]
+	fn same_name_different_layout() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.register_named::<Small>(c"Shared");
+		lua.register_named::<Large>(c"Shared");
+	}
+
+	#[derive(Clone, PartialEq)]
+	struct Point {
+		x: i32,
+	}
+	crate::gmod13_type!(Point);
+	impl UserType for Point {
+		fn init_metatable(mut cx: SelfCtx<'_, Self>) {
+			cx.push_value(-1);
+			cx.set_field(-2, c"__index");
+			cx.set_eq();
+			cx.set_copy(c"Copy");
+			cx.push_method(point_add);
+			cx.set_field(-2, c"__add");
+		}
+	}
+
+	/// `__add`, which adds a number or another [`Point`] to a [`Point`].
+	extern "C-unwind" fn point_add(cx: MethodFuncCtx<'_, Point>) -> Rets {
+		let mut lua = cx.lua();
+		let x = {
+			let operands = lua.check_operands();
+			let other = match operands.other_number() {
+				Some(n) => n as i32,
+				None => operands.other_ud::<Point>().map_or(0, move |other| other.x),
+			};
+			operands.this().x + other
+		};
+		lua.push_new(Point { x });
+		Rets::new(1)
+	}
+
+	/// Calls `__add` of [`Point`] with the values at `a` and `b`, and returns the `x` of the result.
+	fn call_add(lua: &mut Lua, a: StackPos, b: StackPos) -> i32 {
+		let (a, b) = (lua.abs_index(a), lua.abs_index(b));
+		let ty = lua.user_type_of::<Point>();
+		lua.push_metatable(ty);
+		lua.get_field(-1, c"__add");
+		lua.remove(-2);
+		lua.push_value(a);
+		lua.push_value(b);
+		lua.call(2, 1);
+		let x = unsafe { lua.check_ud::<Point>(ty, -1) }.x;
+		lua.pop(1);
+		x
+	}
+
+	#[test]
+	fn eq_and_copy() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_auto_user_type(Point { x: 1 });
+		lua.push_auto_user_type(Point { x: 1 });
+		assert!(lua.equal(-1, -2));
+
+		lua.push_self_method(-1, c"Copy");
+		lua.call(1, 1);
+		let ty = lua.user_type_of::<Point>();
+		let copy = unsafe { lua.test_ud_ptr::<Point>(ty, -1) }.unwrap();
+		assert!(unsafe { copy.as_ref() }.x == 1);
+		assert!(!lua.raw_equal(-1, -2));
+	}
+
+	#[test]
+	#[should_panic(expected = "already mutably borrowed")]
+	fn eq_while_mutably_borrowed() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_auto_user_type(Point { x: 1 });
+		lua.push_auto_user_type(Point { x: 1 });
+		let ty = lua.user_type_of::<Point>();
+		let _guard = unsafe { lua.check_ud_borrow_mut::<Point>(ty, -1) };
+		lua.equal(-1, -2);
+	}
+
+	#[test]
+	fn operands_on_either_side() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_auto_user_type(Point { x: 1 });
+		lua.push_number(2.0);
+		assert_eq!(call_add(lua, -2, -1), 3);
+		assert_eq!(call_add(lua, -1, -2), 3);
+		assert_eq!(call_add(lua, -2, -2), 2);
+	}
+
+	#[test]
+	#[should_panic(expected = "already mutably borrowed")]
+	fn operands_while_mutably_borrowed() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_auto_user_type(Point { x: 1 });
+		lua.push_auto_user_type(Point { x: 2 });
+		let ty = lua.user_type_of::<Point>();
+		let _guard = unsafe { lua.check_ud_borrow_mut::<Point>(ty, -1) };
+		call_add(lua, -2, -1);
+	}
+
+	#[test]
+	#[should_panic(expected = "already mutably borrowed")]
+	fn copy_while_mutably_borrowed() {
+		let mut mock = MockLua::new();
+		let lua = mock.lua();
+		lua.push_auto_user_type(Point { x: 1 });
+		let ty = lua.user_type_of::<Point>();
+		let _guard = unsafe { lua.check_ud_borrow_mut::<Point>(ty, -1) };
+		lua.push_self_method(-1, c"Copy");
+		lua.call(1, 1);
+	}
 }
@@ -0,0 +1,230 @@
+use crate::gmod13_type;
+
+use super::{
+	super::{
+		func::Rets,
+		Lua, Ref, StdType,
+	},
+	MethodFuncCtx, SelfCtx, UserType,
+};
+
+/// Promise which is settled later by Rust code through a [`Promise`],
+/// giving asynchronous native APIs a consistent callback interface in Lua.
+/// 
+/// In Lua, `promise:Then(callback)` calls `callback(value)` once the promise is resolved,
+/// and `promise:Catch(callback)` calls `callback(error)` once the promise is rejected.
+/// Callbacks added after the promise is settled are called immediately.
+/// Both methods return the promise itself, so they can be chained.
+/// `promise:IsPending()` returns `true` if the promise hasn't been settled yet.
+/// 
+/// Values of this type are created with [`Lua::create_promise`].
+#[derive(Debug)]
+pub struct LuaPromise {
+	state: PromiseState,
+	value: Option<Ref>,
+	on_resolve: Option<Ref>,
+	on_reject: Option<Ref>,
+}
+
+gmod13_type!(LuaPromise);
+
+/// State of a [`LuaPromise`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromiseState {
+	/// The promise hasn't been settled yet.
+	Pending,
+	/// The promise has been resolved with a value.
+	Resolved,
+	/// The promise has been rejected with an error.
+	Rejected,
+}
+
+impl LuaPromise {
+	/// Returns the current state of the promise.
+	pub const fn state(&self) -> PromiseState {
+		self.state
+	}
+}
+
+impl UserType for LuaPromise {
+	fn init_metatable(mut cx: SelfCtx<'_, Self>) {
+		cx.push_value(-1);
+		cx.set_field(-2, c"__index");
+
+		cx.push_method(promise_then);
+		cx.set_field(-2, c"Then");
+		cx.push_method(promise_catch);
+		cx.set_field(-2, c"Catch");
+		cx.push_method(promise_is_pending);
+		cx.set_field(-2, c"IsPending");
+	}
+
+	unsafe fn collect(&mut self, cx: SelfCtx<'_, Self>) {
+		for lua_ref in [self.value, self.on_resolve, self.on_reject].into_iter().flatten() {
+			cx.free_ref(lua_ref);
+		}
+	}
+}
+
+/// Functions for promises which are settled by Rust code.
+impl Lua {
+	/// Pushes a new pending [`LuaPromise`] onto the stack,
+	/// registering the type first if needed,
+	/// and returns a [`Promise`] which settles it.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn create_promise(&mut self) -> Promise {
+		self.push_auto_user_type(LuaPromise {
+			state: PromiseState::Pending,
+			value: None,
+			on_resolve: None,
+			on_reject: None,
+		});
+		self.push_value(-1);
+		Promise(self.create_ref())
+	}
+}
+
+/// Handle to a pending [`LuaPromise`], which is used to settle it from Rust code.
+/// 
+/// The handle holds a [`Ref`] to the promise, which is freed when it is settled.
+/// Like any other Lua API, the promise must be settled on the thread that owns the Lua state.
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[must_use = "the promise should be settled with `resolve` or `reject`"]
+pub struct Promise(Ref);
+
+impl Promise {
+	/// Returns the [`Ref`] to the promise.
+	pub const fn lua_ref(&self) -> Ref {
+		self.0
+	}
+
+	/// Resolves the promise with the value pushed by `push_value`,
+	/// and calls the callbacks added with `:Then`.
+	/// 
+	/// Nothing happens if the promise has already been settled or destroyed.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn resolve<F: FnOnce(&mut Lua)>(self, lua: &mut Lua, push_value: F) {
+		self.settle(lua, PromiseState::Resolved, push_value)
+	}
+
+	/// Rejects the promise with the error pushed by `push_error`,
+	/// and calls the callbacks added with `:Catch`.
+	/// 
+	/// Nothing happens if the promise has already been settled or destroyed.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn reject<F: FnOnce(&mut Lua)>(self, lua: &mut Lua, push_error: F) {
+		self.settle(lua, PromiseState::Rejected, push_error)
+	}
+
+	fn settle<F: FnOnce(&mut Lua)>(self, lua: &mut Lua, state: PromiseState, push_value: F) {
+		lua.push_ref(self.0);
+		lua.free_ref(self.0);
+
+		let ty = lua.user_type_of::<LuaPromise>();
+		let promise = match unsafe { lua.test_ud_ptr::<LuaPromise>(ty, -1) } {
+			Some(promise) if unsafe { promise.as_ref() }.state == PromiseState::Pending => promise,
+			_ => {
+				lua.pop(1);
+				return
+			}
+		};
+
+		push_value(lua);
+		let value = lua.create_ref();
+
+		// The promise is kept alive by the stack while callbacks run,
+		// but no reference to it is held so that they can use it too.
+		let (callbacks, discarded) = unsafe {
+			let promise = &mut *promise.as_ptr();
+			promise.state = state;
+			promise.value = Some(value);
+			match state {
+				PromiseState::Resolved => (promise.on_resolve.take(), promise.on_reject.take()),
+				_ => (promise.on_reject.take(), promise.on_resolve.take()),
+			}
+		};
+		if let Some(discarded) = discarded {
+			lua.free_ref(discarded);
+		}
+
+		if let Some(callbacks) = callbacks {
+			lua.push_ref(callbacks);
+			lua.free_ref(callbacks);
+			for i in 1..=lua.length_of(-1) {
+				lua.push_number(i as _);
+				lua.get_table(-2);
+				lua.push_ref(value);
+				lua.call(1, 0);
+			}
+			lua.pop(1);
+		}
+
+		lua.pop(1);
+	}
+}
+
+/// Calls the callback at argument `2` with the value of the promise if it has already settled with `on`,
+/// or adds it to the callbacks for `on` if it is pending,
+/// and returns the promise.
+fn add_callback(mut lua: SelfCtx<'_, LuaPromise>, on: PromiseState) -> Rets {
+	lua.check_type(2, StdType::Function);
+
+	let promise = lua.check_self();
+	let (state, value) = (promise.state, promise.value);
+	let callbacks = match on {
+		PromiseState::Resolved => promise.on_resolve,
+		_ => promise.on_reject,
+	};
+
+	if state == PromiseState::Pending {
+		let callbacks = match callbacks {
+			Some(callbacks) => callbacks,
+			None => {
+				lua.create_table();
+				let callbacks = lua.create_ref();
+				let promise = lua.check_self_mut();
+				match on {
+					PromiseState::Resolved => promise.on_resolve = Some(callbacks),
+					_ => promise.on_reject = Some(callbacks),
+				}
+				callbacks
+			}
+		};
+
+		lua.push_ref(callbacks);
+		let n = lua.length_of(-1);
+		lua.push_number((n + 1) as _);
+		lua.push_value(2);
+		lua.set_table(-3);
+		lua.pop(1);
+	} else if state == on && let Some(value) = value {
+		lua.push_value(2);
+		lua.push_ref(value);
+		lua.call(1, 0);
+	}
+
+	lua.push_value(1);
+	Rets::new(1)
+}
+
+extern "C-unwind" fn promise_then(cx: MethodFuncCtx<'_, LuaPromise>) -> Rets {
+	add_callback(cx.lua(), PromiseState::Resolved)
+}
+
+extern "C-unwind" fn promise_catch(cx: MethodFuncCtx<'_, LuaPromise>) -> Rets {
+	add_callback(cx.lua(), PromiseState::Rejected)
+}
+
+extern "C-unwind" fn promise_is_pending(cx: MethodFuncCtx<'_, LuaPromise>) -> Rets {
+	let lua = cx.lua();
+	lua.push_bool(lua.check_self().state == PromiseState::Pending);
+	Rets::new(1)
+}
@@ -0,0 +1,54 @@
+use super::{
+	super::{
+		Lua, StdType,
+	},
+	UserType,
+	push_static_key,
+};
+
+/// Functions for user types with a single instance per Lua state.
+impl Lua {
+	/// Pushes the instance of `T` which is shared by the whole Lua state,
+	/// creating it with [`Default::default`] and [`register`](Self::register)ing `T` first if needed.
+	/// 
+	/// This is intended for marker types, such as singleton namespaces, which are usually zero-sized.
+	/// Every call pushes the same userdata, which is cached in the registry,
+	/// so it is never collected.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	/// See [`register`](Self::register).
+	pub fn push_singleton<T: UserType + Default>(&mut self) {
+		push_singletons(self);
+		push_static_key(self, T::ID);
+		self.raw_get(-2); // singletons[key]
+		if self.is_type(-1, StdType::Nil) {
+			self.pop(1);
+			self.push_auto_user_type(T::default());
+			push_static_key(self, T::ID);
+			self.push_value(-2);
+			self.raw_set(-4); // singletons[key] = value
+		}
+		self.remove(-2);
+	}
+}
+
+/// Unique address used as the registry key for the table of singleton instances.
+static SINGLETONS_KEY: u8 = 0;
+
+/// Pushes the table at `registry[SINGLETONS_KEY]`, creating it if needed.
+fn push_singletons(lua: &mut Lua) {
+	lua.push_registry();
+	unsafe { lua.push_light_userdata(&raw const SINGLETONS_KEY as *mut u8) }
+	lua.raw_get(-2); // registry[SINGLETONS_KEY]
+	if !lua.is_type(-1, StdType::Table) {
+		lua.pop(1);
+		lua.create_table();
+		unsafe { lua.push_light_userdata(&raw const SINGLETONS_KEY as *mut u8) }
+		lua.push_value(-2);
+		lua.raw_set(-4); // registry[SINGLETONS_KEY] = {}
+	}
+	lua.remove(-2);
+}
@@ -0,0 +1,69 @@
+use core::ffi::{
+	CStr, c_uint,
+};
+
+use super::{
+	Lua, LuaString, Number, StdType,
+};
+
+/// Functions for using the built-in utilities of the `util` library.
+impl Lua {
+	/// Compresses `data` with LZMA using `util.Compress`,
+	/// returning `None` if compression fails.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn compress<D: AsRef<[u8]>>(&mut self, data: D) -> Option<LuaString<'_>> {
+		self.call_util(c"Compress", data, |_| 0);
+		LuaString::from_top(self)
+	}
+
+	/// Decompresses LZMA-compressed `data` using `util.Decompress`,
+	/// returning `None` if it is invalid or if the result would be larger than `max_size` bytes.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn decompress<D: AsRef<[u8]>>(&mut self, data: D, max_size: Option<usize>) -> Option<LuaString<'_>> {
+		self.call_util(c"Decompress", data, move |l| {
+			if let Some(max_size) = max_size {
+				l.push_number(max_size as Number);
+				1
+			} else {
+				0
+			}
+		});
+		LuaString::from_top(self)
+	}
+
+	/// Returns the CRC-32 checksum of `data` using `util.CRC`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn crc<D: AsRef<[u8]>>(&mut self, data: D) -> u32 {
+		self.call_util(c"CRC", data, |_| 0);
+		// `util.CRC` returns the checksum as a decimal string.
+		let crc = self.get_number(-1) as u32;
+		self.pop(1);
+		crc
+	}
+
+	/// Returns the hexadecimal SHA-256 digest of `data` using `util.SHA256`.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn sha256<D: AsRef<[u8]>>(&mut self, data: D) -> LuaString<'_> {
+		self.call_util(c"SHA256", data, |_| 0);
+		if !self.is_type(-1, StdType::String) {
+			self.throw_error(c"util.SHA256 did not return a string")
+		}
+		LuaString::from_top(self).unwrap()
+	}
+
+	/// Calls `util[name](data, ...)` with extra arguments pushed by `push_args`, leaving one result on the stack.
+	fn call_util<D: AsRef<[u8]>>(&mut self, name: &CStr, data: D, push_args: impl FnOnce(&mut Self) -> c_uint) {
+		self.get_global_field(c"util", name);
+		self.push_string(data);
+		let n_args = push_args(self) + 1;
+		self.call(n_args, 1);
+	}
+}
@@ -0,0 +1,103 @@
+use crate::source::{
+	Vector, QAngle,
+};
+
+use super::{
+	Lua, Number, StackPos, StdType,
+};
+
+/// Functions for constructing vectors and angles through their Lua constructors.
+impl Lua {
+	/// Pushes the result of calling the global `Vector(x, y, z)` function.
+	/// 
+	/// Unlike [`Lua::push_vector`], which creates the object natively,
+	/// this creates the vector exactly like Lua code would,
+	/// respecting any changes made to `Vector` by scripts,
+	/// at the cost of a Lua function call.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn construct_vector(&mut self, x: Number, y: Number, z: Number) {
+		self.get_global(c"Vector");
+		self.push_number(x);
+		self.push_number(y);
+		self.push_number(z);
+		self.call(3, 1);
+	}
+
+	/// Pushes the result of calling the global `Angle(pitch, yaw, roll)` function.
+	/// 
+	/// Unlike [`Lua::push_angle`], which creates the object natively,
+	/// this creates the angle exactly like Lua code would,
+	/// respecting any changes made to `Angle` by scripts,
+	/// at the cost of a Lua function call.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn construct_angle(&mut self, pitch: Number, yaw: Number, roll: Number) {
+		self.get_global(c"Angle");
+		self.push_number(pitch);
+		self.push_number(yaw);
+		self.push_number(roll);
+		self.call(3, 1);
+	}
+}
+
+/// Functions for type-checked access to vectors and angles.
+impl Lua {
+	/// If the value at `stack_pos` is a [`Vector`], returns a reference to it.
+	/// Otherwise, returns `None`.
+	/// 
+	/// Unlike [`Lua::get_vector`], this distinguishes an actual zero vector from a value of the wrong type.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn try_get_vector(&self, stack_pos: StackPos) -> Option<&Vector> {
+		if self.is_type(stack_pos, StdType::Vector) {
+			Some(self.get_vector(stack_pos))
+		} else {
+			None
+		}
+	}
+
+	/// If the value at `stack_pos` is a [`QAngle`], returns a reference to it.
+	/// Otherwise, returns `None`.
+	/// 
+	/// Unlike [`Lua::get_angle`], this distinguishes an actual zero angle from a value of the wrong type.
+	/// 
+	/// This method is not part of the public C++ API.
+	pub fn try_get_angle(&self, stack_pos: StackPos) -> Option<&QAngle> {
+		if self.is_type(stack_pos, StdType::Angle) {
+			Some(self.get_angle(stack_pos))
+		} else {
+			None
+		}
+	}
+
+	/// If the value at `stack_pos` is a [`Vector`], returns a reference to it.
+	/// Otherwise, throws an error.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_vector(&self, stack_pos: StackPos) -> &Vector {
+		self.check_type(stack_pos, StdType::Vector);
+		self.get_vector(stack_pos)
+	}
+
+	/// If the value at `stack_pos` is a [`QAngle`], returns a reference to it.
+	/// Otherwise, throws an error.
+	/// 
+	/// This method is not part of the public C++ API.
+	/// 
+	/// # Errors
+	/// The inner Lua state may raise an [error](crate::errors).
+	pub fn check_angle(&self, stack_pos: StackPos) -> &QAngle {
+		self.check_type(stack_pos, StdType::Angle);
+		self.get_angle(stack_pos)
+	}
+}
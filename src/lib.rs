@@ -1,10 +1,16 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod gmod13;
 pub mod source;
 
 pub mod prelude;
 
+#[cfg(feature = "build-support")]
+pub mod build;
+
 #[cfg(doc)]
 
 /// # Explanation of API errors in Rust binary modules
@@ -4,6 +4,7 @@ pub use crate::{
 	gmod13::{
 		func::{
 			Ctx as LuaCtx, Rets as LuaRets,
+			LuaThrow,
 		},
 		Special as LuaSpecial,
 		Type as LuaType,
@@ -13,6 +14,7 @@ pub use crate::{
 		Bits as LuaBits,
 		upvalue_index as lua_upvalue_index,
 		Module as LuaModule,
+		ToLua,
 	},
 	source::{
 		Vector as SeVector,
@@ -21,6 +23,7 @@ pub use crate::{
 	gmod13_fn,
 	gmod13_module, gmod13_module_with, gmod13_module_static,
 	gmod13_type,
+	lua_table,
 };
 
 #[cfg(feature = "user-types")]
@@ -31,3 +34,6 @@ pub use crate::{
 	},
 	gmod13_method,
 };
+
+#[cfg(all(feature = "user-types", feature = "alloc"))]
+pub use crate::gmod13::user_types::UserTypeBoxed as LuaUserTypeBoxed;
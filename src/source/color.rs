@@ -0,0 +1,30 @@
+/// 32-bit RGBA color, with 8 bits per component.
+/// 
+/// Cross-referenced with `color32_s` in `basetypes.h` in `tier0`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Color32 {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8,
+	pub a: u8,
+}
+
+impl Color32 {
+	/// Opaque white.
+	pub const WHITE: Self = Self::rgb(255, 255, 255);
+	/// Opaque black.
+	pub const BLACK: Self = Self::rgb(0, 0, 0);
+
+	/// Create a new color from its components.
+	pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+		Self {
+			r, g, b, a,
+		}
+	}
+
+	/// Create a new opaque color from its red, green and blue components.
+	pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+		Self::new(r, g, b, 255)
+	}
+}
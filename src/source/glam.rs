@@ -0,0 +1,120 @@
+//! Conversions between Source Engine vectors and [`glam`] vectors.
+//! 
+//! Vectors can be converted with the [`GlamVector`] trait,
+//! which also allows them to be viewed as [`Vec3`]s without copying them.
+//! Without the `rse-math` feature, [`Vector`] also implements [`From`] and [`Into`] for [`Vec3`].
+//! 
+//! [`Vec3`]s are also pushed to and read from Lua as [`Vector`]s.
+//! 
+//! # Examples
+//! ```
+//! use gmbm::prelude::*;
+//! use gmbm::source::GlamVector;
+//! use glam::Vec3;
+//! 
+//! /// Lua function `(pos: Vector, dir: Vector, dist: number) -> Vector`.
+//! extern "C-unwind" fn trace_end(cx: LuaCtx<'_>) -> LuaRets {
+//!     let lua = cx.lua();
+//!     let pos: Vec3 = lua.check_vector(1).to_glam();
+//!     let dir = lua.check_vector(2).as_glam().normalize_or_zero();
+//!     let dist = lua.check_number(3) as f32;
+//!     lua.push(pos + dir * dist);
+//!     LuaRets::new(1)
+//! }
+//! ```
+
+use core::mem::{
+	align_of, size_of,
+};
+
+use glam::Vec3;
+
+use crate::gmod13::{
+	FromLua, ToLua,
+	Lua, StackPos,
+};
+
+use super::{
+	QAngle, Vector,
+};
+
+/// Trait for Source Engine vectors which can be converted to and from [`Vec3`].
+/// 
+/// This is implemented for [`Vector`] and [`QAngle`].
+/// The components are kept in the same order, so the pitch, yaw and roll of a [`QAngle`]
+/// are the `x`, `y` and `z` of the [`Vec3`].
+pub trait GlamVector: Sized {
+	/// Creates a vector from the components of `v`.
+	fn from_glam(v: Vec3) -> Self;
+
+	/// Returns the components of this vector as a [`Vec3`].
+	fn to_glam(&self) -> Vec3 {
+		*self.as_glam()
+	}
+
+	/// Returns a reference to this vector as a [`Vec3`], without copying it.
+	fn as_glam(&self) -> &Vec3;
+
+	/// Returns a mutable reference to this vector as a [`Vec3`], without copying it.
+	fn as_glam_mut(&mut self) -> &mut Vec3;
+}
+
+// Casting references is only sound if the layouts are the same, which `#[repr(C)]` guarantees for the fields.
+const _: () = assert!(size_of::<Vector>() == size_of::<Vec3>() && align_of::<Vector>() == align_of::<Vec3>());
+const _: () = assert!(size_of::<QAngle>() == size_of::<Vec3>() && align_of::<QAngle>() == align_of::<Vec3>());
+
+macro_rules! impl_glam_vector {
+	($($Vector:ty)*) => {
+		$(
+			impl GlamVector for $Vector {
+				fn from_glam(v: Vec3) -> Self {
+					Self::new(v.x, v.y, v.z)
+				}
+
+				fn as_glam(&self) -> &Vec3 {
+					// SAFETY: Both types are `#[repr(C)]` structures of three `f32`s.
+					unsafe { &*(self as *const Self as *const Vec3) }
+				}
+
+				fn as_glam_mut(&mut self) -> &mut Vec3 {
+					// SAFETY: See `as_glam`.
+					unsafe { &mut *(self as *mut Self as *mut Vec3) }
+				}
+			}
+		)*
+	};
+}
+
+impl_glam_vector!(Vector);
+// Without `rse-math`, `QAngle` is an alias of `Vector`.
+#[cfg(feature = "rse-math")]
+impl_glam_vector!(QAngle);
+
+// With `rse-math`, `Vector` is defined in another crate, so `From` can't be implemented for it here.
+#[cfg(not(feature = "rse-math"))]
+impl From<Vec3> for Vector {
+	fn from(v: Vec3) -> Self {
+		Self::from_glam(v)
+	}
+}
+
+#[cfg(not(feature = "rse-math"))]
+impl From<Vector> for Vec3 {
+	fn from(v: Vector) -> Self {
+		v.to_glam()
+	}
+}
+
+/// [`Vec3`]s are pushed as Lua `Vector`s.
+impl ToLua for Vec3 {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_vector(&Vector::from_glam(self))
+	}
+}
+
+/// [`Vec3`]s are read from Lua `Vector`s.
+impl FromLua<'_> for Vec3 {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.try_get_vector(stack_pos).map(Vector::to_glam)
+	}
+}
@@ -0,0 +1,37 @@
+//! Compile-time assertions that the Source Engine structures match the layout expected by the engine.
+
+use core::mem::offset_of;
+
+use super::*;
+
+const _: () = {
+	assert!(size_of::<Vector>() == 12 && align_of::<Vector>() == 4);
+	assert!(size_of::<QAngle>() == 12 && align_of::<QAngle>() == 4);
+
+	assert!(size_of::<Color32>() == 4);
+	assert!(offset_of!(Color32, r) == 0);
+	assert!(offset_of!(Color32, g) == 1);
+	assert!(offset_of!(Color32, b) == 2);
+	assert!(offset_of!(Color32, a) == 3);
+
+	assert!(size_of::<VectorAligned>() == 16 && align_of::<VectorAligned>() == 16);
+	assert!(offset_of!(VectorAligned, x) == 0);
+	assert!(offset_of!(VectorAligned, y) == 4);
+	assert!(offset_of!(VectorAligned, z) == 8);
+
+	assert!(size_of::<Ray>() == 80 && align_of::<Ray>() == 16);
+	assert!(offset_of!(Ray, start) == 0);
+	assert!(offset_of!(Ray, delta) == 16);
+	assert!(offset_of!(Ray, start_offset) == 32);
+	assert!(offset_of!(Ray, extents) == 48);
+	assert!(offset_of!(Ray, world_axis_transform) == 64);
+	assert!(offset_of!(Ray, is_ray) == 64 + size_of::<*const ()>());
+	assert!(offset_of!(Ray, is_swept) == 65 + size_of::<*const ()>());
+};
+
+#[cfg(not(feature = "rse-math"))]
+const _: () = {
+	assert!(offset_of!(Vector, x) == 0);
+	assert!(offset_of!(Vector, y) == 4);
+	assert!(offset_of!(Vector, z) == 8);
+};
@@ -5,3 +5,20 @@ pub use no_rse::*;
 
 #[cfg(feature = "rse-math")]
 pub use rse_math::{Vector, QAngle};
+
+mod color;
+pub use color::*;
+mod ray;
+pub use ray::*;
+
+mod layout;
+
+#[cfg(feature = "glam")]
+mod glam;
+#[cfg(feature = "glam")]
+pub use self::glam::*;
+
+#[cfg(feature = "nalgebra")]
+mod nalgebra;
+#[cfg(feature = "nalgebra")]
+pub use self::nalgebra::*;
@@ -0,0 +1,121 @@
+//! Conversions between Source Engine vectors and [`nalgebra`] vectors.
+//! 
+//! Vectors can be converted with the [`NalgebraVector`] trait,
+//! which also allows them to be viewed as [`Vector3<f32>`]s without copying them.
+//! Without the `rse-math` feature, [`Vector`] also implements [`From`] and [`Into`] for [`Vector3<f32>`].
+//! 
+//! [`Vector3<f32>`]s are also pushed to and read from Lua as [`Vector`]s.
+//! 
+//! # Examples
+//! ```
+//! use gmbm::prelude::*;
+//! use gmbm::source::NalgebraVector;
+//! use nalgebra::Vector3;
+//! 
+//! /// Lua function `(a: Vector, b: Vector) -> Vector`,
+//! /// which returns the unit normal of the plane that contains `a` and `b`.
+//! extern "C-unwind" fn plane_normal(cx: LuaCtx<'_>) -> LuaRets {
+//!     let lua = cx.lua();
+//!     let a: Vector3<f32> = lua.check_vector(1).to_nalgebra();
+//!     let normal = a.cross(lua.check_vector(2).as_nalgebra()).normalize();
+//!     lua.push(normal);
+//!     LuaRets::new(1)
+//! }
+//! ```
+
+use core::mem::{
+	align_of, size_of,
+};
+
+use nalgebra::Vector3;
+
+use crate::gmod13::{
+	FromLua, ToLua,
+	Lua, StackPos,
+};
+
+use super::{
+	QAngle, Vector,
+};
+
+/// Trait for Source Engine vectors which can be converted to and from [`Vector3<f32>`].
+/// 
+/// This is implemented for [`Vector`] and [`QAngle`].
+/// The components are kept in the same order, so the pitch, yaw and roll of a [`QAngle`]
+/// are the `x`, `y` and `z` of the [`Vector3<f32>`].
+pub trait NalgebraVector: Sized {
+	/// Creates a vector from the components of `v`.
+	fn from_nalgebra(v: Vector3<f32>) -> Self;
+
+	/// Returns the components of this vector as a [`Vector3<f32>`].
+	fn to_nalgebra(&self) -> Vector3<f32> {
+		*self.as_nalgebra()
+	}
+
+	/// Returns a reference to this vector as a [`Vector3<f32>`], without copying it.
+	fn as_nalgebra(&self) -> &Vector3<f32>;
+
+	/// Returns a mutable reference to this vector as a [`Vector3<f32>`], without copying it.
+	fn as_nalgebra_mut(&mut self) -> &mut Vector3<f32>;
+}
+
+// `Vector3<f32>` is a `#[repr(C)]` matrix which stores its components as `[[f32; 3]; 1]`,
+// so references can be cast if the sizes and alignments match.
+const _: () = assert!(size_of::<Vector>() == size_of::<Vector3<f32>>() && align_of::<Vector>() == align_of::<Vector3<f32>>());
+const _: () = assert!(size_of::<QAngle>() == size_of::<Vector3<f32>>() && align_of::<QAngle>() == align_of::<Vector3<f32>>());
+
+macro_rules! impl_nalgebra_vector {
+	($($Vector:ty)*) => {
+		$(
+			impl NalgebraVector for $Vector {
+				fn from_nalgebra(v: Vector3<f32>) -> Self {
+					Self::new(v.x, v.y, v.z)
+				}
+
+				fn as_nalgebra(&self) -> &Vector3<f32> {
+					// SAFETY: Both types store three `f32`s in the same order.
+					unsafe { &*(self as *const Self as *const Vector3<f32>) }
+				}
+
+				fn as_nalgebra_mut(&mut self) -> &mut Vector3<f32> {
+					// SAFETY: See `as_nalgebra`.
+					unsafe { &mut *(self as *mut Self as *mut Vector3<f32>) }
+				}
+			}
+		)*
+	};
+}
+
+impl_nalgebra_vector!(Vector);
+// Without `rse-math`, `QAngle` is an alias of `Vector`.
+#[cfg(feature = "rse-math")]
+impl_nalgebra_vector!(QAngle);
+
+// With `rse-math`, `Vector` is defined in another crate, so `From` can't be implemented for it here.
+#[cfg(not(feature = "rse-math"))]
+impl From<Vector3<f32>> for Vector {
+	fn from(v: Vector3<f32>) -> Self {
+		Self::from_nalgebra(v)
+	}
+}
+
+#[cfg(not(feature = "rse-math"))]
+impl From<Vector> for Vector3<f32> {
+	fn from(v: Vector) -> Self {
+		v.to_nalgebra()
+	}
+}
+
+/// [`Vector3<f32>`]s are pushed as Lua `Vector`s.
+impl ToLua for Vector3<f32> {
+	fn push_to(self, lua: &mut Lua) {
+		lua.push_vector(&Vector::from_nalgebra(self))
+	}
+}
+
+/// [`Vector3<f32>`]s are read from Lua `Vector`s.
+impl FromLua<'_> for Vector3<f32> {
+	fn from_lua(lua: &Lua, stack_pos: StackPos) -> Option<Self> {
+		lua.try_get_vector(stack_pos).map(Vector::to_nalgebra)
+	}
+}
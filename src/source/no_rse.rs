@@ -12,6 +12,7 @@ type vec_t = c_float;
 /// 
 /// Cross-referenced with `vector.h` in `mathlib`.
 #[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[repr(C)]
 pub struct Vector {
 	pub x: vec_t,
 	pub y: vec_t,
@@ -0,0 +1,101 @@
+use core::{
+	ffi::{
+		c_float, c_void,
+	},
+	ptr::null,
+};
+
+/// 3D vector padded to 16 bytes and aligned to 16 bytes, for use with SIMD instructions.
+/// 
+/// Cross-referenced with `vector.h` in `mathlib`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[repr(C, align(16))]
+pub struct VectorAligned {
+	pub x: c_float,
+	pub y: c_float,
+	pub z: c_float,
+	/// Padding component, which is unused.
+	pub w: c_float,
+}
+
+impl VectorAligned {
+	/// Create a new aligned 3D vector from its components.
+	pub const fn new(x: c_float, y: c_float, z: c_float) -> Self {
+		Self {
+			x, y, z,
+			w: 0.0,
+		}
+	}
+
+	const fn sub(self, other: Self) -> Self {
+		Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+	}
+
+	const fn add(self, other: Self) -> Self {
+		Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+	}
+
+	const fn scale(self, s: c_float) -> Self {
+		Self::new(self.x * s, self.y * s, self.z * s)
+	}
+
+	const fn length_sqr(self) -> c_float {
+		self.x * self.x + self.y * self.y + self.z * self.z
+	}
+}
+
+/// Ray or swept box used for engine traces.
+/// 
+/// Cross-referenced with `Ray_t` in `gametrace.h` in `public`.
+#[doc(alias = "Ray_t")]
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(16))]
+pub struct Ray {
+	/// Starting point, centered within the extents.
+	pub start: VectorAligned,
+	/// Direction and length of the ray.
+	pub delta: VectorAligned,
+	/// Offset to add to `start` to get the actual starting point of the ray.
+	pub start_offset: VectorAligned,
+	/// Half-size of the axis-aligned box swept along the ray.
+	pub extents: VectorAligned,
+	/// Optional transform of the box, which is usually null.
+	pub world_axis_transform: *const c_void,
+	/// Whether `extents` are zero.
+	pub is_ray: bool,
+	/// Whether `delta` is non-zero.
+	pub is_swept: bool,
+}
+
+impl Ray {
+	/// Creates a ray going from `start` to `end`, like `Ray_t::Init(start, end)`.
+	pub const fn new(start: VectorAligned, end: VectorAligned) -> Self {
+		let delta = end.sub(start);
+		Self {
+			start,
+			delta,
+			start_offset: VectorAligned::new(0.0, 0.0, 0.0),
+			extents: VectorAligned::new(0.0, 0.0, 0.0),
+			world_axis_transform: null(),
+			is_ray: true,
+			is_swept: delta.length_sqr() != 0.0,
+		}
+	}
+
+	/// Creates a box with the bounds `mins` and `maxs` swept from `start` to `end`,
+	/// like `Ray_t::Init(start, end, mins, maxs)`.
+	pub const fn new_hull(start: VectorAligned, end: VectorAligned, mins: VectorAligned, maxs: VectorAligned) -> Self {
+		let delta = end.sub(start);
+		let extents = maxs.sub(mins).scale(0.5);
+		let offset = mins.add(maxs).scale(0.5);
+		Self {
+			start: start.add(offset),
+			delta,
+			start_offset: offset.scale(-1.0),
+			extents,
+			world_axis_transform: null(),
+			is_ray: extents.length_sqr() < 1e-6,
+			is_swept: delta.length_sqr() != 0.0,
+		}
+	}
+}